@@ -0,0 +1,8 @@
+#!/bin/sh
+# Rebuilds vmlinux-orc in the parent directory from orc.s.
+set -e
+cd "$(dirname "$0")"
+as -o orc.o orc.s
+ld -static --build-id=none -e outer_func -Ttext=0xffffffff81000000 \
+    -o ../vmlinux-orc orc.o
+rm orc.o
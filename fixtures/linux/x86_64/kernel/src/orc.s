@@ -0,0 +1,38 @@
+    # A minimal stand-in for vmlinux: kernel text at 0xffffffff81000000, and ORC
+    # unwind data in the Linux 6.4 layout for two functions.
+    .text
+    .globl _text
+_text:
+    .globl outer_func
+    .type outer_func, @function
+outer_func:
+    push %rbx
+    call inner_func
+    pop %rbx
+    ret
+    .size outer_func, .-outer_func
+
+    .globl inner_func
+    .type inner_func, @function
+inner_func:
+    nop
+    ret
+.Linner_func_end:
+    .size inner_func, .-inner_func
+
+    # .orc_unwind_ip has the place-relative start addresses of the entries in
+    # .orc_unwind. Each entry is sp_offset, bp_offset, and the bit fields sp_reg:4,
+    # bp_reg:4, type:3, signal:1. 0x205 is CALL with sp_reg SP and bp_reg UNDEFINED.
+    .section .orc_unwind_ip, "a"
+    .long outer_func - .
+    .long outer_func + 1 - .
+    .long outer_func + 6 - .
+    .long inner_func - .
+    .long .Linner_func_end - .
+
+    .section .orc_unwind, "a"
+    .short 8, 0, 0x205          # outer_func: sp + 8
+    .short 16, 0, 0x205         # after push %rbx: sp + 16
+    .short 8, 0, 0x205          # after pop %rbx: sp + 8
+    .short 8, 0, 0x205          # inner_func: sp + 8
+    .short 0, 0, 0x100          # END_OF_STACK
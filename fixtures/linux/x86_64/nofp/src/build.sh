@@ -0,0 +1,24 @@
+#!/bin/sh
+# Rebuilds the small test libraries in the parent directory from the sources in this
+# directory. The tests hard-code addresses in these libraries, so check the
+# disassembly after rebuilding with a different toolchain.
+set -e
+cd "$(dirname "$0")"
+OUT=..
+
+# c_func is only described by .debug_frame, asm_func only by .eh_frame.
+gcc -O2 -fomit-frame-pointer -fno-asynchronous-unwind-tables -g -fPIC -shared -nostdlib \
+    -Wl,--build-id=none -o "$OUT/libmixed-cfi.so" mixed-cfi.c mixed-cfi-asm.S
+# The same without .eh_frame_hdr, so that both sections need to be indexed.
+gcc -O2 -fomit-frame-pointer -fno-asynchronous-unwind-tables -g -fPIC -shared -nostdlib \
+    -Wl,--build-id=none -Wl,--no-eh-frame-hdr -o "$OUT/libmixed-cfi-nohdr.so" \
+    mixed-cfi.c mixed-cfi-asm.S
+objcopy --only-keep-debug "$OUT/libmixed-cfi.so" "$OUT/libmixed-cfi.so.debug"
+objcopy --strip-debug "$OUT/libmixed-cfi.so" "$OUT/libmixed-cfi-stripped.so"
+
+# Both sections describe conflict_func, differently. No .eh_frame_hdr, so that both
+# sections are indexed.
+gcc -shared -nostdlib -Wl,--build-id=none -Wl,--no-eh-frame-hdr \
+    -o "$OUT/libconflicting-cfi.so" conflicting-cfi.s
+gcc -shared -nostdlib -o "$OUT/libfullregs.so" fullregs.s
+gcc -shared -nostdlib -o "$OUT/libnocfi.so" nocfi.s
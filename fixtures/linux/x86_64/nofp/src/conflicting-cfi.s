@@ -0,0 +1,41 @@
+    # conflict_func is described by both .eh_frame and .debug_frame, and the two
+    # sections disagree: the .eh_frame FDE never adjusts the CFA, like CFI which is
+    # only accurate at call sites, and the hand-written .debug_frame FDE is exact.
+    .text
+    .globl conflict_func
+    .type conflict_func, @function
+conflict_func:
+    .cfi_startproc
+    sub $24, %rsp
+    nop
+    add $24, %rsp
+    ret
+    .cfi_endproc
+.Lconflict_func_end:
+    .size conflict_func, .-conflict_func
+
+    .section .debug_frame,"",@progbits
+.Lcie:
+    .long .Lcie_end - .Lcie_start
+.Lcie_start:
+    .long 0xffffffff            # CIE id
+    .byte 1                     # version
+    .asciz ""                   # augmentation
+    .uleb128 1                  # code alignment factor
+    .sleb128 -8                 # data alignment factor
+    .byte 16                    # return address register
+    .byte 0x0c, 7, 8            # DW_CFA_def_cfa: rsp + 8
+    .byte 0x90, 1               # DW_CFA_offset: rip at cfa - 8
+    .balign 8, 0
+.Lcie_end:
+    .long .Lfde_end - .Lfde_start
+.Lfde_start:
+    .long .Lcie                 # CIE pointer
+    .quad conflict_func
+    .quad .Lconflict_func_end - conflict_func
+    .byte 0x44                  # DW_CFA_advance_loc: 4, after sub
+    .byte 0x0e, 32              # DW_CFA_def_cfa_offset: 32
+    .byte 0x45                  # DW_CFA_advance_loc: 5, after add
+    .byte 0x0e, 8               # DW_CFA_def_cfa_offset: 8
+    .balign 8, 0
+.Lfde_end:
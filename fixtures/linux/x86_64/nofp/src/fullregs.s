@@ -0,0 +1,47 @@
+	.text
+	.globl	outer_func
+	.type	outer_func, @function
+outer_func:
+	.cfi_startproc
+	push	%rbx
+	.cfi_def_cfa_offset 16
+	.cfi_offset %rbx, -16
+	push	%r12
+	.cfi_def_cfa_offset 24
+	.cfi_offset %r12, -24
+	sub	$8, %rsp
+	.cfi_def_cfa_offset 32
+	mov	$1, %rbx
+	mov	$2, %r12
+	call	inner_func
+	add	$8, %rsp
+	.cfi_def_cfa_offset 24
+	pop	%r12
+	.cfi_def_cfa_offset 16
+	pop	%rbx
+	.cfi_def_cfa_offset 8
+	ret
+	.cfi_endproc
+	.size	outer_func, .-outer_func
+
+	.globl	inner_func
+	.hidden	inner_func
+	.type	inner_func, @function
+inner_func:
+	.cfi_startproc
+	push	%r15
+	.cfi_def_cfa_offset 16
+	.cfi_offset %r15, -16
+	push	%rbx
+	.cfi_def_cfa_offset 24
+	.cfi_offset %rbx, -24
+	mov	$3, %rbx
+	nop
+	pop	%rbx
+	.cfi_def_cfa_offset 16
+	pop	%r15
+	.cfi_def_cfa_offset 8
+	ret
+	.cfi_endproc
+	.size	inner_func, .-inner_func
+	.section	.note.GNU-stack,"",@progbits
@@ -0,0 +1,8 @@
+int asm_func(int);
+
+int c_func(int x) {
+    volatile int y[8];
+    y[0] = x;
+    y[7] = asm_func(y[0]);
+    return y[7] + 1;
+}
@@ -0,0 +1,58 @@
+    .text
+    .globl cfi_func
+    .type cfi_func, @function
+cfi_func:
+    .cfi_startproc
+    push %rbx
+    .cfi_def_cfa_offset 16
+    .cfi_offset %rbx, -16
+    call nocfi_func
+    pop %rbx
+    .cfi_def_cfa_offset 8
+    ret
+    .cfi_endproc
+    .size cfi_func, .-cfi_func
+
+    # Hand-written function without CFI.
+    .globl nocfi_func
+    .hidden nocfi_func
+    .type nocfi_func, @function
+nocfi_func:
+    push %rbx
+    push %r12
+    sub $0x18, %rsp
+    mov $1, %rbx
+    nop
+    add $0x18, %rsp
+    pop %r12
+    pop %rbx
+    ret
+    .size nocfi_func, .-nocfi_func
+
+    # Hand-written function with CFI and a constant at its end. The constant is
+    # covered by the function's FDE, and its bytes look like push instructions.
+    .globl const_func
+    .hidden const_func
+    .type const_func, @function
+const_func:
+    .cfi_startproc
+    mov mask(%rip), %rax
+    ret
+mask:
+    .quad 0x5555555555555555
+    .cfi_endproc
+    .size const_func, .-const_func
+
+    # Hand-written function without CFI, directly after const_func.
+    .globl nocfi_func2
+    .hidden nocfi_func2
+    .type nocfi_func2, @function
+nocfi_func2:
+    push %rbx
+    sub $0x10, %rsp
+    nop
+    add $0x10, %rsp
+    pop %rbx
+    ret
+    .size nocfi_func2, .-nocfi_func2
+    .section .note.GNU-stack,"",@progbits
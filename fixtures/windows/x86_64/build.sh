@@ -0,0 +1,10 @@
+#!/bin/sh
+# Rebuilds seh.dll from seh.s. Needs llvm-mc and an lld which understands the
+# link.exe flavor (rust-lld from a Rust toolchain works).
+set -e
+cd "$(dirname "$0")"
+LLD=${LLD:-$(rustc --print sysroot)/lib/rustlib/$(rustc -vV | sed -n 's/^host: //p')/bin/rust-lld}
+llvm-mc -triple x86_64-pc-windows-msvc -filetype=obj -o seh.obj seh.s
+"$LLD" -flavor link /dll /noentry /nodefaultlib /base:0x180000000 \
+    /export:nofp_func /out:seh.dll seh.obj
+rm -f seh.obj seh.lib
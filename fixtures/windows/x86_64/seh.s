@@ -0,0 +1,50 @@
+# Small DLL with hand-written SEH unwind info for the PE unwinder tests.
+# Built by build.sh, which records the resulting addresses.
+
+    .text
+
+    # Frame pointer function:
+    #   push rbp; push rbx; mov rbp, rsp; sub rsp, 0x20; call leaf
+    .globl fp_func
+    .def fp_func; .scl 2; .type 32; .endef
+    .seh_proc fp_func
+fp_func:
+    pushq %rbp
+    .seh_pushreg %rbp
+    pushq %rbx
+    .seh_pushreg %rbx
+    movq %rsp, %rbp
+    .seh_setframe %rbp, 0
+    subq $0x20, %rsp
+    .seh_stackalloc 0x20
+    .seh_endprologue
+    callq leaf_func
+    addq $0x20, %rsp
+    popq %rbx
+    popq %rbp
+    retq
+    .seh_endproc
+
+    # Function without a frame pointer:
+    #   push rsi; sub rsp, 0x30; call fp_func
+    .globl nofp_func
+    .def nofp_func; .scl 2; .type 32; .endef
+    .seh_proc nofp_func
+nofp_func:
+    pushq %rsi
+    .seh_pushreg %rsi
+    subq $0x30, %rsp
+    .seh_stackalloc 0x30
+    .seh_endprologue
+    callq fp_func
+    addq $0x30, %rsp
+    popq %rsi
+    retq
+    .seh_endproc
+
+    # Leaf function without unwind info.
+    .globl leaf_func
+    .def leaf_func; .scl 2; .type 32; .endef
+leaf_func:
+    nop
+    retq
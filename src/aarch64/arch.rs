@@ -1,6 +1,8 @@
 use super::unwind_rule::UnwindRuleAarch64;
 use super::unwindregs::UnwindRegsAarch64;
 use crate::arch::Arch;
+use crate::ehabi::ArmExidxUnwinding;
+use crate::orc::OrcUnwinding;
 
 /// The Aarch64 CPU architecture.
 pub struct ArchAarch64;
@@ -8,3 +10,8 @@ impl Arch for ArchAarch64 {
     type UnwindRule = UnwindRuleAarch64;
     type UnwindRegs = UnwindRegsAarch64;
 }
+
+// Unwind data formats which are not used on aarch64. See the traits for what the default
+// implementations do.
+impl ArmExidxUnwinding for ArchAarch64 {}
+impl OrcUnwinding for ArchAarch64 {}
@@ -0,0 +1,199 @@
+use super::arch::ArchAarch64;
+use super::unwind_rule::UnwindRuleAarch64;
+use super::unwindregs::UnwindRegsAarch64;
+use crate::breakpad::{
+    BreakpadCfiRecord, BreakpadStackRecord, BreakpadUnwinderError, BreakpadUnwinding, SimpleRule,
+};
+use crate::unwind_result::UnwindResult;
+
+fn register_value(regs: &UnwindRegsAarch64, name: &str) -> Option<u64> {
+    match name {
+        "sp" => Some(regs.sp()),
+        "x29" => Some(regs.fp()),
+        "x30" => Some(regs.lr()),
+        _ => None,
+    }
+}
+
+impl BreakpadUnwinding for ArchAarch64 {
+    fn unwind_frame<F>(
+        record: Option<BreakpadStackRecord>,
+        is_first_frame: bool,
+        regs: &mut UnwindRegsAarch64,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<UnwindRuleAarch64>, BreakpadUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let record = match record {
+            Some(BreakpadStackRecord::Cfi(record)) => record,
+            None | Some(BreakpadStackRecord::Win(_)) => {
+                // STACK WIN records only exist for 32-bit x86 code.
+                return Ok(UnwindResult::ExecRule(
+                    UnwindRuleAarch64::NoOpIfFirstFrameOtherwiseFp,
+                ));
+            }
+        };
+        if let Some(rule) = translate_into_unwind_rule(&record) {
+            return Ok(UnwindResult::ExecRule(rule));
+        }
+
+        // Could not translate into a cacheable unwind rule. Evaluate the rules directly.
+        let register = |name: &str| register_value(regs, name);
+        let cfa = record
+            .evaluate_rule(".cfa", None, register, read_stack)?
+            .ok_or(BreakpadUnwinderError::NoCfaRule)?;
+        let lr = match record.evaluate_rule(".ra", Some(cfa), register, read_stack)? {
+            Some(lr) => lr,
+            None => record
+                .evaluate_rule("x30", Some(cfa), register, read_stack)?
+                .ok_or(BreakpadUnwinderError::NoReturnAddressRule)?,
+        };
+        let fp = record
+            .evaluate_rule("x29", Some(cfa), register, read_stack)?
+            .unwrap_or(regs.fp());
+
+        if !is_first_frame && cfa <= regs.sp() {
+            return Err(BreakpadUnwinderError::StackPointerMovedBackwards);
+        }
+        regs.set_fp(fp);
+        regs.set_sp(cfa);
+        regs.set_lr(lr);
+        Ok(UnwindResult::Uncacheable(regs.lr_mask().strip_ptr_auth(lr)))
+    }
+}
+
+fn storage_offset_by_8(cfa_offset: i64, rule: SimpleRule) -> Option<Option<i16>> {
+    match rule {
+        SimpleRule::LoadFromCfaPlusOffset(offset) if (cfa_offset + offset) % 8 == 0 => {
+            Some(Some(i16::try_from((cfa_offset + offset) / 8).ok()?))
+        }
+        SimpleRule::Missing => Some(None),
+        _ => None,
+    }
+}
+
+fn translate_into_unwind_rule(record: &BreakpadCfiRecord) -> Option<UnwindRuleAarch64> {
+    let lr_rule = match record.simple_rule(".ra") {
+        SimpleRule::Missing => record.simple_rule("x30"),
+        rule => rule,
+    };
+    let lr_unchanged = matches!(
+        lr_rule,
+        SimpleRule::Missing | SimpleRule::RegisterPlusOffset("x30", 0)
+    );
+    let fp_rule = match record.simple_rule("x29") {
+        SimpleRule::RegisterPlusOffset("x29", 0) => SimpleRule::Missing,
+        rule => rule,
+    };
+    match record.simple_rule(".cfa") {
+        SimpleRule::RegisterPlusOffset("sp", offset) if offset % 16 == 0 => {
+            let sp_offset_by_16 = u16::try_from(offset / 16).ok()?;
+            if lr_unchanged {
+                if fp_rule != SimpleRule::Missing {
+                    return None;
+                }
+                return Some(if offset == 0 {
+                    UnwindRuleAarch64::NoOp
+                } else {
+                    UnwindRuleAarch64::OffsetSp { sp_offset_by_16 }
+                });
+            }
+            let lr_storage_offset_from_sp_by_8 = storage_offset_by_8(offset, lr_rule)??;
+            match storage_offset_by_8(offset, fp_rule)? {
+                None => Some(UnwindRuleAarch64::OffsetSpAndRestoreLr {
+                    sp_offset_by_16,
+                    lr_storage_offset_from_sp_by_8,
+                }),
+                Some(fp_storage_offset_from_sp_by_8) => {
+                    Some(UnwindRuleAarch64::OffsetSpAndRestoreFpAndLr {
+                        sp_offset_by_16,
+                        fp_storage_offset_from_sp_by_8,
+                        lr_storage_offset_from_sp_by_8,
+                    })
+                }
+            }
+        }
+        SimpleRule::RegisterPlusOffset("x29", offset) if offset % 8 == 0 => {
+            let lr_storage_offset_from_fp_by_8 = storage_offset_by_8(offset, lr_rule)??;
+            let fp_storage_offset_from_fp_by_8 = storage_offset_by_8(offset, fp_rule)??;
+            if (
+                offset,
+                fp_storage_offset_from_fp_by_8,
+                lr_storage_offset_from_fp_by_8,
+            ) == (16, 0, 1)
+            {
+                Some(UnwindRuleAarch64::UseFramePointer)
+            } else {
+                Some(UnwindRuleAarch64::UseFramepointerWithOffsets {
+                    sp_offset_from_fp_by_8: u16::try_from(offset / 8).ok()?,
+                    fp_storage_offset_from_fp_by_8,
+                    lr_storage_offset_from_fp_by_8,
+                })
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::breakpad::BreakpadIndex;
+
+    const SYM: &[u8] = b"MODULE Linux arm64 0123456789ABCDEF0123456789ABCDEF0 libtest.so
+STACK CFI INIT 1000 40 .cfa: sp 0 + .ra: x30
+STACK CFI 1004 .cfa: sp 16 + x29: .cfa -16 + ^ .ra: .cfa -8 + ^
+STACK CFI 1008 .cfa: x29 16 +
+STACK CFI INIT 2000 10 .cfa: sp 32 + .ra: .cfa -8 + ^ 8 -
+";
+
+    fn unwind(
+        address: u32,
+        regs: &mut UnwindRegsAarch64,
+        read_stack: &mut impl FnMut(u64) -> Result<u64, ()>,
+    ) -> Result<UnwindResult<UnwindRuleAarch64>, BreakpadUnwinderError> {
+        let index = BreakpadIndex::try_new(SYM).unwrap();
+        let record = index.lookup(SYM, address)?;
+        ArchAarch64::unwind_frame(record, true, regs, read_stack)
+    }
+
+    #[test]
+    fn test_rules() {
+        let mut regs = UnwindRegsAarch64::new(0x1000, 0x10, 0x20);
+        let mut read_stack = |_| Err(());
+        let expected = [
+            (0x1000, UnwindRuleAarch64::NoOp),
+            (
+                0x1004,
+                UnwindRuleAarch64::OffsetSpAndRestoreFpAndLr {
+                    sp_offset_by_16: 1,
+                    fp_storage_offset_from_sp_by_8: 0,
+                    lr_storage_offset_from_sp_by_8: 1,
+                },
+            ),
+            (0x1010, UnwindRuleAarch64::UseFramePointer),
+            (0x1040, UnwindRuleAarch64::NoOpIfFirstFrameOtherwiseFp),
+        ];
+        for (address, rule) in expected {
+            assert_eq!(
+                unwind(address, &mut regs, &mut read_stack),
+                Ok(UnwindResult::ExecRule(rule))
+            );
+        }
+    }
+
+    #[test]
+    fn test_evaluated() {
+        let stack = [1, 2, 3, 0x2348, 5, 6];
+        let mut read_stack = |addr: u64| stack.get((addr / 8) as usize).copied().ok_or(());
+        let mut regs = UnwindRegsAarch64::new(0x1000, 0x0, 0x40);
+        assert_eq!(
+            unwind(0x2004, &mut regs, &mut read_stack),
+            Ok(UnwindResult::Uncacheable(0x2340))
+        );
+        assert_eq!(regs.lr(), 0x2340);
+        assert_eq!(regs.sp(), 0x20);
+        assert_eq!(regs.fp(), 0x40);
+    }
+}
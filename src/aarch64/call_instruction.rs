@@ -0,0 +1,46 @@
+use super::arch::ArchAarch64;
+use crate::call_instruction::CallInstructionAnalysis;
+
+impl CallInstructionAnalysis for ArchAarch64 {
+    fn is_preceded_by_call(text_bytes: &[u8]) -> bool {
+        let Some(insn) = text_bytes
+            .len()
+            .checked_sub(4)
+            .map(|start| u32::from_le_bytes(text_bytes[start..].try_into().unwrap()))
+        else {
+            return false;
+        };
+        // bl <label>
+        insn & 0xfc00_0000 == 0x9400_0000
+            // blr <Xn>
+            || insn & 0xffff_fc1f == 0xd63f_0000
+            // blraa, blraaz, blrab, blrabz
+            || insn & 0xfeff_f800 == 0xd63f_0800
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_preceded_by_call() {
+        let is_call = |insn: u32| ArchAarch64::is_preceded_by_call(&insn.to_le_bytes());
+        // bl #-0x100
+        assert!(is_call(0x97ff_ffc0));
+        // blr x8
+        assert!(is_call(0xd63f_0100));
+        // blraaz x16
+        assert!(is_call(0xd63f_0a1f));
+        // blrab x8, x9
+        assert!(is_call(0xd73f_0d09));
+
+        // b #-0x100
+        assert!(!is_call(0x17ff_ffc0));
+        // br x8
+        assert!(!is_call(0xd61f_0100));
+        // ret
+        assert!(!is_call(0xd65f_03c0));
+        assert!(!ArchAarch64::is_preceded_by_call(&[0x00, 0x01, 0x3f]));
+    }
+}
@@ -3,7 +3,9 @@ use gimli::{
     UnwindContextStorage, UnwindTableRow,
 };
 
-use super::{arch::ArchAarch64, unwind_rule::UnwindRuleAarch64, unwindregs::UnwindRegsAarch64};
+use super::arch::ArchAarch64;
+use super::unwind_rule::UnwindRuleAarch64;
+use super::unwindregs::{FullUnwindRegsAarch64, UnwindRegsAarch64};
 
 use crate::unwind_result::UnwindResult;
 
@@ -23,7 +25,18 @@ impl DwarfUnwindRegs for UnwindRegsAarch64 {
     }
 }
 
+impl DwarfUnwindRegs for FullUnwindRegsAarch64 {
+    fn get(&self, register: Register) -> Option<u64> {
+        match register.0 {
+            19..=28 => self.x(register.0 as usize),
+            _ => self.regs().get(register),
+        }
+    }
+}
+
 impl DwarfUnwinding for ArchAarch64 {
+    const ADDRESS_SIZE: u8 = 8;
+
     fn unwind_frame<F, R, S>(
         unwind_info: &UnwindTableRow<R, S>,
         encoding: Encoding,
@@ -40,14 +53,6 @@ impl DwarfUnwinding for ArchAarch64 {
         let fp_rule = unwind_info.register(AArch64::X29);
         let lr_rule = unwind_info.register(AArch64::X30);
 
-        match translate_into_unwind_rule(cfa_rule, &fp_rule, &lr_rule) {
-            Ok(unwind_rule) => return Ok(UnwindResult::ExecRule(unwind_rule)),
-            Err(_err) => {
-                // Could not translate into a cacheable unwind rule. Fall back to the generic path.
-                // eprintln!("Unwind rule translation failed: {:?}", err);
-            }
-        }
-
         let cfa = eval_cfa_rule::<R, _, S>(cfa_rule, encoding, regs)
             .ok_or(DwarfUnwinderError::CouldNotRecoverCfa)?;
 
@@ -82,6 +87,20 @@ impl DwarfUnwinding for ArchAarch64 {
         Ok(UnwindResult::Uncacheable(lr))
     }
 
+    fn unwind_rule_for_row<R, S>(
+        unwind_info: &UnwindTableRow<R, S>,
+    ) -> Result<Self::UnwindRule, ConversionError>
+    where
+        R: Reader,
+        S: UnwindContextStorage<R>,
+    {
+        translate_into_unwind_rule(
+            unwind_info.cfa(),
+            &unwind_info.register(AArch64::X29),
+            &unwind_info.register(AArch64::X30),
+        )
+    }
+
     fn rule_if_uncovered_by_fde() -> Self::UnwindRule {
         UnwindRuleAarch64::NoOpIfFirstFrameOtherwiseFp
     }
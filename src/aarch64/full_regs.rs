@@ -0,0 +1,94 @@
+use gimli::{AArch64, Register};
+use macho_unwind_info::opcodes::OpcodeArm64;
+
+use super::arch::ArchAarch64;
+use super::unwindregs::{FullUnwindRegsAarch64, UnwindRegsAarch64};
+use crate::full_regs::{CalleeSavedRule, FullRegsUnwinding};
+
+impl FullRegsUnwinding for ArchAarch64 {
+    type FullUnwindRegs = FullUnwindRegsAarch64;
+
+    const CALLEE_SAVED_REGS: &'static [Register] = &[
+        AArch64::X19,
+        AArch64::X20,
+        AArch64::X21,
+        AArch64::X22,
+        AArch64::X23,
+        AArch64::X24,
+        AArch64::X25,
+        AArch64::X26,
+        AArch64::X27,
+        AArch64::X28,
+    ];
+
+    fn unwind_regs_mut(regs: &mut FullUnwindRegsAarch64) -> &mut UnwindRegsAarch64 {
+        regs.regs_mut()
+    }
+
+    fn callee_saved_values_mut(regs: &mut FullUnwindRegsAarch64) -> &mut [Option<u64>] {
+        regs.callee_saved_mut()
+    }
+
+    fn cfa_after_unwinding(regs: &FullUnwindRegsAarch64) -> u64 {
+        regs.regs().sp()
+    }
+
+    fn callee_saved_rule_for_cui_opcode(opcode: u32, register: Register) -> CalleeSavedRule {
+        if !(AArch64::X19.0..=AArch64::X28.0).contains(&register.0) {
+            return CalleeSavedRule::Unknown;
+        }
+        let n = register.0 - AArch64::X19.0;
+        // Bits 0 to 4 say whether the pairs x19/x20, x21/x22, ..., x27/x28 were saved.
+        let saved_pairs = opcode & 0b1_1111;
+        let first_pair_offset = match OpcodeArm64::parse(opcode) {
+            // Only used for the first frame, where a function without unwind info is
+            // treated as a leaf function.
+            OpcodeArm64::Null => return CalleeSavedRule::SameValue,
+            // The pairs are stored right below the frame record at CFA - 16.
+            OpcodeArm64::FrameBased { .. } => -24,
+            // The pairs are stored at the top of the stack frame.
+            OpcodeArm64::Frameless { .. } => -8,
+            OpcodeArm64::Dwarf { .. } | OpcodeArm64::UnrecognizedKind(_) => {
+                return CalleeSavedRule::Unknown
+            }
+        };
+        // The saved pairs are stored consecutively, starting with x19/x20 at the highest
+        // address. Within each pair, the lower-numbered register is at the higher address.
+        if saved_pairs & (1 << (n / 2)) == 0 {
+            return CalleeSavedRule::SameValue;
+        }
+        let pairs_before = (saved_pairs & ((1 << (n / 2)) - 1)).count_ones() as i64;
+        CalleeSavedRule::SavedAtCfaOffset(
+            first_pair_offset - pairs_before * 16 - (n % 2) as i64 * 8,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame_based() {
+        // stp x22, x21, [sp, #-48]!; stp x20, x19, [sp, #16]; stp x29, x30, [sp, #32]
+        // is described as frame-based with x19/x20 and x21/x22 saved.
+        let opcode = 0x0400_0003;
+        let rule = |register| ArchAarch64::callee_saved_rule_for_cui_opcode(opcode, register);
+        assert_eq!(rule(AArch64::X19), CalleeSavedRule::SavedAtCfaOffset(-24));
+        assert_eq!(rule(AArch64::X20), CalleeSavedRule::SavedAtCfaOffset(-32));
+        assert_eq!(rule(AArch64::X21), CalleeSavedRule::SavedAtCfaOffset(-40));
+        assert_eq!(rule(AArch64::X22), CalleeSavedRule::SavedAtCfaOffset(-48));
+        assert_eq!(rule(AArch64::X23), CalleeSavedRule::SameValue);
+    }
+
+    #[test]
+    fn test_frameless() {
+        // stp x24, x23, [sp, #-16]! with a stack size of 16 is described as frameless
+        // with x23/x24 saved.
+        let opcode = 0x0200_1004;
+        let rule = |register| ArchAarch64::callee_saved_rule_for_cui_opcode(opcode, register);
+        assert_eq!(rule(AArch64::X23), CalleeSavedRule::SavedAtCfaOffset(-8));
+        assert_eq!(rule(AArch64::X24), CalleeSavedRule::SavedAtCfaOffset(-16));
+        assert_eq!(rule(AArch64::X19), CalleeSavedRule::SameValue);
+    }
+}
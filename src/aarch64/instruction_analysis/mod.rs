@@ -7,6 +7,11 @@ mod prologue;
 use epilogue::unwind_rule_from_detected_epilogue;
 use prologue::unwind_rule_from_detected_prologue;
 
+/// `__kernel_rt_sigreturn` in the vDSO, and the `__restore_rt` copies in libcs:
+/// mov x8, #139 ; rt_sigreturn
+/// svc #0
+const SIGRETURN_TRAMPOLINE: &[u8] = &[0x68, 0x11, 0x80, 0xd2, 0x01, 0x00, 0x00, 0xd4];
+
 impl InstructionAnalysis for ArchAarch64 {
     fn rule_from_prologue_analysis(
         text_bytes: &[u8],
@@ -22,4 +27,37 @@ impl InstructionAnalysis for ArchAarch64 {
     ) -> Option<Self::UnwindRule> {
         unwind_rule_from_detected_epilogue(text_bytes, pc_offset)
     }
+
+    fn is_linux_sigreturn_trampoline(text_bytes: &[u8], pc_offset: usize) -> bool {
+        // The pc is at the first instruction when a signal handler returns into the
+        // trampoline, and at the second one if the thread was sampled inside it.
+        [0, 4].into_iter().any(|offset| {
+            pc_offset
+                .checked_sub(offset)
+                .and_then(|start| text_bytes.get(start..)?.get(..SIGRETURN_TRAMPOLINE.len()))
+                == Some(SIGRETURN_TRAMPOLINE)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sigreturn_trampoline() {
+        let mut bytes = vec![0x1f, 0x20, 0x03, 0xd5];
+        bytes.extend_from_slice(SIGRETURN_TRAMPOLINE);
+        let is_trampoline =
+            |pc_offset| ArchAarch64::is_linux_sigreturn_trampoline(&bytes, pc_offset);
+        assert!(is_trampoline(4));
+        assert!(is_trampoline(8));
+        assert!(!is_trampoline(0));
+        assert!(!is_trampoline(5));
+        assert!(!is_trampoline(bytes.len()));
+        assert!(!ArchAarch64::is_linux_sigreturn_trampoline(
+            &bytes[..bytes.len() - 1],
+            4
+        ));
+    }
 }
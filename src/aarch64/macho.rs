@@ -27,7 +27,7 @@ impl CompactUnwindInfoUnwinding for ArchAarch64 {
                 ) {
                     // We are inside a prologue / epilogue. Ignore the opcode and use the rule from
                     // instruction analysis.
-                    return Ok(CuiUnwindResult::ExecRule(rule));
+                    return Ok(CuiUnwindResult::ExecRuleFromInstructionAnalysis(rule));
                 }
             }
         }
@@ -0,0 +1,952 @@
+use super::arch::ArchAarch64;
+use super::unwind_rule::UnwindRuleAarch64;
+use super::unwindregs::UnwindRegsAarch64;
+use crate::macho::TextBytes;
+use crate::pe::{read_u32, PeSections, PeUnwinderError, PeUnwinding};
+use crate::unwind_result::UnwindResult;
+
+/// The register numbers of fp (x29) and lr (x30).
+const FP: u8 = 29;
+const LR: u8 = 30;
+
+/// Packed unwind data never needs more than this many unwind codes: pacibsp, 8 stores
+/// for up to 15 integer registers (RegI is a 4-bit field), 4 stores for up to 8 floating
+/// point registers, 4 home area stores, 2 allocations for locals above 4080 bytes, the
+/// frame record store and the `mov x29, sp`.
+const MAX_PACKED_CODES: usize = 21;
+
+/// An unwind code, as far as it's relevant for recovering sp, fp and lr. Unwind codes
+/// are stored in the reverse order of the prolog instructions they describe, and each
+/// code describes exactly one instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum UnwindCode {
+    /// sub sp, sp, #n
+    AllocStack(u32),
+    /// Store one or two registers. `None` means a register we don't track, for example
+    /// a floating point register. If `writeback` is set, the registers are stored at
+    /// sp - offset and sp is decremented by offset, otherwise they're stored at
+    /// sp + offset.
+    SaveRegisters {
+        first: Option<u8>,
+        second: Option<u8>,
+        offset: u32,
+        writeback: bool,
+    },
+    /// add fp, sp, #n
+    SetFramePointer(u32),
+    /// An instruction which doesn't affect sp, fp or lr.
+    Nop,
+    /// The end of the unwind codes for a prolog or epilog.
+    End,
+}
+
+/// Decode the unwind code at the start of `bytes`. Returns the code and its size.
+fn decode_unwind_code(bytes: &[u8]) -> Result<(UnwindCode, usize), PeUnwinderError> {
+    let byte = |index: usize| -> Result<u32, PeUnwinderError> {
+        bytes
+            .get(index)
+            .map(|b| u32::from(*b))
+            .ok_or(PeUnwinderError::TruncatedUnwindCodes)
+    };
+    let int_register = |register: u32| Some(19 + register as u8);
+    let b0 = byte(0)?;
+    let code = match b0 {
+        // alloc_s: sub sp, sp, #X*16
+        0x00..=0x1f => (UnwindCode::AllocStack(b0 * 16), 1),
+        // save_r19r20_x: stp x19, x20, [sp, #-Z*8]!
+        0x20..=0x3f => (
+            UnwindCode::SaveRegisters {
+                first: Some(19),
+                second: Some(20),
+                offset: (b0 & 0x1f) * 8,
+                writeback: true,
+            },
+            1,
+        ),
+        // save_fplr: stp x29, lr, [sp, #Z*8]
+        0x40..=0x7f => (
+            UnwindCode::SaveRegisters {
+                first: Some(FP),
+                second: Some(LR),
+                offset: (b0 & 0x3f) * 8,
+                writeback: false,
+            },
+            1,
+        ),
+        // save_fplr_x: stp x29, lr, [sp, #-(Z+1)*8]!
+        0x80..=0xbf => (
+            UnwindCode::SaveRegisters {
+                first: Some(FP),
+                second: Some(LR),
+                offset: ((b0 & 0x3f) + 1) * 8,
+                writeback: true,
+            },
+            1,
+        ),
+        // alloc_m: sub sp, sp, #X*16
+        0xc0..=0xc7 => (
+            UnwindCode::AllocStack((((b0 & 0x7) << 8) | byte(1)?) * 16),
+            2,
+        ),
+        // save_regp(_x): stp x(19+X), x(20+X), [sp, #Z*8] / [sp, #-(Z+1)*8]!
+        0xc8..=0xcf => {
+            let b1 = byte(1)?;
+            let register = ((b0 & 0x3) << 2) | (b1 >> 6);
+            let writeback = b0 & 0x4 != 0;
+            let z = b1 & 0x3f;
+            (
+                UnwindCode::SaveRegisters {
+                    first: int_register(register),
+                    second: int_register(register + 1),
+                    offset: if writeback { (z + 1) * 8 } else { z * 8 },
+                    writeback,
+                },
+                2,
+            )
+        }
+        // save_reg: str x(19+X), [sp, #Z*8]
+        0xd0..=0xd3 => {
+            let b1 = byte(1)?;
+            (
+                UnwindCode::SaveRegisters {
+                    first: int_register(((b0 & 0x3) << 2) | (b1 >> 6)),
+                    second: None,
+                    offset: (b1 & 0x3f) * 8,
+                    writeback: false,
+                },
+                2,
+            )
+        }
+        // save_reg_x: str x(19+X), [sp, #-(Z+1)*8]!
+        0xd4..=0xd5 => {
+            let b1 = byte(1)?;
+            (
+                UnwindCode::SaveRegisters {
+                    first: int_register(((b0 & 0x1) << 3) | (b1 >> 5)),
+                    second: None,
+                    offset: ((b1 & 0x1f) + 1) * 8,
+                    writeback: true,
+                },
+                2,
+            )
+        }
+        // save_lrpair: stp x(19+2*X), lr, [sp, #Z*8]
+        0xd6..=0xd7 => {
+            let b1 = byte(1)?;
+            (
+                UnwindCode::SaveRegisters {
+                    first: int_register((((b0 & 0x1) << 2) | (b1 >> 6)) * 2),
+                    second: Some(LR),
+                    offset: (b1 & 0x3f) * 8,
+                    writeback: false,
+                },
+                2,
+            )
+        }
+        // save_fregp, save_freg: stp / str d(8+X), [sp, #Z*8]
+        0xd8..=0xd9 | 0xdc..=0xdd => (UnwindCode::Nop, 2),
+        // save_fregp_x: stp d(8+X), d(9+X), [sp, #-(Z+1)*8]!
+        0xda..=0xdb => (
+            UnwindCode::SaveRegisters {
+                first: None,
+                second: None,
+                offset: ((byte(1)? & 0x3f) + 1) * 8,
+                writeback: true,
+            },
+            2,
+        ),
+        // save_freg_x: str d(8+X), [sp, #-(Z+1)*8]!
+        0xde => (
+            UnwindCode::SaveRegisters {
+                first: None,
+                second: None,
+                offset: ((byte(1)? & 0x1f) + 1) * 8,
+                writeback: true,
+            },
+            2,
+        ),
+        // alloc_l: sub sp, sp, #X*16
+        0xe0 => (
+            UnwindCode::AllocStack(((byte(1)? << 16) | (byte(2)? << 8) | byte(3)?) * 16),
+            4,
+        ),
+        // set_fp: mov x29, sp
+        0xe1 => (UnwindCode::SetFramePointer(0), 1),
+        // add_fp: add x29, sp, #X*8
+        0xe2 => (UnwindCode::SetFramePointer(byte(1)? * 8), 2),
+        // nop, save_next, pac_sign_lr
+        0xe3 | 0xe6 | 0xfc => (UnwindCode::Nop, 1),
+        // end, end_c
+        0xe4 | 0xe5 => (UnwindCode::End, 1),
+        // save_any_reg
+        0xe7 => {
+            let b1 = byte(1)?;
+            let b2 = byte(2)?;
+            let is_pair = b1 & 0x40 != 0;
+            let writeback = b1 & 0x20 != 0;
+            let register = (b1 & 0x1f) as u8;
+            let register_kind = b2 >> 6;
+            let o = b2 & 0x3f;
+            let offset = if writeback {
+                (o + 1) * 16
+            } else if is_pair || register_kind == 2 {
+                o * 16
+            } else {
+                o * 8
+            };
+            let (first, second) = match (register_kind, is_pair) {
+                (0, false) => (Some(register), None),
+                (0, true) => (Some(register), Some(register + 1)),
+                _ => (None, None),
+            };
+            (
+                UnwindCode::SaveRegisters {
+                    first,
+                    second,
+                    offset,
+                    writeback,
+                },
+                3,
+            )
+        }
+        // alloc_z, custom stack cases (trap frames, machine frames, contexts) and
+        // reserved codes.
+        _ => return Err(PeUnwinderError::BadUnwindCode(b0 as u8)),
+    };
+    Ok(code)
+}
+
+/// The unwind codes of a function, either as raw bytes from `.xdata`, or synthesized
+/// from packed unwind data.
+// The packed codes are synthesized for each lookup. Boxing them would allocate during
+// unwinding.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Copy)]
+enum UnwindCodes<'a> {
+    Bytes(&'a [u8]),
+    Packed([UnwindCode; MAX_PACKED_CODES], usize),
+}
+
+impl<'a> UnwindCodes<'a> {
+    /// Iterate over the codes starting at `index`, which is a byte index for
+    /// `.xdata` codes and a code index for packed codes, up to the next end code.
+    fn iter_from(&self, index: usize) -> UnwindCodeIter<'_> {
+        match self {
+            UnwindCodes::Bytes(bytes) => UnwindCodeIter::Bytes(bytes.get(index..).unwrap_or(&[])),
+            UnwindCodes::Packed(codes, len) => {
+                UnwindCodeIter::Packed(codes[..*len].get(index..).unwrap_or(&[]))
+            }
+        }
+    }
+
+    /// The number of instructions described by the codes starting at `index`.
+    fn instruction_count_from(&self, index: usize) -> Result<u32, PeUnwinderError> {
+        let mut count = 0;
+        for code in self.iter_from(index) {
+            code?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+enum UnwindCodeIter<'a> {
+    Bytes(&'a [u8]),
+    Packed(&'a [UnwindCode]),
+}
+
+impl<'a> Iterator for UnwindCodeIter<'a> {
+    type Item = Result<UnwindCode, PeUnwinderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let code = match self {
+            UnwindCodeIter::Bytes(bytes) => {
+                if bytes.is_empty() {
+                    return None;
+                }
+                match decode_unwind_code(bytes) {
+                    Ok((code, size)) => {
+                        *bytes = &bytes[size..];
+                        code
+                    }
+                    Err(err) => {
+                        *bytes = &[];
+                        return Some(Err(err));
+                    }
+                }
+            }
+            UnwindCodeIter::Packed(codes) => {
+                let (code, rest) = codes.split_first()?;
+                *codes = rest;
+                *code
+            }
+        };
+        match code {
+            UnwindCode::End => {
+                *self = UnwindCodeIter::Packed(&[]);
+                None
+            }
+            code => Some(Ok(code)),
+        }
+    }
+}
+
+/// Where the epilogs of a function are, and which unwind codes describe them.
+#[derive(Clone, Copy)]
+enum Epilogs<'a> {
+    /// A single epilog at the end of the function, described by the codes starting
+    /// at this index.
+    AtEnd(usize),
+    /// The epilog scopes from `.xdata`, one 32-bit word each.
+    Scopes(&'a [u8]),
+}
+
+/// The unwind information for one function.
+struct FunctionUnwindInfo<'a> {
+    /// The function length in bytes.
+    length: u32,
+    /// Whether the function starts with a prolog. Function fragments have no prolog.
+    has_prolog: bool,
+    codes: UnwindCodes<'a>,
+    epilogs: Epilogs<'a>,
+}
+
+impl<'a> FunctionUnwindInfo<'a> {
+    fn from_pdata_entry(
+        sections: &PeSections<'a>,
+        begin: u32,
+        data: u32,
+    ) -> Result<Self, PeUnwinderError> {
+        match data & 0x3 {
+            0 => Self::from_xdata(sections.xdata_at_rva(data)?, data),
+            1 => Self::from_packed(begin, data, true),
+            2 => Self::from_packed(begin, data, false),
+            _ => Err(PeUnwinderError::BadPdataEntry(begin)),
+        }
+    }
+
+    fn from_xdata(xdata: &'a [u8], rva: u32) -> Result<Self, PeUnwinderError> {
+        let header = read_u32(xdata, 0).ok_or(PeUnwinderError::BadUnwindInfo(rva))?;
+        let length = (header & 0x3ffff) * 4;
+        let version = ((header >> 18) & 0x3) as u8;
+        if version != 0 {
+            return Err(PeUnwinderError::UnsupportedVersion(version));
+        }
+        let single_epilog_at_end = header & (1 << 21) != 0;
+        let mut epilog_count = (header >> 22) & 0x1f;
+        let mut code_words = header >> 27;
+        let mut offset = 4;
+        if epilog_count == 0 && code_words == 0 {
+            // The counts are in an extension word.
+            let extension = read_u32(xdata, 4).ok_or(PeUnwinderError::BadUnwindInfo(rva))?;
+            epilog_count = extension & 0xffff;
+            code_words = (extension >> 16) & 0xff;
+            offset = 8;
+        }
+        let epilogs = if single_epilog_at_end {
+            // The epilog count field is the index of the epilog's first unwind code.
+            Epilogs::AtEnd(epilog_count as usize)
+        } else {
+            let scopes_len = epilog_count as usize * 4;
+            let scopes = xdata
+                .get(offset..offset + scopes_len)
+                .ok_or(PeUnwinderError::BadUnwindInfo(rva))?;
+            offset += scopes_len;
+            Epilogs::Scopes(scopes)
+        };
+        let codes = xdata
+            .get(offset..offset + code_words as usize * 4)
+            .ok_or(PeUnwinderError::BadUnwindInfo(rva))?;
+        Ok(Self {
+            length,
+            has_prolog: true,
+            codes: UnwindCodes::Bytes(codes),
+            epilogs,
+        })
+    }
+
+    /// Synthesize the unwind codes for the canonical prolog described by packed unwind
+    /// data.
+    fn from_packed(begin: u32, data: u32, has_prolog: bool) -> Result<Self, PeUnwinderError> {
+        let length = ((data >> 2) & 0x7ff) * 4;
+        let reg_f = (data >> 13) & 0x7;
+        let reg_i = (data >> 16) & 0xf;
+        let has_home_area = data & (1 << 20) != 0;
+        let cr = (data >> 21) & 0x3;
+        let frame_size = (data >> 23) * 16;
+
+        let lr_saved_with_int_regs = cr == 1;
+        let has_frame_record = cr == 2 || cr == 3;
+        let int_count = reg_i + u32::from(lr_saved_with_int_regs);
+        let fp_count = if reg_f > 0 { reg_f + 1 } else { 0 };
+        let int_size = int_count * 8;
+        let save_size = (int_size + fp_count * 8 + if has_home_area { 64 } else { 0 } + 15) & !15;
+        let locals_size = frame_size
+            .checked_sub(save_size)
+            .ok_or(PeUnwinderError::BadPdataEntry(begin))?;
+        if has_frame_record && locals_size < 16 {
+            return Err(PeUnwinderError::BadPdataEntry(begin));
+        }
+
+        // Build the prolog in execution order, then reverse it.
+        let mut prolog = [UnwindCode::End; MAX_PACKED_CODES];
+        let mut len = 0;
+        let mut push = |code| {
+            prolog[len] = code;
+            len += 1;
+        };
+        if cr == 2 {
+            // pacibsp
+            push(UnwindCode::Nop);
+        }
+        // The first store allocates the entire register save area.
+        let mut predecrement = if save_size > 0 { Some(save_size) } else { None };
+        let mut save = |first, second, offset| match predecrement.take() {
+            Some(size) => UnwindCode::SaveRegisters {
+                first,
+                second,
+                offset: size,
+                writeback: true,
+            },
+            None => UnwindCode::SaveRegisters {
+                first,
+                second,
+                offset,
+                writeback: false,
+            },
+        };
+        let int_register = |index: u32| {
+            if index < reg_i {
+                19 + index as u8
+            } else {
+                LR
+            }
+        };
+        for index in (0..int_count).step_by(2) {
+            let second = if index + 1 < int_count {
+                Some(int_register(index + 1))
+            } else {
+                None
+            };
+            push(save(Some(int_register(index)), second, index * 8));
+        }
+        for index in (0..fp_count).step_by(2) {
+            push(save(None, None, int_size + index * 8));
+        }
+        if has_home_area {
+            for index in 0..4 {
+                push(save(None, None, int_size + fp_count * 8 + index * 16));
+            }
+        }
+        let mut push_alloc = |size: u32| {
+            if size > 4080 {
+                push(UnwindCode::AllocStack(4080));
+                push(UnwindCode::AllocStack(size - 4080));
+            } else if size > 0 {
+                push(UnwindCode::AllocStack(size));
+            }
+        };
+        if has_frame_record {
+            if locals_size <= 512 {
+                push(UnwindCode::SaveRegisters {
+                    first: Some(FP),
+                    second: Some(LR),
+                    offset: locals_size,
+                    writeback: true,
+                });
+            } else {
+                push_alloc(locals_size);
+                push(UnwindCode::SaveRegisters {
+                    first: Some(FP),
+                    second: Some(LR),
+                    offset: 0,
+                    writeback: false,
+                });
+            }
+            push(UnwindCode::SetFramePointer(0));
+        } else {
+            push_alloc(locals_size);
+        }
+        prolog[..len].reverse();
+
+        // The epilog mirrors the prolog, except that it doesn't restore sp from fp.
+        let epilog_start = usize::from(has_frame_record);
+        Ok(Self {
+            length,
+            has_prolog,
+            codes: UnwindCodes::Packed(prolog, len),
+            epilogs: Epilogs::AtEnd(epilog_start),
+        })
+    }
+
+    /// Find the unwind codes which need to be executed to unwind from `offset` within
+    /// the function. Returns the index of the first code and the number of codes to
+    /// skip, because their instructions haven't been executed yet (in a prolog) or
+    /// have already been executed (in an epilog).
+    fn codes_for_offset(&self, offset: u32) -> Result<(usize, u32), PeUnwinderError> {
+        match self.epilogs {
+            Epilogs::AtEnd(code_index) => {
+                let count = self.codes.instruction_count_from(code_index)?;
+                // The epilog instructions are followed by a ret.
+                if let Some(start) = self.length.checked_sub((count + 1) * 4) {
+                    if offset >= start {
+                        return Ok((code_index, (offset - start) / 4));
+                    }
+                }
+            }
+            Epilogs::Scopes(scopes) => {
+                for scope_index in 0..scopes.len() / 4 {
+                    let scope = read_u32(scopes, scope_index * 4)
+                        .ok_or(PeUnwinderError::TruncatedUnwindCodes)?;
+                    let start = (scope & 0x3ffff) * 4;
+                    let code_index = (scope >> 22) as usize;
+                    if offset < start {
+                        continue;
+                    }
+                    let count = self.codes.instruction_count_from(code_index)?;
+                    if offset <= start + count * 4 {
+                        return Ok((code_index, (offset - start) / 4));
+                    }
+                }
+            }
+        }
+        if self.has_prolog {
+            let count = self.codes.instruction_count_from(0)?;
+            let executed = offset / 4;
+            if executed < count {
+                return Ok((0, count - executed));
+            }
+        }
+        Ok((0, 0))
+    }
+}
+
+/// Binary-search the `.pdata` table for the entry with the highest start address which
+/// is less than or equal to `address`. Returns the function start and the entry's
+/// second word.
+fn lookup_pdata_entry(pdata: &[u8], address: u32) -> Option<(u32, u32)> {
+    let entry_count = pdata.len() / 8;
+    let (mut low, mut high) = (0, entry_count);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if read_u32(pdata, mid * 8)? <= address {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    let index = low.checked_sub(1)?;
+    Some((read_u32(pdata, index * 8)?, read_u32(pdata, index * 8 + 4)?))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Base {
+    Sp,
+    Fp,
+}
+
+/// A location relative to the stack pointer or the frame pointer, in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Location {
+    base: Base,
+    offset: i64,
+}
+
+impl Location {
+    fn add(self, offset: u32) -> Self {
+        Self {
+            base: self.base,
+            offset: self.offset + i64::from(offset),
+        }
+    }
+
+    fn resolve(self, regs: &UnwindRegsAarch64) -> Option<u64> {
+        let base = match self.base {
+            Base::Sp => regs.sp(),
+            Base::Fp => regs.fp(),
+        };
+        base.checked_add_signed(self.offset)
+    }
+}
+
+/// The result of executing unwind codes symbolically: where the caller's sp is, and
+/// where fp and lr were restored from, if they were.
+struct UnwindState {
+    sp: Location,
+    fp: Option<Location>,
+    lr: Option<Location>,
+}
+
+fn execute_codes(
+    codes: impl Iterator<Item = Result<UnwindCode, PeUnwinderError>>,
+) -> Result<UnwindState, PeUnwinderError> {
+    let mut state = UnwindState {
+        sp: Location {
+            base: Base::Sp,
+            offset: 0,
+        },
+        fp: None,
+        lr: None,
+    };
+    for code in codes {
+        match code? {
+            UnwindCode::AllocStack(size) => state.sp = state.sp.add(size),
+            UnwindCode::SaveRegisters {
+                first,
+                second,
+                offset,
+                writeback,
+            } => {
+                let location = if writeback {
+                    state.sp
+                } else {
+                    state.sp.add(offset)
+                };
+                for (register, location) in [(first, location), (second, location.add(8))] {
+                    match register {
+                        Some(FP) => state.fp = Some(location),
+                        Some(LR) => state.lr = Some(location),
+                        _ => {}
+                    }
+                }
+                if writeback {
+                    state.sp = state.sp.add(offset);
+                }
+            }
+            UnwindCode::SetFramePointer(offset) => {
+                if state.fp.is_some() {
+                    // fp has already been restored, so we don't know its value at the
+                    // time it was set up.
+                    return Err(PeUnwinderError::UntrackedFrameRegister(FP));
+                }
+                state.sp = Location {
+                    base: Base::Fp,
+                    offset: -i64::from(offset),
+                };
+            }
+            UnwindCode::Nop | UnwindCode::End => {}
+        }
+    }
+    Ok(state)
+}
+
+fn offset_by_8<T: TryFrom<i64>>(offset: i64) -> Option<T> {
+    if offset % 8 != 0 {
+        return None;
+    }
+    T::try_from(offset / 8).ok()
+}
+
+fn translate_into_unwind_rule(state: &UnwindState) -> Option<UnwindRuleAarch64> {
+    let UnwindState { sp, fp, lr } = *state;
+    let rule = match (sp.base, fp, lr) {
+        (Base::Sp, None, None) if sp.offset == 0 => UnwindRuleAarch64::NoOp,
+        (Base::Sp, None, None) if sp.offset % 16 == 0 => UnwindRuleAarch64::OffsetSp {
+            sp_offset_by_16: u16::try_from(sp.offset / 16).ok()?,
+        },
+        (Base::Sp, None, Some(lr)) if sp.offset % 16 == 0 && lr.base == Base::Sp => {
+            UnwindRuleAarch64::OffsetSpAndRestoreLr {
+                sp_offset_by_16: u16::try_from(sp.offset / 16).ok()?,
+                lr_storage_offset_from_sp_by_8: offset_by_8(lr.offset)?,
+            }
+        }
+        (Base::Sp, Some(fp), Some(lr))
+            if sp.offset % 16 == 0 && fp.base == Base::Sp && lr.base == Base::Sp =>
+        {
+            UnwindRuleAarch64::OffsetSpAndRestoreFpAndLr {
+                sp_offset_by_16: u16::try_from(sp.offset / 16).ok()?,
+                fp_storage_offset_from_sp_by_8: offset_by_8(fp.offset)?,
+                lr_storage_offset_from_sp_by_8: offset_by_8(lr.offset)?,
+            }
+        }
+        (Base::Fp, Some(fp), Some(lr)) if fp.base == Base::Fp && lr.base == Base::Fp => {
+            if (sp.offset, fp.offset, lr.offset) == (16, 0, 8) {
+                UnwindRuleAarch64::UseFramePointer
+            } else {
+                UnwindRuleAarch64::UseFramepointerWithOffsets {
+                    sp_offset_from_fp_by_8: offset_by_8(sp.offset)?,
+                    fp_storage_offset_from_fp_by_8: offset_by_8(fp.offset)?,
+                    lr_storage_offset_from_fp_by_8: offset_by_8(lr.offset)?,
+                }
+            }
+        }
+        _ => return None,
+    };
+    Some(rule)
+}
+
+impl PeUnwinding for ArchAarch64 {
+    fn unwind_frame<F>(
+        sections: PeSections,
+        _text_bytes: Option<TextBytes>,
+        rel_lookup_address: u32,
+        is_first_frame: bool,
+        regs: &mut UnwindRegsAarch64,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<UnwindRuleAarch64>, PeUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let info = lookup_pdata_entry(sections.pdata, rel_lookup_address)
+            .map(|(begin, data)| {
+                let info = FunctionUnwindInfo::from_pdata_entry(&sections, begin, data)?;
+                Ok((begin, info))
+            })
+            .transpose()?
+            .filter(|(begin, info)| rel_lookup_address - begin < info.length);
+        let (begin, info) = match info {
+            Some(info) => info,
+            None => {
+                // Functions without a .pdata entry are leaf functions which don't touch
+                // sp and keep the return address in lr.
+                return Ok(UnwindResult::ExecRule(
+                    UnwindRuleAarch64::NoOpIfFirstFrameOtherwiseFp,
+                ));
+            }
+        };
+
+        // Prologs and epilogs can only be interrupted in the first frame.
+        let offset = rel_lookup_address - begin;
+        let (code_index, skip) = if is_first_frame {
+            info.codes_for_offset(offset)?
+        } else {
+            (0, 0)
+        };
+        let codes = info.codes.iter_from(code_index).skip(skip as usize);
+        let state = execute_codes(codes)?;
+        if let Some(rule) = translate_into_unwind_rule(&state) {
+            return Ok(UnwindResult::ExecRule(rule));
+        }
+
+        // Could not translate into a cacheable unwind rule. Evaluate the locations
+        // directly.
+        let mut read = |location: Location| {
+            let address = location
+                .resolve(regs)
+                .ok_or(PeUnwinderError::IntegerOverflow)?;
+            read_stack(address).map_err(|_| PeUnwinderError::CouldNotReadStack(address))
+        };
+        let new_fp = match state.fp {
+            Some(location) => read(location)?,
+            None => regs.fp(),
+        };
+        let new_lr = match state.lr {
+            Some(location) => read(location)?,
+            None => regs.lr(),
+        };
+        let new_sp = state
+            .sp
+            .resolve(regs)
+            .ok_or(PeUnwinderError::IntegerOverflow)?;
+        if !is_first_frame && new_sp == regs.sp() {
+            return Err(PeUnwinderError::DidNotAdvance);
+        }
+        regs.set_fp(new_fp);
+        regs.set_sp(new_sp);
+        regs.set_lr(new_lr);
+        Ok(UnwindResult::Uncacheable(
+            regs.lr_mask().strip_ptr_auth(new_lr),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn unwind(
+        pdata: &[u8],
+        xdata: &[u8],
+        rel_lookup_address: u32,
+        is_first_frame: bool,
+    ) -> Result<UnwindResult<UnwindRuleAarch64>, PeUnwinderError> {
+        let sections = PeSections {
+            pdata,
+            xdata,
+            xdata_rva: 0x2000,
+        };
+        let mut regs = UnwindRegsAarch64::new(0x1234, 0x100, 0x120);
+        let mut read_stack = |_| Err(());
+        ArchAarch64::unwind_frame(
+            sections,
+            None,
+            rel_lookup_address,
+            is_first_frame,
+            &mut regs,
+            &mut read_stack,
+        )
+    }
+
+    fn packed_pdata(
+        function_length: u32,
+        reg_i: u32,
+        reg_f: u32,
+        cr: u32,
+        frame_size: u32,
+    ) -> Vec<u8> {
+        let data = 1
+            | (function_length / 4) << 2
+            | reg_f << 13
+            | reg_i << 16
+            | cr << 21
+            | (frame_size / 16) << 23;
+        let mut pdata = Vec::new();
+        pdata.extend_from_slice(&0x1000u32.to_le_bytes());
+        pdata.extend_from_slice(&data.to_le_bytes());
+        pdata
+    }
+
+    #[test]
+    fn test_packed_frame_record() {
+        // stp x19, x20, [sp, #-16]!
+        // stp x29, lr, [sp, #-32]!
+        // mov x29, sp
+        // ...
+        // ldp x29, lr, [sp], #32
+        // ldp x19, x20, [sp], #16
+        // ret
+        let pdata = packed_pdata(0x40, 2, 0, 3, 48);
+        assert_eq!(
+            unwind(&pdata, &[], 0x1020, false),
+            Ok(UnwindResult::ExecRule(
+                UnwindRuleAarch64::UseFramepointerWithOffsets {
+                    sp_offset_from_fp_by_8: 6,
+                    fp_storage_offset_from_fp_by_8: 0,
+                    lr_storage_offset_from_fp_by_8: 1,
+                }
+            ))
+        );
+        // In the prolog, after the first instruction.
+        assert_eq!(
+            unwind(&pdata, &[], 0x1004, true),
+            Ok(UnwindResult::ExecRule(UnwindRuleAarch64::OffsetSp {
+                sp_offset_by_16: 1
+            }))
+        );
+        // In the prolog, after the second instruction.
+        assert_eq!(
+            unwind(&pdata, &[], 0x1008, true),
+            Ok(UnwindResult::ExecRule(
+                UnwindRuleAarch64::OffsetSpAndRestoreFpAndLr {
+                    sp_offset_by_16: 3,
+                    fp_storage_offset_from_sp_by_8: 0,
+                    lr_storage_offset_from_sp_by_8: 1,
+                }
+            ))
+        );
+        // In the epilog, after ldp x29, lr.
+        assert_eq!(
+            unwind(&pdata, &[], 0x1038, true),
+            Ok(UnwindResult::ExecRule(UnwindRuleAarch64::OffsetSp {
+                sp_offset_by_16: 1
+            }))
+        );
+        // At the ret.
+        assert_eq!(
+            unwind(&pdata, &[], 0x103c, true),
+            Ok(UnwindResult::ExecRule(UnwindRuleAarch64::NoOp))
+        );
+        // Not covered by .pdata: a leaf function.
+        assert_eq!(
+            unwind(&pdata, &[], 0x1040, true),
+            Ok(UnwindResult::ExecRule(
+                UnwindRuleAarch64::NoOpIfFirstFrameOtherwiseFp
+            ))
+        );
+    }
+
+    #[test]
+    fn test_packed_lr_with_int_regs() {
+        // stp x19, x20, [sp, #-32]!
+        // str lr, [sp, #16]
+        // sub sp, sp, #32
+        let pdata = packed_pdata(0x40, 2, 0, 1, 64);
+        assert_eq!(
+            unwind(&pdata, &[], 0x1020, false),
+            Ok(UnwindResult::ExecRule(
+                UnwindRuleAarch64::OffsetSpAndRestoreLr {
+                    sp_offset_by_16: 4,
+                    lr_storage_offset_from_sp_by_8: 6,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_packed_maximal() {
+        // pacibsp, stores for x19-x28, d8-d15 and the home area, two allocations for
+        // the locals, the frame record and mov x29, sp: 18 codes.
+        let mut pdata = packed_pdata(0x200, 10, 7, 2, 8176);
+        // H: the home area is saved.
+        pdata[6] |= 0x10;
+        assert_eq!(
+            unwind(&pdata, &[], 0x1100, false),
+            Ok(UnwindResult::ExecRule(
+                UnwindRuleAarch64::UseFramepointerWithOffsets {
+                    sp_offset_from_fp_by_8: 1022,
+                    fp_storage_offset_from_fp_by_8: 0,
+                    lr_storage_offset_from_fp_by_8: 1,
+                }
+            ))
+        );
+        // After pacibsp, nothing needs to be undone.
+        assert_eq!(
+            unwind(&pdata, &[], 0x1004, true),
+            Ok(UnwindResult::ExecRule(UnwindRuleAarch64::NoOp))
+        );
+
+        // RegI is out of range, which gives 21 codes. This must not panic.
+        let mut pdata = packed_pdata(0x200, 15, 7, 2, 8176);
+        pdata[6] |= 0x10;
+        assert!(unwind(&pdata, &[], 0x1100, false).is_ok());
+        assert!(unwind(&pdata, &[], 0x1004, true).is_ok());
+    }
+
+    #[test]
+    fn test_xdata() {
+        let mut pdata = Vec::new();
+        pdata.extend_from_slice(&0x1000u32.to_le_bytes());
+        pdata.extend_from_slice(&0x2000u32.to_le_bytes());
+        // Function length 0x40, one epilog scope at 0x30 starting with code 1, one
+        // word of unwind codes.
+        let header: u32 = (0x40 / 4) | (1 << 22) | (1 << 27);
+        let scope: u32 = (0x30 / 4) | (1 << 22);
+        let mut xdata = Vec::new();
+        xdata.extend_from_slice(&header.to_le_bytes());
+        xdata.extend_from_slice(&scope.to_le_bytes());
+        // set_fp; save_fplr_x (16 bytes); end; nop (padding)
+        xdata.extend_from_slice(&[0xe1, 0x81, 0xe4, 0xe3]);
+
+        assert_eq!(
+            unwind(&pdata, &xdata, 0x1020, false),
+            Ok(UnwindResult::ExecRule(UnwindRuleAarch64::UseFramePointer))
+        );
+        // After stp x29, lr, [sp, #-16]!, but before mov x29, sp.
+        assert_eq!(
+            unwind(&pdata, &xdata, 0x1004, true),
+            Ok(UnwindResult::ExecRule(
+                UnwindRuleAarch64::OffsetSpAndRestoreFpAndLr {
+                    sp_offset_by_16: 1,
+                    fp_storage_offset_from_sp_by_8: 0,
+                    lr_storage_offset_from_sp_by_8: 1,
+                }
+            ))
+        );
+        // In the epilog, before ldp x29, lr, [sp], #16.
+        assert_eq!(
+            unwind(&pdata, &xdata, 0x1030, true),
+            Ok(UnwindResult::ExecRule(
+                UnwindRuleAarch64::OffsetSpAndRestoreFpAndLr {
+                    sp_offset_by_16: 1,
+                    fp_storage_offset_from_sp_by_8: 0,
+                    lr_storage_offset_from_sp_by_8: 1,
+                }
+            ))
+        );
+        // In the epilog, at the ret.
+        assert_eq!(
+            unwind(&pdata, &xdata, 0x1034, true),
+            Ok(UnwindResult::ExecRule(UnwindRuleAarch64::NoOp))
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use super::arch::ArchAarch64;
+use super::unwind_rule::UnwindRuleAarch64;
+use super::unwindregs::UnwindRegsAarch64;
+use crate::stack_scan::StackScanning;
+
+impl StackScanning for ArchAarch64 {
+    fn scan_start(
+        rule: UnwindRuleAarch64,
+        is_first_frame: bool,
+        regs: &UnwindRegsAarch64,
+    ) -> Option<u64> {
+        match rule {
+            UnwindRuleAarch64::UseFramePointer => Some(regs.sp()),
+            UnwindRuleAarch64::NoOpIfFirstFrameOtherwiseFp if !is_first_frame => Some(regs.sp()),
+            _ => None,
+        }
+    }
+
+    fn return_address_from_stack_value(regs: &UnwindRegsAarch64, value: u64) -> u64 {
+        regs.lr_mask().strip_ptr_auth(value)
+    }
+
+    fn set_scanned_frame(regs: &mut UnwindRegsAarch64, location: u64, value: u64) {
+        regs.set_lr(value);
+        regs.set_sp(location + 8);
+    }
+}
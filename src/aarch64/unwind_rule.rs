@@ -4,6 +4,24 @@ use crate::error::Error;
 
 use crate::unwind_rule::UnwindRule;
 
+/// Offsets of the registers we need in the `struct rt_sigframe` which the Linux kernel
+/// pushes for a signal handler, i.e. the offsets of `uc.uc_mcontext.regs[29]`, `regs[30]`,
+/// `sp` and `pc`. The stack pointer points to it when the handler returns into
+/// `__kernel_rt_sigreturn`.
+const SIGFRAME_FP: u64 = 544;
+const SIGFRAME_LR: u64 = 552;
+const SIGFRAME_SP: u64 = 560;
+const SIGFRAME_PC: u64 = 568;
+
+/// Offsets of the registers we need in the `struct user_sigframe64` which the macOS kernel
+/// pushes for a signal handler, i.e. the offsets of `mctx.__ss.__fp`, `__lr`, `__sp` and
+/// `__pc`. The frame starts at the stack pointer with which `_sigtramp` is entered, which
+/// is 16 bytes above the frame pointer of `_sigtramp`.
+const MACOS_SIGFRAME_FP: u64 = 424;
+const MACOS_SIGFRAME_LR: u64 = 432;
+const MACOS_SIGFRAME_SP: u64 = 440;
+const MACOS_SIGFRAME_PC: u64 = 448;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum UnwindRuleAarch64 {
     /// (sp, fp, lr) = (sp, fp, lr)
@@ -43,6 +61,18 @@ pub enum UnwindRuleAarch64 {
         fp_storage_offset_from_fp_by_8: i16,
         lr_storage_offset_from_fp_by_8: i16,
     },
+    /// (pc, sp, fp, lr) = (*(sp + 568), *(sp + 560), *(sp + 544), *(sp + 552))
+    /// Used for the Linux signal trampoline `__kernel_rt_sigreturn`. The registers are
+    /// restored from the saved `rt_sigframe`, and the new pc is the interrupted
+    /// instruction, not a return address. The restored lr is only valid because the
+    /// next frame is unwound like a first frame.
+    LinuxSignalFrame,
+    /// (pc, sp, fp, lr) = (*(fp + 464), *(fp + 456), *(fp + 440), *(fp + 448))
+    /// Used for the macOS signal trampoline `_sigtramp`. The registers are restored from
+    /// the saved `user_sigframe64` above the trampoline's frame record, and the new pc is
+    /// the interrupted instruction, not a return address. The restored lr is only valid
+    /// because the next frame is unwound like a first frame.
+    MacOsSignalFrame,
 }
 
 impl UnwindRule for UnwindRuleAarch64 {
@@ -57,6 +87,18 @@ impl UnwindRule for UnwindRuleAarch64 {
     fn fallback_rule() -> Self {
         UnwindRuleAarch64::UseFramePointer
     }
+    fn rule_for_linux_signal_frame() -> Option<Self> {
+        Some(UnwindRuleAarch64::LinuxSignalFrame)
+    }
+    fn rule_for_macos_signal_frame() -> Option<Self> {
+        Some(UnwindRuleAarch64::MacOsSignalFrame)
+    }
+    fn is_signal_frame(self) -> bool {
+        matches!(
+            self,
+            UnwindRuleAarch64::LinuxSignalFrame | UnwindRuleAarch64::MacOsSignalFrame
+        )
+    }
 
     fn exec<F>(
         self,
@@ -72,6 +114,20 @@ impl UnwindRule for UnwindRuleAarch64 {
         let fp = regs.fp();
 
         let (new_lr, new_sp, new_fp) = match self {
+            UnwindRuleAarch64::LinuxSignalFrame => {
+                let offsets = (SIGFRAME_PC, SIGFRAME_SP, SIGFRAME_FP, SIGFRAME_LR);
+                return restore_signal_frame(sp, offsets, regs, read_stack);
+            }
+            UnwindRuleAarch64::MacOsSignalFrame => {
+                let sigframe = fp.checked_add(16).ok_or(Error::IntegerOverflow)?;
+                let offsets = (
+                    MACOS_SIGFRAME_PC,
+                    MACOS_SIGFRAME_SP,
+                    MACOS_SIGFRAME_FP,
+                    MACOS_SIGFRAME_LR,
+                );
+                return restore_signal_frame(sigframe, offsets, regs, read_stack);
+            }
             UnwindRuleAarch64::NoOp => {
                 if !is_first_frame {
                     return Err(Error::DidNotAdvance);
@@ -236,6 +292,34 @@ impl UnwindRule for UnwindRuleAarch64 {
     }
 }
 
+/// Restores pc, sp, fp and lr from the registers which the kernel saved at `base` when it
+/// delivered a signal. `offsets` are the offsets of the saved (pc, sp, fp, lr) from `base`.
+fn restore_signal_frame<F>(
+    base: u64,
+    (pc_offset, sp_offset, fp_offset, lr_offset): (u64, u64, u64, u64),
+    regs: &mut UnwindRegsAarch64,
+    read_stack: &mut F,
+) -> Result<Option<u64>, Error>
+where
+    F: FnMut(u64) -> Result<u64, ()>,
+{
+    let mut read = |offset: u64| {
+        let location = base.checked_add(offset).ok_or(Error::IntegerOverflow)?;
+        read_stack(location).map_err(|_| Error::CouldNotReadStack(location))
+    };
+    let pc = regs.lr_mask().strip_ptr_auth(read(pc_offset)?);
+    let new_sp = read(sp_offset)?;
+    let new_fp = read(fp_offset)?;
+    let new_lr = read(lr_offset)?;
+    if pc == 0 {
+        return Ok(None);
+    }
+    regs.set_lr(new_lr);
+    regs.set_sp(new_sp);
+    regs.set_fp(new_fp);
+    Ok(Some(pc))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -261,4 +345,37 @@ mod test {
         let res = UnwindRuleAarch64::UseFramePointer.exec(false, &mut regs, &mut read_stack);
         assert_eq!(res, Ok(None));
     }
+
+    #[test]
+    fn test_linux_signal_frame() {
+        let mut stack = [0; 76];
+        stack[2 + 68] = 0x70;
+        stack[2 + 69] = 0x100100;
+        stack[2 + 70] = 0x300;
+        stack[2 + 71] = 0x100500;
+        let mut read_stack = |addr| Ok(stack[(addr / 8) as usize]);
+        let mut regs = UnwindRegsAarch64::new(0x100300, 0x10, 0x20);
+        let res = UnwindRuleAarch64::LinuxSignalFrame.exec(false, &mut regs, &mut read_stack);
+        assert_eq!(res, Ok(Some(0x100500)));
+        assert_eq!(regs.lr(), 0x100100);
+        assert_eq!(regs.sp(), 0x300);
+        assert_eq!(regs.fp(), 0x70);
+    }
+
+    #[test]
+    fn test_macos_signal_frame() {
+        // The frame pointer is at 0x20, so the user_sigframe64 starts at 0x30.
+        let mut stack = [0; 63];
+        stack[6 + 53] = 0x70;
+        stack[6 + 54] = 0x100100;
+        stack[6 + 55] = 0x300;
+        stack[6 + 56] = 0x100500;
+        let mut read_stack = |addr| Ok(stack[(addr / 8) as usize]);
+        let mut regs = UnwindRegsAarch64::new(0x100300, 0x10, 0x20);
+        let res = UnwindRuleAarch64::MacOsSignalFrame.exec(false, &mut regs, &mut read_stack);
+        assert_eq!(res, Ok(Some(0x100500)));
+        assert_eq!(regs.lr(), 0x100100);
+        assert_eq!(regs.sp(), 0x300);
+        assert_eq!(regs.fp(), 0x70);
+    }
 }
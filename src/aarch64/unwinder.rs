@@ -1,11 +1,14 @@
 use std::ops::Deref;
 
 use crate::{
-    unwinder::UnwinderInternal, AllocationPolicy, Error, FrameAddress, MayAllocateDuringUnwind,
-    Module, Unwinder,
+    unwinder::UnwinderInternal, AllocationPolicy, Error, FrameAddress, FrameInfo,
+    FrameInfoUnwinder, IntoStackReadError, MayAllocateDuringUnwind, Module, ModuleError,
+    ReturnAddressChecks, Unwinder,
 };
 
-use super::{ArchAarch64, CacheAarch64, UnwindRegsAarch64};
+use super::{
+    ArchAarch64, CacheAarch64, FullUnwindRegsAarch64, UnwindRegsAarch64, UnwindRuleAarch64,
+};
 
 /// The unwinder for the Aarch64 CPU architecture. Use the [`Unwinder`] trait for unwinding.
 ///
@@ -29,6 +32,43 @@ impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> UnwinderAarch64<D, P> {
     pub fn new() -> Self {
         Self(UnwinderInternal::new())
     }
+
+    /// Enable or disable stack scanning. It is disabled by default.
+    ///
+    /// When frame pointer unwinding fails, for example because `fp` contains garbage in a
+    /// library which was built without frame pointers and without unwind information, the
+    /// stack is searched upwards from the stack pointer for a value which points into the
+    /// text of a known module, right after a call instruction. This needs the modules'
+    /// [`TextByteData`](crate::TextByteData) and `text` address ranges. Frames found this
+    /// way have the method [`UnwindMethod::StackScan`](crate::UnwindMethod::StackScan).
+    pub fn set_stack_scanning(&mut self, enabled: bool) {
+        self.0.set_stack_scanning(enabled);
+    }
+
+    /// Unwind a single frame in full register unwinding mode. This recovers the caller's
+    /// values of the callee-saved registers `x19` - `x28`, in addition to the registers
+    /// that [`Unwinder::unwind_frame`] recovers.
+    ///
+    /// This is slower than [`Unwinder::unwind_frame`], because the callee-saved registers
+    /// need to be looked up in the unwind information for every frame. They are recovered
+    /// from DWARF CFI and from compact unwind info. For frames whose function is described
+    /// by a different unwind information format, or not described at all, the callee-saved
+    /// registers become unknown (`None`). This is also the case for Linux signal
+    /// trampolines, for which the returned address is the interrupted instruction pointer.
+    pub fn unwind_frame_full<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut FullUnwindRegsAarch64,
+        cache: &mut CacheAarch64<D, P>,
+        read_stack: &mut F,
+    ) -> Result<Option<u64>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        self.0
+            .unwind_frame_full(address, regs, &mut cache.0, read_stack)
+    }
 }
 
 impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> Unwinder for UnwinderAarch64<D, P> {
@@ -40,6 +80,10 @@ impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> Unwinder for UnwinderAarch
         self.0.add_module(module);
     }
 
+    fn try_add_module(&mut self, module: Module<D>) -> Result<(), ModuleError> {
+        self.0.try_add_module(module)
+    }
+
     fn remove_module(&mut self, module_address_range_start: u64) {
         self.0.remove_module(module_address_range_start);
     }
@@ -48,7 +92,15 @@ impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> Unwinder for UnwinderAarch
         self.0.max_known_code_address()
     }
 
-    fn unwind_frame<F>(
+    fn check_return_address(
+        &self,
+        address: u64,
+        checks: &ReturnAddressChecks,
+    ) -> Result<(), Error> {
+        self.0.check_return_address(address, checks)
+    }
+
+    fn unwind_frame<F, E>(
         &self,
         address: FrameAddress,
         regs: &mut UnwindRegsAarch64,
@@ -56,8 +108,43 @@ impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> Unwinder for UnwinderAarch
         read_stack: &mut F,
     ) -> Result<Option<u64>, Error>
     where
-        F: FnMut(u64) -> Result<u64, ()>,
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
     {
         self.0.unwind_frame(address, regs, &mut cache.0, read_stack)
     }
+
+    fn unwind_frame_address<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut UnwindRegsAarch64,
+        cache: &mut CacheAarch64<D, P>,
+        read_stack: &mut F,
+    ) -> Result<Option<FrameAddress>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        self.0
+            .unwind_frame_address(address, regs, &mut cache.0, read_stack)
+    }
+}
+
+impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> FrameInfoUnwinder for UnwinderAarch64<D, P> {
+    type UnwindRule = UnwindRuleAarch64;
+
+    fn unwind_frame_with_info<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut UnwindRegsAarch64,
+        cache: &mut CacheAarch64<D, P>,
+        read_stack: &mut F,
+    ) -> Result<Option<FrameInfo<Self::UnwindRule>>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        self.0
+            .unwind_frame_with_info(address, regs, &mut cache.0, read_stack)
+    }
 }
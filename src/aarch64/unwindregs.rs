@@ -146,6 +146,87 @@ impl Debug for UnwindRegsAarch64 {
     }
 }
 
+/// The registers used in full register unwinding mode on Aarch64, see
+/// [`UnwinderAarch64::unwind_frame_full`](super::UnwinderAarch64::unwind_frame_full).
+///
+/// In addition to the regular [`UnwindRegsAarch64`], this contains the callee-saved
+/// registers `x19` - `x28`. Their values are optional: A value of `None` means that the
+/// register's value is not known, for example because it could not be recovered for a
+/// caller frame.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct FullUnwindRegsAarch64 {
+    regs: UnwindRegsAarch64,
+    /// x19 - x28.
+    callee_saved: [Option<u64>; 10],
+}
+
+impl FullUnwindRegsAarch64 {
+    /// Create a set of full unwind registers. The callee-saved registers start out
+    /// as unknown; use [`set_x`](Self::set_x) to supply their values.
+    pub fn new(regs: UnwindRegsAarch64) -> Self {
+        Self {
+            regs,
+            callee_saved: [None; 10],
+        }
+    }
+
+    /// The regular unwind registers.
+    #[inline(always)]
+    pub fn regs(&self) -> &UnwindRegsAarch64 {
+        &self.regs
+    }
+
+    /// Mutable access to the regular unwind registers.
+    #[inline(always)]
+    pub fn regs_mut(&mut self) -> &mut UnwindRegsAarch64 {
+        &mut self.regs
+    }
+
+    /// Get the value of the callee-saved register `x<n>`, for `n` in `19..=28`.
+    ///
+    /// Panics if `n` is outside of this range.
+    #[inline(always)]
+    pub fn x(&self, n: usize) -> Option<u64> {
+        self.callee_saved[Self::callee_saved_index(n)]
+    }
+
+    /// Set the value of the callee-saved register `x<n>`, for `n` in `19..=28`.
+    ///
+    /// Panics if `n` is outside of this range.
+    #[inline(always)]
+    pub fn set_x(&mut self, n: usize, value: Option<u64>) {
+        self.callee_saved[Self::callee_saved_index(n)] = value
+    }
+
+    fn callee_saved_index(n: usize) -> usize {
+        assert!(
+            (19..=28).contains(&n),
+            "x{n} is not a callee-saved register"
+        );
+        n - 19
+    }
+
+    pub(crate) fn callee_saved_mut(&mut self) -> &mut [Option<u64>; 10] {
+        &mut self.callee_saved
+    }
+}
+
+impl Debug for FullUnwindRegsAarch64 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut s = f.debug_struct("FullUnwindRegsAarch64");
+        s.field("lr", &HexNum(self.regs.lr))
+            .field("sp", &HexNum(self.regs.sp))
+            .field("fp", &HexNum(self.regs.fp));
+        const NAMES: [&str; 10] = [
+            "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28",
+        ];
+        for (name, value) in NAMES.iter().zip(self.callee_saved) {
+            s.field(name, &value.map(HexNum));
+        }
+        s.finish()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::aarch64::PtrAuthMask;
@@ -0,0 +1,21 @@
+use super::unwind_rule::UnwindRuleArm;
+use super::unwindregs::UnwindRegsArm;
+use crate::arch::Arch;
+use crate::breakpad::BreakpadUnwinding;
+use crate::orc::OrcUnwinding;
+use crate::pe::PeUnwinding;
+use crate::stack_scan::StackScanning;
+
+/// The 32-bit ARM CPU architecture.
+pub struct ArchArm;
+impl Arch for ArchArm {
+    type UnwindRule = UnwindRuleArm;
+    type UnwindRegs = UnwindRegsArm;
+}
+
+// Unwind data formats which are not supported on 32-bit ARM, and stack scanning, which is
+// not implemented for it. See the traits for what the default implementations do.
+impl BreakpadUnwinding for ArchArm {}
+impl PeUnwinding for ArchArm {}
+impl OrcUnwinding for ArchArm {}
+impl StackScanning for ArchArm {}
@@ -0,0 +1,81 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
+use super::unwind_rule::*;
+use crate::cache::*;
+use crate::rule_cache::SharedRuleCache;
+
+/// The unwinder cache type for [`UnwinderArm`](super::UnwinderArm).
+pub struct CacheArm<D: Deref<Target = [u8]>, P: AllocationPolicy<D> = MayAllocateDuringUnwind>(
+    pub Cache<D, UnwindRuleArm, P>,
+);
+
+impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> CacheArm<D, P> {
+    /// Create a new cache.
+    pub fn new() -> Self {
+        Self(Cache::new())
+    }
+
+    /// Create a new direct-mapped cache with room for `capacity` unwind rules.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_config(CacheConfig::with_capacity(capacity))
+    }
+
+    /// Create a new cache with the given size, associativity and hash function.
+    pub fn with_config(config: CacheConfig) -> Self {
+        Self(Cache::with_config(config))
+    }
+
+    /// Create a new cache which stores its unwind rules in `shared`. The rules are
+    /// shared with all other caches created from the same [`SharedCacheArm`], for
+    /// example with the caches of the other threads of a thread pool.
+    pub fn with_shared_rule_cache(shared: &SharedCacheArm) -> Self {
+        Self(Cache::with_shared_rule_cache(shared.0.clone()))
+    }
+
+    /// Returns a snapshot of the cache usage statistics. For a cache which was created
+    /// with `with_shared_rule_cache`, these are the statistics of the shared rule cache.
+    pub fn stats(&self) -> CacheStats {
+        self.0.rule_cache.stats()
+    }
+}
+
+impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> Default for CacheArm<D, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A rule cache for [`UnwinderArm`](super::UnwinderArm) which can be shared between
+/// threads.
+///
+/// Each thread still needs its own [`CacheArm`], created with
+/// [`CacheArm::with_shared_rule_cache`], because the unwind context for DWARF CFI
+/// evaluation cannot be shared. Cloning a `SharedCacheArm` is cheap and refers to the
+/// same shared cache.
+#[derive(Clone)]
+pub struct SharedCacheArm(Arc<SharedRuleCache<UnwindRuleArm>>);
+
+impl SharedCacheArm {
+    /// Create a new shared cache with 16 shards of 509 slots each.
+    pub fn new() -> Self {
+        Self::with_config(16, CacheConfig::with_capacity(16 * 509))
+    }
+
+    /// Create a new shared cache with `shard_count` shards. Each shard is protected by
+    /// its own lock. The capacity in `config` is the total capacity of all shards.
+    pub fn with_config(shard_count: usize, config: CacheConfig) -> Self {
+        Self(Arc::new(SharedRuleCache::with_config(shard_count, config)))
+    }
+
+    /// Returns a snapshot of the cache usage statistics, summed over all shards.
+    pub fn stats(&self) -> CacheStats {
+        self.0.stats()
+    }
+}
+
+impl Default for SharedCacheArm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,65 @@
+use super::arch::ArchArm;
+use crate::call_instruction::CallInstructionAnalysis;
+
+impl CallInstructionAnalysis for ArchArm {
+    fn is_preceded_by_call(text_bytes: &[u8]) -> bool {
+        // Return addresses have the Thumb bit cleared, so we don't know whether the
+        // caller is Thumb code or ARM code. Accept calls in either instruction set.
+        let halfword_at = |offset_from_end: usize| {
+            let start = text_bytes.len().checked_sub(offset_from_end)?;
+            Some(u16::from_le_bytes([
+                text_bytes[start],
+                text_bytes[start + 1],
+            ]))
+        };
+        // Thumb: blx <Rm>
+        if halfword_at(2).is_some_and(|hw| hw & 0xff87 == 0x4780) {
+            return true;
+        }
+        let (Some(hw1), Some(hw2)) = (halfword_at(4), halfword_at(2)) else {
+            return false;
+        };
+        // Thumb: bl <label>, blx <label>
+        if hw1 & 0xf800 == 0xf000 && (hw2 & 0xd000 == 0xd000 || hw2 & 0xd001 == 0xc000) {
+            return true;
+        }
+        let insn = u32::from(hw1) | (u32::from(hw2) << 16);
+        let condition = insn >> 28;
+        // ARM: bl <label>
+        (condition != 0xf && insn & 0x0f00_0000 == 0x0b00_0000)
+            // ARM: blx <label>
+            || insn & 0xfe00_0000 == 0xfa00_0000
+            // ARM: blx <Rm>
+            || (condition != 0xf && insn & 0x0fff_fff0 == 0x012f_ff30)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_preceded_by_call() {
+        let is_call = ArchArm::is_preceded_by_call;
+        // Thumb: blx r3
+        assert!(is_call(&[0x00, 0xbf, 0x98, 0x47]));
+        // Thumb: bl #0x1000
+        assert!(is_call(&[0x01, 0xf0, 0xfe, 0xff]));
+        // Thumb: blx #0x1000
+        assert!(is_call(&[0x01, 0xf0, 0xfe, 0xef]));
+        // ARM: bl #0x1000
+        assert!(is_call(&[0xfe, 0x03, 0x00, 0xeb]));
+        // ARM: blx r3
+        assert!(is_call(&[0x33, 0xff, 0x2f, 0xe1]));
+
+        // Thumb: bx lr
+        assert!(!is_call(&[0x70, 0x47]));
+        // Thumb: b.w #0x1000
+        assert!(!is_call(&[0x01, 0xf0, 0xfe, 0xbf]));
+        // ARM: b #0x1000
+        assert!(!is_call(&[0xfe, 0x03, 0x00, 0xea]));
+        // ARM: bx lr
+        assert!(!is_call(&[0x1e, 0xff, 0x2f, 0xe1]));
+        assert!(!is_call(&[0x98]));
+    }
+}
@@ -0,0 +1,186 @@
+use gimli::{
+    Arm, CfaRule, Encoding, EvaluationStorage, Reader, Register, RegisterRule,
+    UnwindContextStorage, UnwindTableRow,
+};
+
+use super::{arch::ArchArm, unwind_rule::UnwindRuleArm, unwindregs::UnwindRegsArm};
+
+use crate::unwind_result::UnwindResult;
+
+use crate::dwarf::{
+    eval_cfa_rule, eval_register_rule, ConversionError, DwarfUnwindRegs, DwarfUnwinderError,
+    DwarfUnwinding,
+};
+
+impl DwarfUnwindRegs for UnwindRegsArm {
+    fn get(&self, register: Register) -> Option<u64> {
+        match register {
+            Arm::SP => Some(self.sp()),
+            Arm::R7 => Some(self.fp()),
+            Arm::LR => Some(self.lr()),
+            _ => None,
+        }
+    }
+}
+
+impl DwarfUnwinding for ArchArm {
+    const ADDRESS_SIZE: u8 = 4;
+
+    fn unwind_frame<F, R, S>(
+        unwind_info: &UnwindTableRow<R, S>,
+        encoding: Encoding,
+        regs: &mut Self::UnwindRegs,
+        is_first_frame: bool,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<Self::UnwindRule>, DwarfUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+        R: Reader,
+        S: UnwindContextStorage<R> + EvaluationStorage<R>,
+    {
+        let cfa_rule = unwind_info.cfa();
+        let fp_rule = unwind_info.register(Arm::R7);
+        let lr_rule = unwind_info.register(Arm::LR);
+
+        let cfa = eval_cfa_rule::<R, _, S>(cfa_rule, encoding, regs)
+            .ok_or(DwarfUnwinderError::CouldNotRecoverCfa)?;
+
+        let lr = regs.lr();
+        let fp = regs.fp();
+        let sp = regs.sp();
+
+        // Only the lower 32 bits of each stack value are used.
+        let mut read_stack = |address| read_stack(address).map(|value| value & 0xffff_ffff);
+        let read_stack = &mut read_stack;
+
+        let (fp, lr) = if !is_first_frame {
+            if cfa <= sp {
+                return Err(DwarfUnwinderError::StackPointerMovedBackwards);
+            }
+            let fp = eval_register_rule::<R, _, _, S>(fp_rule, cfa, encoding, fp, regs, read_stack)
+                .ok_or(DwarfUnwinderError::CouldNotRecoverFramePointer)?;
+            let lr = eval_register_rule::<R, _, _, S>(lr_rule, cfa, encoding, lr, regs, read_stack)
+                .ok_or(DwarfUnwinderError::CouldNotRecoverReturnAddress)?;
+            (fp, lr)
+        } else {
+            // For the first frame, be more lenient when encountering errors.
+            let fp = eval_register_rule::<R, _, _, S>(fp_rule, cfa, encoding, fp, regs, read_stack)
+                .unwrap_or(fp);
+            let lr = eval_register_rule::<R, _, _, S>(lr_rule, cfa, encoding, lr, regs, read_stack)
+                .unwrap_or(lr);
+            (fp, lr)
+        };
+
+        regs.set_fp(fp);
+        regs.set_sp(cfa);
+        regs.set_lr(lr);
+
+        Ok(UnwindResult::Uncacheable(lr & !1))
+    }
+
+    fn unwind_rule_for_row<R, S>(
+        unwind_info: &UnwindTableRow<R, S>,
+    ) -> Result<Self::UnwindRule, ConversionError>
+    where
+        R: Reader,
+        S: UnwindContextStorage<R>,
+    {
+        translate_into_unwind_rule(
+            unwind_info.cfa(),
+            &unwind_info.register(Arm::R7),
+            &unwind_info.register(Arm::LR),
+        )
+    }
+
+    fn rule_if_uncovered_by_fde() -> Self::UnwindRule {
+        UnwindRuleArm::NoOpIfFirstFrameOtherwiseFp
+    }
+}
+
+fn register_rule_to_cfa_offset<R: gimli::Reader>(
+    rule: &RegisterRule<R>,
+) -> Result<Option<i64>, ConversionError> {
+    match *rule {
+        RegisterRule::Undefined | RegisterRule::SameValue => Ok(None),
+        RegisterRule::Offset(offset) => Ok(Some(offset)),
+        _ => Err(ConversionError::RegisterNotStoredRelativeToCfa),
+    }
+}
+
+fn translate_into_unwind_rule<R: gimli::Reader>(
+    cfa_rule: &CfaRule<R>,
+    fp_rule: &RegisterRule<R>,
+    lr_rule: &RegisterRule<R>,
+) -> Result<UnwindRuleArm, ConversionError> {
+    match cfa_rule {
+        CfaRule::RegisterAndOffset { register, offset } => match *register {
+            Arm::SP => {
+                let sp_offset_by_4 =
+                    u16::try_from(offset / 4).map_err(|_| ConversionError::SpOffsetDoesNotFit)?;
+                let lr_cfa_offset = register_rule_to_cfa_offset(lr_rule)?;
+                let fp_cfa_offset = register_rule_to_cfa_offset(fp_rule)?;
+                match (lr_cfa_offset, fp_cfa_offset) {
+                    (None, Some(_)) => Err(ConversionError::RestoringFpButNotLr),
+                    (None, None) => {
+                        if let RegisterRule::Undefined = lr_rule {
+                            // See the comment in the aarch64 translation: An undefined lr
+                            // usually means that the column was omitted.
+                            Ok(UnwindRuleArm::OffsetSpIfFirstFrameOtherwiseStackEndsHere {
+                                sp_offset_by_4,
+                            })
+                        } else {
+                            Ok(UnwindRuleArm::OffsetSp { sp_offset_by_4 })
+                        }
+                    }
+                    (Some(lr_cfa_offset), None) => {
+                        let lr_storage_offset_from_sp_by_4 =
+                            i16::try_from((offset + lr_cfa_offset) / 4)
+                                .map_err(|_| ConversionError::LrStorageOffsetDoesNotFit)?;
+                        Ok(UnwindRuleArm::OffsetSpAndRestoreLr {
+                            sp_offset_by_4,
+                            lr_storage_offset_from_sp_by_4,
+                        })
+                    }
+                    (Some(lr_cfa_offset), Some(fp_cfa_offset)) => {
+                        let lr_storage_offset_from_sp_by_4 =
+                            i16::try_from((offset + lr_cfa_offset) / 4)
+                                .map_err(|_| ConversionError::LrStorageOffsetDoesNotFit)?;
+                        let fp_storage_offset_from_sp_by_4 =
+                            i16::try_from((offset + fp_cfa_offset) / 4)
+                                .map_err(|_| ConversionError::FpStorageOffsetDoesNotFit)?;
+                        Ok(UnwindRuleArm::OffsetSpAndRestoreFpAndLr {
+                            sp_offset_by_4,
+                            fp_storage_offset_from_sp_by_4,
+                            lr_storage_offset_from_sp_by_4,
+                        })
+                    }
+                }
+            }
+            Arm::R7 => {
+                let lr_cfa_offset = register_rule_to_cfa_offset(lr_rule)?
+                    .ok_or(ConversionError::FramePointerRuleDoesNotRestoreLr)?;
+                let fp_cfa_offset = register_rule_to_cfa_offset(fp_rule)?
+                    .ok_or(ConversionError::FramePointerRuleDoesNotRestoreFp)?;
+                if *offset == 8 && fp_cfa_offset == -8 && lr_cfa_offset == -4 {
+                    Ok(UnwindRuleArm::UseFramePointer)
+                } else {
+                    let sp_offset_from_fp_by_4 = u16::try_from(offset / 4)
+                        .map_err(|_| ConversionError::SpOffsetFromFpDoesNotFit)?;
+                    let lr_storage_offset_from_fp_by_4 =
+                        i16::try_from((offset + lr_cfa_offset) / 4)
+                            .map_err(|_| ConversionError::LrStorageOffsetDoesNotFit)?;
+                    let fp_storage_offset_from_fp_by_4 =
+                        i16::try_from((offset + fp_cfa_offset) / 4)
+                            .map_err(|_| ConversionError::FpStorageOffsetDoesNotFit)?;
+                    Ok(UnwindRuleArm::UseFramepointerWithOffsets {
+                        sp_offset_from_fp_by_4,
+                        fp_storage_offset_from_fp_by_4,
+                        lr_storage_offset_from_fp_by_4,
+                    })
+                }
+            }
+            _ => Err(ConversionError::CfaIsOffsetFromUnknownRegister),
+        },
+        CfaRule::Expression(_) => Err(ConversionError::CfaIsExpression),
+    }
+}
@@ -0,0 +1,233 @@
+use super::arch::ArchArm;
+use super::unwind_rule::{read_stack_u32, UnwindRuleArm};
+use super::unwindregs::UnwindRegsArm;
+use crate::add_signed::checked_add_signed;
+use crate::ehabi::{ArmExidxUnwinderError, ArmExidxUnwinding, EhabiInstruction, EhabiInstructions};
+use crate::unwind_result::UnwindResult;
+
+impl ArmExidxUnwinding for ArchArm {
+    fn unwind_frame<F>(
+        instructions: EhabiInstructions,
+        is_first_frame: bool,
+        regs: &mut UnwindRegsArm,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<UnwindRuleArm>, ArmExidxUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        if let Some(rule) = translate_into_unwind_rule(instructions)? {
+            return Ok(UnwindResult::ExecRule(rule));
+        }
+
+        // Could not translate into a cacheable unwind rule. Execute the instructions
+        // directly.
+        let mut vsp = regs.sp();
+        let mut fp = regs.fp();
+        let mut lr = regs.lr();
+        let mut pc = None;
+        let mut restored_lr = false;
+        for instruction in instructions {
+            match instruction? {
+                EhabiInstruction::AdjustVsp(offset) => {
+                    vsp = checked_add_signed(vsp, i64::from(offset))
+                        .ok_or(ArmExidxUnwinderError::IntegerOverflow)?;
+                }
+                EhabiInstruction::PopCoreRegisters(mask) => {
+                    let mut popped_sp = None;
+                    for reg in 0..16 {
+                        if mask & (1 << reg) == 0 {
+                            continue;
+                        }
+                        let value = read_stack_u32(read_stack, vsp)
+                            .map_err(|_| ArmExidxUnwinderError::CouldNotReadStack(vsp))?;
+                        match reg {
+                            7 => fp = value,
+                            13 => popped_sp = Some(value),
+                            14 => {
+                                lr = value;
+                                restored_lr = true;
+                            }
+                            15 => pc = Some(value),
+                            _ => {}
+                        }
+                        vsp += 4;
+                    }
+                    if let Some(sp) = popped_sp {
+                        vsp = sp;
+                    }
+                }
+                EhabiInstruction::SetVspToRegister(reg) => match reg {
+                    7 => vsp = fp,
+                    13 => {}
+                    14 => vsp = lr,
+                    _ => return Err(ArmExidxUnwinderError::UntrackedRegister(reg)),
+                },
+                EhabiInstruction::RefuseToUnwind => {
+                    return Err(ArmExidxUnwinderError::RefuseToUnwind)
+                }
+                EhabiInstruction::Finish => break,
+            }
+        }
+
+        if !is_first_frame && pc.is_none() && !restored_lr {
+            // The return address would come from the lr register, which has been
+            // overwritten by the time we're in a caller frame.
+            return Err(ArmExidxUnwinderError::DidNotAdvance);
+        }
+        if !is_first_frame && vsp <= regs.sp() {
+            return Err(ArmExidxUnwinderError::DidNotAdvance);
+        }
+        let new_lr = pc.unwrap_or(lr);
+        regs.set_lr(new_lr);
+        regs.set_sp(vsp);
+        regs.set_fp(fp);
+        Ok(UnwindResult::Uncacheable(new_lr & !1))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Base {
+    Sp,
+    Fp,
+}
+
+/// A location relative to the stack pointer or the frame pointer, in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Location {
+    base: Base,
+    offset: i32,
+}
+
+impl Location {
+    fn offset_by_4(&self) -> Option<i16> {
+        if self.offset % 4 != 0 {
+            return None;
+        }
+        i16::try_from(self.offset / 4).ok()
+    }
+}
+
+/// Translate the instructions into an unwind rule by keeping track of where the
+/// registers we care about are stored, relative to the initial sp or fp.
+///
+/// Returns `Ok(None)` if the instructions do something that can't be expressed with
+/// an [`UnwindRuleArm`], for example if they pop registers relative to sp and then
+/// switch to fp.
+fn translate_into_unwind_rule(
+    instructions: EhabiInstructions,
+) -> Result<Option<UnwindRuleArm>, ArmExidxUnwinderError> {
+    let mut vsp = Location {
+        base: Base::Sp,
+        offset: 0,
+    };
+    let mut fp_location = None;
+    let mut lr_location = None;
+    let mut pc_location = None;
+    for instruction in instructions {
+        match instruction? {
+            EhabiInstruction::AdjustVsp(offset) => {
+                vsp.offset = vsp
+                    .offset
+                    .checked_add(offset)
+                    .ok_or(ArmExidxUnwinderError::IntegerOverflow)?;
+            }
+            EhabiInstruction::PopCoreRegisters(mask) => {
+                for reg in 0..16 {
+                    if mask & (1 << reg) == 0 {
+                        continue;
+                    }
+                    match reg {
+                        7 => fp_location = Some(vsp),
+                        13 => return Ok(None),
+                        14 => lr_location = Some(vsp),
+                        15 => pc_location = Some(vsp),
+                        _ => {}
+                    }
+                    vsp.offset = vsp
+                        .offset
+                        .checked_add(4)
+                        .ok_or(ArmExidxUnwinderError::IntegerOverflow)?;
+                }
+            }
+            EhabiInstruction::SetVspToRegister(reg) => match reg {
+                7 => {
+                    if fp_location.is_some() || lr_location.is_some() || pc_location.is_some() {
+                        // The locations would be relative to different bases.
+                        return Ok(None);
+                    }
+                    vsp = Location {
+                        base: Base::Fp,
+                        offset: 0,
+                    };
+                }
+                13 => {}
+                14 => return Ok(None),
+                _ => return Err(ArmExidxUnwinderError::UntrackedRegister(reg)),
+            },
+            EhabiInstruction::RefuseToUnwind => return Err(ArmExidxUnwinderError::RefuseToUnwind),
+            EhabiInstruction::Finish => break,
+        }
+    }
+
+    // The return address is pc if it was popped, otherwise lr.
+    let ra_location = pc_location.or(lr_location);
+
+    let rule = match vsp.base {
+        Base::Sp => {
+            let sp_offset_by_4 = match vsp.offset_by_4().and_then(|o| u16::try_from(o).ok()) {
+                Some(offset) => offset,
+                None => return Ok(None),
+            };
+            match (ra_location, fp_location) {
+                (None, None) if sp_offset_by_4 == 0 => UnwindRuleArm::NoOp,
+                (None, None) => UnwindRuleArm::OffsetSp { sp_offset_by_4 },
+                (None, Some(_)) => return Ok(None),
+                (Some(ra_location), None) => match ra_location.offset_by_4() {
+                    Some(lr_storage_offset_from_sp_by_4) => UnwindRuleArm::OffsetSpAndRestoreLr {
+                        sp_offset_by_4,
+                        lr_storage_offset_from_sp_by_4,
+                    },
+                    None => return Ok(None),
+                },
+                (Some(ra_location), Some(fp_location)) => {
+                    match (ra_location.offset_by_4(), fp_location.offset_by_4()) {
+                        (
+                            Some(lr_storage_offset_from_sp_by_4),
+                            Some(fp_storage_offset_from_sp_by_4),
+                        ) => UnwindRuleArm::OffsetSpAndRestoreFpAndLr {
+                            sp_offset_by_4,
+                            fp_storage_offset_from_sp_by_4,
+                            lr_storage_offset_from_sp_by_4,
+                        },
+                        _ => return Ok(None),
+                    }
+                }
+            }
+        }
+        Base::Fp => {
+            let (ra_location, fp_location) = match (ra_location, fp_location) {
+                (Some(ra_location), Some(fp_location)) => (ra_location, fp_location),
+                _ => return Ok(None),
+            };
+            let offsets = (
+                vsp.offset_by_4().and_then(|o| u16::try_from(o).ok()),
+                fp_location.offset_by_4(),
+                ra_location.offset_by_4(),
+            );
+            match offsets {
+                (Some(2), Some(0), Some(1)) => UnwindRuleArm::UseFramePointer,
+                (
+                    Some(sp_offset_from_fp_by_4),
+                    Some(fp_storage_offset_from_fp_by_4),
+                    Some(lr_storage_offset_from_fp_by_4),
+                ) => UnwindRuleArm::UseFramepointerWithOffsets {
+                    sp_offset_from_fp_by_4,
+                    fp_storage_offset_from_fp_by_4,
+                    lr_storage_offset_from_fp_by_4,
+                },
+                _ => return Ok(None),
+            }
+        }
+    };
+    Ok(Some(rule))
+}
@@ -0,0 +1,22 @@
+use super::arch::ArchArm;
+use crate::instruction_analysis::InstructionAnalysis;
+
+impl InstructionAnalysis for ArchArm {
+    // Instruction analysis is not implemented for 32-bit ARM yet. Both ARM EHABI and
+    // DWARF CFI on Linux / Android describe every instruction, so this is only missed
+    // for code without unwind information.
+
+    fn rule_from_prologue_analysis(
+        _text_bytes: &[u8],
+        _pc_offset: usize,
+    ) -> Option<Self::UnwindRule> {
+        None
+    }
+
+    fn rule_from_epilogue_analysis(
+        _text_bytes: &[u8],
+        _pc_offset: usize,
+    ) -> Option<Self::UnwindRule> {
+        None
+    }
+}
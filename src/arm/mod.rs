@@ -0,0 +1,16 @@
+mod arch;
+mod cache;
+mod call_instruction;
+mod dwarf;
+mod ehabi;
+mod instruction_analysis;
+mod macho;
+mod unwind_rule;
+mod unwinder;
+mod unwindregs;
+
+pub use arch::*;
+pub use cache::*;
+pub use unwind_rule::*;
+pub use unwinder::*;
+pub use unwindregs::*;
@@ -0,0 +1,250 @@
+use super::unwindregs::UnwindRegsArm;
+use crate::add_signed::checked_add_signed;
+use crate::error::Error;
+
+use crate::unwind_rule::UnwindRule;
+
+/// For all of these: return address is the new lr value, with the Thumb bit cleared.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnwindRuleArm {
+    /// (sp, fp, lr) = (sp, fp, lr)
+    /// Only possible for the first frame. Subsequent frames must get the
+    /// return address from somewhere other than the lr register to avoid
+    /// infinite loops.
+    NoOp,
+    /// (sp, fp, lr) = if is_first_frame (sp, fp, lr) else (fp + 8, *fp, *(fp + 4))
+    /// Used as a fallback rule.
+    NoOpIfFirstFrameOtherwiseFp,
+    /// (sp, fp, lr) = (sp + 4x, fp, lr)
+    /// Only possible for the first frame. Subsequent frames must get the
+    /// return address from somewhere other than the lr register to avoid
+    /// infinite loops.
+    OffsetSp { sp_offset_by_4: u16 },
+    /// (sp, fp, lr) = (sp + 4x, fp, lr) if is_first_frame
+    /// This rule reflects an ambiguity in DWARF CFI information. When the
+    /// return address is "undefined" because it was omitted, it could mean
+    /// "same value", but this is only allowed for the first frame.
+    OffsetSpIfFirstFrameOtherwiseStackEndsHere { sp_offset_by_4: u16 },
+    /// (sp, fp, lr) = (sp + 4x, fp, *(sp + 4y))
+    OffsetSpAndRestoreLr {
+        sp_offset_by_4: u16,
+        lr_storage_offset_from_sp_by_4: i16,
+    },
+    /// (sp, fp, lr) = (sp + 4x, *(sp + 4y), *(sp + 4z))
+    OffsetSpAndRestoreFpAndLr {
+        sp_offset_by_4: u16,
+        fp_storage_offset_from_sp_by_4: i16,
+        lr_storage_offset_from_sp_by_4: i16,
+    },
+    /// (sp, fp, lr) = (fp + 8, *fp, *(fp + 4))
+    UseFramePointer,
+    /// (sp, fp, lr) = (fp + 4x, *(fp + 4y), *(fp + 4z))
+    UseFramepointerWithOffsets {
+        sp_offset_from_fp_by_4: u16,
+        fp_storage_offset_from_fp_by_4: i16,
+        lr_storage_offset_from_fp_by_4: i16,
+    },
+}
+
+/// Read a 32-bit value from the stack. Only the lower 32 bits of the value returned
+/// by `read_stack` are used.
+#[inline(always)]
+pub(crate) fn read_stack_u32<F>(read_stack: &mut F, address: u64) -> Result<u64, Error>
+where
+    F: FnMut(u64) -> Result<u64, ()>,
+{
+    match read_stack(address) {
+        Ok(value) => Ok(value & 0xffff_ffff),
+        Err(()) => Err(Error::CouldNotReadStack(address)),
+    }
+}
+
+/// Add a byte offset to a 32-bit address, checking for overflow of the 32-bit
+/// address space.
+#[inline(always)]
+fn offset_address(address: u64, offset_by_4: i64) -> Result<u64, Error> {
+    checked_add_signed(address, offset_by_4 * 4)
+        .filter(|address| *address <= u64::from(u32::MAX))
+        .ok_or(Error::IntegerOverflow)
+}
+
+impl UnwindRule for UnwindRuleArm {
+    type UnwindRegs = UnwindRegsArm;
+
+    fn rule_for_stub_functions() -> Self {
+        UnwindRuleArm::NoOp
+    }
+    fn rule_for_function_start() -> Self {
+        UnwindRuleArm::NoOp
+    }
+    fn fallback_rule() -> Self {
+        UnwindRuleArm::UseFramePointer
+    }
+
+    fn exec<F>(
+        self,
+        is_first_frame: bool,
+        regs: &mut UnwindRegsArm,
+        read_stack: &mut F,
+    ) -> Result<Option<u64>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let lr = regs.lr();
+        let sp = regs.sp();
+        let fp = regs.fp();
+
+        let (new_lr, new_sp, new_fp) = match self {
+            UnwindRuleArm::NoOp => {
+                if !is_first_frame {
+                    return Err(Error::DidNotAdvance);
+                }
+                (lr, sp, fp)
+            }
+            UnwindRuleArm::NoOpIfFirstFrameOtherwiseFp => {
+                if is_first_frame {
+                    (lr, sp, fp)
+                } else {
+                    let new_sp = offset_address(fp, 2)?;
+                    let new_lr = read_stack_u32(read_stack, offset_address(fp, 1)?)?;
+                    let new_fp = read_stack_u32(read_stack, fp)?;
+                    if new_sp <= sp {
+                        return Err(Error::FramepointerUnwindingMovedBackwards);
+                    }
+                    (new_lr, new_sp, new_fp)
+                }
+            }
+            UnwindRuleArm::OffsetSpIfFirstFrameOtherwiseStackEndsHere { sp_offset_by_4 } => {
+                if !is_first_frame {
+                    return Ok(None);
+                }
+                let new_sp = offset_address(sp, i64::from(sp_offset_by_4))?;
+                (lr, new_sp, fp)
+            }
+            UnwindRuleArm::OffsetSp { sp_offset_by_4 } => {
+                if !is_first_frame {
+                    return Err(Error::DidNotAdvance);
+                }
+                let new_sp = offset_address(sp, i64::from(sp_offset_by_4))?;
+                (lr, new_sp, fp)
+            }
+            UnwindRuleArm::OffsetSpAndRestoreLr {
+                sp_offset_by_4,
+                lr_storage_offset_from_sp_by_4,
+            } => {
+                let new_sp = offset_address(sp, i64::from(sp_offset_by_4))?;
+                let lr_location = offset_address(sp, i64::from(lr_storage_offset_from_sp_by_4))?;
+                let new_lr = read_stack_u32(read_stack, lr_location)?;
+                (new_lr, new_sp, fp)
+            }
+            UnwindRuleArm::OffsetSpAndRestoreFpAndLr {
+                sp_offset_by_4,
+                fp_storage_offset_from_sp_by_4,
+                lr_storage_offset_from_sp_by_4,
+            } => {
+                let new_sp = offset_address(sp, i64::from(sp_offset_by_4))?;
+                let lr_location = offset_address(sp, i64::from(lr_storage_offset_from_sp_by_4))?;
+                let new_lr = read_stack_u32(read_stack, lr_location)?;
+                let fp_location = offset_address(sp, i64::from(fp_storage_offset_from_sp_by_4))?;
+                let new_fp = read_stack_u32(read_stack, fp_location)?;
+                (new_lr, new_sp, new_fp)
+            }
+            UnwindRuleArm::UseFramePointer => {
+                // Do a frame pointer stack walk. Thumb code which maintains a frame pointer
+                // (this includes all code compiled for Android and iOS, unless frame pointers
+                // are explicitly omitted) pushes the caller's r7 and lr next to each other
+                // and then points r7 at the saved r7:
+                //
+                // push  {r4, r5, r6, r7, lr}
+                // add   r7, sp, #12
+                //
+                // So *fp is the caller's frame pointer, and *(fp + 4) is the return address.
+                let new_sp = offset_address(fp, 2)?;
+                let new_lr = read_stack_u32(read_stack, offset_address(fp, 1)?)?;
+                let new_fp = read_stack_u32(read_stack, fp)?;
+                if new_fp == 0 {
+                    return Ok(None);
+                }
+                if new_fp <= fp || new_sp <= sp {
+                    return Err(Error::FramepointerUnwindingMovedBackwards);
+                }
+                (new_lr, new_sp, new_fp)
+            }
+            UnwindRuleArm::UseFramepointerWithOffsets {
+                sp_offset_from_fp_by_4,
+                fp_storage_offset_from_fp_by_4,
+                lr_storage_offset_from_fp_by_4,
+            } => {
+                let new_sp = offset_address(fp, i64::from(sp_offset_from_fp_by_4))?;
+                let lr_location = offset_address(fp, i64::from(lr_storage_offset_from_fp_by_4))?;
+                let new_lr = read_stack_u32(read_stack, lr_location)?;
+                let fp_location = offset_address(fp, i64::from(fp_storage_offset_from_fp_by_4))?;
+                let new_fp = read_stack_u32(read_stack, fp_location)?;
+
+                if new_fp == 0 {
+                    return Ok(None);
+                }
+                if new_fp <= fp || new_sp <= sp {
+                    return Err(Error::FramepointerUnwindingMovedBackwards);
+                }
+                (new_lr, new_sp, new_fp)
+            }
+        };
+        // Return addresses into Thumb code have the lowest bit set. Strip it so that
+        // the return address points at the instruction after the call.
+        let return_address = new_lr & !1;
+        if return_address == 0 {
+            return Ok(None);
+        }
+        if !is_first_frame && new_sp == sp {
+            return Err(Error::DidNotAdvance);
+        }
+        regs.set_lr(new_lr);
+        regs.set_sp(new_sp);
+        regs.set_fp(new_fp);
+
+        Ok(Some(return_address))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_basic() {
+        let stack = [
+            1, 2, 3, 4, 0x40, 0x100201, 5, 6, 0x70, 0x100100, 7, 8, 9, 10, 0x0, 0x0,
+        ];
+        let mut read_stack = |addr| Ok(stack[(addr / 4) as usize]);
+        let mut regs = UnwindRegsArm::new(0x100301, 0x8, 0x10);
+        let res = UnwindRuleArm::NoOp.exec(true, &mut regs, &mut read_stack);
+        assert_eq!(res, Ok(Some(0x100300)));
+        assert_eq!(regs.sp(), 0x8);
+        let res = UnwindRuleArm::UseFramePointer.exec(false, &mut regs, &mut read_stack);
+        assert_eq!(res, Ok(Some(0x100200)));
+        assert_eq!(regs.sp(), 0x18);
+        assert_eq!(regs.fp(), 0x40);
+        let res = UnwindRuleArm::OffsetSpAndRestoreFpAndLr {
+            sp_offset_by_4: 4,
+            fp_storage_offset_from_sp_by_4: 2,
+            lr_storage_offset_from_sp_by_4: 3,
+        }
+        .exec(false, &mut regs, &mut read_stack);
+        assert_eq!(res, Ok(Some(0x100100)));
+        assert_eq!(regs.sp(), 0x28);
+        assert_eq!(regs.fp(), 0x70);
+    }
+
+    #[test]
+    fn test_ignores_upper_bits() {
+        let mut stack = [0; 10];
+        stack[0] = 0xffff_ffff_0000_0020;
+        stack[1] = 0xffff_ffff_0010_0101;
+        let mut read_stack = |addr| Ok(stack[(addr / 4) as usize]);
+        let mut regs = UnwindRegsArm::new(0x100301, 0x0, 0x0);
+        let res = UnwindRuleArm::UseFramePointer.exec(false, &mut regs, &mut read_stack);
+        assert_eq!(res, Ok(Some(0x100100)));
+        assert_eq!(regs.fp(), 0x20);
+    }
+}
@@ -0,0 +1,115 @@
+use std::ops::Deref;
+
+use crate::{
+    unwinder::UnwinderInternal, AllocationPolicy, Error, FrameAddress, FrameInfo,
+    FrameInfoUnwinder, IntoStackReadError, MayAllocateDuringUnwind, Module, ModuleError,
+    ReturnAddressChecks, Unwinder,
+};
+
+use super::{ArchArm, CacheArm, UnwindRegsArm, UnwindRuleArm};
+
+/// The unwinder for the 32-bit ARM CPU architecture. Use the [`Unwinder`] trait for
+/// unwinding.
+///
+/// Registers and stack values are 32 bits wide on this architecture. The `read_stack`
+/// callback is called with 4-byte aligned addresses, and only the lower 32 bits of the
+/// value it returns are used.
+///
+/// Type arguments:
+///
+///  - `D`: The type for unwind section data in the modules. See [`Module`].
+/// -  `P`: The [`AllocationPolicy`].
+pub struct UnwinderArm<D: Deref<Target = [u8]>, P: AllocationPolicy<D> = MayAllocateDuringUnwind>(
+    UnwinderInternal<D, ArchArm, P>,
+);
+
+impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> Default for UnwinderArm<D, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> UnwinderArm<D, P> {
+    /// Create an unwinder for a process.
+    pub fn new() -> Self {
+        Self(UnwinderInternal::new())
+    }
+}
+
+impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> Unwinder for UnwinderArm<D, P> {
+    type UnwindRegs = UnwindRegsArm;
+    type Cache = CacheArm<D, P>;
+    type Module = Module<D>;
+
+    fn add_module(&mut self, module: Module<D>) {
+        self.0.add_module(module);
+    }
+
+    fn try_add_module(&mut self, module: Module<D>) -> Result<(), ModuleError> {
+        self.0.try_add_module(module)
+    }
+
+    fn remove_module(&mut self, module_address_range_start: u64) {
+        self.0.remove_module(module_address_range_start);
+    }
+
+    fn max_known_code_address(&self) -> u64 {
+        self.0.max_known_code_address()
+    }
+
+    fn check_return_address(
+        &self,
+        address: u64,
+        checks: &ReturnAddressChecks,
+    ) -> Result<(), Error> {
+        self.0.check_return_address(address, checks)
+    }
+
+    fn unwind_frame<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut UnwindRegsArm,
+        cache: &mut CacheArm<D, P>,
+        read_stack: &mut F,
+    ) -> Result<Option<u64>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        self.0.unwind_frame(address, regs, &mut cache.0, read_stack)
+    }
+
+    fn unwind_frame_address<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut UnwindRegsArm,
+        cache: &mut CacheArm<D, P>,
+        read_stack: &mut F,
+    ) -> Result<Option<FrameAddress>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        self.0
+            .unwind_frame_address(address, regs, &mut cache.0, read_stack)
+    }
+}
+
+impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> FrameInfoUnwinder for UnwinderArm<D, P> {
+    type UnwindRule = UnwindRuleArm;
+
+    fn unwind_frame_with_info<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut UnwindRegsArm,
+        cache: &mut CacheArm<D, P>,
+        read_stack: &mut F,
+    ) -> Result<Option<FrameInfo<Self::UnwindRule>>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        self.0
+            .unwind_frame_with_info(address, regs, &mut cache.0, read_stack)
+    }
+}
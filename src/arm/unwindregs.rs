@@ -0,0 +1,69 @@
+use std::fmt::Debug;
+
+use crate::display_utils::HexNum;
+
+/// The registers used for unwinding on 32-bit ARM. We only need lr (r14), sp (r13),
+/// and fp (r7).
+///
+/// r7 is the frame pointer register in Thumb code, which is what Android and iOS use.
+/// Values are stored as `u64` for consistency with the other architectures, but only
+/// the lower 32 bits are meaningful.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct UnwindRegsArm {
+    lr: u64,
+    sp: u64,
+    fp: u64,
+}
+
+impl UnwindRegsArm {
+    /// Create a set of unwind register values.
+    pub fn new(lr: u64, sp: u64, fp: u64) -> Self {
+        Self { lr, sp, fp }
+    }
+
+    /// Get the stack pointer value (r13).
+    #[inline(always)]
+    pub fn sp(&self) -> u64 {
+        self.sp
+    }
+
+    /// Set the stack pointer value (r13).
+    #[inline(always)]
+    pub fn set_sp(&mut self, sp: u64) {
+        self.sp = sp
+    }
+
+    /// Get the frame pointer value (r7).
+    #[inline(always)]
+    pub fn fp(&self) -> u64 {
+        self.fp
+    }
+
+    /// Set the frame pointer value (r7).
+    #[inline(always)]
+    pub fn set_fp(&mut self, fp: u64) {
+        self.fp = fp
+    }
+
+    /// Get the lr register value (r14).
+    #[inline(always)]
+    pub fn lr(&self) -> u64 {
+        self.lr
+    }
+
+    /// Set the lr register value (r14).
+    #[inline(always)]
+    pub fn set_lr(&mut self, lr: u64) {
+        self.lr = lr
+    }
+}
+
+impl Debug for UnwindRegsArm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UnwindRegsArm")
+            .field("lr", &HexNum(self.lr))
+            .field("sp", &HexNum(self.sp))
+            .field("fp", &HexNum(self.fp))
+            .finish()
+    }
+}
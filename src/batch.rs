@@ -0,0 +1,199 @@
+use std::num::NonZeroU64;
+use std::ops::Range;
+
+use crate::code_address::FrameAddress;
+use crate::error::Error;
+use crate::return_address_checks::ReturnAddressChecks;
+use crate::stack_memory::IntoStackReadError;
+use crate::unwinder::{UnwindIterator, Unwinder};
+
+/// Options for [`Unwinder::unwind_batch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// Unwind the samples in the order of their initial instruction pointer instead of
+    /// in the order in which they were given. Samples which start at the same or nearby
+    /// addresses usually go through the same functions, so this makes it more likely
+    /// that the unwind rules they need are still in the cache. This requires collecting
+    /// all samples before unwinding the first one. The results are always in the order
+    /// of the samples.
+    pub sort_by_pc: bool,
+    /// The maximum number of frames per stack, including the instruction pointer. Stacks
+    /// which reach this limit end with [`StackStatus::FrameLimit`].
+    pub max_frames: usize,
+    /// The checks for each return address, see [`UnwindIterator::with_return_address_checks`].
+    /// Stacks with a rejected return address end with
+    /// [`Error::ImplausibleReturnAddress`].
+    pub return_address_checks: ReturnAddressChecks,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            sort_by_pc: false,
+            max_frames: 1024,
+            return_address_checks: ReturnAddressChecks::default(),
+        }
+    }
+}
+
+/// How unwinding a stack of a batch ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackStatus {
+    /// The root function was found. This corresponds to `Ok(None)` from
+    /// [`UnwindIterator::next`].
+    Complete,
+    /// Unwinding stopped with an error. The stack contains the frames found before the
+    /// error, and may be truncated.
+    Error(Error),
+    /// The stack reached [`BatchOptions::max_frames`].
+    FrameLimit,
+}
+
+/// The stacks of a batch of samples, returned from [`Unwinder::unwind_batch`].
+///
+/// The addresses of all stacks are stored in one array. The first address of each stack
+/// is the instruction pointer, and the others are usually return addresses, like the
+/// frames of [`UnwindIterator`]. The address after a signal frame is the instruction
+/// pointer of the interrupted code instead, see [`Unwinder::unwind_frame_address`]. Use
+/// [`UnwindBatch::frames`] or [`UnwindBatch::frame_address`] to tell the two apart.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnwindBatch {
+    addresses: Vec<u64>,
+    /// One bit per entry of `addresses`, which is set if the address is an instruction
+    /// pointer rather than a return address.
+    instruction_pointer_bits: Vec<u64>,
+    /// Per sample, the range of the sample's stack in `addresses`.
+    ranges: Vec<Range<usize>>,
+    statuses: Vec<StackStatus>,
+}
+
+impl UnwindBatch {
+    /// The number of samples.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Whether the batch has no samples.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The addresses of the stack of sample `index`, starting with the instruction pointer.
+    pub fn stack(&self, index: usize) -> &[u64] {
+        &self.addresses[self.ranges[index].clone()]
+    }
+
+    /// The frames of the stack of sample `index`, starting with the instruction pointer.
+    /// These are the same addresses as in [`UnwindBatch::stack`], together with their kind.
+    pub fn frames(&self, index: usize) -> impl Iterator<Item = FrameAddress> + '_ {
+        self.ranges[index]
+            .clone()
+            .map(|address_index| self.frame_address(address_index))
+    }
+
+    /// The frame for the entry `address_index` of [`UnwindBatch::addresses`]: whether
+    /// the address is an instruction pointer or a return address.
+    pub fn frame_address(&self, address_index: usize) -> FrameAddress {
+        let address = self.addresses[address_index];
+        let is_instruction_pointer =
+            self.instruction_pointer_bits[address_index / 64] & (1 << (address_index % 64)) != 0;
+        match NonZeroU64::new(address) {
+            Some(address) if !is_instruction_pointer => FrameAddress::ReturnAddress(address),
+            _ => FrameAddress::InstructionPointer(address),
+        }
+    }
+
+    /// How unwinding the stack of sample `index` ended.
+    pub fn status(&self, index: usize) -> StackStatus {
+        self.statuses[index]
+    }
+
+    /// The addresses of all stacks, in the order in which they were unwound.
+    pub fn addresses(&self) -> &[u64] {
+        &self.addresses
+    }
+
+    /// Iterates over the stacks and their statuses, in the order of the samples.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u64], StackStatus)> + '_ {
+        self.ranges
+            .iter()
+            .zip(&self.statuses)
+            .map(|(range, status)| (&self.addresses[range.clone()], *status))
+    }
+
+    fn push_stack<U, F, E>(
+        &mut self,
+        unwinder: &U,
+        sample: (u64, U::UnwindRegs, F),
+        cache: &mut U::Cache,
+        options: &BatchOptions,
+    ) -> (Range<usize>, StackStatus)
+    where
+        U: Unwinder + ?Sized,
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        let (pc, regs, mut read_stack) = sample;
+        let start = self.addresses.len();
+        let mut iter = UnwindIterator::new(unwinder, pc, regs, cache, &mut read_stack)
+            .with_return_address_checks(options.return_address_checks);
+        let status = loop {
+            if self.addresses.len() - start >= options.max_frames {
+                break StackStatus::FrameLimit;
+            }
+            match iter.next() {
+                Ok(Some(address)) => self.push_address(address),
+                Ok(None) => break StackStatus::Complete,
+                Err(err) => break StackStatus::Error(err),
+            }
+        };
+        (start..self.addresses.len(), status)
+    }
+
+    fn push_address(&mut self, address: FrameAddress) {
+        let address_index = self.addresses.len();
+        self.addresses.push(address.address());
+        if address_index % 64 == 0 {
+            self.instruction_pointer_bits.push(0);
+        }
+        if !address.is_return_address() {
+            self.instruction_pointer_bits[address_index / 64] |= 1 << (address_index % 64);
+        }
+    }
+}
+
+pub(crate) fn unwind_batch<U, I, F, E>(
+    unwinder: &U,
+    samples: I,
+    cache: &mut U::Cache,
+    options: &BatchOptions,
+) -> UnwindBatch
+where
+    U: Unwinder + ?Sized,
+    I: IntoIterator<Item = (u64, U::UnwindRegs, F)>,
+    F: FnMut(u64) -> Result<u64, E>,
+    E: IntoStackReadError,
+{
+    let mut batch = UnwindBatch::default();
+    if !options.sort_by_pc {
+        for sample in samples {
+            let (range, status) = batch.push_stack(unwinder, sample, cache, options);
+            batch.ranges.push(range);
+            batch.statuses.push(status);
+        }
+        return batch;
+    }
+
+    let mut samples: Vec<_> = samples.into_iter().map(Some).collect();
+    let mut order: Vec<usize> = (0..samples.len()).collect();
+    order.sort_by_key(|&index| samples[index].as_ref().map(|sample| sample.0));
+    batch.ranges = vec![0..0; samples.len()];
+    batch.statuses = vec![StackStatus::Complete; samples.len()];
+    for index in order {
+        let sample = samples[index].take().unwrap();
+        let (range, status) = batch.push_stack(unwinder, sample, cache, options);
+        batch.ranges[index] = range;
+        batch.statuses[index] = status;
+    }
+    batch
+}
@@ -0,0 +1,703 @@
+use std::marker::PhantomData;
+
+use crate::arch::Arch;
+use crate::unwind_result::UnwindResult;
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakpadUnwinderError {
+    #[error("The STACK record at offset {0} of the symbol file is not valid UTF-8")]
+    RecordNotUtf8(u32),
+
+    #[error("The STACK CFI record does not have a rule for the CFA")]
+    NoCfaRule,
+
+    #[error("The STACK CFI record does not have a rule for the return address")]
+    NoReturnAddressRule,
+
+    #[error("The STACK WIN program did not compute the register {0}")]
+    ProgramDidNotSetRegister(&'static str),
+
+    #[error("A postfix expression referred to an unknown register or variable")]
+    UnknownVariable,
+
+    #[error("A postfix expression was malformed")]
+    BadExpression,
+
+    #[error("A postfix expression had too many operands or variables")]
+    ExpressionTooComplex,
+
+    #[error("Could not read stack memory at 0x{0:x}")]
+    CouldNotReadStack(u64),
+
+    #[error("Stack pointer moved backwards")]
+    StackPointerMovedBackwards,
+
+    #[error("Neither the code address nor the stack pointer changed, would loop")]
+    DidNotAdvance,
+
+    #[error("Breakpad symbol files are not supported for this CPU architecture")]
+    UnsupportedArch,
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakpadIndexError {
+    #[error("Could not parse the STACK record in line {0} of the symbol file")]
+    BadStackRecord(u32),
+
+    #[error("The symbol file is larger than 4GB")]
+    SymbolFileTooBig,
+}
+
+/// The address range and the location of the text of a STACK record in the symbol file.
+#[derive(Clone, Copy, Debug)]
+struct IndexEntry {
+    start: u32,
+    end: u32,
+    text_start: u32,
+    text_end: u32,
+}
+
+/// A binary search table for the `STACK CFI INIT` and `STACK WIN` records of a Breakpad
+/// symbol file. We generate this whenever a module with a symbol file is added.
+/// The records themselves are only parsed during unwinding.
+pub struct BreakpadIndex {
+    /// Contains one entry for every `STACK CFI INIT` record, sorted by start address.
+    /// The entry's text covers the `STACK CFI` delta records which follow it.
+    cfi: Vec<IndexEntry>,
+    /// Contains one entry for every `STACK WIN` record which has a program string,
+    /// sorted by start address.
+    win: Vec<IndexEntry>,
+}
+
+impl BreakpadIndex {
+    pub fn try_new(data: &[u8]) -> Result<Self, BreakpadIndexError> {
+        let mut cfi: Vec<IndexEntry> = Vec::new();
+        let mut win = Vec::new();
+        let mut line_start = 0;
+        for (line_index, line) in data.split(|b| *b == b'\n').enumerate() {
+            let text_start =
+                u32::try_from(line_start).map_err(|_| BreakpadIndexError::SymbolFileTooBig)?;
+            line_start += line.len() + 1;
+            let text_end =
+                u32::try_from(line_start - 1).map_err(|_| BreakpadIndexError::SymbolFileTooBig)?;
+            let bad_record = || BreakpadIndexError::BadStackRecord(line_index as u32 + 1);
+
+            if let Some(rest) = line.strip_prefix(b"STACK CFI INIT ") {
+                let mut fields = std::str::from_utf8(rest)
+                    .map_err(|_| bad_record())?
+                    .split_ascii_whitespace();
+                let start = parse_hex(fields.next()).ok_or_else(bad_record)?;
+                let size = parse_hex(fields.next()).ok_or_else(bad_record)?;
+                cfi.push(IndexEntry {
+                    start,
+                    end: start.saturating_add(size),
+                    text_start,
+                    text_end,
+                });
+            } else if line.starts_with(b"STACK CFI ") {
+                // A delta record. It belongs to the preceding INIT record.
+                let entry = cfi.last_mut().ok_or_else(bad_record)?;
+                if entry.text_end + 1 != text_start {
+                    return Err(bad_record());
+                }
+                entry.text_end = text_end;
+            } else if let Some(rest) = line.strip_prefix(b"STACK WIN ") {
+                let mut fields = std::str::from_utf8(rest)
+                    .map_err(|_| bad_record())?
+                    .split_ascii_whitespace();
+                let _record_type = fields.next();
+                let start = parse_hex(fields.next()).ok_or_else(bad_record)?;
+                let size = parse_hex(fields.next()).ok_or_else(bad_record)?;
+                let has_program_string = fields.nth(6).ok_or_else(bad_record)?;
+                if has_program_string == "1" {
+                    win.push(IndexEntry {
+                        start,
+                        end: start.saturating_add(size),
+                        text_start,
+                        text_end,
+                    });
+                }
+            }
+        }
+        cfi.sort_by_key(|entry| entry.start);
+        win.sort_by_key(|entry| entry.start);
+        Ok(Self { cfi, win })
+    }
+
+    /// Find the STACK record covering `rel_lookup_address`. `STACK CFI` records are
+    /// preferred over `STACK WIN` records.
+    pub fn lookup<'a>(
+        &self,
+        data: &'a [u8],
+        rel_lookup_address: u32,
+    ) -> Result<Option<BreakpadStackRecord<'a>>, BreakpadUnwinderError> {
+        if let Some(entry) = lookup_entry(&self.cfi, rel_lookup_address) {
+            let text = record_text(data, entry)?;
+            return Ok(Some(BreakpadStackRecord::Cfi(BreakpadCfiRecord {
+                text,
+                address: rel_lookup_address,
+            })));
+        }
+        if let Some(entry) = lookup_entry(&self.win, rel_lookup_address) {
+            let text = record_text(data, entry)?;
+            return Ok(BreakpadWinRecord::parse(text).map(BreakpadStackRecord::Win));
+        }
+        Ok(None)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cfi.is_empty() && self.win.is_empty()
+    }
+}
+
+fn lookup_entry(entries: &[IndexEntry], address: u32) -> Option<&IndexEntry> {
+    let index = entries
+        .partition_point(|entry| entry.start <= address)
+        .checked_sub(1)?;
+    let entry = &entries[index];
+    (address < entry.end).then_some(entry)
+}
+
+fn record_text<'a>(data: &'a [u8], entry: &IndexEntry) -> Result<&'a str, BreakpadUnwinderError> {
+    data.get(entry.text_start as usize..entry.text_end as usize)
+        .and_then(|bytes| std::str::from_utf8(bytes).ok())
+        .ok_or(BreakpadUnwinderError::RecordNotUtf8(entry.text_start))
+}
+
+fn parse_hex(s: Option<&str>) -> Option<u32> {
+    u32::from_str_radix(s?, 16).ok()
+}
+
+/// The STACK record which covers a looked-up address.
+#[derive(Clone, Copy, Debug)]
+pub enum BreakpadStackRecord<'a> {
+    Cfi(BreakpadCfiRecord<'a>),
+    Win(BreakpadWinRecord<'a>),
+}
+
+/// A `STACK CFI INIT` record with its `STACK CFI` delta records, applied up to the
+/// looked-up address.
+#[derive(Clone, Copy, Debug)]
+pub struct BreakpadCfiRecord<'a> {
+    text: &'a str,
+    address: u32,
+}
+
+/// A simple rule which can be translated into an unwind rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimpleRule<'a> {
+    /// The record has no rule for this register.
+    Missing,
+    /// The value is `register + offset`.
+    RegisterPlusOffset(&'a str, i64),
+    /// The value is loaded from the address `.cfa + offset`.
+    LoadFromCfaPlusOffset(i64),
+    /// Anything else.
+    Complex,
+}
+
+impl<'a> BreakpadCfiRecord<'a> {
+    /// The postfix expression for the register `name` (e.g. `.cfa`, `.ra` or `$rbp`)
+    /// which applies at the looked-up address, if there is one.
+    pub fn rule(&self, name: &str) -> Option<&'a str> {
+        let mut result = None;
+        for line in self.text.lines() {
+            let rules = if let Some(rest) = line.strip_prefix("STACK CFI INIT ") {
+                let mut parts = rest.splitn(3, ' ');
+                parts.nth(2).unwrap_or("")
+            } else if let Some(rest) = line.strip_prefix("STACK CFI ") {
+                let (address, rules) = rest.split_once(' ').unwrap_or((rest, ""));
+                match u32::from_str_radix(address, 16) {
+                    Ok(address) if address <= self.address => rules,
+                    _ => break,
+                }
+            } else {
+                continue;
+            };
+            if let Some(rule) = find_rule(rules, name) {
+                result = Some(rule);
+            }
+        }
+        result
+    }
+
+    /// Classify the rule for the register `name`, so that it can be translated into an
+    /// unwind rule.
+    pub fn simple_rule(&self, name: &str) -> SimpleRule<'a> {
+        let rule = match self.rule(name) {
+            Some(rule) => rule,
+            None => return SimpleRule::Missing,
+        };
+        let mut tokens = [""; 4];
+        let mut len = 0;
+        for token in rule.split_ascii_whitespace() {
+            if len == tokens.len() {
+                return SimpleRule::Complex;
+            }
+            tokens[len] = token;
+            len += 1;
+        }
+        match tokens[..len] {
+            [".cfa", "^"] => SimpleRule::LoadFromCfaPlusOffset(0),
+            [".cfa", offset, op, "^"] => match (parse_decimal(offset), op) {
+                (Some(offset), "+") => SimpleRule::LoadFromCfaPlusOffset(offset),
+                (Some(offset), "-") => SimpleRule::LoadFromCfaPlusOffset(-offset),
+                _ => SimpleRule::Complex,
+            },
+            [register] if parse_decimal(register).is_none() => {
+                SimpleRule::RegisterPlusOffset(register, 0)
+            }
+            [register, offset, op] if parse_decimal(register).is_none() => {
+                match (parse_decimal(offset), op) {
+                    (Some(offset), "+") => SimpleRule::RegisterPlusOffset(register, offset),
+                    (Some(offset), "-") => SimpleRule::RegisterPlusOffset(register, -offset),
+                    _ => SimpleRule::Complex,
+                }
+            }
+            _ => SimpleRule::Complex,
+        }
+    }
+
+    /// Evaluate the rule for the register `name`. `register` supplies the values of the
+    /// registers referenced by the rule, and `cfa` the value of `.cfa`, if it is known.
+    /// Returns `None` if the record has no rule for this register.
+    pub fn evaluate_rule<F, G>(
+        &self,
+        name: &str,
+        cfa: Option<u64>,
+        register: G,
+        read_stack: &mut F,
+    ) -> Result<Option<u64>, BreakpadUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+        G: Fn(&str) -> Option<u64>,
+    {
+        let rule = match self.rule(name) {
+            Some(rule) => rule,
+            None => return Ok(None),
+        };
+        let mut variables = Variables::default();
+        if let Some(cfa) = cfa {
+            variables.set(".cfa", cfa)?;
+        }
+        let mut evaluator = Evaluator::default();
+        evaluator.run(rule, &mut variables, &register, read_stack)?;
+        let value = evaluator.finish(&variables, &register)?;
+        Ok(Some(value))
+    }
+}
+
+/// Find the expression for the register `name` in a list of rules of the form
+/// `name1: expr1 name2: expr2`.
+fn find_rule<'a>(rules: &'a str, name: &str) -> Option<&'a str> {
+    let mut result = None;
+    let mut current: Option<(&str, usize)> = None;
+    let mut position = 0;
+    for token in rules.split(' ') {
+        let token_start = position;
+        position += token.len() + 1;
+        if let Some(token_name) = token.strip_suffix(':') {
+            if let Some((current_name, expression_start)) = current {
+                if current_name == name {
+                    result = Some(rules[expression_start..token_start].trim());
+                }
+            }
+            current = Some((token_name, position.min(rules.len())));
+        }
+    }
+    if let Some((current_name, expression_start)) = current {
+        if current_name == name {
+            result = Some(rules[expression_start..].trim());
+        }
+    }
+    result
+}
+
+fn parse_decimal(s: &str) -> Option<i64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// A `STACK WIN` record with a program string.
+#[derive(Clone, Copy, Debug)]
+pub struct BreakpadWinRecord<'a> {
+    parameter_size: u32,
+    saved_register_size: u32,
+    local_size: u32,
+    program: &'a str,
+}
+
+impl<'a> BreakpadWinRecord<'a> {
+    fn parse(text: &'a str) -> Option<Self> {
+        let rest = text.strip_prefix("STACK WIN ")?;
+        let mut fields = rest.splitn(11, ' ');
+        let mut field = || parse_hex(fields.next());
+        let _record_type = field()?;
+        let _rva = field()?;
+        let _code_size = field()?;
+        let _prologue_size = field()?;
+        let _epilogue_size = field()?;
+        let parameter_size = field()?;
+        let saved_register_size = field()?;
+        let local_size = field()?;
+        let _max_stack_size = field()?;
+        let _has_program_string = field()?;
+        let program = fields.next()?;
+        Some(Self {
+            parameter_size,
+            saved_register_size,
+            local_size,
+            program,
+        })
+    }
+
+    /// Run the program string and return the variables it assigned, for example
+    /// `$eip`, `$esp` and `$ebp`. `register` supplies the values of the registers
+    /// referenced by the program.
+    ///
+    /// `.raSearch` is computed from the frame sizes; no stack scanning is done.
+    pub fn evaluate_program<F, G>(
+        &self,
+        register: G,
+        read_stack: &mut F,
+    ) -> Result<Variables<'a>, BreakpadUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+        G: Fn(&str) -> Option<u64>,
+    {
+        let mut variables = Variables::default();
+        variables.set(".cbParams", u64::from(self.parameter_size))?;
+        variables.set(".cbSavedRegs", u64::from(self.saved_register_size))?;
+        variables.set(".cbLocals", u64::from(self.local_size))?;
+        if let Some(sp) = register("$esp") {
+            let ra_search = sp
+                .wrapping_add(u64::from(self.local_size))
+                .wrapping_add(u64::from(self.saved_register_size));
+            variables.set(".raSearch", ra_search)?;
+            variables.set(".raSearchStart", ra_search)?;
+        }
+        let mut evaluator = Evaluator::default();
+        evaluator.run(self.program, &mut variables, &register, read_stack)?;
+        Ok(variables)
+    }
+}
+
+const MAX_VARIABLES: usize = 16;
+const MAX_STACK_DEPTH: usize = 32;
+
+/// The variables known while evaluating a postfix expression.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Variables<'a> {
+    entries: [(&'a str, u64); MAX_VARIABLES],
+    len: usize,
+}
+
+impl<'a> Variables<'a> {
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.entries[..self.len]
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| *value)
+    }
+
+    fn set(&mut self, name: &'a str, value: u64) -> Result<(), BreakpadUnwinderError> {
+        if let Some(entry) = self.entries[..self.len]
+            .iter_mut()
+            .find(|(n, _)| *n == name)
+        {
+            entry.1 = value;
+            return Ok(());
+        }
+        let entry = self
+            .entries
+            .get_mut(self.len)
+            .ok_or(BreakpadUnwinderError::ExpressionTooComplex)?;
+        *entry = (name, value);
+        self.len += 1;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Operand<'a> {
+    Value(u64),
+    Name(&'a str),
+}
+
+/// An evaluator for Breakpad's postfix expressions. Arithmetic wraps around, like in
+/// Breakpad's own evaluator.
+#[derive(Default)]
+struct Evaluator<'a> {
+    stack: [Option<Operand<'a>>; MAX_STACK_DEPTH],
+    len: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    fn push(&mut self, operand: Operand<'a>) -> Result<(), BreakpadUnwinderError> {
+        let slot = self
+            .stack
+            .get_mut(self.len)
+            .ok_or(BreakpadUnwinderError::ExpressionTooComplex)?;
+        *slot = Some(operand);
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Operand<'a>, BreakpadUnwinderError> {
+        self.len = self
+            .len
+            .checked_sub(1)
+            .ok_or(BreakpadUnwinderError::BadExpression)?;
+        self.stack[self.len]
+            .take()
+            .ok_or(BreakpadUnwinderError::BadExpression)
+    }
+
+    fn pop_value<G>(
+        &mut self,
+        variables: &Variables<'a>,
+        register: &G,
+    ) -> Result<u64, BreakpadUnwinderError>
+    where
+        G: Fn(&str) -> Option<u64>,
+    {
+        match self.pop()? {
+            Operand::Value(value) => Ok(value),
+            Operand::Name(name) => variables
+                .get(name)
+                .or_else(|| register(name))
+                .ok_or(BreakpadUnwinderError::UnknownVariable),
+        }
+    }
+
+    fn run<F, G>(
+        &mut self,
+        expression: &'a str,
+        variables: &mut Variables<'a>,
+        register: &G,
+        read_stack: &mut F,
+    ) -> Result<(), BreakpadUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+        G: Fn(&str) -> Option<u64>,
+    {
+        for token in expression.split_ascii_whitespace() {
+            match token {
+                "+" | "-" | "*" | "/" | "%" | "@" => {
+                    let b = self.pop_value(variables, register)?;
+                    let a = self.pop_value(variables, register)?;
+                    let result = match token {
+                        "+" => a.wrapping_add(b),
+                        "-" => a.wrapping_sub(b),
+                        "*" => a.wrapping_mul(b),
+                        "/" => a
+                            .checked_div(b)
+                            .ok_or(BreakpadUnwinderError::BadExpression)?,
+                        "%" => a
+                            .checked_rem(b)
+                            .ok_or(BreakpadUnwinderError::BadExpression)?,
+                        _ => {
+                            if !b.is_power_of_two() {
+                                return Err(BreakpadUnwinderError::BadExpression);
+                            }
+                            a & !(b - 1)
+                        }
+                    };
+                    self.push(Operand::Value(result))?;
+                }
+                "^" => {
+                    let address = self.pop_value(variables, register)?;
+                    let value = read_stack(address)
+                        .map_err(|_| BreakpadUnwinderError::CouldNotReadStack(address))?;
+                    self.push(Operand::Value(value))?;
+                }
+                "=" => {
+                    let value = self.pop_value(variables, register)?;
+                    match self.pop()? {
+                        Operand::Name(name) => variables.set(name, value)?,
+                        Operand::Value(_) => return Err(BreakpadUnwinderError::BadExpression),
+                    }
+                }
+                _ => match parse_decimal(token) {
+                    Some(value) => self.push(Operand::Value(value as u64))?,
+                    None => self.push(Operand::Name(token))?,
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the single value that's left on the stack after evaluating an expression.
+    fn finish<G>(
+        mut self,
+        variables: &Variables<'a>,
+        register: &G,
+    ) -> Result<u64, BreakpadUnwinderError>
+    where
+        G: Fn(&str) -> Option<u64>,
+    {
+        let value = self.pop_value(variables, register)?;
+        if self.len != 0 {
+            return Err(BreakpadUnwinderError::BadExpression);
+        }
+        Ok(value)
+    }
+}
+
+/// Unwinding with the `STACK` records of Breakpad symbol files.
+///
+/// Only x86_64 and aarch64 implement `unwind_frame`. 32-bit ARM uses the default, which
+/// fails with [`BreakpadUnwinderError::UnsupportedArch`], so that the fallback rule is
+/// used for modules with this unwind data.
+pub trait BreakpadUnwinding: Arch {
+    /// Unwind using the STACK record covering the looked-up address, or, if there is
+    /// none, return a rule for uncovered addresses.
+    fn unwind_frame<F>(
+        record: Option<BreakpadStackRecord>,
+        is_first_frame: bool,
+        regs: &mut Self::UnwindRegs,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<Self::UnwindRule>, BreakpadUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let _ = (record, is_first_frame, regs, read_stack);
+        Err(BreakpadUnwinderError::UnsupportedArch)
+    }
+}
+
+pub struct BreakpadUnwinder<'a, A: BreakpadUnwinding> {
+    data: &'a [u8],
+    index: &'a BreakpadIndex,
+    _arch: PhantomData<A>,
+}
+
+impl<'a, A: BreakpadUnwinding> BreakpadUnwinder<'a, A> {
+    pub fn new(data: &'a [u8], index: &'a BreakpadIndex) -> Self {
+        Self {
+            data,
+            index,
+            _arch: PhantomData,
+        }
+    }
+
+    pub fn unwind_frame<F>(
+        &self,
+        rel_lookup_address: u32,
+        is_first_frame: bool,
+        regs: &mut A::UnwindRegs,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<A::UnwindRule>, BreakpadUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let record = self.index.lookup(self.data, rel_lookup_address)?;
+        A::unwind_frame(record, is_first_frame, regs, read_stack)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SYM: &[u8] = b"MODULE Linux x86_64 0123456789ABCDEF0123456789ABCDEF0 libtest.so
+FILE 0 test.c
+FUNC 1000 40 0 main
+1000 40 1 0
+STACK CFI INIT 1000 40 .cfa: $rsp 8 + .ra: .cfa -8 + ^
+STACK CFI 1001 .cfa: $rsp 16 + $rbp: .cfa -16 + ^
+STACK CFI 1004 .cfa: $rbp 16 +
+STACK CFI INIT 2000 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^
+STACK WIN 4 3000 20 4 0 8 4 10 0 1 $T0 $ebp = $eip $T0 4 + ^ = $ebp $T0 ^ = $esp $T0 8 + =
+";
+
+    #[test]
+    fn test_lookup() {
+        let index = BreakpadIndex::try_new(SYM).unwrap();
+        let record = match index.lookup(SYM, 0x1000).unwrap() {
+            Some(BreakpadStackRecord::Cfi(record)) => record,
+            other => panic!("unexpected record {other:?}"),
+        };
+        assert_eq!(record.rule(".cfa"), Some("$rsp 8 +"));
+        assert_eq!(record.rule("$rbp"), None);
+        assert_eq!(
+            record.simple_rule(".ra"),
+            SimpleRule::LoadFromCfaPlusOffset(-8)
+        );
+
+        let record = match index.lookup(SYM, 0x1020).unwrap() {
+            Some(BreakpadStackRecord::Cfi(record)) => record,
+            other => panic!("unexpected record {other:?}"),
+        };
+        assert_eq!(record.rule(".cfa"), Some("$rbp 16 +"));
+        assert_eq!(record.rule("$rbp"), Some(".cfa -16 + ^"));
+        assert_eq!(record.rule(".ra"), Some(".cfa -8 + ^"));
+        assert_eq!(
+            record.simple_rule(".cfa"),
+            SimpleRule::RegisterPlusOffset("$rbp", 16)
+        );
+
+        assert!(matches!(
+            index.lookup(SYM, 0x3010),
+            Ok(Some(BreakpadStackRecord::Win(_)))
+        ));
+        assert!(matches!(index.lookup(SYM, 0x1040), Ok(None)));
+        assert!(matches!(index.lookup(SYM, 0x500), Ok(None)));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let index = BreakpadIndex::try_new(SYM).unwrap();
+        let stack = [0, 0, 0x30, 0x1234, 0, 0];
+        let mut read_stack = |addr: u64| stack.get((addr / 8) as usize).copied().ok_or(());
+        let register = |name: &str| match name {
+            "$rsp" | "$esp" => Some(0x8),
+            "$rbp" | "$ebp" => Some(0x10),
+            _ => None,
+        };
+        let record = match index.lookup(SYM, 0x1020).unwrap() {
+            Some(BreakpadStackRecord::Cfi(record)) => record,
+            other => panic!("unexpected record {other:?}"),
+        };
+        let cfa = record
+            .evaluate_rule(".cfa", None, register, &mut read_stack)
+            .unwrap();
+        assert_eq!(cfa, Some(0x20));
+        let ra = record.evaluate_rule(".ra", cfa, register, &mut read_stack);
+        assert_eq!(ra, Ok(Some(0x1234)));
+        let bp = record.evaluate_rule("$rbp", cfa, register, &mut read_stack);
+        assert_eq!(bp, Ok(Some(0x30)));
+
+        let record = match index.lookup(SYM, 0x3000).unwrap() {
+            Some(BreakpadStackRecord::Win(record)) => record,
+            other => panic!("unexpected record {other:?}"),
+        };
+        let variables = record.evaluate_program(register, &mut read_stack).unwrap();
+        assert_eq!(variables.get("$eip"), Some(0x30));
+        assert_eq!(variables.get("$ebp"), Some(0x30));
+        assert_eq!(variables.get("$esp"), Some(0x18));
+        assert_eq!(variables.get(".cbLocals"), Some(0x10));
+    }
+
+    #[test]
+    fn test_bad_expression() {
+        let mut read_stack = |_| Err(());
+        let register = |_: &str| None;
+        let sym = b"STACK CFI INIT 0 10 .cfa: 8 + .ra: $unknown\n";
+        let index = BreakpadIndex::try_new(sym).unwrap();
+        let Some(BreakpadStackRecord::Cfi(record)) = index.lookup(sym, 0).unwrap() else {
+            panic!("no record");
+        };
+        assert_eq!(
+            record.evaluate_rule(".cfa", None, register, &mut read_stack),
+            Err(BreakpadUnwinderError::BadExpression)
+        );
+        assert_eq!(
+            record.evaluate_rule(".ra", Some(0), register, &mut read_stack),
+            Err(BreakpadUnwinderError::UnknownVariable)
+        );
+        assert!(BreakpadIndex::try_new(b"STACK CFI 10 .cfa: $rsp 8 +\n").is_err());
+    }
+}
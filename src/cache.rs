@@ -1,10 +1,12 @@
 use std::ops::Deref;
+use std::sync::Arc;
 
-use crate::{rule_cache::RuleCache, unwind_rule::UnwindRule};
+use crate::rule_cache::{RuleCache, RuleCacheStorage, SharedRuleCache};
+use crate::unwind_rule::UnwindRule;
 
 use super::arcdata::ArcDataReader;
 
-pub use crate::rule_cache::CacheStats;
+pub use crate::rule_cache::{CacheConfig, CacheHash, CacheStats};
 
 /// A trait which lets you opt into allocation-free unwinding. The two implementations of
 /// this trait are [`MustNotAllocateDuringUnwind`] and [`MayAllocateDuringUnwind`].
@@ -58,21 +60,34 @@ impl<D: Deref<Target = [u8]>> AllocationPolicy<D> for MayAllocateDuringUnwind {
 /// A single unwinder cache can be used with multiple unwinders alternatingly.
 ///
 /// The cache stores unwind rules for addresses it has seen before, and it stores the
-/// unwind context which gimli needs for DWARF CFI evaluation.
+/// unwind context which gimli needs for DWARF CFI evaluation. The unwind rules can also
+/// be stored in a rule cache which is shared between threads, see `SharedCacheX86_64`
+/// and its counterparts for the other architectures.
 pub struct Cache<
     D: Deref<Target = [u8]>,
     R: UnwindRule,
     P: AllocationPolicy<D> = MayAllocateDuringUnwind,
 > {
     pub(crate) gimli_unwind_context: Box<gimli::UnwindContext<ArcDataReader<D>, P::GimliStorage>>,
-    pub(crate) rule_cache: RuleCache<R>,
+    pub(crate) rule_cache: RuleCacheStorage<R>,
 }
 
 impl<D: Deref<Target = [u8]>, R: UnwindRule, P: AllocationPolicy<D>> Cache<D, R, P> {
     pub fn new() -> Self {
+        Self::with_config(CacheConfig::default())
+    }
+
+    pub fn with_config(config: CacheConfig) -> Self {
+        Self {
+            gimli_unwind_context: Box::new(gimli::UnwindContext::new_in()),
+            rule_cache: RuleCacheStorage::Local(RuleCache::with_config(config)),
+        }
+    }
+
+    pub fn with_shared_rule_cache(rule_cache: Arc<SharedRuleCache<R>>) -> Self {
         Self {
             gimli_unwind_context: Box::new(gimli::UnwindContext::new_in()),
-            rule_cache: RuleCache::new(),
+            rule_cache: RuleCacheStorage::Shared(rule_cache),
         }
     }
 }
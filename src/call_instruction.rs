@@ -0,0 +1,10 @@
+use crate::arch::Arch;
+
+/// Recognizing call instructions in a module's `TextByteData`. This is used for finding
+/// return addresses by stack scanning, see `StackScanning`, and for
+/// `ReturnAddressChecks::after_call`.
+pub trait CallInstructionAnalysis: Arch {
+    /// Whether the instruction which ends at the end of `text_bytes` is a call
+    /// instruction. `text_bytes` ends at the candidate return address.
+    fn is_preceded_by_call(text_bytes: &[u8]) -> bool;
+}
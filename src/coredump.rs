@@ -0,0 +1,448 @@
+//! Unwinding the threads of a Linux ELF core dump.
+//!
+//! [`CoreDump`] reads the threads' registers from the `NT_PRSTATUS` notes, the mapped
+//! files from the `NT_FILE` note, and the dumped memory from the `PT_LOAD` segments. It
+//! turns them into the inputs of the unwinder: [`Module`]s, created from binaries which
+//! you look up by path or build id, the initial unwind registers of each thread, and a
+//! reader for all of the dumped memory.
+//!
+//! ```rust,no_run
+//! use framehop::coredump::CoreDump;
+//! use framehop::x86_64::{CacheX86_64, UnwinderX86_64};
+//! use framehop::Unwinder;
+//!
+//! let core_bytes = std::fs::read("core").unwrap();
+//! let core = CoreDump::parse(&core_bytes).unwrap();
+//! let mut unwinder = UnwinderX86_64::new();
+//! for module in core.unwinder_modules(|module| std::fs::read(&module.path).ok()) {
+//!     unwinder.add_module(module);
+//! }
+//! let mut cache = CacheX86_64::<_>::new();
+//! let mut read_stack = core.memory().reader();
+//! for thread in core.threads() {
+//!     let (pc, regs) = thread.regs_x86_64().unwrap();
+//!     let mut iter = unwinder.iter_frames(pc, regs, &mut cache, &mut read_stack);
+//!     while let Ok(Some(frame)) = iter.next() {
+//!         println!("{:?}", frame);
+//!     }
+//! }
+//! ```
+//!
+//! Only 64-bit little-endian core dumps are supported. Requires the `coredump` feature.
+
+use std::ops::Range;
+
+use crate::aarch64::UnwindRegsAarch64;
+use crate::events::unwind_event;
+use crate::pe::{read_u16, read_u32, read_u64};
+use crate::stack_memory::{StackMemory, StackMemoryRegions};
+use crate::unwinder::{Module, ModuleSvmaInfo, ModuleUnwindData};
+use crate::x86_64::UnwindRegsX86_64;
+
+const ET_CORE: u16 = 4;
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+
+/// The value of `e_phnum` if the number of program headers is in the `sh_info` field of
+/// section header 0, because it doesn't fit into 16 bits.
+const PN_XNUM: u64 = 0xffff;
+/// The offset of `sh_info` in `Elf64_Shdr`.
+const SECTION_HEADER_INFO: usize = 44;
+
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+
+const NT_PRSTATUS: u32 = 1;
+const NT_FILE: u32 = 0x4649_4c45;
+const NT_GNU_BUILD_ID: u32 = 3;
+
+const PROGRAM_HEADER_SIZE: usize = 56;
+
+/// The offset of `pr_pid` in `struct elf_prstatus`.
+const PRSTATUS_PID: usize = 32;
+/// The offset of `pr_reg` in `struct elf_prstatus`.
+const PRSTATUS_REGS: usize = 112;
+
+/// The offsets of rbp, rip and rsp in the x86_64 `struct user_regs_struct`.
+const X86_64_RBP: usize = 32;
+const X86_64_RIP: usize = 128;
+const X86_64_RSP: usize = 152;
+
+/// The offsets of x29, x30, sp and pc in the aarch64 `struct user_pt_regs`.
+const AARCH64_FP: usize = 232;
+const AARCH64_LR: usize = 240;
+const AARCH64_SP: usize = 248;
+const AARCH64_PC: usize = 256;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum CoreDumpError {
+    #[error("The data is not a 64-bit little-endian ELF file")]
+    NotElf64,
+
+    #[error("The ELF file is not a core dump")]
+    NotCore,
+
+    #[error("The core dump is truncated: the {0} is out of bounds")]
+    OutOfBounds(&'static str),
+
+    #[error("The core dump is for ELF machine {0}, not for {1}")]
+    WrongCpu(u16, &'static str),
+
+    #[error("Could not parse the binary for module {0}: {1}")]
+    BadModuleBinary(String, object::Error),
+}
+
+/// A parsed core dump.
+#[derive(Clone, Debug)]
+pub struct CoreDump<'a> {
+    machine: u16,
+    threads: Vec<CoreThread<'a>>,
+    modules: Vec<CoreModule>,
+    memory: StackMemoryRegions<'a>,
+}
+
+impl<'a> CoreDump<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, CoreDumpError> {
+        // e_ident: magic, ELFCLASS64, ELFDATA2LSB
+        if data.get(..6) != Some(b"\x7fELF\x02\x01") {
+            return Err(CoreDumpError::NotElf64);
+        }
+        let header_field =
+            |value: Option<u64>| value.ok_or(CoreDumpError::OutOfBounds("ELF header"));
+        if read_u16(data, 16) != Some(ET_CORE) {
+            return Err(CoreDumpError::NotCore);
+        }
+        let machine = header_field(read_u16(data, 18).map(u64::from))? as u16;
+        let phoff = header_field(read_u64(data, 32))?;
+        let mut phnum = header_field(read_u16(data, 56).map(u64::from))?;
+        if phnum == PN_XNUM {
+            // Cores with many mappings store the real count in section header 0.
+            let shoff = header_field(read_u64(data, 40))?;
+            phnum = usize::try_from(shoff)
+                .ok()
+                .and_then(|shoff| read_u32(data, shoff.checked_add(SECTION_HEADER_INFO)?))
+                .map(u64::from)
+                .ok_or(CoreDumpError::OutOfBounds("section header 0"))?;
+        }
+        let program_headers = usize::try_from(phoff)
+            .ok()
+            .zip(usize::try_from(phnum).ok())
+            .and_then(|(phoff, phnum)| {
+                data.get(phoff..phoff.checked_add(phnum.checked_mul(PROGRAM_HEADER_SIZE)?)?)
+            })
+            .ok_or(CoreDumpError::OutOfBounds("program headers"))?;
+
+        let mut memory = StackMemoryRegions::new();
+        let mut threads = Vec::new();
+        let mut mappings = Vec::new();
+        for header in program_headers.chunks_exact(PROGRAM_HEADER_SIZE) {
+            let p_type = read_u32(header, 0).unwrap();
+            let p_offset = read_u64(header, 8).unwrap();
+            let p_vaddr = read_u64(header, 16).unwrap();
+            let p_filesz = read_u64(header, 32).unwrap();
+            let segment_data = || {
+                let start = usize::try_from(p_offset).ok()?;
+                data.get(start..start.checked_add(usize::try_from(p_filesz).ok()?)?)
+            };
+            match p_type {
+                PT_LOAD if p_filesz != 0 => {
+                    let bytes = segment_data().ok_or(CoreDumpError::OutOfBounds("segment"))?;
+                    memory.add(StackMemory::new(p_vaddr, bytes));
+                }
+                PT_NOTE => {
+                    let notes = segment_data().ok_or(CoreDumpError::OutOfBounds("notes"))?;
+                    for (note_type, _, desc) in Notes(notes) {
+                        match note_type {
+                            NT_PRSTATUS => threads.push(CoreThread {
+                                thread_id: read_u32(desc, PRSTATUS_PID).unwrap_or(0),
+                                regs: desc.get(PRSTATUS_REGS..).unwrap_or(&[]),
+                                machine,
+                            }),
+                            NT_FILE => mappings.extend(parse_file_note(desc)),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let modules = modules_from_mappings(mappings, &memory);
+        Ok(Self {
+            machine,
+            threads,
+            modules,
+            memory,
+        })
+    }
+
+    /// The ELF machine of the core dump, for example 62 for x86_64 and 183 for aarch64.
+    pub fn machine(&self) -> u16 {
+        self.machine
+    }
+
+    /// The threads of the process, in the order of their `NT_PRSTATUS` notes. The first
+    /// thread is usually the one that crashed.
+    pub fn threads(&self) -> &[CoreThread<'a>] {
+        &self.threads
+    }
+
+    /// The files which were mapped into the process, with one entry per file.
+    pub fn modules(&self) -> &[CoreModule] {
+        &self.modules
+    }
+
+    /// All of the dumped memory. Use [`StackMemoryRegions::reader`] for the `read_stack`
+    /// callback.
+    pub fn memory(&self) -> &StackMemoryRegions<'a> {
+        &self.memory
+    }
+
+    /// Creates a [`Module`] for each mapped file. `lookup_binary` is called for each
+    /// module and returns the bytes of the module's binary, if available, for example
+    /// from the file system by its path, or from a symbol server by its
+    /// [build id](CoreModule::build_id). Modules without a binary, or whose binary can't
+    /// be parsed, are added without unwind information, so that the unwinder uses frame
+    /// pointers for them. This includes mapped files which aren't binaries, such as
+    /// locale archives and fonts.
+    pub fn unwinder_modules<F>(&self, mut lookup_binary: F) -> Vec<Module<Vec<u8>>>
+    where
+        F: FnMut(&CoreModule) -> Option<Vec<u8>>,
+    {
+        self.modules
+            .iter()
+            .map(|module| match lookup_binary(module) {
+                Some(binary) => module.to_module(Some(&binary)).unwrap_or_else(|err| {
+                    unwind_event!(
+                        debug,
+                        "Could not use the module's binary, adding it without unwind information",
+                        module = module.path,
+                        error = err,
+                    );
+                    module.module_without_unwind_data()
+                }),
+                None => module.module_without_unwind_data(),
+            })
+            .collect()
+    }
+}
+
+/// A thread of a core dump, from an `NT_PRSTATUS` note.
+#[derive(Clone, Copy, Debug)]
+pub struct CoreThread<'a> {
+    pub thread_id: u32,
+    regs: &'a [u8],
+    machine: u16,
+}
+
+impl CoreThread<'_> {
+    /// Returns the thread's instruction pointer and its unwind registers.
+    pub fn regs_x86_64(&self) -> Result<(u64, UnwindRegsX86_64), CoreDumpError> {
+        self.check_machine(EM_X86_64, "x86_64")?;
+        let reg =
+            |offset| read_u64(self.regs, offset).ok_or(CoreDumpError::OutOfBounds("registers"));
+        let ip = reg(X86_64_RIP)?;
+        Ok((
+            ip,
+            UnwindRegsX86_64::new(ip, reg(X86_64_RSP)?, reg(X86_64_RBP)?),
+        ))
+    }
+
+    /// Returns the thread's program counter and its unwind registers.
+    pub fn regs_aarch64(&self) -> Result<(u64, UnwindRegsAarch64), CoreDumpError> {
+        self.check_machine(EM_AARCH64, "aarch64")?;
+        let reg =
+            |offset| read_u64(self.regs, offset).ok_or(CoreDumpError::OutOfBounds("registers"));
+        let pc = reg(AARCH64_PC)?;
+        let regs = UnwindRegsAarch64::new(reg(AARCH64_LR)?, reg(AARCH64_SP)?, reg(AARCH64_FP)?);
+        Ok((pc, regs))
+    }
+
+    fn check_machine(&self, expected: u16, name: &'static str) -> Result<(), CoreDumpError> {
+        if self.machine != expected {
+            return Err(CoreDumpError::WrongCpu(self.machine, name));
+        }
+        Ok(())
+    }
+}
+
+/// A file which was mapped into the process, from the `NT_FILE` note.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoreModule {
+    /// The path of the file.
+    pub path: String,
+    /// The address range covered by all mappings of the file.
+    pub avma_range: Range<u64>,
+    /// The start address of the mapping of the start of the file, or of the lowest
+    /// mapping if the start of the file was not mapped.
+    pub start_avma: u64,
+    /// The GNU build id, if the core dump contains the file's ELF headers and notes.
+    pub build_id: Option<Vec<u8>>,
+}
+
+impl CoreModule {
+    /// The build id in lowercase hex, which is how symbol servers identify ELF binaries.
+    pub fn code_id(&self) -> Option<String> {
+        let build_id = self.build_id.as_ref()?;
+        Some(build_id.iter().map(|byte| format!("{byte:02x}")).collect())
+    }
+
+    /// Creates a [`Module`] from the module's `binary`, or a module without unwind
+    /// information if `binary` is `None`.
+    ///
+    /// The module's base address is the address at which the binary's virtual address
+    /// zero ended up, so that non-PIE executables get a base address of zero.
+    pub fn to_module(&self, binary: Option<&[u8]>) -> Result<Module<Vec<u8>>, CoreDumpError> {
+        let bad_binary = |err| CoreDumpError::BadModuleBinary(self.path.clone(), err);
+        let module = match binary {
+            Some(binary) => {
+                Module::from_mapped_object_file(self.path.clone(), binary, self.start_avma)
+                    .map_err(bad_binary)?
+            }
+            None => self.module_without_unwind_data(),
+        };
+        Ok(module)
+    }
+
+    fn module_without_unwind_data(&self) -> Module<Vec<u8>> {
+        Module::new(
+            self.path.clone(),
+            self.avma_range.clone(),
+            self.start_avma,
+            ModuleSvmaInfo {
+                base_svma: 0,
+                text: None,
+                text_env: None,
+                stubs: None,
+                stub_helper: None,
+                eh_frame: None,
+                eh_frame_hdr: None,
+                got: None,
+                ..Default::default()
+            },
+            ModuleUnwindData::None,
+            None,
+        )
+    }
+}
+
+/// An iterator over the (type, name, desc) of the notes in a note segment.
+struct Notes<'a>(&'a [u8]);
+
+impl<'a> Iterator for Notes<'a> {
+    type Item = (u32, &'a [u8], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let namesz = read_u32(self.0, 0)? as usize;
+        let descsz = read_u32(self.0, 4)? as usize;
+        let note_type = read_u32(self.0, 8)?;
+        let name_end = 12usize.checked_add(namesz)?;
+        let desc_start = name_end.checked_add(3)? & !3;
+        let desc_end = desc_start.checked_add(descsz)?;
+        let name = self.0.get(12..name_end)?;
+        let desc = self.0.get(desc_start..desc_end)?;
+        let next = desc_end.checked_add(3)? & !3;
+        self.0 = self.0.get(next..).unwrap_or(&[]);
+        Some((note_type, name, desc))
+    }
+}
+
+/// A mapping from the `NT_FILE` note: (address range, file offset, path).
+type Mapping = (Range<u64>, u64, String);
+
+/// Parses the `NT_FILE` note, which starts with the mapping count and the page size,
+/// followed by (start, end, file offset in pages) for each mapping, followed by the
+/// NUL-terminated paths.
+fn parse_file_note(desc: &[u8]) -> Vec<Mapping> {
+    let (Some(count), Some(page_size)) = (read_u64(desc, 0), read_u64(desc, 8)) else {
+        return Vec::new();
+    };
+    let Some(paths_start) = usize::try_from(count)
+        .ok()
+        .and_then(|count| count.checked_mul(24)?.checked_add(16))
+    else {
+        return Vec::new();
+    };
+    let paths = desc
+        .get(paths_start..)
+        .unwrap_or(&[])
+        .split(|byte| *byte == 0);
+    (0..paths_start.saturating_sub(16) / 24)
+        .zip(paths)
+        .filter_map(|(i, path)| {
+            let entry = 16 + i * 24;
+            let start = read_u64(desc, entry)?;
+            let end = read_u64(desc, entry + 8)?;
+            let file_offset = read_u64(desc, entry + 16)?.checked_mul(page_size)?;
+            Some((
+                start..end,
+                file_offset,
+                String::from_utf8_lossy(path).into_owned(),
+            ))
+        })
+        .collect()
+}
+
+/// Groups the mappings by path.
+fn modules_from_mappings(mappings: Vec<Mapping>, memory: &StackMemoryRegions) -> Vec<CoreModule> {
+    let mut modules: Vec<CoreModule> = Vec::new();
+    let mut start_offsets: Vec<u64> = Vec::new();
+    for (range, file_offset, path) in mappings {
+        match modules.iter().position(|module| module.path == path) {
+            Some(index) => {
+                let module = &mut modules[index];
+                module.avma_range.start = module.avma_range.start.min(range.start);
+                module.avma_range.end = module.avma_range.end.max(range.end);
+                if file_offset < start_offsets[index] {
+                    start_offsets[index] = file_offset;
+                    module.start_avma = range.start;
+                }
+            }
+            None => {
+                start_offsets.push(file_offset);
+                modules.push(CoreModule {
+                    path,
+                    start_avma: range.start,
+                    avma_range: range,
+                    build_id: None,
+                });
+            }
+        }
+    }
+    for (module, start_offset) in modules.iter_mut().zip(start_offsets) {
+        if start_offset == 0 {
+            module.build_id = read_build_id(memory, module.start_avma);
+        }
+    }
+    modules
+}
+
+/// Reads the GNU build id from the ELF headers of a file which was mapped at
+/// `start_avma`. The kernel usually dumps the first page of each mapped ELF file.
+fn read_build_id(memory: &StackMemoryRegions, start_avma: u64) -> Option<Vec<u8>> {
+    let region = memory
+        .regions()
+        .iter()
+        .find(|region| region.base() <= start_avma && start_avma < region.end())?;
+    let header = region
+        .bytes()
+        .get(usize::try_from(start_avma - region.base()).ok()?..)?;
+    if header.get(..6) != Some(b"\x7fELF\x02\x01") {
+        return None;
+    }
+    let phoff = usize::try_from(read_u64(header, 32)?).ok()?;
+    let phnum = read_u16(header, 56)? as usize;
+    let program_headers =
+        header.get(phoff..phoff.checked_add(phnum.checked_mul(PROGRAM_HEADER_SIZE)?)?)?;
+    program_headers
+        .chunks_exact(PROGRAM_HEADER_SIZE)
+        .filter(|ph| read_u32(ph, 0) == Some(PT_NOTE))
+        .find_map(|ph| {
+            let offset = usize::try_from(read_u64(ph, 8)?).ok()?;
+            let size = usize::try_from(read_u64(ph, 32)?).ok()?;
+            let notes = header.get(offset..offset.checked_add(size)?)?;
+            Notes(notes)
+                .find(|(note_type, name, _)| *note_type == NT_GNU_BUILD_ID && *name == b"GNU\0")
+                .map(|(_, _, desc)| desc.to_vec())
+        })
+}
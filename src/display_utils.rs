@@ -8,6 +8,7 @@ impl<N: LowerHex> Debug for HexNum<N> {
     }
 }
 
+#[allow(unused)]
 pub struct BinNum<N: Binary>(pub N);
 
 impl<N: Binary> Debug for BinNum<N> {
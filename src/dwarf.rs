@@ -1,4 +1,4 @@
-use std::{marker::PhantomData, ops::Range};
+use std::{marker::PhantomData, ops::Deref, ops::Range};
 
 use gimli::{
     BaseAddresses, CfaRule, CieOrFde, DebugFrame, EhFrame, EhFrameHdr, Encoding, EndianSlice,
@@ -7,7 +7,8 @@ use gimli::{
     UnwindContextStorage, UnwindOffset, UnwindSection, UnwindTableRow, Value,
 };
 
-use crate::{arch::Arch, unwind_result::UnwindResult, ModuleSvmaInfo};
+use crate::events::unwind_event;
+use crate::{arch::Arch, unwind_result::UnwindResult, unwind_rule::UnwindRule, ModuleSvmaInfo};
 
 #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DwarfUnwinderError {
@@ -33,25 +34,61 @@ pub enum DwarfUnwinderError {
     CouldNotRecoverFramePointer,
 }
 
-#[derive(Clone, Debug)]
+/// Describes why a DWARF CFI row could not be translated into an unwind rule.
+#[derive(thiserror::Error, Clone, Debug)]
 pub enum ConversionError {
+    #[error("The CFA rule is a DWARF expression")]
     CfaIsExpression,
+
+    #[error("The CFA rule is an offset from an unexpected register")]
     CfaIsOffsetFromUnknownRegister,
+
+    #[error("The return address rule has an unexpected offset")]
     ReturnAddressRuleWithUnexpectedOffset,
+
+    #[error("The return address rule is not supported")]
     ReturnAddressRuleWasWeird,
+
+    #[error("The stack pointer offset does not fit into the rule representation")]
     SpOffsetDoesNotFit,
+
+    #[error("A register is not stored at an offset from the CFA")]
     RegisterNotStoredRelativeToCfa,
+
+    #[error("The frame pointer is restored but the link register is not")]
     RestoringFpButNotLr,
+
+    #[error("The link register storage offset does not fit into the rule representation")]
     LrStorageOffsetDoesNotFit,
+
+    #[error("The frame pointer storage offset does not fit into the rule representation")]
     FpStorageOffsetDoesNotFit,
+
+    #[error(
+        "The stack pointer offset from the frame pointer does not fit into the rule representation"
+    )]
     SpOffsetFromFpDoesNotFit,
+
+    #[error("The CFA is based on the frame pointer but the link register is not restored")]
     FramePointerRuleDoesNotRestoreLr,
+
+    #[error("The CFA is based on the frame pointer but the frame pointer is not restored")]
     FramePointerRuleDoesNotRestoreFp,
+
+    #[error("The CFA is based on rbp but rbp is not restored")]
     FramePointerRuleDoesNotRestoreBp,
+
+    #[error("The CFA is based on rbp but rbp is stored at an unexpected offset")]
     FramePointerRuleHasStrangeBpOffset,
 }
 
 pub trait DwarfUnwinding: Arch {
+    /// The size of a code address, in bytes. This is needed to parse address-sized
+    /// values in the unwind sections.
+    const ADDRESS_SIZE: u8;
+
+    /// Unwinds by evaluating the rules of the unwind table row. This is used for rows
+    /// which [`DwarfUnwinding::unwind_rule_for_row`] can't translate into an unwind rule.
     fn unwind_frame<F, R, S>(
         unwind_info: &UnwindTableRow<R, S>,
         encoding: Encoding,
@@ -64,11 +101,24 @@ pub trait DwarfUnwinding: Arch {
         R: Reader,
         S: UnwindContextStorage<R> + EvaluationStorage<R>;
 
+    /// Translates a row of an unwind table into an unwind rule, if the row can be
+    /// expressed as one.
+    fn unwind_rule_for_row<R, S>(
+        unwind_info: &UnwindTableRow<R, S>,
+    ) -> Result<Self::UnwindRule, ConversionError>
+    where
+        R: Reader,
+        S: UnwindContextStorage<R>;
+
     fn rule_if_uncovered_by_fde() -> Self::UnwindRule;
 }
 
+/// The section which contains DWARF CFI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UnwindSectionType {
+    /// `.eh_frame` or `__eh_frame`.
     EhFrame,
+    /// `.debug_frame`.
     DebugFrame,
 }
 
@@ -77,6 +127,8 @@ pub struct DwarfUnwinder<'a, R: Reader, A: DwarfUnwinding + ?Sized, S: UnwindCon
     unwind_section_type: UnwindSectionType,
     eh_frame_hdr: Option<ParsedEhFrameHdr<EndianSlice<'a, R::Endian>>>,
     unwind_context: &'a mut UnwindContext<R, S>,
+    /// The name of the module, for events.
+    module_name: &'a str,
     base_svma: u64,
     bases: BaseAddresses,
     _arch: PhantomData<A>,
@@ -90,16 +142,14 @@ impl<'a, R: Reader, A: DwarfUnwinding, S: UnwindContextStorage<R> + EvaluationSt
         unwind_section_type: UnwindSectionType,
         eh_frame_hdr_data: Option<&'a [u8]>,
         unwind_context: &'a mut UnwindContext<R, S>,
+        module_name: &'a str,
         svma_info: &ModuleSvmaInfo,
     ) -> Self {
         let bases = base_addresses_for_sections(svma_info);
         let eh_frame_hdr = match eh_frame_hdr_data {
             Some(eh_frame_hdr_data) => {
                 let hdr = EhFrameHdr::new(eh_frame_hdr_data, unwind_section_data.endian());
-                match hdr.parse(&bases, 8) {
-                    Ok(hdr) => Some(hdr),
-                    Err(_) => None,
-                }
+                hdr.parse(&bases, A::ADDRESS_SIZE).ok()
             }
             None => None,
         };
@@ -108,6 +158,7 @@ impl<'a, R: Reader, A: DwarfUnwinding, S: UnwindContextStorage<R> + EvaluationSt
             unwind_section_type,
             eh_frame_hdr,
             unwind_context,
+            module_name,
             bases,
             base_svma: svma_info.base_svma,
             _arch: PhantomData,
@@ -116,13 +167,12 @@ impl<'a, R: Reader, A: DwarfUnwinding, S: UnwindContextStorage<R> + EvaluationSt
 
     pub fn get_fde_offset_for_relative_address(&self, rel_lookup_address: u32) -> Option<u32> {
         let lookup_svma = self.base_svma + rel_lookup_address as u64;
-        let eh_frame_hdr = self.eh_frame_hdr.as_ref()?;
-        let table = eh_frame_hdr.table()?;
-        let fde_ptr = table.lookup(lookup_svma, &self.bases).ok()?;
-        let fde_offset = table.pointer_to_offset(fde_ptr).ok()?;
-        fde_offset.0.into_u64().try_into().ok()
+        fde_offset_from_eh_frame_hdr(self.eh_frame_hdr.as_ref()?, &self.bases, lookup_svma)
     }
 
+    /// This is used for the DWARF entries of mach-O compact unwind info, so FDEs of signal
+    /// trampolines (with the `S` CIE augmentation) are unwound with the macOS signal frame
+    /// rule.
     pub fn unwind_frame_with_fde<F>(
         &mut self,
         regs: &mut A::UnwindRegs,
@@ -134,25 +184,147 @@ impl<'a, R: Reader, A: DwarfUnwinding, S: UnwindContextStorage<R> + EvaluationSt
     where
         F: FnMut(u64) -> Result<u64, ()>,
     {
+        let unwind_result = self.try_unwind_frame_with_fde_impl(
+            regs,
+            is_first_frame,
+            rel_lookup_address,
+            fde_offset,
+            A::UnwindRule::rule_for_macos_signal_frame(),
+            read_stack,
+        )?;
+        Ok(unwind_result.unwrap_or(UnwindResult::ExecRule(A::rule_if_uncovered_by_fde())))
+    }
+
+    /// Like `unwind_frame_with_fde`, but returns `Ok(None)` if the FDE does not cover
+    /// the lookup address, so that the caller can consult a different unwind section.
+    ///
+    /// This is used for ELF modules, so FDEs of signal trampolines (with the `S` CIE
+    /// augmentation) are unwound with the Linux signal frame rule.
+    pub fn try_unwind_frame_with_fde<F>(
+        &mut self,
+        regs: &mut A::UnwindRegs,
+        is_first_frame: bool,
+        rel_lookup_address: u32,
+        fde_offset: u32,
+        read_stack: &mut F,
+    ) -> Result<Option<UnwindResult<A::UnwindRule>>, DwarfUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        self.try_unwind_frame_with_fde_impl(
+            regs,
+            is_first_frame,
+            rel_lookup_address,
+            fde_offset,
+            A::UnwindRule::rule_for_linux_signal_frame(),
+            read_stack,
+        )
+    }
+
+    fn try_unwind_frame_with_fde_impl<F>(
+        &mut self,
+        regs: &mut A::UnwindRegs,
+        is_first_frame: bool,
+        rel_lookup_address: u32,
+        fde_offset: u32,
+        signal_frame_rule: Option<A::UnwindRule>,
+        read_stack: &mut F,
+    ) -> Result<Option<UnwindResult<A::UnwindRule>>, DwarfUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let module_name = self.module_name;
+        let (unwind_info, encoding, is_signal_trampoline) =
+            match self.unwind_info_for_relative_address(rel_lookup_address, fde_offset) {
+                Ok(unwind_info) => unwind_info,
+                Err(DwarfUnwinderError::UnwindInfoForAddressFailed(_)) => return Ok(None),
+                Err(err) => return Err(err),
+            };
+        if is_signal_trampoline {
+            // The CFI of signal trampolines describes the signal frame in ways that we
+            // can't always express as a rule, for example with DWARF expressions, or as
+            // a fake frame record which skips the interrupted function.
+            if let Some(rule) = signal_frame_rule {
+                return Ok(Some(UnwindResult::ExecRule(rule)));
+            }
+        }
+        match A::unwind_rule_for_row(unwind_info) {
+            Ok(unwind_rule) => return Ok(Some(UnwindResult::ExecRule(unwind_rule))),
+            Err(err) => {
+                // Could not translate into a cacheable unwind rule. Fall back to the generic path.
+                unwind_event!(
+                    debug,
+                    "Unwind rule translation failed",
+                    module = module_name,
+                    row_address = format_args!("0x{:x}", unwind_info.start_address()),
+                    error = err,
+                );
+            }
+        }
+        A::unwind_frame::<F, R, S>(unwind_info, encoding, regs, is_first_frame, read_stack)
+            .map(Some)
+    }
+
+    /// Recover the caller's values of `registers` for full register unwinding, by
+    /// evaluating their register rules in the unwind table row for the lookup address.
+    /// `regs` has the register values of the current frame. Registers without a rule
+    /// are treated as unchanged, and registers whose value cannot be recovered become
+    /// `None`. The recovered values are written to the corresponding elements of
+    /// `recovered`.
+    ///
+    /// Returns `Ok(false)` if the FDE does not cover the lookup address.
+    pub fn try_recover_registers_with_fde<F, UR>(
+        &mut self,
+        regs: &UR,
+        registers: &[Register],
+        recovered: &mut [Option<u64>],
+        rel_lookup_address: u32,
+        fde_offset: u32,
+        read_stack: &mut F,
+    ) -> Result<bool, DwarfUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+        UR: DwarfUnwindRegs,
+    {
+        let (unwind_info, encoding, _) =
+            match self.unwind_info_for_relative_address(rel_lookup_address, fde_offset) {
+                Ok(unwind_info) => unwind_info,
+                Err(DwarfUnwinderError::UnwindInfoForAddressFailed(_)) => return Ok(false),
+                Err(err) => return Err(err),
+            };
+        let cfa = eval_cfa_rule::<R, _, S>(unwind_info.cfa(), encoding, regs)
+            .ok_or(DwarfUnwinderError::CouldNotRecoverCfa)?;
+        for (register, value) in registers.iter().zip(recovered) {
+            *value = match unwind_info.register(*register) {
+                RegisterRule::Undefined | RegisterRule::SameValue => regs.get(*register),
+                rule => eval_register_rule::<R, F, _, S>(rule, cfa, encoding, 0, regs, read_stack),
+            };
+        }
+        Ok(true)
+    }
+
+    /// Returns the row, the encoding, and whether the FDE belongs to a signal trampoline.
+    /// Returns `Err(DwarfUnwinderError::UnwindInfoForAddressFailed(_))` if the FDE does
+    /// not cover the lookup address.
+    fn unwind_info_for_relative_address(
+        &mut self,
+        rel_lookup_address: u32,
+        fde_offset: u32,
+    ) -> Result<(&UnwindTableRow<R, S>, Encoding, bool), DwarfUnwinderError> {
         let lookup_svma = self.base_svma + rel_lookup_address as u64;
         let unwind_section_data = self.unwind_section_data.clone();
-        let unwind_info = match self.unwind_section_type {
+        match self.unwind_section_type {
             UnwindSectionType::EhFrame => {
                 let mut eh_frame = EhFrame::from(unwind_section_data);
-                eh_frame.set_address_size(8);
+                eh_frame.set_address_size(A::ADDRESS_SIZE);
                 self.unwind_info_for_fde(eh_frame, lookup_svma, fde_offset)
             }
             UnwindSectionType::DebugFrame => {
                 let mut debug_frame = DebugFrame::from(unwind_section_data);
-                debug_frame.set_address_size(8);
+                debug_frame.set_address_size(A::ADDRESS_SIZE);
                 self.unwind_info_for_fde(debug_frame, lookup_svma, fde_offset)
             }
-        };
-        if let Err(DwarfUnwinderError::UnwindInfoForAddressFailed(_)) = unwind_info {
-            return Ok(UnwindResult::ExecRule(A::rule_if_uncovered_by_fde()));
         }
-        let (unwind_info, encoding) = unwind_info?;
-        A::unwind_frame::<F, R, S>(unwind_info, encoding, regs, is_first_frame, read_stack)
     }
 
     fn unwind_info_for_fde<US: UnwindSection<R>>(
@@ -160,7 +332,7 @@ impl<'a, R: Reader, A: DwarfUnwinding, S: UnwindContextStorage<R> + EvaluationSt
         unwind_section: US,
         lookup_svma: u64,
         fde_offset: u32,
-    ) -> Result<(&UnwindTableRow<R, S>, Encoding), DwarfUnwinderError> {
+    ) -> Result<(&UnwindTableRow<R, S>, Encoding, bool), DwarfUnwinderError> {
         let fde = unwind_section.fde_from_offset(
             &self.bases,
             US::Offset::from(R::Offset::from_u32(fde_offset)),
@@ -168,6 +340,7 @@ impl<'a, R: Reader, A: DwarfUnwinding, S: UnwindContextStorage<R> + EvaluationSt
         );
         let fde = fde.map_err(DwarfUnwinderError::FdeFromOffsetFailed)?;
         let encoding = fde.cie().encoding();
+        let is_signal_trampoline = fde.is_signal_trampoline();
         let unwind_info: &UnwindTableRow<_, _> = fde
             .unwind_info_for_address(
                 &unwind_section,
@@ -176,11 +349,87 @@ impl<'a, R: Reader, A: DwarfUnwinding, S: UnwindContextStorage<R> + EvaluationSt
                 lookup_svma,
             )
             .map_err(DwarfUnwinderError::UnwindInfoForAddressFailed)?;
-        Ok((unwind_info, encoding))
+        Ok((unwind_info, encoding, is_signal_trampoline))
     }
 }
 
-fn base_addresses_for_sections(svma_info: &ModuleSvmaInfo) -> BaseAddresses {
+fn fde_offset_from_eh_frame_hdr<R: Reader>(
+    eh_frame_hdr: &ParsedEhFrameHdr<R>,
+    bases: &BaseAddresses,
+    lookup_svma: u64,
+) -> Option<u32> {
+    let table = eh_frame_hdr.table()?;
+    let fde_ptr = table.lookup(lookup_svma, bases).ok()?;
+    let fde_offset = table.pointer_to_offset(fde_ptr).ok()?;
+    fde_offset.0.into_u64().try_into().ok()
+}
+
+/// Look up the offset of the FDE for `rel_lookup_address` in the `.eh_frame_hdr` search
+/// table, without setting up a [`DwarfUnwinder`].
+pub fn eh_frame_hdr_fde_offset<A: DwarfUnwinding>(
+    eh_frame_hdr_data: &[u8],
+    svma_info: &ModuleSvmaInfo,
+    rel_lookup_address: u32,
+) -> Option<u32> {
+    let bases = base_addresses_for_sections(svma_info);
+    let hdr = EhFrameHdr::new(eh_frame_hdr_data, LittleEndian)
+        .parse(&bases, A::ADDRESS_SIZE)
+        .ok()?;
+    let lookup_svma = svma_info.base_svma + u64::from(rel_lookup_address);
+    fde_offset_from_eh_frame_hdr(&hdr, &bases, lookup_svma)
+}
+
+/// Find where the function containing `rel_lookup_address` starts, or a lower bound for
+/// it, using the FDE at `fde_offset`. This FDE is the last one which starts at or before
+/// the lookup address. If it covers the address, the function starts where the FDE starts.
+/// Otherwise the function can only start at or after the end of the FDE.
+pub fn function_start_lower_bound<A: DwarfUnwinding>(
+    section_type: UnwindSectionType,
+    section_data: &[u8],
+    svma_info: &ModuleSvmaInfo,
+    fde_offset: u32,
+    rel_lookup_address: u32,
+) -> Option<u32> {
+    fn fde_range<R: Reader, US: UnwindSection<R>>(
+        unwind_section: US,
+        bases: &BaseAddresses,
+        fde_offset: u32,
+    ) -> Option<Range<u64>> {
+        let fde = unwind_section
+            .fde_from_offset(
+                bases,
+                US::Offset::from(R::Offset::from_u32(fde_offset)),
+                US::cie_from_offset,
+            )
+            .ok()?;
+        let start = fde.initial_address();
+        Some(start..start.checked_add(fde.len())?)
+    }
+
+    let bases = base_addresses_for_sections(svma_info);
+    let data = EndianSlice::new(section_data, LittleEndian);
+    let svma_range = match section_type {
+        UnwindSectionType::EhFrame => {
+            let mut eh_frame = EhFrame::from(data);
+            eh_frame.set_address_size(A::ADDRESS_SIZE);
+            fde_range(eh_frame, &bases, fde_offset)?
+        }
+        UnwindSectionType::DebugFrame => {
+            let mut debug_frame = DebugFrame::from(data);
+            debug_frame.set_address_size(A::ADDRESS_SIZE);
+            fde_range(debug_frame, &bases, fde_offset)?
+        }
+    };
+    let to_relative = |svma: u64| u32::try_from(svma.checked_sub(svma_info.base_svma)?).ok();
+    let (start, end) = (to_relative(svma_range.start)?, to_relative(svma_range.end)?);
+    if rel_lookup_address < end {
+        Some(start)
+    } else {
+        Some(end)
+    }
+}
+
+pub(crate) fn base_addresses_for_sections(svma_info: &ModuleSvmaInfo) -> BaseAddresses {
     fn start_addr(range: &Option<Range<u64>>) -> u64 {
         if let Some(range) = range {
             range.start
@@ -195,6 +444,39 @@ fn base_addresses_for_sections(svma_info: &ModuleSvmaInfo) -> BaseAddresses {
         .set_got(start_addr(&svma_info.got))
 }
 
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EhFrameHdrError {
+    #[error("Could not parse .eh_frame_hdr: {0}")]
+    Parse(#[source] gimli::Error),
+
+    #[error(".eh_frame_hdr does not have a search table")]
+    NoSearchTable,
+
+    #[error("The .eh_frame_hdr search table cannot be searched: {0}")]
+    Unsearchable(#[source] gimli::Error),
+}
+
+/// Checks that the `.eh_frame_hdr` data parses, and that it has a search table which
+/// can be used to look up FDEs. The unwinder ignores `.eh_frame_hdr` data which fails
+/// this check, so the module's `.eh_frame` data is effectively unused.
+pub fn check_eh_frame_hdr<A: DwarfUnwinding>(
+    eh_frame_hdr_data: &[u8],
+    svma_info: &ModuleSvmaInfo,
+) -> Result<(), EhFrameHdrError> {
+    let bases = base_addresses_for_sections(svma_info);
+    let hdr = EhFrameHdr::new(eh_frame_hdr_data, LittleEndian)
+        .parse(&bases, A::ADDRESS_SIZE)
+        .map_err(EhFrameHdrError::Parse)?;
+    let table = hdr.table().ok_or(EhFrameHdrError::NoSearchTable)?;
+    let fde_ptr = table
+        .lookup(u64::MAX, &bases)
+        .map_err(EhFrameHdrError::Unsearchable)?;
+    table
+        .pointer_to_offset(fde_ptr)
+        .map_err(EhFrameHdrError::Unsearchable)?;
+    Ok(())
+}
+
 #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DwarfCfiIndexError {
     #[error("EhFrame processing failed: {0}")]
@@ -210,19 +492,100 @@ pub enum DwarfCfiIndexError {
     FdeOffsetTooBig,
 }
 
+/// A problem with a serialized DWARF CFI index, see [`Module::set_cfi_index`](crate::Module::set_cfi_index).
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerializedCfiIndexError {
+    #[error("The data is not a serialized CFI index")]
+    BadMagic,
+
+    #[error("Unsupported serialized CFI index version {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("The serialized CFI index is truncated")]
+    Truncated,
+
+    #[error("The serialized CFI index was built for a different section or architecture")]
+    WrongSection,
+
+    #[error("The serialized CFI index was built from different section contents")]
+    SectionMismatch,
+
+    #[error("The serialized CFI index is corrupt")]
+    Corrupt,
+}
+
 /// A binary search table for eh_frame FDEs. We generate this whenever a module
-/// without eh_frame_hdr is added.
-pub struct DwarfCfiIndex {
-    /// Contains the initial address for every FDE, relative to the base address.
-    /// This vector is sorted so that it can be used for binary search.
-    /// It has the same length as `fde_offsets`.
-    sorted_fde_pc_starts: Vec<u32>,
-    /// Contains the FDE offset for every FDE. The FDE at offset `fde_offsets[i]`
-    /// has a PC range which starts at `sorted_fde_pc_starts[i]`.
-    fde_offsets: Vec<u32>,
+/// without eh_frame_hdr is added, unless a serialized index is supplied.
+pub struct DwarfCfiIndex<D> {
+    storage: DwarfCfiIndexStorage<D>,
+}
+
+enum DwarfCfiIndexStorage<D> {
+    Owned {
+        /// Contains the initial address for every FDE, relative to the base address.
+        /// This vector is sorted so that it can be used for binary search.
+        /// It has the same length as `fde_offsets`.
+        sorted_fde_pc_starts: Vec<u32>,
+        /// Contains the FDE offset for every FDE. The FDE at offset `fde_offsets[i]`
+        /// has a PC range which starts at `sorted_fde_pc_starts[i]`.
+        fde_offsets: Vec<u32>,
+    },
+    /// Validated serialized data, see `SERIALIZED_HEADER_LEN`. The two arrays are read
+    /// in place, so the data doesn't need to be aligned.
+    Serialized { data: D, len: usize },
+}
+
+/// The serialized format is little-endian:
+///
+/// ```text
+/// magic             [u8; 8]  b"FHCFIIDX"
+/// version           u32      1
+/// section type      u32      0 = .eh_frame, 1 = .debug_frame
+/// address size      u32
+/// FDE count (n)     u32
+/// base SVMA         u64
+/// section length    u64
+/// section hash      u64      see `section_hash`
+/// FDE PC starts     [u32; n] relative to the base SVMA, sorted
+/// FDE offsets       [u32; n]
+/// ```
+const SERIALIZED_MAGIC: &[u8; 8] = b"FHCFIIDX";
+const SERIALIZED_VERSION: u32 = 1;
+const SERIALIZED_HEADER_LEN: usize = 48;
+
+/// A fast non-cryptographic hash of the section contents, so that a serialized index
+/// isn't used with a section it wasn't built from. This reads 8 bytes at a time and is
+/// much cheaper than parsing the section.
+fn section_hash(data: &[u8]) -> u64 {
+    const K: u64 = 0x517c_c1b7_2722_0a95;
+    let mut chunks = data.chunks_exact(8);
+    let mut hash = data.len() as u64;
+    for chunk in &mut chunks {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        hash = (hash.rotate_left(5) ^ word).wrapping_mul(K);
+    }
+    for byte in chunks.remainder() {
+        hash = (hash.rotate_left(5) ^ u64::from(*byte)).wrapping_mul(K);
+    }
+    hash
 }
 
-impl DwarfCfiIndex {
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn section_type_id(section_type: UnwindSectionType) -> u32 {
+    match section_type {
+        UnwindSectionType::EhFrame => 0,
+        UnwindSectionType::DebugFrame => 1,
+    }
+}
+
+impl<D: Deref<Target = [u8]>> DwarfCfiIndex<D> {
     pub fn try_new<R, US>(
         unwind_section: US,
         bases: BaseAddresses,
@@ -274,18 +637,21 @@ impl DwarfCfiIndex {
         let sorted_fde_pc_starts = fde_pc_and_offset.iter().map(|(pc, _)| *pc).collect();
         let fde_offsets = fde_pc_and_offset.into_iter().map(|(_, fde)| fde).collect();
         Ok(Self {
-            sorted_fde_pc_starts,
-            fde_offsets,
+            storage: DwarfCfiIndexStorage::Owned {
+                sorted_fde_pc_starts,
+                fde_offsets,
+            },
         })
     }
 
     pub fn try_new_eh_frame(
         eh_frame_data: &[u8],
         svma_info: &ModuleSvmaInfo,
+        address_size: u8,
     ) -> Result<Self, DwarfCfiIndexError> {
         let bases = base_addresses_for_sections(svma_info);
         let mut eh_frame = EhFrame::from(EndianSlice::new(eh_frame_data, LittleEndian));
-        eh_frame.set_address_size(8);
+        eh_frame.set_address_size(address_size);
 
         Self::try_new(eh_frame, bases, svma_info.base_svma)
     }
@@ -293,21 +659,161 @@ impl DwarfCfiIndex {
     pub fn try_new_debug_frame(
         debug_frame_data: &[u8],
         svma_info: &ModuleSvmaInfo,
+        address_size: u8,
     ) -> Result<Self, DwarfCfiIndexError> {
         let bases = base_addresses_for_sections(svma_info);
         let mut debug_frame = DebugFrame::from(EndianSlice::new(debug_frame_data, LittleEndian));
-        debug_frame.set_address_size(8);
+        debug_frame.set_address_size(address_size);
 
         Self::try_new(debug_frame, bases, svma_info.base_svma)
     }
 
-    pub fn fde_offset_for_relative_address(&self, rel_lookup_address: u32) -> Option<u32> {
-        let i = match self.sorted_fde_pc_starts.binary_search(&rel_lookup_address) {
-            Err(0) => return None,
-            Ok(i) => i,
-            Err(i) => i - 1,
+    pub fn try_new_for_section(
+        section_type: UnwindSectionType,
+        section_data: &[u8],
+        svma_info: &ModuleSvmaInfo,
+        address_size: u8,
+    ) -> Result<Self, DwarfCfiIndexError> {
+        match section_type {
+            UnwindSectionType::EhFrame => {
+                Self::try_new_eh_frame(section_data, svma_info, address_size)
+            }
+            UnwindSectionType::DebugFrame => {
+                Self::try_new_debug_frame(section_data, svma_info, address_size)
+            }
+        }
+    }
+
+    /// Uses serialized index data which was created by [`DwarfCfiIndex::serialize`], after
+    /// checking that it was created for this section and architecture.
+    pub fn from_serialized(
+        data: D,
+        section_type: UnwindSectionType,
+        section_data: &[u8],
+        base_svma: u64,
+        address_size: u8,
+    ) -> Result<Self, SerializedCfiIndexError> {
+        let bytes = &data[..];
+        if bytes.len() < SERIALIZED_HEADER_LEN {
+            return Err(SerializedCfiIndexError::Truncated);
+        }
+        if &bytes[..8] != SERIALIZED_MAGIC {
+            return Err(SerializedCfiIndexError::BadMagic);
+        }
+        let version = read_u32(bytes, 8);
+        if version != SERIALIZED_VERSION {
+            return Err(SerializedCfiIndexError::UnsupportedVersion(version));
+        }
+        if read_u32(bytes, 12) != section_type_id(section_type)
+            || read_u32(bytes, 16) != u32::from(address_size)
+            || read_u64(bytes, 24) != base_svma
+        {
+            return Err(SerializedCfiIndexError::WrongSection);
+        }
+        if read_u64(bytes, 32) != section_data.len() as u64
+            || read_u64(bytes, 40) != section_hash(section_data)
+        {
+            return Err(SerializedCfiIndexError::SectionMismatch);
+        }
+        let len = read_u32(bytes, 20) as usize;
+        // The length comes from the data, so the expected size can overflow on 32-bit
+        // targets. Once it matches, the array offsets below can't overflow.
+        let expected_len = len
+            .checked_mul(8)
+            .and_then(|arrays_len| arrays_len.checked_add(SERIALIZED_HEADER_LEN));
+        if expected_len != Some(bytes.len()) {
+            return Err(SerializedCfiIndexError::Truncated);
+        }
+        let index = Self {
+            storage: DwarfCfiIndexStorage::Serialized { data, len },
         };
-        Some(self.fde_offsets[i])
+        // Lookups rely on the PC starts being sorted, and the FDE offsets are used to
+        // read from the section.
+        let is_sorted = (1..len).all(|i| index.pc_start(i - 1) <= index.pc_start(i));
+        let offsets_in_bounds =
+            (0..len).all(|i| (index.fde_offset(i) as usize) < section_data.len());
+        if !is_sorted || !offsets_in_bounds {
+            return Err(SerializedCfiIndexError::Corrupt);
+        }
+        Ok(index)
+    }
+
+    /// Serializes the index into a versioned byte format which can be passed to
+    /// [`DwarfCfiIndex::from_serialized`]. `section_data` must be the section the index
+    /// was created from.
+    pub fn serialize(
+        &self,
+        section_type: UnwindSectionType,
+        section_data: &[u8],
+        base_svma: u64,
+        address_size: u8,
+    ) -> Vec<u8> {
+        let len = self.len();
+        let mut data = Vec::with_capacity(SERIALIZED_HEADER_LEN + len * 8);
+        data.extend_from_slice(SERIALIZED_MAGIC);
+        data.extend_from_slice(&SERIALIZED_VERSION.to_le_bytes());
+        data.extend_from_slice(&section_type_id(section_type).to_le_bytes());
+        data.extend_from_slice(&u32::from(address_size).to_le_bytes());
+        data.extend_from_slice(&(len as u32).to_le_bytes());
+        data.extend_from_slice(&base_svma.to_le_bytes());
+        data.extend_from_slice(&(section_data.len() as u64).to_le_bytes());
+        data.extend_from_slice(&section_hash(section_data).to_le_bytes());
+        for i in 0..len {
+            data.extend_from_slice(&self.pc_start(i).to_le_bytes());
+        }
+        for i in 0..len {
+            data.extend_from_slice(&self.fde_offset(i).to_le_bytes());
+        }
+        data
+    }
+
+    fn len(&self) -> usize {
+        match &self.storage {
+            DwarfCfiIndexStorage::Owned { fde_offsets, .. } => fde_offsets.len(),
+            DwarfCfiIndexStorage::Serialized { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn pc_start(&self, i: usize) -> u32 {
+        match &self.storage {
+            DwarfCfiIndexStorage::Owned {
+                sorted_fde_pc_starts,
+                ..
+            } => sorted_fde_pc_starts[i],
+            DwarfCfiIndexStorage::Serialized { data, .. } => {
+                read_u32(data, SERIALIZED_HEADER_LEN + i * 4)
+            }
+        }
+    }
+
+    fn fde_offset(&self, i: usize) -> u32 {
+        match &self.storage {
+            DwarfCfiIndexStorage::Owned { fde_offsets, .. } => fde_offsets[i],
+            DwarfCfiIndexStorage::Serialized { data, len } => {
+                read_u32(data, SERIALIZED_HEADER_LEN + len * 4 + i * 4)
+            }
+        }
+    }
+
+    pub fn fde_offset_for_relative_address(&self, rel_lookup_address: u32) -> Option<u32> {
+        // Find the number of FDEs which start at or before the address.
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.pc_start(mid) <= rel_lookup_address {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        match low {
+            0 => None,
+            i => Some(self.fde_offset(i - 1)),
+        }
     }
 }
 
@@ -0,0 +1,508 @@
+use std::marker::PhantomData;
+
+use crate::arch::Arch;
+use crate::unwind_result::UnwindResult;
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArmExidxUnwinderError {
+    #[error("Address 0x{0:x} is before the first function covered by .ARM.exidx")]
+    AddressOutsideRange(u32),
+
+    #[error("The .ARM.exidx entry for this function is EXIDX_CANTUNWIND")]
+    CantUnwind,
+
+    #[error("The .ARM.exidx section size is not a multiple of 8")]
+    BadExidxSize,
+
+    #[error("The .ARM.exidx entry refers to an address outside of .ARM.extab")]
+    ExtabOutOfBounds,
+
+    #[error("The .ARM.exidx entry refers to .ARM.extab but we do not have .ARM.extab data")]
+    NoExtabData,
+
+    #[error("Unsupported personality routine index {0}")]
+    UnsupportedPersonality(u8),
+
+    #[error("The unwind opcodes say that this function cannot be unwound")]
+    RefuseToUnwind,
+
+    #[error("Encountered a reserved or spare unwind opcode 0x{0:02x}")]
+    BadOpcode(u8),
+
+    #[error("The unwind opcodes ended in the middle of an instruction")]
+    TruncatedOpcodes,
+
+    #[error("The unwind opcodes set vsp to r{0}, whose value we don't know")]
+    UntrackedRegister(u8),
+
+    #[error("Unwinding caused integer overflow")]
+    IntegerOverflow,
+
+    #[error("Could not read stack memory at 0x{0:x}")]
+    CouldNotReadStack(u64),
+
+    #[error("Neither the code address nor the stack pointer changed, would loop")]
+    DidNotAdvance,
+
+    #[error("ARM EHABI unwind information is not supported for this CPU architecture")]
+    UnsupportedArch,
+}
+
+/// An instruction from an ARM EHABI unwind opcode sequence, as described in the
+/// "Exception Handling ABI for the ARM Architecture", section 10.3.
+///
+/// The opcodes manipulate a "virtual stack pointer" (vsp) which starts out with the
+/// value of the stack pointer. Popping a register reads it from vsp and increments vsp
+/// by 4. Once all instructions have been executed, the caller's sp is vsp and the
+/// caller's pc is the popped pc, or the (possibly popped) lr if pc was not popped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EhabiInstruction {
+    /// vsp = vsp + x. Also used for popping VFP / WMMX registers, which we don't track.
+    AdjustVsp(i32),
+    /// Pop the core registers r0 to r15 whose bits are set in the mask, lowest register
+    /// first.
+    PopCoreRegisters(u16),
+    /// vsp = r[n]
+    SetVspToRegister(u8),
+    /// This function must not be unwound through.
+    RefuseToUnwind,
+    /// We're done. Remaining opcodes are ignored.
+    Finish,
+}
+
+/// The raw opcode bytes from an .ARM.exidx or .ARM.extab entry. The first word can
+/// have fewer than four opcode bytes; the remaining words are used in full. Within each
+/// 32-bit word, opcode bytes are stored most significant byte first.
+#[derive(Clone, Copy)]
+struct EhabiOpcodeBytes<'a> {
+    first_word: u32,
+    first_word_remaining_bytes: u8,
+    remaining_words: &'a [u8],
+    byte_index_in_remaining_words: usize,
+}
+
+impl<'a> EhabiOpcodeBytes<'a> {
+    fn new(first_word: u32, first_word_byte_count: u8, remaining_words: &'a [u8]) -> Self {
+        Self {
+            first_word,
+            first_word_remaining_bytes: first_word_byte_count,
+            remaining_words,
+            byte_index_in_remaining_words: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for EhabiOpcodeBytes<'a> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.first_word_remaining_bytes > 0 {
+            self.first_word_remaining_bytes -= 1;
+            let shift = u32::from(self.first_word_remaining_bytes) * 8;
+            return Some((self.first_word >> shift) as u8);
+        }
+        let i = self.byte_index_in_remaining_words;
+        let word = read_u32(self.remaining_words, i / 4)?;
+        self.byte_index_in_remaining_words += 1;
+        Some(word.to_be_bytes()[i % 4])
+    }
+}
+
+/// An iterator over the unwind instructions of a function. Decoding happens lazily and
+/// does not allocate.
+#[derive(Clone, Copy)]
+pub struct EhabiInstructions<'a> {
+    bytes: EhabiOpcodeBytes<'a>,
+    done: bool,
+}
+
+impl<'a> EhabiInstructions<'a> {
+    fn new(bytes: EhabiOpcodeBytes<'a>) -> Self {
+        Self { bytes, done: false }
+    }
+
+    fn next_byte(&mut self) -> Result<u8, ArmExidxUnwinderError> {
+        self.bytes
+            .next()
+            .ok_or(ArmExidxUnwinderError::TruncatedOpcodes)
+    }
+
+    fn decode(&mut self, op: u8) -> Result<EhabiInstruction, ArmExidxUnwinderError> {
+        let instruction = match op {
+            0x00..=0x3f => EhabiInstruction::AdjustVsp((i32::from(op & 0x3f) << 2) + 4),
+            0x40..=0x7f => EhabiInstruction::AdjustVsp(-((i32::from(op & 0x3f) << 2) + 4)),
+            0x80..=0x8f => {
+                let op2 = self.next_byte()?;
+                let mask = (u16::from(op & 0x0f) << 8) | u16::from(op2);
+                if mask == 0 {
+                    EhabiInstruction::RefuseToUnwind
+                } else {
+                    EhabiInstruction::PopCoreRegisters(mask << 4)
+                }
+            }
+            0x9d | 0x9f => return Err(ArmExidxUnwinderError::BadOpcode(op)),
+            0x90..=0x9f => EhabiInstruction::SetVspToRegister(op & 0x0f),
+            0xa0..=0xa7 => EhabiInstruction::PopCoreRegisters(pop_r4_to_rn_mask(op)),
+            0xa8..=0xaf => EhabiInstruction::PopCoreRegisters(pop_r4_to_rn_mask(op) | (1 << 14)),
+            0xb0 => EhabiInstruction::Finish,
+            0xb1 => {
+                let op2 = self.next_byte()?;
+                if op2 == 0 || op2 & 0xf0 != 0 {
+                    return Err(ArmExidxUnwinderError::BadOpcode(op));
+                }
+                EhabiInstruction::PopCoreRegisters(u16::from(op2))
+            }
+            0xb2 => {
+                let mut value: u32 = 0;
+                let mut shift = 0;
+                loop {
+                    let byte = self.next_byte()?;
+                    value |= u32::from(byte & 0x7f)
+                        .checked_shl(shift)
+                        .ok_or(ArmExidxUnwinderError::BadOpcode(op))?;
+                    shift += 7;
+                    if byte & 0x80 == 0 {
+                        break;
+                    }
+                }
+                let offset = value
+                    .checked_mul(4)
+                    .and_then(|v| v.checked_add(0x204))
+                    .and_then(|v| i32::try_from(v).ok())
+                    .ok_or(ArmExidxUnwinderError::IntegerOverflow)?;
+                EhabiInstruction::AdjustVsp(offset)
+            }
+            0xb3 => {
+                // Pop VFP double-precision registers saved by FSTMFDX.
+                let count = i32::from(self.next_byte()? & 0x0f) + 1;
+                EhabiInstruction::AdjustVsp(count * 8 + 4)
+            }
+            0xb8..=0xbf => {
+                // Pop VFP registers d8 to d(8+nnn) saved by FSTMFDX.
+                EhabiInstruction::AdjustVsp((i32::from(op & 0x07) + 1) * 8 + 4)
+            }
+            0xc0..=0xc5 => {
+                // Pop WMMX registers wR10 to wR(10+nnn).
+                EhabiInstruction::AdjustVsp((i32::from(op & 0x07) + 1) * 8)
+            }
+            0xc6 | 0xc8 | 0xc9 => {
+                // Pop WMMX registers or VFP double-precision registers saved by FSTMFDD.
+                let count = i32::from(self.next_byte()? & 0x0f) + 1;
+                EhabiInstruction::AdjustVsp(count * 8)
+            }
+            0xc7 => {
+                // Pop WMMX control registers.
+                let op2 = self.next_byte()?;
+                if op2 == 0 || op2 & 0xf0 != 0 {
+                    return Err(ArmExidxUnwinderError::BadOpcode(op));
+                }
+                EhabiInstruction::AdjustVsp(op2.count_ones() as i32 * 4)
+            }
+            0xd0..=0xd7 => {
+                // Pop VFP registers d8 to d(8+nnn) saved by FSTMFDD.
+                EhabiInstruction::AdjustVsp((i32::from(op & 0x07) + 1) * 8)
+            }
+            _ => return Err(ArmExidxUnwinderError::BadOpcode(op)),
+        };
+        Ok(instruction)
+    }
+}
+
+impl<'a> Iterator for EhabiInstructions<'a> {
+    type Item = Result<EhabiInstruction, ArmExidxUnwinderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let op = match self.bytes.next() {
+            Some(op) => op,
+            None => {
+                self.done = true;
+                return None;
+            }
+        };
+        let instruction = self.decode(op);
+        if matches!(instruction, Ok(EhabiInstruction::Finish) | Err(_)) {
+            self.done = true;
+        }
+        Some(instruction)
+    }
+}
+
+/// The mask for "pop r4 to r[4+nnn]".
+fn pop_r4_to_rn_mask(op: u8) -> u16 {
+    let count = u16::from(op & 0x07) + 1;
+    ((1 << count) - 1) << 4
+}
+
+fn read_u32(data: &[u8], word_index: usize) -> Option<u32> {
+    let offset = word_index.checked_mul(4)?;
+    let b = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Sign-extend a 31 bit place-relative offset ("prel31") and add it to the address of
+/// the word it was read from.
+fn resolve_prel31(word: u32, word_svma: u64) -> u64 {
+    let offset = ((word << 1) as i32 >> 1) as i64;
+    word_svma.wrapping_add(offset as u64)
+}
+
+/// Unwinding with the `.ARM.exidx` / `.ARM.extab` tables of 32-bit ARM binaries.
+///
+/// Only 32-bit ARM implements `unwind_frame`. The other architectures use the default,
+/// which fails with [`ArmExidxUnwinderError::UnsupportedArch`], so that the fallback
+/// rule is used for modules with this unwind data.
+pub trait ArmExidxUnwinding: Arch {
+    /// Translate the unwind instructions for a function into an unwind rule, or, if that's
+    /// not possible, execute them directly.
+    fn unwind_frame<F>(
+        instructions: EhabiInstructions,
+        is_first_frame: bool,
+        regs: &mut Self::UnwindRegs,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<Self::UnwindRule>, ArmExidxUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let _ = (instructions, is_first_frame, regs, read_stack);
+        Err(ArmExidxUnwinderError::UnsupportedArch)
+    }
+}
+
+pub struct ArmExidxUnwinder<'a, A: ArmExidxUnwinding> {
+    exidx_data: &'a [u8],
+    extab_data: Option<&'a [u8]>,
+    exidx_svma: u64,
+    extab_svma: u64,
+    base_svma: u64,
+    _arch: PhantomData<A>,
+}
+
+impl<'a, A: ArmExidxUnwinding> ArmExidxUnwinder<'a, A> {
+    pub fn new(
+        exidx_data: &'a [u8],
+        extab_data: Option<&'a [u8]>,
+        exidx_svma: u64,
+        extab_svma: u64,
+        base_svma: u64,
+    ) -> Self {
+        Self {
+            exidx_data,
+            extab_data,
+            exidx_svma,
+            extab_svma,
+            base_svma,
+            _arch: PhantomData,
+        }
+    }
+
+    /// Returns the SVMA of the function start for the exidx entry at `index`, and the
+    /// entry's second word.
+    fn entry(&self, index: usize) -> Option<(u64, u32)> {
+        let entry_svma = self.exidx_svma + index as u64 * 8;
+        let function = read_u32(self.exidx_data, index * 2)?;
+        let data = read_u32(self.exidx_data, index * 2 + 1)?;
+        Some((resolve_prel31(function, entry_svma), data))
+    }
+
+    /// Find the unwind instructions for the function covering the address.
+    pub fn instructions_for_address(
+        &self,
+        rel_lookup_address: u32,
+    ) -> Result<EhabiInstructions<'a>, ArmExidxUnwinderError> {
+        if self.exidx_data.len() % 8 != 0 {
+            return Err(ArmExidxUnwinderError::BadExidxSize);
+        }
+        let entry_count = self.exidx_data.len() / 8;
+        let lookup_svma = self.base_svma + u64::from(rel_lookup_address);
+
+        // The entries are sorted by function start address. Find the last entry which
+        // starts at or before the lookup address.
+        let (mut low, mut high) = (0, entry_count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let (function_svma, _) = self.entry(mid).ok_or(ArmExidxUnwinderError::BadExidxSize)?;
+            if function_svma <= lookup_svma {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        if low == 0 {
+            return Err(ArmExidxUnwinderError::AddressOutsideRange(
+                rel_lookup_address,
+            ));
+        }
+        let index = low - 1;
+        let (_, data) = self
+            .entry(index)
+            .ok_or(ArmExidxUnwinderError::BadExidxSize)?;
+
+        if data == 1 {
+            return Err(ArmExidxUnwinderError::CantUnwind);
+        }
+        if data & 0x8000_0000 != 0 {
+            // The unwind instructions are inline in the exidx entry.
+            let personality = ((data >> 24) & 0x0f) as u8;
+            if personality != 0 {
+                return Err(ArmExidxUnwinderError::UnsupportedPersonality(personality));
+            }
+            return Ok(EhabiInstructions::new(EhabiOpcodeBytes::new(data, 3, &[])));
+        }
+
+        // The second word is a prel31 offset to the extab entry.
+        let data_svma = self.exidx_svma + index as u64 * 8 + 4;
+        let extab_entry_svma = resolve_prel31(data, data_svma);
+        let extab_data = self.extab_data.ok_or(ArmExidxUnwinderError::NoExtabData)?;
+        let extab_offset = extab_entry_svma
+            .checked_sub(self.extab_svma)
+            .and_then(|offset| usize::try_from(offset).ok())
+            .ok_or(ArmExidxUnwinderError::ExtabOutOfBounds)?;
+        let extab_entry = extab_data
+            .get(extab_offset..)
+            .ok_or(ArmExidxUnwinderError::ExtabOutOfBounds)?;
+        let first_word = read_u32(extab_entry, 0).ok_or(ArmExidxUnwinderError::ExtabOutOfBounds)?;
+
+        let (first_word, first_word_byte_count, remaining_words) = if first_word & 0x8000_0000 != 0
+        {
+            // Compact model, with the personality routine index in bits 24-27.
+            match (first_word >> 24) & 0x0f {
+                0 => (first_word, 3, &extab_entry[0..0]),
+                1 | 2 => {
+                    let additional_word_count = ((first_word >> 16) & 0xff) as usize;
+                    (
+                        first_word,
+                        2,
+                        extab_words(extab_entry, 1, additional_word_count)?,
+                    )
+                }
+                personality => {
+                    return Err(ArmExidxUnwinderError::UnsupportedPersonality(
+                        personality as u8,
+                    ))
+                }
+            }
+        } else {
+            // Generic model: The first word is a prel31 offset to the personality
+            // routine, and the personality routine's data follows. For the GNU C++
+            // personality routine (the only one used in practice), this data has
+            // the same layout as the compact model with index 1, except that the
+            // additional word count is in the top byte and the first word contains
+            // three opcode bytes.
+            let data_word =
+                read_u32(extab_entry, 1).ok_or(ArmExidxUnwinderError::ExtabOutOfBounds)?;
+            let additional_word_count = (data_word >> 24) as usize;
+            (
+                data_word,
+                3,
+                extab_words(extab_entry, 2, additional_word_count)?,
+            )
+        };
+        Ok(EhabiInstructions::new(EhabiOpcodeBytes::new(
+            first_word,
+            first_word_byte_count,
+            remaining_words,
+        )))
+    }
+
+    pub fn unwind_frame<F>(
+        &self,
+        rel_lookup_address: u32,
+        is_first_frame: bool,
+        regs: &mut A::UnwindRegs,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<A::UnwindRule>, ArmExidxUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let instructions = self.instructions_for_address(rel_lookup_address)?;
+        A::unwind_frame(instructions, is_first_frame, regs, read_stack)
+    }
+}
+
+fn extab_words(
+    extab_entry: &[u8],
+    start_word: usize,
+    word_count: usize,
+) -> Result<&[u8], ArmExidxUnwinderError> {
+    extab_entry
+        .get(start_word * 4..(start_word + word_count) * 4)
+        .ok_or(ArmExidxUnwinderError::ExtabOutOfBounds)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode_inline(data: u32) -> Vec<EhabiInstruction> {
+        EhabiInstructions::new(EhabiOpcodeBytes::new(data, 3, &[]))
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_inline_opcodes() {
+        // pop {r4, r5, r6, r7, r14}; finish; finish
+        assert_eq!(
+            decode_inline(0x80abb0b0),
+            vec![
+                EhabiInstruction::PopCoreRegisters(0b0100_0000_1111_0000),
+                EhabiInstruction::Finish,
+            ]
+        );
+        // vsp = r7; vsp = vsp - 8; finish
+        assert_eq!(
+            decode_inline(0x809741b0),
+            vec![
+                EhabiInstruction::SetVspToRegister(7),
+                EhabiInstruction::AdjustVsp(-8),
+                EhabiInstruction::Finish,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extab_opcodes() {
+        // Compact model index 1: vsp = r7; vsp = vsp - 8; pop {r4, r5, r7, r14}; vsp = vsp + 4; finish
+        let words = 0x840b_00b0_u32.to_le_bytes();
+        let bytes = EhabiOpcodeBytes::new(0x8101_9741, 2, &words);
+        let instructions: Vec<_> = EhabiInstructions::new(bytes)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                EhabiInstruction::SetVspToRegister(7),
+                EhabiInstruction::AdjustVsp(-8),
+                EhabiInstruction::PopCoreRegisters(0b0100_0000_1011_0000),
+                EhabiInstruction::AdjustVsp(4),
+                EhabiInstruction::Finish,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_vsp_adjustment() {
+        // vsp = vsp + 0x204 + (0x81 << 2)
+        let words = 0xb2_81_01_b0_u32.to_le_bytes();
+        let bytes = EhabiOpcodeBytes::new(0, 0, &words);
+        let instructions: Vec<_> = EhabiInstructions::new(bytes)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                EhabiInstruction::AdjustVsp(0x204 + (0x81 << 2)),
+                EhabiInstruction::Finish
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prel31() {
+        assert_eq!(resolve_prel31(0x7fff_fffc, 0x1000), 0xffc);
+        assert_eq!(resolve_prel31(0x10, 0x1000), 0x1010);
+    }
+}
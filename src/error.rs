@@ -1,12 +1,29 @@
-use crate::dwarf::DwarfUnwinderError;
+use crate::breakpad::{BreakpadIndexError, BreakpadUnwinderError};
+use crate::dwarf::{
+    DwarfCfiIndexError, DwarfUnwinderError, EhFrameHdrError, SerializedCfiIndexError,
+};
+use crate::ehabi::ArmExidxUnwinderError;
 use crate::macho::CompactUnwindInfoUnwinderError;
+use crate::orc::OrcUnwinderError;
+use crate::pe::PeUnwinderError;
+use crate::return_address_checks::ReturnAddressCheckFailure;
+use crate::stack_memory::StackReadError;
 
 /// The error type used in this crate.
+///
+/// New variants may be added for new failure modes, so matches on this enum need a
+/// wildcard arm.
+#[non_exhaustive]
 #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
     #[error("Could not read stack memory at 0x{0:x}")]
     CouldNotReadStack(u64),
 
+    /// Like [`Error::CouldNotReadStack`], for a `read_stack` callback whose error says why
+    /// the read failed, such as the readers of [`StackMemory`](crate::StackMemory).
+    #[error("Could not read stack memory: {0}")]
+    CouldNotReadStackMemory(#[source] StackReadError),
+
     #[error("Frame pointer unwinding moved backwards")]
     FramepointerUnwindingMovedBackwards,
 
@@ -18,8 +35,67 @@ pub enum Error {
 
     #[error("Return address is null")]
     ReturnAddressIsNull,
+
+    #[error("Return address 0x{0:x} is implausible: {1}")]
+    ImplausibleReturnAddress(u64, ReturnAddressCheckFailure),
 }
 
+impl Error {
+    /// Replaces [`Error::CouldNotReadStack`] with [`Error::CouldNotReadStackMemory`] if
+    /// `reason` is for the same address, i.e. if the `read_stack` callback said why the
+    /// failing read failed.
+    pub(crate) fn with_stack_read_error(self, reason: Option<StackReadError>) -> Self {
+        match (self, reason) {
+            (Error::CouldNotReadStack(address), Some(reason)) if reason.address() == address => {
+                Error::CouldNotReadStackMemory(reason)
+            }
+            (err, _) => err,
+        }
+    }
+}
+
+/// An error that makes a module's unwind data unusable, returned from
+/// [`Unwinder::try_add_module`](crate::Unwinder::try_add_module).
+///
+/// Addresses in a module whose unwind data is unusable are unwound with the fallback
+/// rule, which uses frame pointers on x86_64 and aarch64.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleError {
+    #[error("Could not parse __unwind_info: {0}")]
+    BadCompactUnwindInfo(#[source] macho_unwind_info::Error),
+
+    #[error("Bad .eh_frame_hdr: {0}")]
+    BadEhFrameHdr(#[source] EhFrameHdrError),
+
+    #[error("Could not create an index for .eh_frame: {0}")]
+    EhFrameIndex(#[source] DwarfCfiIndexError),
+
+    #[error("Could not create an index for .debug_frame: {0}")]
+    DebugFrameIndex(#[source] DwarfCfiIndexError),
+
+    #[error("Could not create an index for the .debug_frame of the debug companion: {0}")]
+    DebugCompanionIndex(#[source] DwarfCfiIndexError),
+
+    #[error("The serialized CFI index could not be used: {0}")]
+    BadSerializedCfiIndex(#[source] SerializedCfiIndexError),
+
+    #[error("Could not create an index for the Breakpad symbol file: {0}")]
+    BreakpadIndex(#[source] BreakpadIndexError),
+
+    #[error("The unwind data does not describe any functions")]
+    NoFunctions,
+
+    #[error(".orc_unwind_ip and .orc_unwind have different numbers of entries")]
+    OrcEntryCountMismatch,
+
+    #[error("The ORC entries use the layout of Linux versions before 6.4, which is not supported")]
+    UnsupportedOrcLayout,
+}
+
+/// An error from looking up or evaluating a module's unwind information.
+///
+/// These errors are not returned from unwinding; instead, the unwinder falls back to
+/// frame pointer unwinding. They are reported in [`FrameInfo::suppressed_error`](crate::FrameInfo::suppressed_error).
 #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UnwinderError {
     #[error("Compact Unwind Info unwinding failed: {0}")]
@@ -28,6 +104,37 @@ pub enum UnwinderError {
     #[error("DWARF unwinding failed: {0}")]
     Dwarf(#[from] DwarfUnwinderError),
 
+    #[error("ARM EHABI unwinding failed: {0}")]
+    ArmExidx(#[from] ArmExidxUnwinderError),
+
+    #[error("The module has .ARM.exidx unwind data but no .ARM.exidx address")]
+    NoArmExidxAddress,
+
+    #[error("PE unwinding failed: {0}")]
+    Pe(#[from] PeUnwinderError),
+
+    #[error("The module has .pdata unwind data but no address for the unwind info section")]
+    NoPeXdataAddress,
+
+    #[error(
+        "The unwind info section address 0x{0:x} is not within 4GB after the module's base address"
+    )]
+    PeXdataAddressOutOfRange(u64),
+
+    #[error("ORC unwinding failed: {0}")]
+    Orc(#[from] OrcUnwinderError),
+
+    #[error("The module has ORC unwind data but no .orc_unwind_ip address")]
+    NoOrcUnwindIpAddress,
+
+    #[error(
+        "The .orc_unwind_ip section address 0x{0:x} is not within 4GB after the module's base address"
+    )]
+    OrcUnwindIpAddressOutOfRange(u64),
+
+    #[error("Breakpad unwinding failed: {0}")]
+    Breakpad(#[from] BreakpadUnwinderError),
+
     #[error("__unwind_info referred to DWARF FDE but we do not have __eh_frame data")]
     NoDwarfData,
 
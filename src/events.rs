@@ -0,0 +1,17 @@
+/// Emits a `tracing` event if the `tracing` feature is enabled, and does nothing
+/// otherwise.
+///
+/// Usage: `unwind_event!(level, "message", field = value, ...)`, where `level` is one of
+/// the `tracing` level macros (`warn`, `debug`, ...), and each value implements `Display`.
+macro_rules! unwind_event {
+    ($level:ident, $message:literal $(, $field:ident = $value:expr)* $(,)?) => {{
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($field = %$value,)* $message);
+        #[cfg(not(feature = "tracing"))]
+        {
+            $(let _ = &$value;)*
+        }
+    }};
+}
+
+pub(crate) use unwind_event;
@@ -0,0 +1,162 @@
+use gimli::{
+    BaseAddresses, CieOrFde, DebugFrame, EhFrame, EndianSlice, LittleEndian, Reader, UnwindContext,
+    UnwindSection,
+};
+
+use crate::dwarf::{
+    base_addresses_for_sections, DwarfCfiIndexError, DwarfUnwinding, UnwindSectionType,
+};
+use crate::unwind_rule::UnwindRule;
+use crate::ModuleSvmaInfo;
+
+/// An entry of a [`FlatRuleTable`]. It applies from its start address up to the start
+/// address of the next entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlatRuleTableEntry<R> {
+    /// The DWARF CFI row for these addresses translates into this rule.
+    Rule(R),
+    /// These addresses are covered by an FDE, but the row can't be expressed as an unwind
+    /// rule, or the FDE's instructions could not be evaluated. The CFI needs to be
+    /// evaluated at unwind time.
+    NotRepresentable,
+    /// These addresses are not covered by any FDE.
+    Uncovered,
+}
+
+/// The unwind rules for all addresses covered by a DWARF CFI section, sorted by address.
+/// This is created ahead of time when a module is added, if requested with
+/// [`Module::set_flatten_unwind_rules`](crate::Module::set_flatten_unwind_rules), so that
+/// finding the rule for an address is a single binary search.
+pub struct FlatRuleTable<R: UnwindRule> {
+    /// The start address of every entry, relative to the base address. Sorted.
+    starts: Vec<u32>,
+    /// Has the same length as `starts`.
+    entries: Vec<FlatRuleTableEntry<R>>,
+}
+
+impl<R: UnwindRule> FlatRuleTable<R> {
+    pub fn try_new<A, Rd, US>(
+        unwind_section: US,
+        bases: BaseAddresses,
+        base_svma: u64,
+    ) -> Result<Self, DwarfCfiIndexError>
+    where
+        A: DwarfUnwinding<UnwindRule = R>,
+        Rd: Reader,
+        US: UnwindSection<Rd>,
+    {
+        let mut fdes = Vec::new();
+        let mut entries_iter = unwind_section.entries(&bases);
+        while let Some(entry) = entries_iter.next()? {
+            if let CieOrFde::Fde(partial_fde) = entry {
+                fdes.push(partial_fde.parse(US::cie_from_offset)?);
+            }
+        }
+        fdes.sort_by_key(|fde| fde.initial_address());
+
+        let relative_address = |address: u64| {
+            let relative_address = address
+                .checked_sub(base_svma)
+                .ok_or(DwarfCfiIndexError::CouldNotSubtractBaseAddress)?;
+            u32::try_from(relative_address).map_err(|_| DwarfCfiIndexError::RelativeAddressTooBig)
+        };
+
+        let mut table = Self {
+            starts: Vec::new(),
+            entries: Vec::new(),
+        };
+        let mut unwind_context = Box::new(UnwindContext::new());
+        for (i, fde) in fdes.iter().enumerate() {
+            // An address is looked up in the last FDE which starts at or before it, so
+            // an FDE ends where the next FDE starts, even if they overlap.
+            let next_fde_start = fdes.get(i + 1).map(|fde| fde.initial_address());
+            let is_before_next_fde = |address: u64| match next_fde_start {
+                Some(next_start) => address < next_start,
+                None => true,
+            };
+            let fde_start = relative_address(fde.initial_address())?;
+            let entry_count = table.starts.len();
+            let rows_result: Result<(), DwarfCfiIndexError> = (|| {
+                if fde.is_signal_trampoline() {
+                    // Signal trampolines need the signal frame rule, which the DWARF
+                    // unwinder picks based on the FDE, not based on a row.
+                    table.push(fde_start, FlatRuleTableEntry::NotRepresentable);
+                    return Ok(());
+                }
+                let mut rows = fde.rows(&unwind_section, &bases, &mut unwind_context)?;
+                while let Some(row) = rows.next_row()? {
+                    if !is_before_next_fde(row.start_address()) {
+                        break;
+                    }
+                    let entry = match A::unwind_rule_for_row(row) {
+                        Ok(rule) => FlatRuleTableEntry::Rule(rule),
+                        Err(_) => FlatRuleTableEntry::NotRepresentable,
+                    };
+                    table.push(relative_address(row.start_address())?, entry);
+                }
+                Ok(())
+            })();
+            if rows_result.is_err() {
+                table.starts.truncate(entry_count);
+                table.entries.truncate(entry_count);
+                table.push(fde_start, FlatRuleTableEntry::NotRepresentable);
+            }
+            let fde_end = fde.initial_address().saturating_add(fde.len());
+            if is_before_next_fde(fde_end) {
+                if let Ok(fde_end) = relative_address(fde_end) {
+                    table.push(fde_end, FlatRuleTableEntry::Uncovered);
+                }
+            }
+        }
+        table.starts.shrink_to_fit();
+        table.entries.shrink_to_fit();
+        Ok(table)
+    }
+
+    pub fn try_new_for_section<A>(
+        section_type: UnwindSectionType,
+        section_data: &[u8],
+        svma_info: &ModuleSvmaInfo,
+    ) -> Result<Self, DwarfCfiIndexError>
+    where
+        A: DwarfUnwinding<UnwindRule = R>,
+    {
+        let bases = base_addresses_for_sections(svma_info);
+        let data = EndianSlice::new(section_data, LittleEndian);
+        match section_type {
+            UnwindSectionType::EhFrame => {
+                let mut eh_frame = EhFrame::from(data);
+                eh_frame.set_address_size(A::ADDRESS_SIZE);
+                Self::try_new::<A, _, _>(eh_frame, bases, svma_info.base_svma)
+            }
+            UnwindSectionType::DebugFrame => {
+                let mut debug_frame = DebugFrame::from(data);
+                debug_frame.set_address_size(A::ADDRESS_SIZE);
+                Self::try_new::<A, _, _>(debug_frame, bases, svma_info.base_svma)
+            }
+        }
+    }
+
+    fn push(&mut self, start: u32, entry: FlatRuleTableEntry<R>) {
+        if self.starts.last() == Some(&start) {
+            self.starts.pop();
+            self.entries.pop();
+        }
+        // Consecutive rows often translate into the same rule.
+        if self.entries.last() == Some(&entry) {
+            return;
+        }
+        self.starts.push(start);
+        self.entries.push(entry);
+    }
+
+    pub fn lookup(&self, rel_lookup_address: u32) -> FlatRuleTableEntry<R> {
+        match self
+            .starts
+            .partition_point(|start| *start <= rel_lookup_address)
+        {
+            0 => FlatRuleTableEntry::Uncovered,
+            i => self.entries[i - 1],
+        }
+    }
+}
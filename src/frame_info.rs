@@ -0,0 +1,91 @@
+use crate::code_address::FrameAddress;
+use crate::error::UnwinderError;
+
+/// The way in which the unwinder found a frame's address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnwindMethod {
+    /// The frame is the first frame. Its address is the instruction pointer, so no
+    /// unwinding was needed to find it.
+    InstructionPointer,
+    /// The callee's rule came from the callee's `__unwind_info` opcode.
+    CompactUnwindInfo,
+    /// The callee's `__unwind_info` opcode referred to DWARF CFI in `__eh_frame`.
+    CompactUnwindInfoDwarf,
+    /// The callee was interrupted in a prologue or epilogue, and the rule was found by
+    /// analyzing the instructions around the callee's instruction pointer.
+    PrologueEpilogueAnalysis,
+    /// The callee's rule came from DWARF CFI in `.eh_frame`, found via `.eh_frame_hdr`.
+    EhFrameHdr,
+    /// The callee's rule came from DWARF CFI in `.eh_frame` or `.debug_frame`, found via
+    /// the index that framehop builds for sections without a search table.
+    DwarfCfiIndex,
+    /// The callee's rule came from DWARF CFI in the `.debug_frame` of the module's debug
+    /// companion, see [`Module::set_debug_companion`](crate::Module::set_debug_companion).
+    /// `Module::from_object_file` also uses the file's own `.debug_frame` as the debug
+    /// companion if the file has `.eh_frame_hdr`.
+    DebugCompanion,
+    /// The callee's rule came from the table of unwind rules which was created from the
+    /// module's DWARF CFI when the module was added, see
+    /// [`Module::set_flatten_unwind_rules`](crate::Module::set_flatten_unwind_rules).
+    FlattenedRuleTable,
+    /// The callee's rule came from `.ARM.exidx` / `.ARM.extab`.
+    ArmExidx,
+    /// The callee's rule came from `.pdata` / `.xdata`.
+    PeUnwindInfo,
+    /// The callee's rule came from the Linux kernel's `.orc_unwind_ip` / `.orc_unwind`.
+    Orc,
+    /// The callee's rule came from a `STACK CFI` or `STACK WIN` record in a Breakpad
+    /// symbol file.
+    Breakpad,
+    /// The callee is the Linux signal return trampoline, which was recognized by its
+    /// instructions. The caller's registers were restored from the signal frame, and the
+    /// caller's address is the instruction pointer at which the signal interrupted it.
+    SignalTrampoline,
+    /// The callee's module has DWARF CFI, but no FDE covers the callee's address, so the
+    /// rule for uncovered code was used.
+    UncoveredByFde,
+    /// Executing the callee's frame pointer rule failed, and the frame was found by
+    /// scanning the callee's stack for a value which looks like a return address: an
+    /// address in a module's text, right after a call instruction. Such frames can be
+    /// wrong, for example if the value was left over from an earlier call. Stack scanning
+    /// needs to be enabled with `set_stack_scanning` on the unwinder.
+    StackScan,
+    /// The callee's rule is the fallback rule. This happens if the callee's address is not
+    /// inside a known module, or if the unwind information lookup failed, see
+    /// [`FrameInfo::suppressed_error`].
+    Fallback,
+}
+
+/// Describes how the unwinder found a frame. This is useful to diagnose incorrect stacks.
+///
+/// A frame is found by unwinding the previous frame, the callee. All fields other than
+/// `address` describe that unwinding step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameInfo<R> {
+    /// The address of the frame.
+    pub address: FrameAddress,
+    /// How the callee was unwound.
+    pub method: UnwindMethod,
+    /// The unwind rule that was executed to unwind the callee. This is `None` for the
+    /// first frame, for DWARF CFI which could not be expressed as an unwind rule, and for
+    /// frames found by stack scanning.
+    pub rule: Option<R>,
+    /// Whether the rule was found in the unwinder cache. If so, `method` is the method
+    /// that was used when the rule was inserted into the cache.
+    pub cache_hit: bool,
+    /// The error that made the unwinder use the fallback rule, if any. This is `None` if
+    /// the fallback rule was found in the cache.
+    pub suppressed_error: Option<UnwinderError>,
+}
+
+impl<R> FrameInfo<R> {
+    pub(crate) fn for_instruction_pointer(pc: u64) -> Self {
+        Self {
+            address: FrameAddress::InstructionPointer(pc),
+            method: UnwindMethod::InstructionPointer,
+            rule: None,
+            cache_hit: false,
+            suppressed_error: None,
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use gimli::Register;
+
+use crate::add_signed::checked_add_signed;
+use crate::arch::Arch;
+use crate::dwarf::DwarfUnwindRegs;
+
+/// How to recover the caller's value of a callee-saved register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CalleeSavedRule {
+    /// The function did not modify the register.
+    SameValue,
+    /// The function saved the register on the stack, at this offset from the CFA.
+    SavedAtCfaOffset(i64),
+    /// The caller's value cannot be recovered.
+    Unknown,
+}
+
+impl CalleeSavedRule {
+    /// Compute the caller's value of a register whose value in the current frame is
+    /// `value`. The CFA is the stack pointer of the caller frame.
+    pub fn exec<F>(self, value: Option<u64>, cfa: u64, read_stack: &mut F) -> Option<u64>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        match self {
+            CalleeSavedRule::SameValue => value,
+            CalleeSavedRule::SavedAtCfaOffset(offset) => {
+                read_stack(checked_add_signed(cfa, offset)?).ok()
+            }
+            CalleeSavedRule::Unknown => None,
+        }
+    }
+}
+
+/// Implemented by architectures which support full register unwinding, i.e. which can
+/// recover the callee-saved registers in addition to the registers in `UnwindRegs`.
+pub trait FullRegsUnwinding: Arch {
+    type FullUnwindRegs: DwarfUnwindRegs + Clone;
+
+    /// The DWARF register numbers of the callee-saved registers in `FullUnwindRegs`, in
+    /// the order used by `callee_saved_values_mut`.
+    const CALLEE_SAVED_REGS: &'static [Register];
+
+    fn unwind_regs_mut(regs: &mut Self::FullUnwindRegs) -> &mut Self::UnwindRegs;
+
+    fn callee_saved_values_mut(regs: &mut Self::FullUnwindRegs) -> &mut [Option<u64>];
+
+    /// The CFA of a frame which has just been unwound. This is the caller's stack pointer.
+    fn cfa_after_unwinding(regs: &Self::FullUnwindRegs) -> u64;
+
+    /// How a function with this `__unwind_info` opcode saved `register`.
+    fn callee_saved_rule_for_cui_opcode(opcode: u32, register: Register) -> CalleeSavedRule;
+}
@@ -17,4 +17,11 @@ pub trait InstructionAnalysis: Arch {
         Self::rule_from_prologue_analysis(text_bytes, pc_offset)
             .or_else(|| Self::rule_from_epilogue_analysis(text_bytes, pc_offset))
     }
+
+    /// Whether the instruction at `pc_offset` is part of the Linux signal return
+    /// trampoline, i.e. the code a signal handler returns into.
+    /// Caller guarantees pc_offset <= text_bytes.len()
+    fn is_linux_sigreturn_trampoline(_text_bytes: &[u8], _pc_offset: usize) -> bool {
+        false
+    }
 }
@@ -2,21 +2,21 @@
 //!
 //! Framehop is a stack frame unwinder written in 100% Rust. It produces high quality stacks at high speed, on multiple platforms and architectures, without an expensive pre-processing step for unwind information. This makes it suitable for sampling profilers.
 //!
-//! It currently supports unwinding x86_64 and aarch64, with unwind information formats commonly used on macOS, Linux and Android.
+//! It currently supports unwinding x86_64, aarch64 and 32-bit ARM, with unwind information formats commonly used on macOS, Linux, Android and Windows.
 //!
 //! You give framehop register values, stack memory and unwind data, and framehop produces a list of return addresses.
 //!
 //! Framehop can be used in the following scenarios:
 //!
 //!  - Live unwinding of a remote process. This is how [`perfrecord`](https://github.com/mstange/perfrecord/) uses it.
-//!  - Offline unwinding from saved registers and stack bytes, even on a different machine, a different OS, or a different CPU architecture.
+//!  - Offline unwinding from saved registers and stack bytes, even on a different machine, a different OS, or a different CPU architecture. With the `minidump` cargo feature, `framehop::minidump` reads these from the threads of a minidump file, and with the `coredump` cargo feature, `framehop::coredump` reads them from the threads of a Linux ELF core dump.
 //!  - Live unwinding inside the same process. This is currently unproven, but should work as long as you can do heap allocation before sampling, in order to allocate a cache and to update the list of modules. The actual unwinding does not require any heap allocation and should work even inside a signal handler, as long as you use `MustNotAllocateDuringUnwind`.
 //!
 //! As a user of framehop, your responsibilities are the following:
 //!
 //!  - You need to enumerate the modules (libraries) that are loaded in the sampled process ahead of time, or ideally maintain a live list which is updated whenever modules are loaded / unloaded.
-//!  - You need to provide address ranges and unwind section data for those modules.
-//!  - When sampling, you provide the register values and a callback  to read arbitrary stack memory without segfaulting.
+//!  - You need to provide address ranges and unwind section data for those modules. With the `object` cargo feature, `Module::from_object_file` can find these in an ELF, mach-O or PE file for you.
+//!  - When sampling, you provide the register values and a callback  to read arbitrary stack memory without segfaulting. [`StackMemory`] provides this callback for captured stack bytes, and for Linux `perf_event_open` samples, the [`perf`] module creates both from the sample record.
 //!  - On aarch64, picking the right bitmask to strip pointer authentication bits from return addresses is up to you.
 //!  - You will need to do symbol resolution yourself, if you want function names. Framehop only produces addresses, it does not do any symbolication.
 //!
@@ -25,12 +25,18 @@
 //!  - It parses a number of different unwind information formats. At the moment, it supports the following:
 //!    - Apple's Compact Unwinding Format, in `__unwind_info` (macOS)
 //!    - DWARF CFI in `.eh_frame` (using `.eh_frame_hdr` as an index, if available)
-//!    - DWARF CFI in `.debug_frame`
-//!  - It supports correct unwinding even when the program is interrupted inside a function prologue or epilogue. On macOS, it has to analyze assembly instructions in order to do this.
-//!  - On x86_64 and aarch64, it falls back to frame pointer unwinding if it cannot find unwind information for an address.
+//!    - DWARF CFI in `.debug_frame`, on its own, combined with `.eh_frame`, or from a separate debug file
+//!    - ARM EHABI unwind tables in `.ARM.exidx` / `.ARM.extab` (32-bit ARM)
+//!    - PE unwind info in `.pdata` / `.xdata` (Windows x86_64 and aarch64)
+//!    - ORC unwind tables in `.orc_unwind_ip` / `.orc_unwind` (Linux kernel 6.4 and later, x86_64)
+//!    - `STACK CFI` records in Breakpad symbol files, and their `STACK WIN` records for 32-bit x86 code
+//!  - It supports correct unwinding even when the program is interrupted inside a function prologue or epilogue. On macOS, it has to analyze assembly instructions in order to do this. On Linux, it does the same for code which is not covered by DWARF CFI, such as hand-written assembly.
+//!  - On x86_64 and aarch64, it falls back to frame pointer unwinding if it cannot find unwind information for an address. If frame pointer unwinding fails, it can optionally scan the stack for return addresses, like Breakpad does.
+//!  - It unwinds through signal handler frames on x86_64 and aarch64. On Linux, the signal return trampoline (`__restore_rt` in libc, `__kernel_rt_sigreturn` in the vDSO) is recognized by its instructions or by the `S` augmentation of its DWARF CFI, and the interrupted function's registers are restored from the signal frame. The interrupted function's address is then an instruction pointer, not a return address. On macOS, the same is done for `_sigtramp` in libsystem_platform, which is recognized by the `S` augmentation of the DWARF CFI that its compact unwind info refers to.
 //!  - It caches the unwind rule for each address in a fixed-size cache, so that repeated unwinding from the same address is even faster.
-//!  - It generates binary search indexes for unwind information formats which don't have them. Specifically, for `.debug_frame` and for `.eh_frame` without `.eh_frame_hdr`.
-//!  - It does a reasonable job of detecting the end of the stack, so that you can differentiate between properly terminated stacks and prematurely truncated stacks.
+//!  - It generates binary search indexes for unwind information formats which don't have them. Specifically, for `.debug_frame` and for `.eh_frame` without `.eh_frame_hdr`. These indexes can be serialized and supplied again when the module is added next time, to skip re-scanning large sections.
+//!  - It does a reasonable job of detecting the end of the stack, so that you can differentiate between properly terminated stacks and prematurely truncated stacks. Optional return address checks, based on the module list and the text bytes, end the stack cleanly instead of producing junk frames.
+//!  - With the `tracing` cargo feature, it reports bad unwind data as `tracing` events, with the module name and the address, for example when an index for `.eh_frame` cannot be created or when unwinding falls back to frame pointers because of an error.
 //!
 //! Framehop is not suitable for debuggers or to implement exception handling. Debuggers usually need to recover all register values for every frame whereas framehop only cares about return addresses. And exception handling needs the ability to call destructors, which is also a non-goal for framehop.
 //!
@@ -38,10 +44,10 @@
 //!
 //! Framehop achieves high speed in the following ways:
 //!
-//!  1. It only recovers registers which are needed for computing return addresses. On x86_64 that's `rip`, `rsp` and `rbp`, and on aarch64 that's `lr`, `sp` and `fp`. All other registers are not needed - in theory they could be used as inputs to DWARF CFI expressions, but in practice they are not.
+//!  1. It only recovers registers which are needed for computing return addresses. On x86_64 that's `rip`, `rsp` and `rbp`, and on aarch64 that's `lr`, `sp` and `fp`. All other registers are not needed - in theory they could be used as inputs to DWARF CFI expressions, but in practice they are not. If you need the callee-saved registers of caller frames, there is an opt-in full register unwinding mode, `unwind_frame_full`, which recovers them from DWARF CFI and compact unwind info at the cost of some speed.
 //!  2. It uses zero-copy parsing wherever possible. For example, the bytes in `__unwind_info` are only accessed during unwinding, and the binary search happens right inside the original `__unwind_info` memory. For DWARF unwinding, framehop uses the excellent [`gimli` crate](https://github.com/gimli-rs/gimli/), which was written with performance in mind.
 //!  3. It uses binary search to find the correct unwind rule in all supported unwind information formats. For formats without an built-in index, it creates an index when the module is added.
-//!  4. It caches unwind rules based on address. In practice, the 509-slot cache achieves a hit rate of around 80% on complicated code like Firefox (with the cache being shared across all Firefox processes). When profiling simpler applications, the hit rate is likely much higher.
+//!  4. It caches unwind rules based on address. In practice, the 509-slot cache achieves a hit rate of around 80% on complicated code like Firefox (with the cache being shared across all Firefox processes). When profiling simpler applications, the hit rate is likely much higher. The cache size, associativity and hash function can be configured with `CacheConfig`, for example for whole-system profiling. For unwinding on multiple threads, the rules can be kept in a shared cache such as `SharedCacheX86_64`. And if cache misses are too expensive, a module's DWARF CFI can be converted into a sorted table of unwind rules when the module is added, with `Module::set_flatten_unwind_rules`.
 //!
 //! Furthermore, adding a module is fast too because framehop only does minimal up-front parsing and processing - really, the only thing it does is to create the index of FDE offsets for `.eh_frame` / `.debug_frame`.
 //!
@@ -50,7 +56,9 @@
 //! ```rust
 //! # fn test_root_doc_comment() {
 //! use framehop::aarch64::{CacheAarch64, UnwindRegsAarch64, UnwinderAarch64};
-//! use framehop::{FrameAddress, Module, ModuleSvmaInfo, ModuleUnwindData, TextByteData};
+//! use framehop::{
+//!     FrameAddress, Module, ModuleSvmaInfo, ModuleUnwindData, StackMemory, TextByteData,
+//! };
 //!
 //! let mut cache = CacheAarch64::<_>::new();
 //! let mut unwinder = UnwinderAarch64::new();
@@ -68,6 +76,7 @@
 //!         eh_frame: Some(0x100237f80..0x100237ffc),
 //!         eh_frame_hdr: None,
 //!         got: Some(0x100238000..0x100238010),
+//!         ..Default::default()
 //!     },
 //!     ModuleUnwindData::CompactUnwindInfoAndEhFrame(vec![/* __unwind_info */], None),
 //!     Some(TextByteData::new(
@@ -86,7 +95,8 @@
 //!     5, 6, 0x70, 0x1003fc000 + 0x12ca28,
 //!     7, 8, 9, 10, 0x0, 0x0,
 //! ];
-//! let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+//! let stack_bytes = stack.map(u64::to_le_bytes).concat();
+//! let mut read_stack = StackMemory::new(0, &stack_bytes).reader();
 //!
 //! use framehop::Unwinder;
 //! let mut iter = unwinder.iter_frames(
@@ -115,29 +125,68 @@
 mod add_signed;
 mod arcdata;
 mod arch;
+mod batch;
+mod breakpad;
 mod cache;
+mod call_instruction;
 mod code_address;
 mod display_utils;
 mod dwarf;
+mod ehabi;
 mod error;
+mod events;
+mod flat_rule_table;
+mod frame_info;
+mod full_regs;
 mod instruction_analysis;
 mod macho;
+#[cfg(feature = "object")]
+mod object_file;
+mod orc;
+mod pe;
+mod return_address_checks;
 mod rule_cache;
+mod stack_memory;
+mod stack_scan;
 mod unwind_result;
 mod unwind_rule;
 mod unwinder;
 
 /// Types for unwinding on the aarch64 CPU architecture.
 pub mod aarch64;
+/// Types for unwinding on the 32-bit ARM CPU architecture.
+pub mod arm;
 /// Types for unwinding on the x86_64 CPU architecture.
 pub mod x86_64;
 
-pub use cache::{AllocationPolicy, MayAllocateDuringUnwind, MustNotAllocateDuringUnwind};
+#[cfg(feature = "coredump")]
+pub mod coredump;
+#[cfg(feature = "minidump")]
+pub mod minidump;
+pub mod perf;
+
+pub use batch::{BatchOptions, StackStatus, UnwindBatch};
+pub use breakpad::{BreakpadIndexError, BreakpadUnwinderError};
+pub use cache::{
+    AllocationPolicy, CacheConfig, CacheHash, MayAllocateDuringUnwind, MustNotAllocateDuringUnwind,
+};
 pub use code_address::FrameAddress;
-pub use error::Error;
+pub use dwarf::{
+    DwarfCfiIndexError, DwarfUnwinderError, EhFrameHdrError, SerializedCfiIndexError,
+    UnwindSectionType,
+};
+pub use ehabi::ArmExidxUnwinderError;
+pub use error::{Error, ModuleError, UnwinderError};
+pub use frame_info::{FrameInfo, UnwindMethod};
+pub use macho::CompactUnwindInfoUnwinderError;
+pub use orc::OrcUnwinderError;
+pub use pe::PeUnwinderError;
+pub use return_address_checks::{ReturnAddressCheckFailure, ReturnAddressChecks};
 pub use rule_cache::CacheStats;
+pub use stack_memory::{IntoStackReadError, StackMemory, StackMemoryRegions, StackReadError};
 pub use unwinder::{
-    Module, ModuleSvmaInfo, ModuleUnwindData, TextByteData, UnwindIterator, Unwinder,
+    FrameInfoIterator, FrameInfoUnwinder, Module, ModuleSvmaInfo, ModuleUnwindData, TextByteData,
+    UnwindIterator, Unwinder,
 };
 
 /// The unwinder cache for the native CPU architecture.
@@ -159,3 +208,13 @@ pub type UnwindRegsNative = x86_64::UnwindRegsX86_64;
 /// The unwinder type for the native CPU architecture.
 #[cfg(target_arch = "x86_64")]
 pub type UnwinderNative<D, P> = x86_64::UnwinderX86_64<D, P>;
+
+/// The unwinder cache for the native CPU architecture.
+#[cfg(target_arch = "arm")]
+pub type CacheNative<D, P> = arm::CacheArm<D, P>;
+/// The unwind registers type for the native CPU architecture.
+#[cfg(target_arch = "arm")]
+pub type UnwindRegsNative = arm::UnwindRegsArm;
+/// The unwinder type for the native CPU architecture.
+#[cfg(target_arch = "arm")]
+pub type UnwinderNative<D, P> = arm::UnwinderArm<D, P>;
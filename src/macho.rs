@@ -44,11 +44,30 @@ pub enum CompactUnwindInfoUnwinderError {
 
     #[error("Encountered invalid unwind entry")]
     InvalidFrameless,
+
+    #[error("Compact unwind info is not supported for this CPU architecture")]
+    UnsupportedArch,
 }
 
 #[derive(Clone, Debug)]
 pub enum CuiUnwindResult<R: UnwindRule> {
     ExecRule(R),
+    /// The address is in a prologue or epilogue, and the rule was found by instruction
+    /// analysis rather than from the opcode.
+    ExecRuleFromInstructionAnalysis(R),
+    NeedDwarf(u32),
+}
+
+/// Describes how to recover the callee-saved registers in full register unwinding mode.
+#[derive(Clone, Debug)]
+pub enum CuiCalleeSavedResult {
+    /// The callee-saved registers still have the caller's values, because the address
+    /// is in a stub function or in a function's prologue or epilogue.
+    SameValue,
+    /// The callee-saved registers were saved as described by this opcode.
+    Opcode(u32),
+    /// The callee-saved registers are described by the DWARF FDE at this `__eh_frame`
+    /// offset.
     NeedDwarf(u32),
 }
 
@@ -78,6 +97,14 @@ impl<'a> TextBytes<'a> {
             bytes,
         }
     }
+
+    /// Returns the bytes for the function at `start..end`, which are relative to the
+    /// base address, or `None` if the function is not covered by the text bytes.
+    pub fn function_bytes(&self, start: u32, end: u32) -> Option<&'a [u8]> {
+        let start_relative_to_text = start.checked_sub(self.offset_from_base_address)? as usize;
+        let end_relative_to_text = end.checked_sub(self.offset_from_base_address)? as usize;
+        self.bytes.get(start_relative_to_text..end_relative_to_text)
+    }
 }
 
 pub struct CompactUnwindInfoUnwinder<'a, A: CompactUnwindInfoUnwinding> {
@@ -166,17 +193,7 @@ impl<'a, A: CompactUnwindInfoUnwinding> CompactUnwindInfoUnwinder<'a, A> {
         let address_offset_within_function =
             usize::try_from(rel_lookup_address - function.start_address).unwrap();
         let function_bytes = self.text_bytes.and_then(|text_bytes| {
-            let TextBytes {
-                offset_from_base_address,
-                bytes,
-            } = text_bytes;
-            let function_start_relative_to_text = function
-                .start_address
-                .checked_sub(offset_from_base_address)?
-                as usize;
-            let function_end_relative_to_text =
-                function.end_address.checked_sub(offset_from_base_address)? as usize;
-            bytes.get(function_start_relative_to_text..function_end_relative_to_text)
+            text_bytes.function_bytes(function.start_address, function.end_address)
         });
         <A as CompactUnwindInfoUnwinding>::unwind_frame(
             function,
@@ -185,4 +202,55 @@ impl<'a, A: CompactUnwindInfoUnwinding> CompactUnwindInfoUnwinder<'a, A> {
             function_bytes,
         )
     }
+
+    /// Find out how to recover the callee-saved registers for full register unwinding.
+    /// This does the same checks as `unwind_frame`.
+    pub fn callee_saved_regs(
+        &mut self,
+        rel_lookup_address: u32,
+        is_first_frame: bool,
+    ) -> Result<CuiCalleeSavedResult, CompactUnwindInfoUnwinderError> {
+        let in_stubs =
+            self.stubs_range.0 <= rel_lookup_address && rel_lookup_address < self.stubs_range.1;
+        let in_stub_helper = self.stub_helper_range.0 <= rel_lookup_address
+            && rel_lookup_address < self.stub_helper_range.1;
+        if in_stubs || in_stub_helper {
+            if !is_first_frame {
+                return Err(CompactUnwindInfoUnwinderError::StubFunctionCannotBeCaller);
+            }
+            return Ok(CuiCalleeSavedResult::SameValue);
+        }
+        let function = match self.function_for_address(rel_lookup_address) {
+            Ok(f) => f,
+            Err(CompactUnwindInfoUnwinderError::AddressOutsideRange(_)) if is_first_frame => {
+                return Ok(CuiCalleeSavedResult::SameValue);
+            }
+            Err(err) => return Err(err),
+        };
+        if is_first_frame && rel_lookup_address == function.start_address {
+            return Ok(CuiCalleeSavedResult::SameValue);
+        }
+        let address_offset_within_function =
+            usize::try_from(rel_lookup_address - function.start_address).unwrap();
+        let function_bytes = self.text_bytes.and_then(|text_bytes| {
+            text_bytes.function_bytes(function.start_address, function.end_address)
+        });
+        let opcode = function.opcode;
+        match <A as CompactUnwindInfoUnwinding>::unwind_frame(
+            function,
+            is_first_frame,
+            address_offset_within_function,
+            function_bytes,
+        )? {
+            CuiUnwindResult::ExecRule(_) => Ok(CuiCalleeSavedResult::Opcode(opcode)),
+            CuiUnwindResult::ExecRuleFromInstructionAnalysis(_) => {
+                // In a prologue or epilogue, the callee-saved registers are either not
+                // saved yet or already restored.
+                Ok(CuiCalleeSavedResult::SameValue)
+            }
+            CuiUnwindResult::NeedDwarf(fde_offset) => {
+                Ok(CuiCalleeSavedResult::NeedDwarf(fde_offset))
+            }
+        }
+    }
 }
@@ -0,0 +1,458 @@
+//! Unwinding the threads of a minidump (`.dmp`) file.
+//!
+//! [`Minidump`] reads the module list, the thread list with each thread's register
+//! context, and the memory list from the bytes of a minidump. It turns them into the
+//! inputs of the unwinder: [`Module`]s, created from binaries which you look up by code
+//! id, the initial unwind registers of each thread, and a reader for the thread's stack
+//! memory.
+//!
+//! ```rust,no_run
+//! use framehop::minidump::Minidump;
+//! use framehop::x86_64::{CacheX86_64, UnwinderX86_64};
+//! use framehop::Unwinder;
+//!
+//! # fn find_binary(code_id: Option<&str>) -> Option<Vec<u8>> { None }
+//! let dump_bytes = std::fs::read("crash.dmp").unwrap();
+//! let dump = Minidump::parse(&dump_bytes).unwrap();
+//! let mut unwinder = UnwinderX86_64::new();
+//! for module in dump.unwinder_modules(|module| find_binary(module.code_id().as_deref())).unwrap() {
+//!     unwinder.add_module(module);
+//! }
+//! let mut cache = CacheX86_64::<_>::new();
+//! for thread in dump.threads().unwrap() {
+//!     let (pc, regs) = thread.regs_x86_64().unwrap();
+//!     let mut read_stack = thread.stack().reader();
+//!     let mut iter = unwinder.iter_frames(pc, regs, &mut cache, &mut read_stack);
+//!     while let Ok(Some(frame)) = iter.next() {
+//!         println!("{:?}", frame);
+//!     }
+//! }
+//! ```
+//!
+//! Requires the `minidump` feature.
+
+use std::fmt::Write;
+
+use crate::aarch64::UnwindRegsAarch64;
+use crate::events::unwind_event;
+use crate::pe::{read_u16, read_u32, read_u64};
+use crate::stack_memory::{StackMemory, StackMemoryRegions};
+use crate::unwinder::{Module, ModuleSvmaInfo, ModuleUnwindData};
+use crate::x86_64::UnwindRegsX86_64;
+
+const MINIDUMP_SIGNATURE: u32 = 0x504d_444d; // "MDMP"
+
+const THREAD_LIST_STREAM: u32 = 3;
+const MODULE_LIST_STREAM: u32 = 4;
+const MEMORY_LIST_STREAM: u32 = 5;
+const SYSTEM_INFO_STREAM: u32 = 7;
+const MEMORY64_LIST_STREAM: u32 = 9;
+
+const PROCESSOR_ARCHITECTURE_AMD64: u16 = 9;
+const PROCESSOR_ARCHITECTURE_ARM64: u16 = 12;
+/// The value that older versions of Breakpad use for arm64.
+const PROCESSOR_ARCHITECTURE_ARM64_BREAKPAD: u16 = 0x8003;
+
+const PLATFORM_WIN32_NT: u32 = 2;
+const PLATFORM_MACOS: u32 = 0x8101;
+const PLATFORM_IOS: u32 = 0x8102;
+
+const CV_SIGNATURE_RSDS: u32 = 0x5344_5352; // "RSDS"
+const CV_SIGNATURE_BPEL: u32 = 0x4c45_7042; // "BpEL"
+
+const THREAD_SIZE: usize = 48;
+const MODULE_SIZE: usize = 108;
+const MEMORY_DESCRIPTOR_SIZE: usize = 16;
+const MEMORY64_DESCRIPTOR_SIZE: usize = 16;
+
+/// The offsets of rsp, rbp and rip in the AMD64 `CONTEXT` structure.
+const CONTEXT_AMD64_RSP: usize = 152;
+const CONTEXT_AMD64_RBP: usize = 160;
+const CONTEXT_AMD64_RIP: usize = 248;
+
+/// The offsets of fp, lr, sp and pc in the ARM64 `CONTEXT` structure. Older versions of
+/// Breakpad use a different layout for the start of the context, but the same offsets
+/// for these registers.
+const CONTEXT_ARM64_FP: usize = 240;
+const CONTEXT_ARM64_LR: usize = 248;
+const CONTEXT_ARM64_SP: usize = 256;
+const CONTEXT_ARM64_PC: usize = 264;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum MinidumpError {
+    #[error("The data is not a minidump")]
+    BadSignature,
+
+    #[error("The minidump is truncated: the {0} is out of bounds")]
+    OutOfBounds(&'static str),
+
+    #[error("The minidump has no {0} stream")]
+    MissingStream(&'static str),
+
+    #[error("The thread context is too small for the {0} registers")]
+    ContextTooSmall(&'static str),
+
+    #[error("The minidump is for CPU architecture {0}, not for {1}")]
+    WrongCpu(u16, &'static str),
+
+    #[error("Could not parse the binary for module {0}: {1}")]
+    BadModuleBinary(String, object::Error),
+
+    #[error("The address range of module {0} overflows")]
+    ModuleRangeOverflow(String),
+}
+
+/// A parsed minidump. This only reads the stream directory up front; the streams are
+/// parsed when they are accessed.
+#[derive(Clone, Copy, Debug)]
+pub struct Minidump<'a> {
+    data: &'a [u8],
+    stream_directory: &'a [u8],
+}
+
+impl<'a> Minidump<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, MinidumpError> {
+        if read_u32(data, 0) != Some(MINIDUMP_SIGNATURE) {
+            return Err(MinidumpError::BadSignature);
+        }
+        let stream_count = read_u32(data, 8).ok_or(MinidumpError::OutOfBounds("header"))?;
+        let directory_rva = read_u32(data, 12).ok_or(MinidumpError::OutOfBounds("header"))?;
+        let stream_directory = location(data, directory_rva, stream_count as u64 * 12)
+            .ok_or(MinidumpError::OutOfBounds("stream directory"))?;
+        Ok(Self {
+            data,
+            stream_directory,
+        })
+    }
+
+    /// Returns the data of the first stream with the given type.
+    fn stream(&self, stream_type: u32) -> Option<Result<&'a [u8], MinidumpError>> {
+        let entry = self
+            .stream_directory
+            .chunks_exact(12)
+            .find(|entry| read_u32(entry, 0) == Some(stream_type))?;
+        let size = read_u32(entry, 4).unwrap();
+        let rva = read_u32(entry, 8).unwrap();
+        Some(location(self.data, rva, size.into()).ok_or(MinidumpError::OutOfBounds("stream")))
+    }
+
+    /// The processor architecture from the system info stream, for example 9 for x86_64
+    /// and 12 for aarch64.
+    pub fn processor_architecture(&self) -> Option<u16> {
+        read_u16(self.stream(SYSTEM_INFO_STREAM)?.ok()?, 0)
+    }
+
+    /// The platform id from the system info stream, for example 2 for Windows.
+    pub fn platform_id(&self) -> Option<u32> {
+        read_u32(self.stream(SYSTEM_INFO_STREAM)?.ok()?, 20)
+    }
+
+    /// The modules which were loaded in the process.
+    pub fn modules(&self) -> Result<Vec<MinidumpModule<'a>>, MinidumpError> {
+        let stream = self
+            .stream(MODULE_LIST_STREAM)
+            .ok_or(MinidumpError::MissingStream("module list"))??;
+        let platform_id = self.platform_id();
+        list_entries(stream, MODULE_SIZE, "module list")?
+            .map(|entry| {
+                let name_rva = read_u32(entry, 20).unwrap();
+                let name = read_string(self.data, name_rva)
+                    .ok_or(MinidumpError::OutOfBounds("module name"))?;
+                let cv_record = location_descriptor(self.data, entry, 76)
+                    .ok_or(MinidumpError::OutOfBounds("module CodeView record"))?;
+                Ok(MinidumpModule {
+                    name,
+                    base_address: read_u64(entry, 0).unwrap(),
+                    size: read_u32(entry, 8).unwrap(),
+                    timestamp: read_u32(entry, 16).unwrap(),
+                    cv_record,
+                    platform_id,
+                })
+            })
+            .collect()
+    }
+
+    /// Creates a [`Module`] for each module in the minidump. `lookup_binary` is called for
+    /// each module and returns the bytes of the module's binary, if available, for
+    /// example from a symbol server, by the module's [code id](MinidumpModule::code_id).
+    /// Modules without a binary, or whose binary can't be parsed, are added without
+    /// unwind information, so that the unwinder uses frame pointers for them. Modules
+    /// whose address range overflows are skipped.
+    pub fn unwinder_modules<F>(
+        &self,
+        mut lookup_binary: F,
+    ) -> Result<Vec<Module<Vec<u8>>>, MinidumpError>
+    where
+        F: FnMut(&MinidumpModule) -> Option<Vec<u8>>,
+    {
+        let mut modules = Vec::new();
+        for module in self.modules()? {
+            let binary = lookup_binary(&module);
+            let result = module.to_module(binary.as_deref()).or_else(|err| {
+                unwind_event!(
+                    debug,
+                    "Could not use the module's binary, adding it without unwind information",
+                    module = module.name,
+                    error = err,
+                );
+                module.to_module(None)
+            });
+            match result {
+                Ok(module) => modules.push(module),
+                Err(err) => {
+                    unwind_event!(debug, "Skipping module", module = module.name, error = err);
+                }
+            }
+        }
+        Ok(modules)
+    }
+
+    /// The threads of the process.
+    pub fn threads(&self) -> Result<Vec<MinidumpThread<'a>>, MinidumpError> {
+        let stream = self
+            .stream(THREAD_LIST_STREAM)
+            .ok_or(MinidumpError::MissingStream("thread list"))??;
+        let processor_architecture = self.processor_architecture();
+        list_entries(stream, THREAD_SIZE, "thread list")?
+            .map(|entry| {
+                let stack_start = read_u64(entry, 24).unwrap();
+                let stack = location_descriptor(self.data, entry, 32)
+                    .ok_or(MinidumpError::OutOfBounds("thread stack"))?;
+                let context = location_descriptor(self.data, entry, 40)
+                    .ok_or(MinidumpError::OutOfBounds("thread context"))?;
+                Ok(MinidumpThread {
+                    thread_id: read_u32(entry, 0).unwrap(),
+                    stack: StackMemory::new(stack_start, stack),
+                    context,
+                    processor_architecture,
+                })
+            })
+            .collect()
+    }
+
+    /// All memory in the minidump's memory list, including the threads' stacks. This can
+    /// be used for reading stack memory outside a thread's own stack, for example on an
+    /// alternate signal stack.
+    pub fn memory(&self) -> Result<StackMemoryRegions<'a>, MinidumpError> {
+        let mut regions = StackMemoryRegions::new();
+        if let Some(stream) = self.stream(MEMORY_LIST_STREAM) {
+            for entry in list_entries(stream?, MEMORY_DESCRIPTOR_SIZE, "memory list")? {
+                let bytes = location_descriptor(self.data, entry, 8)
+                    .ok_or(MinidumpError::OutOfBounds("memory range"))?;
+                regions.add(StackMemory::new(read_u64(entry, 0).unwrap(), bytes));
+            }
+        }
+        if let Some(stream) = self.stream(MEMORY64_LIST_STREAM) {
+            // Full-memory dumps store the memory contiguously, starting at base_rva.
+            let stream = stream?;
+            let count = read_u64(stream, 0).ok_or(MinidumpError::OutOfBounds("memory list"))?;
+            let mut rva = read_u64(stream, 8).ok_or(MinidumpError::OutOfBounds("memory list"))?;
+            let descriptors = usize::try_from(count)
+                .ok()
+                .and_then(|count| count.checked_mul(MEMORY64_DESCRIPTOR_SIZE))
+                .and_then(|size| stream.get(16..16usize.checked_add(size)?))
+                .ok_or(MinidumpError::OutOfBounds("memory list"))?;
+            for entry in descriptors.chunks_exact(MEMORY64_DESCRIPTOR_SIZE) {
+                let size = read_u64(entry, 8).unwrap();
+                let bytes = usize::try_from(rva)
+                    .ok()
+                    .zip(usize::try_from(size).ok())
+                    .and_then(|(start, size)| self.data.get(start..start.checked_add(size)?))
+                    .ok_or(MinidumpError::OutOfBounds("memory range"))?;
+                regions.add(StackMemory::new(read_u64(entry, 0).unwrap(), bytes));
+                rva = rva.saturating_add(size);
+            }
+        }
+        Ok(regions)
+    }
+}
+
+/// A module from the minidump's module list.
+#[derive(Clone, Debug)]
+pub struct MinidumpModule<'a> {
+    /// The path of the module, as recorded in the minidump.
+    pub name: String,
+    /// The address at which the module was loaded.
+    pub base_address: u64,
+    /// The size of the module's mapping.
+    pub size: u32,
+    /// The `TimeDateStamp` of the module. This is only meaningful for PE binaries.
+    pub timestamp: u32,
+    cv_record: &'a [u8],
+    platform_id: Option<u32>,
+}
+
+impl MinidumpModule<'_> {
+    /// The module's code id, which identifies the binary, in the format used by symbol
+    /// servers:
+    ///
+    ///  - For PE binaries, the timestamp and the image size, as in `5AB380779000`.
+    ///  - For ELF binaries, the build id in lowercase hex.
+    ///  - For mach-O binaries, the UUID in uppercase hex.
+    ///
+    /// Returns `None` if the minidump has no identifying information for the module.
+    pub fn code_id(&self) -> Option<String> {
+        let is_apple = matches!(self.platform_id, Some(PLATFORM_MACOS | PLATFORM_IOS));
+        match self.platform_id {
+            Some(PLATFORM_WIN32_NT) => Some(format!("{:08X}{:x}", self.timestamp, self.size)),
+            _ => match read_u32(self.cv_record, 0)? {
+                CV_SIGNATURE_BPEL => Some(hex(&self.cv_record[4..], false)),
+                // Crashpad stores the mach-O UUID, or the first 16 bytes of the ELF build
+                // id, as the RSDS GUID, in their original byte order.
+                CV_SIGNATURE_RSDS => Some(hex(self.cv_record.get(4..20)?, is_apple)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Creates a [`Module`] from the module's `binary`, or a module without unwind
+    /// information if `binary` is `None`.
+    pub fn to_module(&self, binary: Option<&[u8]>) -> Result<Module<Vec<u8>>, MinidumpError> {
+        let module = match binary {
+            Some(binary) => {
+                Module::from_mapped_object_file(self.name.clone(), binary, self.base_address)
+                    .map_err(|err| MinidumpError::BadModuleBinary(self.name.clone(), err))?
+            }
+            None => Module::new(
+                self.name.clone(),
+                self.base_address..self.end_address()?,
+                self.base_address,
+                ModuleSvmaInfo {
+                    base_svma: 0,
+                    text: None,
+                    text_env: None,
+                    stubs: None,
+                    stub_helper: None,
+                    eh_frame: None,
+                    eh_frame_hdr: None,
+                    got: None,
+                    ..Default::default()
+                },
+                ModuleUnwindData::None,
+                None,
+            ),
+        };
+        Ok(module)
+    }
+
+    fn end_address(&self) -> Result<u64, MinidumpError> {
+        self.base_address
+            .checked_add(u64::from(self.size))
+            .ok_or_else(|| MinidumpError::ModuleRangeOverflow(self.name.clone()))
+    }
+}
+
+/// A thread from the minidump's thread list.
+#[derive(Clone, Copy, Debug)]
+pub struct MinidumpThread<'a> {
+    pub thread_id: u32,
+    stack: StackMemory<'a>,
+    context: &'a [u8],
+    processor_architecture: Option<u16>,
+}
+
+impl<'a> MinidumpThread<'a> {
+    /// The thread's stack memory, which the minidump captured starting at the stack
+    /// pointer. Use [`StackMemory::reader`] for the `read_stack` callback.
+    pub fn stack(&self) -> StackMemory<'a> {
+        self.stack
+    }
+
+    /// The raw `CONTEXT` record with the thread's registers.
+    pub fn context(&self) -> &'a [u8] {
+        self.context
+    }
+
+    /// Returns the thread's instruction pointer and its unwind registers, from an AMD64
+    /// `CONTEXT` record.
+    pub fn regs_x86_64(&self) -> Result<(u64, UnwindRegsX86_64), MinidumpError> {
+        self.check_cpu(&[PROCESSOR_ARCHITECTURE_AMD64], "x86_64")?;
+        let reg =
+            |offset| read_u64(self.context, offset).ok_or(MinidumpError::ContextTooSmall("x86_64"));
+        let ip = reg(CONTEXT_AMD64_RIP)?;
+        Ok((
+            ip,
+            UnwindRegsX86_64::new(ip, reg(CONTEXT_AMD64_RSP)?, reg(CONTEXT_AMD64_RBP)?),
+        ))
+    }
+
+    /// Returns the thread's program counter and its unwind registers, from an ARM64
+    /// `CONTEXT` record.
+    pub fn regs_aarch64(&self) -> Result<(u64, UnwindRegsAarch64), MinidumpError> {
+        self.check_cpu(
+            &[
+                PROCESSOR_ARCHITECTURE_ARM64,
+                PROCESSOR_ARCHITECTURE_ARM64_BREAKPAD,
+            ],
+            "aarch64",
+        )?;
+        let reg = |offset| {
+            read_u64(self.context, offset).ok_or(MinidumpError::ContextTooSmall("aarch64"))
+        };
+        let pc = reg(CONTEXT_ARM64_PC)?;
+        let regs = UnwindRegsAarch64::new(
+            reg(CONTEXT_ARM64_LR)?,
+            reg(CONTEXT_ARM64_SP)?,
+            reg(CONTEXT_ARM64_FP)?,
+        );
+        Ok((pc, regs))
+    }
+
+    /// If the minidump has no system info stream, the context is assumed to be for the
+    /// requested architecture.
+    fn check_cpu(&self, expected: &[u16], name: &'static str) -> Result<(), MinidumpError> {
+        match self.processor_architecture {
+            Some(arch) if !expected.contains(&arch) => Err(MinidumpError::WrongCpu(arch, name)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn location(data: &[u8], rva: u32, size: u64) -> Option<&[u8]> {
+    let start = usize::try_from(rva).ok()?;
+    data.get(start..start.checked_add(usize::try_from(size).ok()?)?)
+}
+
+/// Resolves the `MINIDUMP_LOCATION_DESCRIPTOR` (size, rva) at `offset` in `entry`.
+fn location_descriptor<'a>(data: &'a [u8], entry: &[u8], offset: usize) -> Option<&'a [u8]> {
+    let size = read_u32(entry, offset)?;
+    let rva = read_u32(entry, offset + 4)?;
+    location(data, rva, size.into())
+}
+
+/// Returns the entries of a list stream which starts with a 32-bit count.
+fn list_entries<'a>(
+    stream: &'a [u8],
+    entry_size: usize,
+    name: &'static str,
+) -> Result<impl Iterator<Item = &'a [u8]>, MinidumpError> {
+    let count = read_u32(stream, 0).ok_or(MinidumpError::OutOfBounds(name))? as usize;
+    let entries = count
+        .checked_mul(entry_size)
+        .and_then(|size| stream.get(4..4usize.checked_add(size)?))
+        .ok_or(MinidumpError::OutOfBounds(name))?;
+    Ok(entries.chunks_exact(entry_size))
+}
+
+/// Reads a `MINIDUMP_STRING`, a 32-bit byte length followed by UTF-16LE.
+fn read_string(data: &[u8], rva: u32) -> Option<String> {
+    let len = read_u32(data, rva as usize)?;
+    let bytes = location(data, rva.checked_add(4)?, len.into())?;
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    Some(String::from_utf16_lossy(&units))
+}
+
+fn hex(bytes: &[u8], uppercase: bool) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        if uppercase {
+            write!(s, "{byte:02X}").unwrap();
+        } else {
+            write!(s, "{byte:02x}").unwrap();
+        }
+    }
+    s
+}
@@ -0,0 +1,306 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use object::{Object, ObjectSection, ObjectSegment, SegmentFlags};
+
+use crate::{Module, ModuleSvmaInfo, ModuleUnwindData, TextByteData};
+
+impl Module<Vec<u8>> {
+    /// Create a module from the bytes of an ELF, mach-O or PE file which is mapped into
+    /// the process at `base_avma`.
+    ///
+    /// This finds the sections which framehop needs, computes the [`ModuleSvmaInfo`],
+    /// picks the best available [`ModuleUnwindData`], and copies the unwind sections
+    /// and the text bytes into the module.
+    ///
+    /// `base_avma` is the address at which the module's base is mapped, see
+    /// [`ModuleSvmaInfo::base_svma`] for what "base" means for each format. For a Linux
+    /// kernel image with ORC unwind data, this is the address of the kernel text, i.e. of
+    /// the `_text` symbol.
+    ///
+    /// Requires the `object` feature.
+    pub fn from_object_file(
+        name: String,
+        bytes: &[u8],
+        base_avma: u64,
+    ) -> Result<Self, object::Error> {
+        let file = object::File::parse(bytes)?;
+        let base_svma = relative_address_base(&file);
+
+        let text = file.section_by_name(".text");
+        let unwind_info = file.section_by_name("__unwind_info");
+        let eh_frame = file
+            .section_by_name(".eh_frame")
+            .or_else(|| file.section_by_name("__eh_frame"));
+        let eh_frame_hdr = file.section_by_name(".eh_frame_hdr");
+        let arm_exidx = file.section_by_name(".ARM.exidx");
+        let arm_extab = file.section_by_name(".ARM.extab");
+        let pdata = file.section_by_name(".pdata");
+        // MSVC puts the unwind info into .rdata.
+        let xdata = file
+            .section_by_name(".xdata")
+            .or_else(|| file.section_by_name(".rdata"));
+        // The Linux kernel and kernel modules.
+        let orc_unwind_ip = file.section_by_name(".orc_unwind_ip");
+        let orc_unwind = file.section_by_name(".orc_unwind");
+        let debug_frame = file
+            .section_by_name(".debug_frame")
+            .or_else(|| file.section_by_name("__zdebug_frame"));
+
+        let debug_frame = debug_frame
+            .as_ref()
+            .and_then(uncompressed_section_data)
+            .map(Cow::into_owned);
+        let orc_unwind_ip = orc_unwind_ip.as_ref().and_then(section_data);
+        let orc_unwind = orc_unwind.as_ref().and_then(section_data);
+        let unwind_data = if let (Some(orc_unwind_ip), Some(orc_unwind)) =
+            (orc_unwind_ip, orc_unwind)
+        {
+            ModuleUnwindData::Orc(orc_unwind_ip, orc_unwind)
+        } else {
+            match (
+                pdata.as_ref().and_then(section_data),
+                xdata.as_ref().and_then(section_data),
+                unwind_info.as_ref().and_then(section_data),
+                eh_frame.as_ref().and_then(section_data),
+                eh_frame_hdr.as_ref().and_then(section_data),
+                arm_exidx.as_ref().and_then(section_data),
+                debug_frame.clone(),
+            ) {
+                (Some(pdata), Some(xdata), ..) => ModuleUnwindData::PeUnwindInfo(pdata, xdata),
+                (_, _, Some(unwind_info), eh_frame, ..) => {
+                    ModuleUnwindData::CompactUnwindInfoAndEhFrame(unwind_info, eh_frame)
+                }
+                (_, _, None, Some(eh_frame), Some(eh_frame_hdr), ..) => {
+                    ModuleUnwindData::EhFrameHdrAndEhFrame(eh_frame_hdr, eh_frame)
+                }
+                (_, _, None, Some(eh_frame), None, _, Some(debug_frame)) => {
+                    ModuleUnwindData::EhFrameAndDebugFrame(eh_frame, debug_frame)
+                }
+                (_, _, None, Some(eh_frame), None, ..) => ModuleUnwindData::EhFrame(eh_frame),
+                (_, _, None, None, _, Some(arm_exidx), _) => {
+                    ModuleUnwindData::ArmExidx(arm_exidx, arm_extab.as_ref().and_then(section_data))
+                }
+                (_, _, None, None, _, None, Some(debug_frame)) => {
+                    ModuleUnwindData::DebugFrame(debug_frame)
+                }
+                (_, _, None, None, _, None, None) => ModuleUnwindData::None,
+            }
+        };
+
+        let svma_to_avma = |svma: u64| base_avma.wrapping_add(svma.wrapping_sub(base_svma));
+        let text_data =
+            if let Some(text_segment) = file
+                .segments()
+                .find(|segment| segment.name_bytes() == Ok(Some(b"__TEXT")))
+            {
+                let start = svma_to_avma(text_segment.address());
+                text_segment.data().ok().map(|data| {
+                    TextByteData::new(data.to_owned(), start..start + data.len() as u64)
+                })
+            } else if let Some(text_section) = &text {
+                let start = svma_to_avma(text_section.address());
+                text_section.data().ok().map(|data| {
+                    TextByteData::new(data.to_owned(), start..start + data.len() as u64)
+                })
+            } else {
+                None
+            };
+
+        // The mapped range covers all segments at or above the base address. This skips
+        // mach-O's __PAGEZERO, which isn't actually mapped.
+        let svma_end = file
+            .segments()
+            .filter(|segment| segment.address() >= base_svma)
+            .map(|segment| segment.address() + segment.size())
+            .max()
+            .unwrap_or(base_svma + bytes.len() as u64);
+        let avma_range = base_avma..svma_to_avma(svma_end);
+
+        // With .eh_frame_hdr, we don't need to index .eh_frame, so we don't use
+        // ModuleUnwindData::EhFrameAndDebugFrame. Functions which are only described in
+        // .debug_frame are still found by using it as the debug companion.
+        let debug_companion = match (&unwind_data, debug_frame) {
+            (ModuleUnwindData::EhFrameHdrAndEhFrame(..), Some(debug_frame)) => Some(debug_frame),
+            _ => None,
+        };
+        let mut module = Module::new(
+            name,
+            avma_range,
+            base_avma,
+            svma_info(&file),
+            unwind_data,
+            text_data,
+        );
+        if let Some(debug_frame) = debug_companion {
+            module.set_debug_companion(debug_frame, svma_info(&file));
+        }
+        Ok(module)
+    }
+
+    /// Like [`Module::from_object_file`], for a binary whose mapping of the start of the
+    /// file begins at `start_avma`, as recorded in minidumps and core dumps.
+    pub(crate) fn from_mapped_object_file(
+        name: String,
+        bytes: &[u8],
+        start_avma: u64,
+    ) -> Result<Self, object::Error> {
+        let file = object::File::parse(bytes)?;
+        // Mappings are page-aligned, so round the address of the segment which contains
+        // the start of the file down. Skip empty segments like mach-O's __PAGEZERO. PE
+        // sections never contain the headers, and PE images are mapped at their base.
+        let first_segment_svma = file
+            .segments()
+            .find(|segment| matches!(segment.file_range(), (0, size) if size != 0))
+            .map(|segment| segment.address() & !0xfff);
+        let base_avma = match first_segment_svma {
+            Some(svma) => start_avma
+                .wrapping_sub(svma)
+                .wrapping_add(relative_address_base(&file)),
+            None => start_avma,
+        };
+        Self::from_object_file(name, bytes, base_avma)
+    }
+
+    /// Attach the `.debug_frame` section of a separate debug file, given the bytes of
+    /// that file. See [`Module::set_debug_companion`].
+    ///
+    /// Does nothing if the debug file has no `.debug_frame` section.
+    ///
+    /// Requires the `object` feature.
+    pub fn set_debug_companion_from_object_file(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<(), object::Error> {
+        let file = object::File::parse(bytes)?;
+        let debug_frame = file
+            .section_by_name(".debug_frame")
+            .or_else(|| file.section_by_name("__zdebug_frame"));
+        if let Some(debug_frame) = debug_frame.as_ref().and_then(uncompressed_section_data) {
+            self.set_debug_companion(debug_frame.into_owned(), svma_info(&file));
+        }
+        Ok(())
+    }
+}
+
+fn svma_info<'data: 'file, 'file>(file: &'file impl Object<'data, 'file>) -> ModuleSvmaInfo {
+    let section_range = |name: &str| svma_range(&file.section_by_name(name));
+    ModuleSvmaInfo {
+        base_svma: relative_address_base(file),
+        text: section_range(".text"),
+        text_env: section_range("__text_env"),
+        stubs: section_range("__stubs"),
+        stub_helper: section_range("__stub_helper"),
+        eh_frame: section_range(".eh_frame").or_else(|| section_range("__eh_frame")),
+        eh_frame_hdr: section_range(".eh_frame_hdr"),
+        got: section_range(".got"),
+        arm_exidx: section_range(".ARM.exidx"),
+        arm_extab: section_range(".ARM.extab"),
+        xdata: section_range(".xdata").or_else(|| section_range(".rdata")),
+        orc_unwind_ip: section_range(".orc_unwind_ip"),
+        executable_segments: executable_segments(file),
+    }
+}
+
+fn executable_segments<'data: 'file, 'file>(
+    file: &'file impl Object<'data, 'file>,
+) -> Vec<Range<u64>> {
+    file.segments()
+        .filter(|segment| match segment.flags() {
+            SegmentFlags::Elf { p_flags } => p_flags & object::elf::PF_X != 0,
+            SegmentFlags::MachO { initprot, .. } => initprot & object::macho::VM_PROT_EXECUTE != 0,
+            SegmentFlags::Coff { characteristics } => {
+                characteristics & object::pe::IMAGE_SCN_MEM_EXECUTE != 0
+            }
+            _ => false,
+        })
+        .map(|segment| segment.address()..segment.address() + segment.size())
+        .collect()
+}
+
+fn section_data<'a>(section: &impl ObjectSection<'a>) -> Option<Vec<u8>> {
+    section.data().ok().map(|data| data.to_owned())
+}
+
+fn svma_range<'a>(section: &Option<impl ObjectSection<'a>>) -> Option<Range<u64>> {
+    section
+        .as_ref()
+        .map(|section| section.address()..section.address() + section.size())
+}
+
+fn uncompressed_section_data<'a>(section: &impl ObjectSection<'a>) -> Option<Cow<'a, [u8]>> {
+    let section_data = section.uncompressed_data().ok()?;
+
+    // Make sure the data is actually decompressed.
+    if section.name_bytes().ok()?.starts_with(b"__zdebug_")
+        && section_data.starts_with(b"ZLIB\0\0\0\0")
+    {
+        // Object's built-in compressed section handling didn't detect this as a
+        // compressed section. This happens on Go binaries which use compressed
+        // sections like __zdebug_ranges, which is generally uncommon on macOS, so
+        // object's mach-O parser doesn't handle them.
+        // But we want to handle them.
+        // Go stopped using zdebug sections for ELF files in https://github.com/golang/go/issues/50796
+        // but still uses them for mach-O builds.
+        let b = section_data.get(8..12)?;
+        let uncompressed_size = u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+        let compressed_bytes = &section_data[12..];
+
+        let mut decompressed = Vec::with_capacity(uncompressed_size as usize);
+        let mut decompress = flate2::Decompress::new(true);
+        decompress
+            .decompress_vec(
+                compressed_bytes,
+                &mut decompressed,
+                flate2::FlushDecompress::Finish,
+            )
+            .ok()?;
+        Some(Cow::Owned(decompressed))
+    } else {
+        Some(section_data)
+    }
+}
+
+/// Relative addresses are u32 offsets which are relative to some "base address".
+///
+/// This function computes that base address. It is defined as follows:
+///
+///  - For Windows binaries, the base address is the "image base address".
+///  - For mach-O binaries, the base address is the vmaddr of the __TEXT segment.
+///  - For ELF binaries, the base address is zero.
+///
+/// Stand-alone mach-O dylibs usually have a base address of zero because their
+/// __TEXT segment is at address zero.
+///
+/// In the following cases, the base address is usually non-zero:
+///
+///  - The "image base address" of Windows binaries is usually non-zero.
+///  - mach-O executable files (not dylibs) usually have their __TEXT segment at
+///    address 0x100000000.
+///  - mach-O libraries in the dyld shared cache have a __TEXT segment at some
+///    non-zero address in the cache.
+fn relative_address_base<'data: 'file, 'file>(
+    object_file: &'file impl Object<'data, 'file>,
+) -> u64 {
+    if let Some(text_segment) = object_file
+        .segments()
+        .find(|s| s.name() == Ok(Some("__TEXT")))
+    {
+        // This is a mach-O image. "Relative addresses" are relative to the
+        // vmaddr of the __TEXT segment.
+        return text_segment.address();
+    }
+
+    if object_file.section_by_name(".orc_unwind_ip").is_some() {
+        if let Some(text_section) = object_file.section_by_name(".text") {
+            // This is a Linux kernel image. Its addresses are near the top of the address
+            // space, so relative addresses are relative to the start of the kernel text.
+            return text_section.address();
+        }
+    }
+
+    // For PE binaries, relative_address_base() returns the image base address.
+    // Otherwise it returns zero. This gives regular ELF images a base address of zero,
+    // which is what we want.
+    object_file.relative_address_base()
+}
@@ -0,0 +1,273 @@
+use std::marker::PhantomData;
+
+use crate::arch::Arch;
+use crate::unwind_result::UnwindResult;
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrcUnwinderError {
+    #[error("No ORC entry covers the address")]
+    NoEntry,
+
+    #[error("The ORC entry for the address is undefined")]
+    UndefinedEntry,
+
+    #[error("Unknown ORC entry type {0}")]
+    UnknownType(u8),
+
+    #[error("The ORC entry uses register {0}, which is not tracked by the unwinder")]
+    UntrackedRegister(u8),
+
+    #[error("Unwinding caused integer overflow")]
+    IntegerOverflow,
+
+    #[error("Could not read stack memory at 0x{0:x}")]
+    CouldNotReadStack(u64),
+
+    #[error("Neither the code address nor the stack pointer changed, would loop")]
+    DidNotAdvance,
+
+    #[error("ORC unwind information is not supported for this CPU architecture")]
+    UnsupportedArch,
+}
+
+/// ORC register numbers, from the kernel's `orc_types.h`.
+pub const ORC_REG_UNDEFINED: u8 = 0;
+pub const ORC_REG_PREV_SP: u8 = 1;
+pub const ORC_REG_BP: u8 = 4;
+pub const ORC_REG_SP: u8 = 5;
+pub const ORC_REG_BP_INDIRECT: u8 = 8;
+pub const ORC_REG_SP_INDIRECT: u8 = 9;
+
+/// ORC entry types, from the kernel's `orc_types.h`.
+pub const ORC_TYPE_UNDEFINED: u8 = 0;
+pub const ORC_TYPE_END_OF_STACK: u8 = 1;
+pub const ORC_TYPE_CALL: u8 = 2;
+pub const ORC_TYPE_REGS: u8 = 3;
+pub const ORC_TYPE_REGS_PARTIAL: u8 = 4;
+
+/// The size of an entry in `.orc_unwind`.
+const ORC_ENTRY_SIZE: usize = 6;
+
+/// An entry of the `.orc_unwind` section, which describes how to find the caller's
+/// stack pointer and frame pointer.
+///
+/// This is the layout used since Linux 6.4. Modules with the older layout are detected
+/// with [`uses_pre_6_4_layout`] and not used.
+///
+/// ```text
+/// struct orc_entry {
+///     s16 sp_offset;
+///     s16 bp_offset;
+///     unsigned sp_reg:4;
+///     unsigned bp_reg:4;
+///     unsigned type:3;
+///     unsigned signal:1;
+/// } __packed;
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OrcEntry {
+    /// The offset of the caller's stack pointer from the value of `sp_reg`.
+    pub sp_offset: i16,
+    /// The offset of the saved frame pointer from the value of `bp_reg`.
+    pub bp_offset: i16,
+    pub sp_reg: u8,
+    pub bp_reg: u8,
+    pub ty: u8,
+    /// Whether the caller's frame was interrupted, so that the instruction pointer which
+    /// is recovered with this entry is not a return address.
+    pub signal: bool,
+}
+
+impl OrcEntry {
+    fn parse(bytes: &[u8]) -> Self {
+        let sp_offset = i16::from_le_bytes([bytes[0], bytes[1]]);
+        let bp_offset = i16::from_le_bytes([bytes[2], bytes[3]]);
+        let bits = u16::from_le_bytes([bytes[4], bytes[5]]);
+        Self {
+            sp_offset,
+            bp_offset,
+            sp_reg: (bits & 0xf) as u8,
+            bp_reg: ((bits >> 4) & 0xf) as u8,
+            ty: ((bits >> 8) & 0x7) as u8,
+            signal: (bits >> 11) & 1 != 0,
+        }
+    }
+}
+
+/// Whether the `.orc_unwind` entries use the layout of Linux 4.14 to 6.3, which has a
+/// 2-bit `type` field followed by an `end` bit, and in which CALL entries have type 0.
+///
+/// In the current layout, type 0 is UNDEFINED, and undefined entries have no stack
+/// pointer register. An entry with type 0 and a stack pointer register therefore means
+/// that the old layout is used.
+pub fn uses_pre_6_4_layout(orc_unwind: &[u8]) -> bool {
+    orc_unwind.chunks_exact(ORC_ENTRY_SIZE).any(|entry| {
+        let entry = OrcEntry::parse(entry);
+        entry.ty == ORC_TYPE_UNDEFINED && entry.sp_reg != ORC_REG_UNDEFINED
+    })
+}
+
+/// The two sections of a module's ORC unwind information.
+///
+/// `.orc_unwind_ip` is a sorted array of 32-bit place-relative offsets: entry `i`, at
+/// address `orc_unwind_ip_svma + 4 * i`, holds the offset from its own address to the first
+/// instruction address covered by entry `i` of `.orc_unwind`.
+#[derive(Clone, Copy)]
+pub struct OrcSections<'a> {
+    pub orc_unwind_ip: &'a [u8],
+    pub orc_unwind: &'a [u8],
+    /// The address of `.orc_unwind_ip`, relative to the module's base address.
+    pub orc_unwind_ip_rel_address: u32,
+}
+
+impl OrcSections<'_> {
+    /// The number of entries, which is the same in both sections.
+    pub fn len(&self) -> usize {
+        (self.orc_unwind_ip.len() / 4).min(self.orc_unwind.len() / ORC_ENTRY_SIZE)
+    }
+
+    /// The address of the first instruction covered by entry `i`, relative to the
+    /// module's base address.
+    fn entry_address(&self, i: usize) -> i64 {
+        let offset = &self.orc_unwind_ip[i * 4..i * 4 + 4];
+        let offset = i32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]);
+        i64::from(self.orc_unwind_ip_rel_address) + (i * 4) as i64 + i64::from(offset)
+    }
+
+    /// Finds the entry which covers `rel_lookup_address`, i.e. the last entry which
+    /// starts at or before the address.
+    pub fn lookup(&self, rel_lookup_address: u32) -> Option<OrcEntry> {
+        let address = i64::from(rel_lookup_address);
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.entry_address(mid) <= address {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        let i = low.checked_sub(1)?;
+        let entry = &self.orc_unwind[i * ORC_ENTRY_SIZE..(i + 1) * ORC_ENTRY_SIZE];
+        Some(OrcEntry::parse(entry))
+    }
+}
+
+/// Unwinding with the ORC tables of the x86_64 Linux kernel.
+///
+/// Only x86_64 implements `unwind_frame`. The other architectures use the default,
+/// which fails with [`OrcUnwinderError::UnsupportedArch`], so that the fallback rule is
+/// used for modules with this unwind data.
+pub trait OrcUnwinding: Arch {
+    /// Translate the ORC entry into an unwind rule, or, if that's not possible, evaluate
+    /// it directly.
+    fn unwind_frame<F>(
+        entry: OrcEntry,
+        is_first_frame: bool,
+        regs: &mut Self::UnwindRegs,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<Self::UnwindRule>, OrcUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let _ = (entry, is_first_frame, regs, read_stack);
+        Err(OrcUnwinderError::UnsupportedArch)
+    }
+}
+
+pub struct OrcUnwinder<'a, A: OrcUnwinding> {
+    sections: OrcSections<'a>,
+    _arch: PhantomData<A>,
+}
+
+impl<'a, A: OrcUnwinding> OrcUnwinder<'a, A> {
+    pub fn new(
+        orc_unwind_ip: &'a [u8],
+        orc_unwind: &'a [u8],
+        orc_unwind_ip_rel_address: u32,
+    ) -> Self {
+        Self {
+            sections: OrcSections {
+                orc_unwind_ip,
+                orc_unwind,
+                orc_unwind_ip_rel_address,
+            },
+            _arch: PhantomData,
+        }
+    }
+
+    pub fn unwind_frame<F>(
+        &self,
+        rel_lookup_address: u32,
+        is_first_frame: bool,
+        regs: &mut A::UnwindRegs,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<A::UnwindRule>, OrcUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let entry = self
+            .sections
+            .lookup(rel_lookup_address)
+            .ok_or(OrcUnwinderError::NoEntry)?;
+        A::unwind_frame(entry, is_first_frame, regs, read_stack)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        // Three entries, starting at relative addresses 0x1000, 0x1010 and 0x1020. The
+        // section is at relative address 0x8000.
+        let mut orc_unwind_ip = Vec::new();
+        for (i, address) in [0x1000i64, 0x1010, 0x1020].into_iter().enumerate() {
+            let offset = address - (0x8000 + 4 * i as i64);
+            orc_unwind_ip.extend_from_slice(&(offset as i32).to_le_bytes());
+        }
+        let mut orc_unwind = Vec::new();
+        for sp_offset in [8i16, 16, 24] {
+            orc_unwind.extend_from_slice(&sp_offset.to_le_bytes());
+            orc_unwind.extend_from_slice(&0i16.to_le_bytes());
+            let bits = u16::from(ORC_REG_SP) | (u16::from(ORC_TYPE_CALL) << 8);
+            orc_unwind.extend_from_slice(&bits.to_le_bytes());
+        }
+        let sections = OrcSections {
+            orc_unwind_ip: &orc_unwind_ip,
+            orc_unwind: &orc_unwind,
+            orc_unwind_ip_rel_address: 0x8000,
+        };
+        assert_eq!(sections.lookup(0xfff), None);
+        let sp_offset = |address| sections.lookup(address).map(|entry| entry.sp_offset);
+        assert_eq!(sp_offset(0x1000), Some(8));
+        assert_eq!(sp_offset(0x100f), Some(8));
+        assert_eq!(sp_offset(0x1010), Some(16));
+        assert_eq!(sp_offset(0x5000), Some(24));
+        assert_eq!(
+            sections.lookup(0x1000),
+            Some(OrcEntry {
+                sp_offset: 8,
+                bp_offset: 0,
+                sp_reg: ORC_REG_SP,
+                bp_reg: ORC_REG_UNDEFINED,
+                ty: ORC_TYPE_CALL,
+                signal: false,
+            })
+        );
+        assert!(!uses_pre_6_4_layout(&orc_unwind));
+    }
+
+    #[test]
+    fn test_pre_6_4_layout() {
+        // An undefined entry, and a CALL entry with sp_reg SP in the old layout, where
+        // CALL is type 0.
+        let mut orc_unwind = vec![0; ORC_ENTRY_SIZE];
+        orc_unwind.extend_from_slice(&8i16.to_le_bytes());
+        orc_unwind.extend_from_slice(&0i16.to_le_bytes());
+        orc_unwind.extend_from_slice(&u16::from(ORC_REG_SP).to_le_bytes());
+        assert!(uses_pre_6_4_layout(&orc_unwind));
+        assert!(!uses_pre_6_4_layout(&orc_unwind[..ORC_ENTRY_SIZE]));
+    }
+}
@@ -0,0 +1,155 @@
+use std::marker::PhantomData;
+
+use crate::arch::Arch;
+use crate::macho::TextBytes;
+use crate::unwind_result::UnwindResult;
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeUnwinderError {
+    #[error("The .pdata entry refers to unwind info at RVA 0x{0:x}, which is outside the unwind info section")]
+    UnwindInfoOutOfBounds(u32),
+
+    #[error("The .pdata entry for the function at RVA 0x{0:x} is invalid")]
+    BadPdataEntry(u32),
+
+    #[error("The unwind info at RVA 0x{0:x} could not be parsed")]
+    BadUnwindInfo(u32),
+
+    #[error("Unsupported unwind info version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Encountered an unknown unwind code 0x{0:x}")]
+    BadUnwindCode(u8),
+
+    #[error("The unwind codes ended in the middle of an unwind code")]
+    TruncatedUnwindCodes,
+
+    #[error("The chain of unwind info entries is too long")]
+    ChainTooLong,
+
+    #[error("The frame register r{0} is not tracked by the unwinder")]
+    UntrackedFrameRegister(u8),
+
+    #[error("Unwinding caused integer overflow")]
+    IntegerOverflow,
+
+    #[error("Could not read stack memory at 0x{0:x}")]
+    CouldNotReadStack(u64),
+
+    #[error("Neither the code address nor the stack pointer changed, would loop")]
+    DidNotAdvance,
+
+    #[error("PE unwind information is not supported for this CPU architecture")]
+    UnsupportedArch,
+}
+
+/// The raw unwind sections of a PE module: the `.pdata` function table, and the
+/// section which contains the unwind info that the `.pdata` entries refer to.
+#[derive(Clone, Copy)]
+pub struct PeSections<'a> {
+    pub pdata: &'a [u8],
+    pub xdata: &'a [u8],
+    pub xdata_rva: u32,
+}
+
+impl<'a> PeSections<'a> {
+    /// Returns the unwind info bytes starting at `rva`, up to the end of the section.
+    pub fn xdata_at_rva(&self, rva: u32) -> Result<&'a [u8], PeUnwinderError> {
+        rva.checked_sub(self.xdata_rva)
+            .and_then(|offset| self.xdata.get(offset as usize..))
+            .ok_or(PeUnwinderError::UnwindInfoOutOfBounds(rva))
+    }
+}
+
+/// Unwinding with the `.pdata` / `.xdata` unwind info of Windows binaries.
+///
+/// Only x86_64 and aarch64 implement `unwind_frame`. 32-bit ARM uses the default, which
+/// fails with [`PeUnwinderError::UnsupportedArch`], so that the fallback rule is used
+/// for modules with this unwind data.
+pub trait PeUnwinding: Arch {
+    /// Look up the function covering `rel_lookup_address` in `.pdata` and translate
+    /// its unwind info into an unwind rule, or, if that's not possible, evaluate it
+    /// directly.
+    fn unwind_frame<F>(
+        sections: PeSections,
+        text_bytes: Option<TextBytes>,
+        rel_lookup_address: u32,
+        is_first_frame: bool,
+        regs: &mut Self::UnwindRegs,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<Self::UnwindRule>, PeUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let _ = (
+            sections,
+            text_bytes,
+            rel_lookup_address,
+            is_first_frame,
+            regs,
+            read_stack,
+        );
+        Err(PeUnwinderError::UnsupportedArch)
+    }
+}
+
+pub struct PeUnwinder<'a, A: PeUnwinding> {
+    sections: PeSections<'a>,
+    text_bytes: Option<TextBytes<'a>>,
+    _arch: PhantomData<A>,
+}
+
+impl<'a, A: PeUnwinding> PeUnwinder<'a, A> {
+    pub fn new(
+        pdata: &'a [u8],
+        xdata: &'a [u8],
+        xdata_rva: u32,
+        text_bytes: Option<TextBytes<'a>>,
+    ) -> Self {
+        Self {
+            sections: PeSections {
+                pdata,
+                xdata,
+                xdata_rva,
+            },
+            text_bytes,
+            _arch: PhantomData,
+        }
+    }
+
+    pub fn unwind_frame<F>(
+        &self,
+        rel_lookup_address: u32,
+        is_first_frame: bool,
+        regs: &mut A::UnwindRegs,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<A::UnwindRule>, PeUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        A::unwind_frame(
+            self.sections,
+            self.text_bytes,
+            rel_lookup_address,
+            is_first_frame,
+            regs,
+            read_stack,
+        )
+    }
+}
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let b = data.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes([b[0], b[1]]))
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let b = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+#[cfg(any(feature = "minidump", feature = "coredump"))]
+pub(crate) fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let b = data.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(b.try_into().unwrap()))
+}
@@ -0,0 +1,219 @@
+//! Glue between Linux `perf_event_open` sample records and the unwinder.
+//!
+//! A sample record with `PERF_SAMPLE_REGS_USER` contains the values of the registers
+//! which were requested in `perf_event_attr.sample_regs_user`, in the order of their bit
+//! in that mask. A sample record with `PERF_SAMPLE_STACK_USER` contains a copy of the
+//! user stack, starting at the sampled stack pointer, and the number of bytes of that
+//! copy which are valid ("dyn_size").
+//!
+//! The functions in this module turn these into the registers and the `read_stack`
+//! closure which the unwinder needs:
+//!
+//! ```rust
+//! use framehop::perf::{regs_x86_64, UserStack, PERF_REG_MASK_X86_64};
+//!
+//! # let (regs, stack_bytes, dyn_size) = (vec![0x40, 0x1000, 0x7f0012345678], vec![0u8; 64], 48);
+//! // `regs`, `stack_bytes` and `dyn_size` come from the sample record, and the
+//! // event was opened with sample_regs_user = PERF_REG_MASK_X86_64.
+//! let (pc, unwind_regs) = regs_x86_64(PERF_REG_MASK_X86_64, &regs).unwrap();
+//! let stack = UserStack::new(unwind_regs.sp(), &stack_bytes, dyn_size);
+//! let mut read_stack = stack.memory().reader();
+//! # let _ = (pc, &mut read_stack);
+//! ```
+
+use crate::aarch64::UnwindRegsAarch64;
+use crate::stack_memory::StackMemory;
+use crate::x86_64::UnwindRegsX86_64;
+
+/// The index of `rbp` in `enum perf_event_x86_regs`.
+pub const PERF_REG_X86_BP: u32 = 6;
+/// The index of `rsp` in `enum perf_event_x86_regs`.
+pub const PERF_REG_X86_SP: u32 = 7;
+/// The index of `rip` in `enum perf_event_x86_regs`.
+pub const PERF_REG_X86_IP: u32 = 8;
+
+/// The index of `x29` (the frame pointer) in `enum perf_event_arm64_regs`.
+pub const PERF_REG_ARM64_FP: u32 = 29;
+/// The index of `x30` (the link register) in `enum perf_event_arm64_regs`.
+pub const PERF_REG_ARM64_LR: u32 = 30;
+/// The index of `sp` in `enum perf_event_arm64_regs`.
+pub const PERF_REG_ARM64_SP: u32 = 31;
+/// The index of `pc` in `enum perf_event_arm64_regs`.
+pub const PERF_REG_ARM64_PC: u32 = 32;
+
+/// The `sample_regs_user` mask with the registers needed for unwinding on x86_64.
+pub const PERF_REG_MASK_X86_64: u64 =
+    (1 << PERF_REG_X86_BP) | (1 << PERF_REG_X86_SP) | (1 << PERF_REG_X86_IP);
+
+/// The `sample_regs_user` mask with the registers needed for unwinding on aarch64.
+pub const PERF_REG_MASK_AARCH64: u64 = (1 << PERF_REG_ARM64_FP)
+    | (1 << PERF_REG_ARM64_LR)
+    | (1 << PERF_REG_ARM64_SP)
+    | (1 << PERF_REG_ARM64_PC);
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerfRegsError {
+    #[error("Register {0} is not in the sample_regs_user mask")]
+    MissingRegister(u32),
+
+    #[error("The sample has {actual} register values, but the mask has {expected} bits set")]
+    WrongRegisterCount { expected: usize, actual: usize },
+}
+
+/// The register values of a `PERF_SAMPLE_REGS_USER` sample, together with the
+/// `sample_regs_user` mask that says which registers they are.
+#[derive(Clone, Copy, Debug)]
+pub struct PerfRegs<'a> {
+    mask: u64,
+    regs: &'a [u64],
+}
+
+impl<'a> PerfRegs<'a> {
+    /// `regs` needs to contain one value for every bit that is set in `mask`.
+    pub fn new(mask: u64, regs: &'a [u64]) -> Result<Self, PerfRegsError> {
+        let expected = mask.count_ones() as usize;
+        if regs.len() != expected {
+            return Err(PerfRegsError::WrongRegisterCount {
+                expected,
+                actual: regs.len(),
+            });
+        }
+        Ok(Self { mask, regs })
+    }
+
+    /// Returns the value of the register with the given perf register index.
+    pub fn get(&self, register: u32) -> Result<u64, PerfRegsError> {
+        let bit = 1u64
+            .checked_shl(register)
+            .ok_or(PerfRegsError::MissingRegister(register))?;
+        if self.mask & bit == 0 {
+            return Err(PerfRegsError::MissingRegister(register));
+        }
+        let index = (self.mask & (bit - 1)).count_ones() as usize;
+        Ok(self.regs[index])
+    }
+}
+
+/// Returns the sampled instruction pointer and the unwind registers for an x86_64
+/// sample. The mask needs to include at least [`PERF_REG_MASK_X86_64`].
+pub fn regs_x86_64(
+    sample_regs_user: u64,
+    regs: &[u64],
+) -> Result<(u64, UnwindRegsX86_64), PerfRegsError> {
+    let regs = PerfRegs::new(sample_regs_user, regs)?;
+    let ip = regs.get(PERF_REG_X86_IP)?;
+    let sp = regs.get(PERF_REG_X86_SP)?;
+    let bp = regs.get(PERF_REG_X86_BP)?;
+    Ok((ip, UnwindRegsX86_64::new(ip, sp, bp)))
+}
+
+/// Returns the sampled program counter and the unwind registers for an aarch64 sample.
+/// The mask needs to include at least [`PERF_REG_MASK_AARCH64`].
+///
+/// Pointer authentication bits are not stripped from the link register. Use
+/// [`UnwindRegsAarch64::new_with_ptr_auth_mask`] with the register values from
+/// [`PerfRegs`] if you need that.
+pub fn regs_aarch64(
+    sample_regs_user: u64,
+    regs: &[u64],
+) -> Result<(u64, UnwindRegsAarch64), PerfRegsError> {
+    let regs = PerfRegs::new(sample_regs_user, regs)?;
+    let pc = regs.get(PERF_REG_ARM64_PC)?;
+    let lr = regs.get(PERF_REG_ARM64_LR)?;
+    let sp = regs.get(PERF_REG_ARM64_SP)?;
+    let fp = regs.get(PERF_REG_ARM64_FP)?;
+    Ok((pc, UnwindRegsAarch64::new(lr, sp, fp)))
+}
+
+/// The user stack bytes of a `PERF_SAMPLE_STACK_USER` sample.
+///
+/// The bytes were copied starting at the sampled stack pointer, so memory below the
+/// stack pointer, or beyond the valid ("dyn_size") part of the copy, can't be read.
+/// The readers of [`UserStack::memory`] return a [`StackReadError`] for such reads,
+/// which the unwinder reports in [`Error::CouldNotReadStackMemory`].
+///
+/// [`StackReadError`]: crate::StackReadError
+/// [`Error::CouldNotReadStackMemory`]: crate::Error::CouldNotReadStackMemory
+#[derive(Clone, Copy, Debug)]
+pub struct UserStack<'a> {
+    memory: StackMemory<'a>,
+}
+
+impl<'a> UserStack<'a> {
+    /// `sp` is the sampled stack pointer, `data` is the stack data from the sample
+    /// record and `dyn_size` is the number of valid bytes at the start of `data`.
+    pub fn new(sp: u64, data: &'a [u8], dyn_size: u64) -> Self {
+        let len = usize::try_from(dyn_size).map_or(data.len(), |len| len.min(data.len()));
+        Self {
+            memory: StackMemory::new(sp, &data[..len]),
+        }
+    }
+
+    /// Reads the 8-byte little-endian value at `address`, or returns `None` if the
+    /// value is not in the valid part of the copied stack.
+    pub fn read_u64(&self, address: u64) -> Option<u64> {
+        self.memory.read_u64(address).ok()
+    }
+
+    /// The valid part of the copied stack, which provides the `read_stack` callback.
+    pub fn memory(&self) -> StackMemory<'a> {
+        self.memory
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stack_memory::StackReadError;
+
+    #[test]
+    fn test_regs() {
+        // ax, bp, sp, ip, r15
+        let mask = 1 | PERF_REG_MASK_X86_64 | (1 << 23);
+        let regs = [1, 0x7ffd0100, 0x7ffd0000, 0x401000, 15];
+        let (ip, unwind_regs) = regs_x86_64(mask, &regs).unwrap();
+        assert_eq!(ip, 0x401000);
+        assert_eq!(
+            unwind_regs,
+            UnwindRegsX86_64::new(0x401000, 0x7ffd0000, 0x7ffd0100)
+        );
+        assert_eq!(
+            regs_x86_64(mask, &regs[..4]),
+            Err(PerfRegsError::WrongRegisterCount {
+                expected: 5,
+                actual: 4
+            })
+        );
+        assert_eq!(
+            regs_x86_64(0b11 << 7, &regs[..2]),
+            Err(PerfRegsError::MissingRegister(PERF_REG_X86_BP))
+        );
+
+        let regs = [0x7ffd0100, 0x400500, 0x7ffd0000, 0x401000];
+        let (pc, unwind_regs) = regs_aarch64(PERF_REG_MASK_AARCH64, &regs).unwrap();
+        assert_eq!(pc, 0x401000);
+        assert_eq!(unwind_regs.lr(), 0x400500);
+        assert_eq!(unwind_regs.sp(), 0x7ffd0000);
+        assert_eq!(unwind_regs.fp(), 0x7ffd0100);
+    }
+
+    #[test]
+    fn test_user_stack() {
+        let mut data = Vec::new();
+        for value in [0x10u64, 0x20, 0x30, 0x40] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let stack = UserStack::new(0x1000, &data, 24);
+        assert_eq!(stack.read_u64(0x1000), Some(0x10));
+        assert_eq!(stack.read_u64(0x1010), Some(0x30));
+        // Beyond dyn_size.
+        assert_eq!(stack.read_u64(0x1018), None);
+        // Below the stack pointer.
+        assert_eq!(stack.read_u64(0xff8), None);
+        assert_eq!(stack.read_u64(u64::MAX), None);
+        assert_eq!(
+            stack.memory().reader()(0x1018),
+            Err(StackReadError::AboveEnd(0x1018))
+        );
+    }
+}
@@ -0,0 +1,65 @@
+/// Plausibility checks for the return addresses found during unwinding.
+///
+/// Frame pointer unwinding through code which doesn't maintain frame pointers often
+/// produces "return addresses" which point into no module, or into the middle of data.
+/// With these checks, unwinding stops with [`Error::ImplausibleReturnAddress`] at the
+/// first such address, so that the stack ends cleanly instead of containing junk frames.
+///
+/// All checks are disabled by default. Use them with
+/// [`UnwindIterator::with_return_address_checks`](crate::UnwindIterator::with_return_address_checks),
+/// [`FrameInfoIterator::with_return_address_checks`](crate::FrameInfoIterator::with_return_address_checks)
+/// or [`BatchOptions::return_address_checks`](crate::BatchOptions::return_address_checks).
+///
+/// New checks may be added in the future, so this struct can't be constructed with a
+/// struct literal. Start from [`ReturnAddressChecks::default`] or
+/// [`ReturnAddressChecks::all`] and set the fields you need.
+///
+/// [`Error::ImplausibleReturnAddress`]: crate::Error::ImplausibleReturnAddress
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReturnAddressChecks {
+    /// Reject return addresses which are not inside the address range of a known module.
+    pub in_module: bool,
+    /// Reject return addresses which are not inside one of their module's executable
+    /// ranges: the
+    /// [`executable_segments`](crate::ModuleSvmaInfo::executable_segments) of its
+    /// [`ModuleSvmaInfo`](crate::ModuleSvmaInfo), or, if those are unknown, its `text`,
+    /// `text_env`, `stubs` and `stub_helper` sections, and the range of the module's
+    /// [`TextByteData`](crate::TextByteData). Also rejects return addresses which are not
+    /// inside a known module. Modules without any of these ranges are not checked.
+    pub in_executable_range: bool,
+    /// Reject return addresses which are not preceded by a call instruction (`call` on
+    /// x86_64, `bl` / `blr` on aarch64, `bl` / `blx` on ARM). This can only be checked
+    /// if the module's [`TextByteData`](crate::TextByteData) contains the bytes before the
+    /// return address.
+    pub after_call: bool,
+}
+
+impl ReturnAddressChecks {
+    /// All checks enabled.
+    pub fn all() -> Self {
+        Self {
+            in_module: true,
+            in_executable_range: true,
+            after_call: true,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.in_module && !self.in_executable_range && !self.after_call
+    }
+}
+
+/// The reason why a return address was rejected, see [`ReturnAddressChecks`].
+#[non_exhaustive]
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReturnAddressCheckFailure {
+    #[error("it is not inside a known module")]
+    NotInModule,
+
+    #[error("it is not inside an executable range of its module")]
+    NotInExecutableRange,
+
+    #[error("it does not follow a call instruction")]
+    NotAfterCall,
+}
@@ -1,54 +1,156 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::frame_info::UnwindMethod;
 use crate::unwind_rule::UnwindRule;
 
+/// The layout of the unwinder's rule cache. The default is the layout framehop has always
+/// used: 509 slots, direct-mapped, with the address modulo 509 as the slot index.
+///
+/// The cache memory is allocated when the cache is created. Unwinding never allocates
+/// cache memory, regardless of the configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// The total number of cached unwind rules. This is rounded down to a multiple of
+    /// `ways`, with a minimum of one set.
+    pub capacity: usize,
+    /// The number of slots per set. `1` gives a direct-mapped cache. With more than one
+    /// way, an address can be stored in any slot of its set, and the least recently used
+    /// slot of the set is replaced on insertion. Lookups check all slots of a set, so
+    /// this should be small, for example 2, 4 or 8.
+    pub ways: usize,
+    /// How the set index is computed from the address.
+    pub hash: CacheHash,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 509,
+            ways: 1,
+            hash: CacheHash::Modulo,
+        }
+    }
+}
+
+impl CacheConfig {
+    /// A direct-mapped cache with the given capacity and the default hash.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+}
+
+/// The function which maps an address to a set in the rule cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheHash {
+    /// The address modulo the number of sets. This works well if the number of sets is
+    /// a prime number, but distributes addresses poorly for powers of two, because
+    /// return addresses are often aligned.
+    Modulo,
+    /// Fibonacci hashing: the address is multiplied with 2^64 divided by the golden
+    /// ratio, and the high bits are used. This distributes addresses well for any number
+    /// of sets.
+    Multiplicative,
+}
+
+impl CacheHash {
+    fn set_index(self, address: u64, set_count: usize) -> usize {
+        let hash = match self {
+            CacheHash::Modulo => address,
+            CacheHash::Multiplicative => address.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32,
+        };
+        (hash % set_count as u64) as usize
+    }
+}
+
 pub struct RuleCache<R: UnwindRule> {
-    entries: Box<[Option<CacheEntry<R>>; 509]>,
+    /// The slots, `ways` consecutive slots per set. Within a set, the slots are ordered
+    /// from most recently used to least recently used, and empty slots come last.
+    entries: Box<[Option<CacheEntry<R>>]>,
+    ways: usize,
+    hash: CacheHash,
     stats: CacheStats,
 }
 
 impl<R: UnwindRule> RuleCache<R> {
-    pub fn new() -> Self {
+    pub fn with_config(config: CacheConfig) -> Self {
+        let ways = config.ways.max(1);
+        let set_count = (config.capacity / ways).max(1);
         Self {
-            entries: Box::new([None; 509]),
+            entries: vec![None; set_count * ways].into_boxed_slice(),
+            ways,
+            hash: config.hash,
             stats: CacheStats::new(),
         }
     }
 
+    fn set_range(&self, set: usize) -> std::ops::Range<usize> {
+        let start = set * self.ways;
+        start..start + self.ways
+    }
+
     pub fn lookup(&mut self, address: u64, modules_generation: u16) -> CacheResult<R> {
-        let slot = (address % 509) as u16;
-        match &self.entries[slot as usize] {
-            None => {
-                self.stats.miss_empty_slot_count += 1;
+        let set = self.hash.set_index(address, self.entries.len() / self.ways);
+        let range = self.set_range(set);
+        let entries = &mut self.entries[range];
+        // Like in a direct-mapped cache, a miss counts as a modules miss if the set
+        // contains an entry from a different modules generation, whatever its address.
+        let mut has_stale_entry = false;
+        for i in 0..entries.len() {
+            let Some(entry) = entries[i] else {
+                break;
+            };
+            if entry.modules_generation != modules_generation {
+                has_stale_entry = true;
+                continue;
             }
-            Some(entry) => {
-                if entry.modules_generation == modules_generation {
-                    if entry.address == address {
-                        self.stats.hit_count += 1;
-                        return CacheResult::Hit(entry.unwind_rule);
-                    } else {
-                        self.stats.miss_wrong_address_count += 1;
-                    }
-                } else {
-                    self.stats.miss_wrong_modules_count += 1;
-                }
+            if entry.address != address {
+                continue;
             }
+            // Move the entry to the front of the set.
+            entries[..=i].rotate_right(1);
+            self.stats.hit_count += 1;
+            return CacheResult::Hit(entry.unwind_rule, entry.method);
+        }
+        if has_stale_entry {
+            self.stats.miss_wrong_modules_count += 1;
+        } else if entries.iter().any(Option::is_none) {
+            self.stats.miss_empty_slot_count += 1;
+        } else {
+            self.stats.miss_wrong_address_count += 1;
         }
         CacheResult::Miss(CacheHandle {
-            slot,
+            set: set as u32,
             address,
             modules_generation,
         })
     }
 
-    pub fn insert(&mut self, handle: CacheHandle, unwind_rule: R) {
+    pub fn insert(&mut self, handle: CacheHandle, unwind_rule: R, method: UnwindMethod) {
         let CacheHandle {
-            slot,
+            set,
             address,
             modules_generation,
         } = handle;
-        self.entries[slot as usize] = Some(CacheEntry {
+        let range = self.set_range(set as usize);
+        let entries = &mut self.entries[range];
+        // Replace an existing entry for the same address, if there is one, and the least
+        // recently used entry otherwise.
+        let replaced_index = entries
+            .iter()
+            .position(|entry| match entry {
+                Some(entry) => entry.address == address,
+                None => true,
+            })
+            .unwrap_or(entries.len() - 1);
+        entries[..=replaced_index].rotate_right(1);
+        entries[0] = Some(CacheEntry {
             address,
             modules_generation,
             unwind_rule,
+            method,
         });
     }
 
@@ -58,13 +160,102 @@ impl<R: UnwindRule> RuleCache<R> {
     }
 }
 
+/// A rule cache which can be used by multiple threads at the same time.
+///
+/// The slots are split into shards, and each shard is protected by a mutex. The shard is
+/// picked based on the address, so threads which unwind different addresses rarely
+/// contend for the same shard.
+pub struct SharedRuleCache<R: UnwindRule> {
+    shards: Box<[Mutex<RuleCache<R>>]>,
+}
+
+impl<R: UnwindRule> SharedRuleCache<R> {
+    /// `config` describes the whole cache; its capacity is divided evenly among the shards.
+    pub fn with_config(shard_count: usize, config: CacheConfig) -> Self {
+        let shard_count = shard_count.max(1);
+        let shard_config = CacheConfig {
+            capacity: config.capacity / shard_count,
+            ..config
+        };
+        Self {
+            shards: (0..shard_count)
+                .map(|_| Mutex::new(RuleCache::with_config(shard_config)))
+                .collect(),
+        }
+    }
+
+    fn shard(&self, address: u64) -> MutexGuard<'_, RuleCache<R>> {
+        // Use a different multiplier than `CacheHash::Multiplicative`, so that the shard
+        // doesn't depend on the same hash bits as the set index within the shard, and the
+        // addresses in a shard are still spread over all of its sets.
+        let hash = address.wrapping_mul(0xff51_afd7_ed55_8ccd) >> 32;
+        let shard = &self.shards[(hash % self.shards.len() as u64) as usize];
+        // A panic while holding the lock cannot leave a shard in an inconsistent state.
+        shard.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn lookup(&self, address: u64, modules_generation: u16) -> CacheResult<R> {
+        self.shard(address).lookup(address, modules_generation)
+    }
+
+    pub fn insert(&self, handle: CacheHandle, unwind_rule: R, method: UnwindMethod) {
+        self.shard(handle.address)
+            .insert(handle, unwind_rule, method)
+    }
+
+    /// Returns the sum of the usage statistics of all shards.
+    pub fn stats(&self) -> CacheStats {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap_or_else(|e| e.into_inner()).stats())
+            .fold(CacheStats::new(), |sum, stats| CacheStats {
+                hit_count: sum.hit_count + stats.hit_count,
+                miss_empty_slot_count: sum.miss_empty_slot_count + stats.miss_empty_slot_count,
+                miss_wrong_modules_count: sum.miss_wrong_modules_count
+                    + stats.miss_wrong_modules_count,
+                miss_wrong_address_count: sum.miss_wrong_address_count
+                    + stats.miss_wrong_address_count,
+            })
+    }
+}
+
+/// The rule cache of a [`Cache`](crate::cache::Cache): either owned by the cache, or
+/// shared with the caches of other threads.
+pub enum RuleCacheStorage<R: UnwindRule> {
+    Local(RuleCache<R>),
+    Shared(Arc<SharedRuleCache<R>>),
+}
+
+impl<R: UnwindRule> RuleCacheStorage<R> {
+    pub fn lookup(&mut self, address: u64, modules_generation: u16) -> CacheResult<R> {
+        match self {
+            RuleCacheStorage::Local(cache) => cache.lookup(address, modules_generation),
+            RuleCacheStorage::Shared(cache) => cache.lookup(address, modules_generation),
+        }
+    }
+
+    pub fn insert(&mut self, handle: CacheHandle, unwind_rule: R, method: UnwindMethod) {
+        match self {
+            RuleCacheStorage::Local(cache) => cache.insert(handle, unwind_rule, method),
+            RuleCacheStorage::Shared(cache) => cache.insert(handle, unwind_rule, method),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        match self {
+            RuleCacheStorage::Local(cache) => cache.stats(),
+            RuleCacheStorage::Shared(cache) => cache.stats(),
+        }
+    }
+}
+
 pub enum CacheResult<R: UnwindRule> {
     Miss(CacheHandle),
-    Hit(R),
+    Hit(R, UnwindMethod),
 }
 
 pub struct CacheHandle {
-    slot: u16,
+    set: u32,
     address: u64,
     modules_generation: u16,
 }
@@ -74,6 +265,7 @@ pub struct CacheEntry<R: UnwindRule> {
     address: u64,
     modules_generation: u16,
     unwind_rule: R,
+    method: UnwindMethod,
 }
 
 /// Statistics about the effectiveness of the rule cache.
@@ -115,3 +307,118 @@ impl CacheStats {
         self.miss_empty_slot_count + self.miss_wrong_modules_count + self.miss_wrong_address_count
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::x86_64::UnwindRuleX86_64;
+
+    fn insert(cache: &mut RuleCache<UnwindRuleX86_64>, address: u64, generation: u16) {
+        match cache.lookup(address, generation) {
+            CacheResult::Miss(handle) => {
+                cache.insert(handle, UnwindRuleX86_64::JustReturn, UnwindMethod::Fallback)
+            }
+            CacheResult::Hit(..) => panic!("unexpected hit for 0x{address:x}"),
+        }
+    }
+
+    fn is_hit(cache: &mut RuleCache<UnwindRuleX86_64>, address: u64, generation: u16) -> bool {
+        matches!(cache.lookup(address, generation), CacheResult::Hit(..))
+    }
+
+    #[test]
+    fn test_direct_mapped() {
+        let mut cache = RuleCache::with_config(CacheConfig::default());
+        insert(&mut cache, 0x1000, 0);
+        assert!(is_hit(&mut cache, 0x1000, 0));
+        // 0x1000 + 509 maps to the same slot and evicts 0x1000.
+        insert(&mut cache, 0x1000 + 509, 0);
+        assert!(!is_hit(&mut cache, 0x1000, 0));
+        assert!(!is_hit(&mut cache, 0x1000 + 509, 1));
+        let stats = cache.stats();
+        assert_eq!(stats.hit_count, 1);
+        assert_eq!(stats.miss_empty_slot_count, 1);
+        assert_eq!(stats.miss_wrong_address_count, 2);
+        assert_eq!(stats.miss_wrong_modules_count, 1);
+    }
+
+    #[test]
+    fn test_set_associative_lru() {
+        let mut cache = RuleCache::with_config(CacheConfig {
+            capacity: 8,
+            ways: 2,
+            hash: CacheHash::Modulo,
+        });
+        // These three addresses all map to set 0 of 4.
+        insert(&mut cache, 0x100, 0);
+        insert(&mut cache, 0x200, 0);
+        assert!(is_hit(&mut cache, 0x100, 0));
+        // 0x200 is now the least recently used entry in the set and gets replaced.
+        insert(&mut cache, 0x300, 0);
+        assert!(is_hit(&mut cache, 0x100, 0));
+        assert!(is_hit(&mut cache, 0x300, 0));
+        assert!(!is_hit(&mut cache, 0x200, 0));
+    }
+
+    #[test]
+    fn test_stale_entry_is_replaced() {
+        let mut cache = RuleCache::with_config(CacheConfig {
+            capacity: 4,
+            ways: 4,
+            hash: CacheHash::Multiplicative,
+        });
+        insert(&mut cache, 0x100, 0);
+        insert(&mut cache, 0x200, 0);
+        insert(&mut cache, 0x100, 1);
+        assert!(is_hit(&mut cache, 0x100, 1));
+        assert!(is_hit(&mut cache, 0x200, 0));
+        assert_eq!(cache.entries.iter().filter(|e| e.is_some()).count(), 2);
+    }
+
+    #[test]
+    fn test_stale_entry_at_different_address() {
+        let mut cache = RuleCache::with_config(CacheConfig {
+            capacity: 2,
+            ways: 2,
+            hash: CacheHash::Modulo,
+        });
+        insert(&mut cache, 0x100, 0);
+        insert(&mut cache, 0x200, 0);
+        // The set only has entries for other addresses, but they are from an older
+        // modules generation.
+        assert!(!is_hit(&mut cache, 0x300, 1));
+        assert!(!is_hit(&mut cache, 0x300, 0));
+        let stats = cache.stats();
+        assert_eq!(stats.miss_empty_slot_count, 2);
+        assert_eq!(stats.miss_wrong_modules_count, 1);
+        assert_eq!(stats.miss_wrong_address_count, 1);
+    }
+
+    #[test]
+    fn test_shared() {
+        let cache = SharedRuleCache::with_config(4, CacheConfig::with_capacity(4 * 509));
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let cache = &cache;
+                scope.spawn(move || {
+                    for address in (0..100).map(|i| 0x1000 + i * 4) {
+                        if let CacheResult::Miss(handle) = cache.lookup(address, 0) {
+                            cache.insert(
+                                handle,
+                                UnwindRuleX86_64::JustReturn,
+                                UnwindMethod::Fallback,
+                            );
+                        }
+                    }
+                });
+            }
+        });
+        for address in (0..100).map(|i| 0x1000 + i * 4) {
+            assert!(matches!(cache.lookup(address, 0), CacheResult::Hit(..)));
+        }
+        let stats = cache.stats();
+        assert_eq!(stats.total(), 500);
+        assert_eq!(stats.hits(), 500 - stats.misses());
+        assert!(stats.misses() >= 100);
+    }
+}
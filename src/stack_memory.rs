@@ -0,0 +1,255 @@
+/// The reason why [`StackMemory`] or [`StackMemoryRegions`] could not read a value.
+///
+/// The readers of these types return this as the error of the `read_stack` callback, and
+/// unwinding then fails with [`Error::CouldNotReadStackMemory`](crate::Error::CouldNotReadStackMemory)
+/// instead of [`Error::CouldNotReadStack`](crate::Error::CouldNotReadStack). This can help
+/// to tell a truncated stack capture apart from a bad stack pointer.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackReadError {
+    #[error("The address 0x{0:x} is below the captured stack memory")]
+    BelowStart(u64),
+
+    #[error("The address 0x{0:x} is above the captured stack memory")]
+    AboveEnd(u64),
+
+    #[error("The address 0x{0:x} is in a gap between captured stack memory regions")]
+    InGap(u64),
+}
+
+impl StackReadError {
+    /// The address which could not be read.
+    pub fn address(&self) -> u64 {
+        match *self {
+            StackReadError::BelowStart(address)
+            | StackReadError::AboveEnd(address)
+            | StackReadError::InGap(address) => address,
+        }
+    }
+}
+
+/// The error type of a `read_stack` callback.
+///
+/// Unwinding fails with [`Error::CouldNotReadStack`](crate::Error::CouldNotReadStack) if
+/// a value which is needed can't be read. If the callback's error converts into a
+/// [`StackReadError`], unwinding fails with
+/// [`Error::CouldNotReadStackMemory`](crate::Error::CouldNotReadStackMemory) instead,
+/// which includes the reason.
+pub trait IntoStackReadError {
+    fn into_stack_read_error(self) -> Option<StackReadError>;
+}
+
+impl IntoStackReadError for () {
+    fn into_stack_read_error(self) -> Option<StackReadError> {
+        None
+    }
+}
+
+impl IntoStackReadError for StackReadError {
+    fn into_stack_read_error(self) -> Option<StackReadError> {
+        Some(self)
+    }
+}
+
+/// A captured region of stack memory: the bytes at `base..base + bytes.len()`.
+///
+/// This provides the `read_stack` callback for unwinding, with bounds checks. Values are
+/// little-endian and don't need to be aligned.
+///
+/// ```rust
+/// use framehop::{StackMemory, StackReadError};
+///
+/// let bytes = [0x10u64, 0x20, 0x30].map(u64::to_le_bytes).concat();
+/// let stack = StackMemory::new(0x7ffd0000, &bytes);
+/// let mut read_stack = stack.reader();
+/// assert_eq!(read_stack(0x7ffd0008), Ok(0x20));
+/// assert_eq!(
+///     read_stack(0x7ffd0018),
+///     Err(StackReadError::AboveEnd(0x7ffd0018))
+/// );
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct StackMemory<'a> {
+    base: u64,
+    bytes: &'a [u8],
+}
+
+impl<'a> StackMemory<'a> {
+    /// Create a reader for `bytes`, which were captured starting at address `base`.
+    pub fn new(base: u64, bytes: &'a [u8]) -> Self {
+        Self { base, bytes }
+    }
+
+    /// The address of the first captured byte.
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// The address after the last captured byte.
+    pub fn end(&self) -> u64 {
+        self.base.saturating_add(self.bytes.len() as u64)
+    }
+
+    /// The captured bytes.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Reads the 8-byte value at `address`.
+    pub fn read_u64(&self, address: u64) -> Result<u64, StackReadError> {
+        let bytes = self.read_bytes::<8>(address)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// Reads the 4-byte value at `address`.
+    pub fn read_u32(&self, address: u64) -> Result<u32, StackReadError> {
+        let bytes = self.read_bytes::<4>(address)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// Returns a `read_stack` callback which reads 8-byte values, for unwinding x86_64
+    /// and aarch64 stacks.
+    pub fn reader(&self) -> impl FnMut(u64) -> Result<u64, StackReadError> + 'a {
+        let memory = *self;
+        move |address| memory.read_u64(address)
+    }
+
+    /// Returns a `read_stack` callback which reads 4-byte values, for unwinding 32-bit
+    /// ARM stacks. Unlike with [`reader`](Self::reader), the last 4 bytes of the region
+    /// can be read.
+    pub fn reader_32(&self) -> impl FnMut(u64) -> Result<u64, StackReadError> + 'a {
+        let memory = *self;
+        move |address| memory.read_u32(address).map(u64::from)
+    }
+
+    fn read_bytes<const N: usize>(&self, address: u64) -> Result<[u8; N], StackReadError> {
+        let offset = address
+            .checked_sub(self.base)
+            .ok_or(StackReadError::BelowStart(address))?;
+        let bytes = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.bytes.get(offset..offset.checked_add(N)?))
+            .ok_or(StackReadError::AboveEnd(address))?;
+        Ok(bytes.try_into().unwrap())
+    }
+}
+
+/// Multiple captured regions of stack memory, for example the main stack and the
+/// alternate signal stack (`sigaltstack`) of a thread which was sampled inside a signal
+/// handler. A value needs to be entirely inside one region in order to be read.
+#[derive(Clone, Debug, Default)]
+pub struct StackMemoryRegions<'a> {
+    /// Sorted by base address.
+    regions: Vec<StackMemory<'a>>,
+}
+
+impl<'a> StackMemoryRegions<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a region. Regions may be added in any order.
+    pub fn add(&mut self, region: StackMemory<'a>) {
+        let index = self
+            .regions
+            .partition_point(|existing| existing.base <= region.base);
+        self.regions.insert(index, region);
+    }
+
+    /// The regions, sorted by base address.
+    pub fn regions(&self) -> &[StackMemory<'a>] {
+        &self.regions
+    }
+
+    /// Reads the 8-byte value at `address`.
+    pub fn read_u64(&self, address: u64) -> Result<u64, StackReadError> {
+        self.region_for_address(address)?.read_u64(address)
+    }
+
+    /// Reads the 4-byte value at `address`.
+    pub fn read_u32(&self, address: u64) -> Result<u32, StackReadError> {
+        self.region_for_address(address)?.read_u32(address)
+    }
+
+    /// Returns a `read_stack` callback which reads 8-byte values, for unwinding x86_64
+    /// and aarch64 stacks.
+    pub fn reader(&self) -> impl FnMut(u64) -> Result<u64, StackReadError> + '_ {
+        move |address| self.read_u64(address)
+    }
+
+    /// Returns a `read_stack` callback which reads 4-byte values, for unwinding 32-bit
+    /// ARM stacks.
+    pub fn reader_32(&self) -> impl FnMut(u64) -> Result<u64, StackReadError> + '_ {
+        move |address| self.read_u32(address).map(u64::from)
+    }
+
+    /// Finds the last region which starts at or before `address`. Reads beyond its end
+    /// are reported as gaps if there is another region above.
+    fn region_for_address(&self, address: u64) -> Result<&StackMemory<'a>, StackReadError> {
+        let index = self
+            .regions
+            .partition_point(|region| region.base <= address);
+        let region = match index.checked_sub(1) {
+            Some(index) => &self.regions[index],
+            None => return Err(StackReadError::BelowStart(address)),
+        };
+        if index < self.regions.len() && address >= region.end() {
+            return Err(StackReadError::InGap(address));
+        }
+        Ok(region)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stack_memory() {
+        let bytes: Vec<u8> = (0..16).collect();
+        let stack = StackMemory::new(0x1000, &bytes);
+        assert_eq!(stack.read_u64(0x1000), Ok(0x0706050403020100));
+        // Unaligned.
+        assert_eq!(stack.read_u64(0x1003), Ok(0x0a09080706050403));
+        assert_eq!(stack.read_u64(0x1008), Ok(0x0f0e0d0c0b0a0908));
+        assert_eq!(
+            stack.read_u64(0x1009),
+            Err(StackReadError::AboveEnd(0x1009))
+        );
+        assert_eq!(
+            stack.read_u64(0xfff),
+            Err(StackReadError::BelowStart(0xfff))
+        );
+        assert_eq!(stack.read_u32(0x100c), Ok(0x0f0e0d0c));
+        assert_eq!(stack.reader_32()(0x100c), Ok(0x0f0e0d0c));
+        assert_eq!(
+            stack.reader()(u64::MAX),
+            Err(StackReadError::AboveEnd(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn test_regions() {
+        let main_stack = [1u64, 2, 3, 4].map(u64::to_le_bytes).concat();
+        let alt_stack = [5u64, 6].map(u64::to_le_bytes).concat();
+        let mut regions = StackMemoryRegions::new();
+        regions.add(StackMemory::new(0x7000, &main_stack));
+        regions.add(StackMemory::new(0x2000, &alt_stack));
+        assert_eq!(regions.read_u64(0x2008), Ok(6));
+        assert_eq!(regions.read_u64(0x7018), Ok(4));
+        assert_eq!(
+            regions.read_u64(0x1ff8),
+            Err(StackReadError::BelowStart(0x1ff8))
+        );
+        assert_eq!(regions.read_u64(0x2010), Err(StackReadError::InGap(0x2010)));
+        // Straddles the end of the alternate stack.
+        assert_eq!(
+            regions.read_u64(0x200c),
+            Err(StackReadError::AboveEnd(0x200c))
+        );
+        assert_eq!(
+            regions.read_u64(0x7020),
+            Err(StackReadError::AboveEnd(0x7020))
+        );
+        assert_eq!(regions.reader()(0x7000), Ok(1));
+    }
+}
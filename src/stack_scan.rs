@@ -0,0 +1,32 @@
+use crate::arch::Arch;
+
+/// The number of stack words which are checked for a return address, starting at the
+/// stack pointer. This is the same limit as in Breakpad.
+pub const STACK_SCAN_WORDS: u64 = 40;
+
+/// Finding the caller by scanning the stack for return addresses, for when frame pointer
+/// unwinding fails. See `set_stack_scanning` on the unwinders. Candidate return addresses
+/// need to follow a call instruction, see `CallInstructionAnalysis`.
+///
+/// The default implementations never scan.
+pub trait StackScanning: Arch {
+    /// If `rule` walks the frame pointer chain, returns the address at which to start
+    /// scanning when executing it fails, i.e. the stack pointer.
+    fn scan_start(
+        _rule: Self::UnwindRule,
+        _is_first_frame: bool,
+        _regs: &Self::UnwindRegs,
+    ) -> Option<u64> {
+        None
+    }
+
+    /// Turns a value from the stack into the code address it would return to, for
+    /// example by stripping pointer authentication bits.
+    fn return_address_from_stack_value(_regs: &Self::UnwindRegs, value: u64) -> u64 {
+        value
+    }
+
+    /// Updates `regs` for the caller, after the return address `value` was found on the
+    /// stack at `location`.
+    fn set_scanned_frame(_regs: &mut Self::UnwindRegs, _location: u64, _value: u64) {}
+}
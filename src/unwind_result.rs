@@ -1,5 +1,13 @@
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum UnwindResult<R> {
     ExecRule(R),
+    /// Like `ExecRule`, but the rule must not be cached. This is used next to signal
+    /// trampolines: an instruction pointer and the return address one byte after it share
+    /// a cache entry, but only one of them can be in the trampoline.
+    ExecUncacheableRule(R),
     Uncacheable(u64),
+    /// Like `Uncacheable`, but the frame was interrupted, for example by a signal or an
+    /// interrupt, so the address is the instruction pointer of the interrupted code and
+    /// not a return address.
+    UncacheableSignalFrame(u64),
 }
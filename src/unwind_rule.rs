@@ -1,6 +1,6 @@
 use crate::error::Error;
 
-pub trait UnwindRule: Copy + std::fmt::Debug {
+pub trait UnwindRule: Copy + std::fmt::Debug + PartialEq {
     type UnwindRegs;
 
     fn exec<F>(
@@ -15,4 +15,24 @@ pub trait UnwindRule: Copy + std::fmt::Debug {
     fn rule_for_stub_functions() -> Self;
     fn rule_for_function_start() -> Self;
     fn fallback_rule() -> Self;
+
+    /// The rule for the return trampoline of a Linux signal handler, which restores the
+    /// registers that were saved when the signal was delivered. `None` if this
+    /// architecture doesn't have such a rule.
+    fn rule_for_linux_signal_frame() -> Option<Self> {
+        None
+    }
+
+    /// The rule for the macOS signal trampoline `_sigtramp`, which calls the signal
+    /// handler and restores the registers that were saved when the signal was delivered.
+    /// `None` if this architecture doesn't have such a rule.
+    fn rule_for_macos_signal_frame() -> Option<Self> {
+        None
+    }
+
+    /// Whether this rule unwinds a signal frame. The address it returns is then the
+    /// interrupted instruction pointer, not a return address.
+    fn is_signal_frame(self) -> bool {
+        false
+    }
 }
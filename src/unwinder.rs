@@ -1,20 +1,39 @@
 use fallible_iterator::FallibleIterator;
 use gimli::{EndianReader, LittleEndian};
+use macho_unwind_info::UnwindInfo;
 
 use crate::arcdata::ArcData;
 use crate::arch::Arch;
+use crate::batch::{BatchOptions, UnwindBatch};
+use crate::breakpad::{BreakpadIndex, BreakpadUnwinder, BreakpadUnwinding};
 use crate::cache::{AllocationPolicy, Cache};
-use crate::dwarf::{DwarfCfiIndex, DwarfUnwinder, DwarfUnwinding, UnwindSectionType};
-use crate::error::{Error, UnwinderError};
+use crate::call_instruction::CallInstructionAnalysis;
+use crate::dwarf::{
+    self, check_eh_frame_hdr, DwarfCfiIndex, DwarfCfiIndexError, DwarfUnwindRegs, DwarfUnwinder,
+    DwarfUnwinding, UnwindSectionType,
+};
+use crate::ehabi::{ArmExidxUnwinder, ArmExidxUnwinding};
+use crate::error::{Error, ModuleError, UnwinderError};
+use crate::events::unwind_event;
+use crate::flat_rule_table::{FlatRuleTable, FlatRuleTableEntry};
+use crate::frame_info::{FrameInfo, UnwindMethod};
+use crate::full_regs::FullRegsUnwinding;
 use crate::instruction_analysis::InstructionAnalysis;
 use crate::macho::{
-    CompactUnwindInfoUnwinder, CompactUnwindInfoUnwinding, CuiUnwindResult, TextBytes,
+    CompactUnwindInfoUnwinder, CompactUnwindInfoUnwinding, CuiCalleeSavedResult, CuiUnwindResult,
+    TextBytes,
 };
+use crate::orc::{self, OrcUnwinder, OrcUnwinding};
+use crate::pe::{PeUnwinder, PeUnwinding};
+use crate::return_address_checks::{ReturnAddressCheckFailure, ReturnAddressChecks};
 use crate::rule_cache::CacheResult;
+use crate::stack_memory::{IntoStackReadError, StackReadError};
+use crate::stack_scan::{StackScanning, STACK_SCAN_WORDS};
 use crate::unwind_result::UnwindResult;
 use crate::unwind_rule::UnwindRule;
 use crate::FrameAddress;
 
+use std::cell::Cell;
 use std::marker::PhantomData;
 use std::sync::atomic::{AtomicU16, Ordering};
 use std::{
@@ -45,6 +64,21 @@ pub trait Unwinder {
     /// This should be called whenever a new module is loaded into the process.
     fn add_module(&mut self, module: Self::Module);
 
+    /// Add a module, like `add_module`, but check its unwind data first.
+    ///
+    /// This checks that the unwind data can be parsed and indexed, that an `.eh_frame_hdr`
+    /// search table can be searched, and that the unwind data describes at least one
+    /// function. If any of these checks fail, the module is not added and the first
+    /// problem is returned. Addresses inside the module are then unwound as if they were
+    /// not inside any module, which means that frame pointers are used on x86_64 and aarch64.
+    /// You can still call `add_module` if you want to use the module's unwind data anyway.
+    ///
+    /// The default implementation adds the module with `add_module` without checking it.
+    fn try_add_module(&mut self, module: Self::Module) -> Result<(), ModuleError> {
+        self.add_module(module);
+        Ok(())
+    }
+
     /// Remove a module that was added before using `add_module`, keyed by the start
     /// address of that module's address range. If no match is found, the call is ignored.
     /// This should be called whenever a module is unloaded from the process.
@@ -58,9 +92,28 @@ pub trait Unwinder {
     /// to make an educated guess at a pointer authentication mask for Aarch64 return addresses.
     fn max_known_code_address(&self) -> u64;
 
+    /// Check whether `address` is a plausible return address, based on the modules and
+    /// their text bytes. Returns [`Error::ImplausibleReturnAddress`] if one of the enabled
+    /// checks fails.
+    ///
+    /// This is used by the iterators if [`ReturnAddressChecks`] are configured, see
+    /// [`UnwindIterator::with_return_address_checks`]. The default implementation accepts
+    /// all addresses.
+    fn check_return_address(
+        &self,
+        _address: u64,
+        _checks: &ReturnAddressChecks,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
     /// Unwind a single frame, to recover return address and caller register values.
     /// This is the main entry point for unwinding.
-    fn unwind_frame<F>(
+    ///
+    /// If the frame is a Linux signal trampoline, the returned address is the instruction
+    /// pointer of the interrupted code, not a return address. Use
+    /// [`Unwinder::unwind_frame_address`] to tell the two apart.
+    fn unwind_frame<F, E>(
         &self,
         address: FrameAddress,
         regs: &mut Self::UnwindRegs,
@@ -68,10 +121,35 @@ pub trait Unwinder {
         read_stack: &mut F,
     ) -> Result<Option<u64>, Error>
     where
-        F: FnMut(u64) -> Result<u64, ()>;
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError;
+
+    /// Unwind a single frame, like [`Unwinder::unwind_frame`], and return the caller's
+    /// address as a [`FrameAddress`]. This is a [`FrameAddress::InstructionPointer`] if
+    /// the frame is a Linux signal trampoline, because the caller was interrupted by the
+    /// signal instead of calling the trampoline. Otherwise it's a
+    /// [`FrameAddress::ReturnAddress`].
+    fn unwind_frame_address<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut Self::UnwindRegs,
+        cache: &mut Self::Cache,
+        read_stack: &mut F,
+    ) -> Result<Option<FrameAddress>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        match self.unwind_frame(address, regs, cache, read_stack)? {
+            Some(return_address) => FrameAddress::from_return_address(return_address)
+                .ok_or(Error::ReturnAddressIsNull)
+                .map(Some),
+            None => Ok(None),
+        }
+    }
 
     /// Return an iterator that unwinds frame by frame until the end of the stack is found.
-    fn iter_frames<'u, 'c, 'r, F>(
+    fn iter_frames<'u, 'c, 'r, F, E>(
         &'u self,
         pc: u64,
         regs: Self::UnwindRegs,
@@ -79,16 +157,79 @@ pub trait Unwinder {
         read_stack: &'r mut F,
     ) -> UnwindIterator<'u, 'c, 'r, Self, F>
     where
-        F: FnMut(u64) -> Result<u64, ()>,
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
     {
         UnwindIterator::new(self, pc, regs, cache, read_stack)
     }
+
+    /// Unwind the stacks of many samples, using one cache. Each sample is a tuple of the
+    /// initial instruction pointer, the initial register values and the `read_stack`
+    /// callback for the sample's stack memory.
+    ///
+    /// The stacks are stored in one [`UnwindBatch`] instead of one `Vec` per stack,
+    /// together with a [`StackStatus`](crate::StackStatus) for each sample, which says
+    /// whether the stack was unwound to the root function.
+    fn unwind_batch<I, F, E>(
+        &self,
+        samples: I,
+        cache: &mut Self::Cache,
+        options: &BatchOptions,
+    ) -> UnwindBatch
+    where
+        I: IntoIterator<Item = (u64, Self::UnwindRegs, F)>,
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        crate::batch::unwind_batch(self, samples, cache, options)
+    }
+}
+
+/// Unwinding which also describes how each caller frame was found. This is useful to
+/// diagnose incorrect stacks.
+///
+/// This is implemented by each CPU architecture's concrete unwinder type, in addition to
+/// [`Unwinder`].
+pub trait FrameInfoUnwinder: Unwinder {
+    /// The unwind rule type for the targeted CPU architecture. This is reported in
+    /// [`FrameInfo::rule`].
+    type UnwindRule;
+
+    /// Unwind a single frame, like [`Unwinder::unwind_frame`], and describe how the caller
+    /// frame was found.
+    fn unwind_frame_with_info<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut Self::UnwindRegs,
+        cache: &mut Self::Cache,
+        read_stack: &mut F,
+    ) -> Result<Option<FrameInfo<Self::UnwindRule>>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError;
+
+    /// Return an iterator that unwinds frame by frame like [`Unwinder::iter_frames`], but
+    /// which yields a [`FrameInfo`] for each frame.
+    fn iter_frames_with_info<'u, 'c, 'r, F, E>(
+        &'u self,
+        pc: u64,
+        regs: Self::UnwindRegs,
+        cache: &'c mut Self::Cache,
+        read_stack: &'r mut F,
+    ) -> FrameInfoIterator<'u, 'c, 'r, Self, F>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        FrameInfoIterator::new(self, pc, regs, cache, read_stack)
+    }
 }
 
 /// An iterator for unwinding the entire stack, starting from the initial register values.
 ///
 /// The first yielded frame is the instruction pointer. Subsequent addresses are return
-/// addresses.
+/// addresses, except for the frames which were interrupted by a signal, see
+/// [`Unwinder::unwind_frame_address`].
 ///
 /// This iterator attempts to detect if stack unwinding completed successfully, or if the
 /// stack was truncated prematurely. If it thinks that it successfully found the root
@@ -103,12 +244,13 @@ pub trait Unwinder {
 ///  - `'u`: The lifetime of the [`Unwinder`].
 ///  - `'c`: The lifetime of the unwinder cache.
 ///  - `'r`: The lifetime of the exclusive access to the `read_stack` callback.
-pub struct UnwindIterator<'u, 'c, 'r, U: Unwinder + ?Sized, F: FnMut(u64) -> Result<u64, ()>> {
+pub struct UnwindIterator<'u, 'c, 'r, U: Unwinder + ?Sized, F> {
     unwinder: &'u U,
     state: UnwindIteratorState,
     regs: U::UnwindRegs,
     cache: &'c mut U::Cache,
     read_stack: &'r mut F,
+    return_address_checks: ReturnAddressChecks,
 }
 
 enum UnwindIteratorState {
@@ -117,8 +259,11 @@ enum UnwindIteratorState {
     Done,
 }
 
-impl<'u, 'c, 'r, U: Unwinder + ?Sized, F: FnMut(u64) -> Result<u64, ()>>
-    UnwindIterator<'u, 'c, 'r, U, F>
+impl<'u, 'c, 'r, U, F, E> UnwindIterator<'u, 'c, 'r, U, F>
+where
+    U: Unwinder + ?Sized,
+    F: FnMut(u64) -> Result<u64, E>,
+    E: IntoStackReadError,
 {
     /// Create a new iterator. You'd usually use [`Unwinder::iter_frames`] instead.
     pub fn new(
@@ -134,17 +279,31 @@ impl<'u, 'c, 'r, U: Unwinder + ?Sized, F: FnMut(u64) -> Result<u64, ()>>
             regs,
             cache,
             read_stack,
+            return_address_checks: ReturnAddressChecks::default(),
         }
     }
+
+    /// Stop unwinding with [`Error::ImplausibleReturnAddress`] when a return address fails
+    /// one of the enabled `checks`. No checks are done by default. The instruction
+    /// pointers of frames which were interrupted by a signal are not checked.
+    pub fn with_return_address_checks(mut self, checks: ReturnAddressChecks) -> Self {
+        self.return_address_checks = checks;
+        self
+    }
 }
 
-impl<'u, 'c, 'r, U: Unwinder + ?Sized, F: FnMut(u64) -> Result<u64, ()>>
-    UnwindIterator<'u, 'c, 'r, U, F>
+impl<'u, 'c, 'r, U, F, E> UnwindIterator<'u, 'c, 'r, U, F>
+where
+    U: Unwinder + ?Sized,
+    F: FnMut(u64) -> Result<u64, E>,
+    E: IntoStackReadError,
 {
     /// Yield the next frame in the stack.
     ///
     /// The first frame is `Ok(Some(FrameAddress::InstructionPointer(...)))`.
-    /// Subsequent frames are `Ok(Some(FrameAddress::ReturnAddress(...)))`.
+    /// Subsequent frames are `Ok(Some(FrameAddress::ReturnAddress(...)))`, or
+    /// `Ok(Some(FrameAddress::InstructionPointer(...)))` for frames which were interrupted
+    /// by a signal.
     ///
     /// If a root function has been reached, this iterator completes with `Ok(None)`.
     /// Otherwise it completes with `Err(...)`, usually indicating that a certain stack
@@ -156,18 +315,22 @@ impl<'u, 'c, 'r, U: Unwinder + ?Sized, F: FnMut(u64) -> Result<u64, ()>>
                 self.state = UnwindIteratorState::Unwinding(FrameAddress::InstructionPointer(pc));
                 return Ok(Some(FrameAddress::InstructionPointer(pc)));
             }
-            UnwindIteratorState::Unwinding(address) => {
-                self.unwinder
-                    .unwind_frame(address, &mut self.regs, self.cache, self.read_stack)?
-            }
+            UnwindIteratorState::Unwinding(address) => self.unwinder.unwind_frame_address(
+                address,
+                &mut self.regs,
+                self.cache,
+                self.read_stack,
+            )?,
             UnwindIteratorState::Done => return Ok(None),
         };
         match next {
-            Some(return_address) => {
-                let return_address = FrameAddress::from_return_address(return_address)
-                    .ok_or(Error::ReturnAddressIsNull)?;
-                self.state = UnwindIteratorState::Unwinding(return_address);
-                Ok(Some(return_address))
+            Some(address) => {
+                if address.is_return_address() {
+                    self.unwinder
+                        .check_return_address(address.address(), &self.return_address_checks)?;
+                }
+                self.state = UnwindIteratorState::Unwinding(address);
+                Ok(Some(address))
             }
             None => {
                 self.state = UnwindIteratorState::Done;
@@ -177,8 +340,11 @@ impl<'u, 'c, 'r, U: Unwinder + ?Sized, F: FnMut(u64) -> Result<u64, ()>>
     }
 }
 
-impl<'u, 'c, 'r, U: Unwinder + ?Sized, F: FnMut(u64) -> Result<u64, ()>> FallibleIterator
-    for UnwindIterator<'u, 'c, 'r, U, F>
+impl<'u, 'c, 'r, U, F, E> FallibleIterator for UnwindIterator<'u, 'c, 'r, U, F>
+where
+    U: Unwinder + ?Sized,
+    F: FnMut(u64) -> Result<u64, E>,
+    E: IntoStackReadError,
 {
     type Item = FrameAddress;
     type Error = Error;
@@ -188,6 +354,114 @@ impl<'u, 'c, 'r, U: Unwinder + ?Sized, F: FnMut(u64) -> Result<u64, ()>> Fallibl
     }
 }
 
+/// An iterator for unwinding the entire stack which describes how each frame was found.
+///
+/// This works like [`UnwindIterator`], but it yields a [`FrameInfo`] for each frame
+/// instead of just the frame's address. The first frame has the method
+/// [`UnwindMethod::InstructionPointer`].
+///
+/// Lifetimes:
+///
+///  - `'u`: The lifetime of the [`Unwinder`].
+///  - `'c`: The lifetime of the unwinder cache.
+///  - `'r`: The lifetime of the exclusive access to the `read_stack` callback.
+pub struct FrameInfoIterator<'u, 'c, 'r, U: FrameInfoUnwinder + ?Sized, F> {
+    unwinder: &'u U,
+    state: UnwindIteratorState,
+    regs: U::UnwindRegs,
+    cache: &'c mut U::Cache,
+    read_stack: &'r mut F,
+    return_address_checks: ReturnAddressChecks,
+}
+
+impl<'u, 'c, 'r, U, F, E> FrameInfoIterator<'u, 'c, 'r, U, F>
+where
+    U: FrameInfoUnwinder + ?Sized,
+    F: FnMut(u64) -> Result<u64, E>,
+    E: IntoStackReadError,
+{
+    /// Create a new iterator. You'd usually use [`FrameInfoUnwinder::iter_frames_with_info`]
+    /// instead.
+    pub fn new(
+        unwinder: &'u U,
+        pc: u64,
+        regs: U::UnwindRegs,
+        cache: &'c mut U::Cache,
+        read_stack: &'r mut F,
+    ) -> Self {
+        Self {
+            unwinder,
+            state: UnwindIteratorState::Initial(pc),
+            regs,
+            cache,
+            read_stack,
+            return_address_checks: ReturnAddressChecks::default(),
+        }
+    }
+
+    /// Stop unwinding with [`Error::ImplausibleReturnAddress`] when a return address fails
+    /// one of the enabled `checks`. No checks are done by default. The instruction
+    /// pointers of frames which were interrupted by a signal are not checked.
+    pub fn with_return_address_checks(mut self, checks: ReturnAddressChecks) -> Self {
+        self.return_address_checks = checks;
+        self
+    }
+
+    /// Yield the next frame in the stack, see [`UnwindIterator::next`].
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<FrameInfo<U::UnwindRule>>, Error> {
+        let next = match self.state {
+            UnwindIteratorState::Initial(pc) => {
+                self.state = UnwindIteratorState::Unwinding(FrameAddress::InstructionPointer(pc));
+                return Ok(Some(FrameInfo::for_instruction_pointer(pc)));
+            }
+            UnwindIteratorState::Unwinding(address) => self.unwinder.unwind_frame_with_info(
+                address,
+                &mut self.regs,
+                self.cache,
+                self.read_stack,
+            )?,
+            UnwindIteratorState::Done => return Ok(None),
+        };
+        match next {
+            Some(frame_info) => {
+                if frame_info.address.is_return_address() {
+                    self.unwinder.check_return_address(
+                        frame_info.address.address(),
+                        &self.return_address_checks,
+                    )?;
+                }
+                self.state = UnwindIteratorState::Unwinding(frame_info.address);
+                Ok(Some(frame_info))
+            }
+            None => {
+                self.state = UnwindIteratorState::Done;
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl<'u, 'c, 'r, U, F, E> FallibleIterator for FrameInfoIterator<'u, 'c, 'r, U, F>
+where
+    U: FrameInfoUnwinder + ?Sized,
+    F: FnMut(u64) -> Result<u64, E>,
+    E: IntoStackReadError,
+{
+    type Item = FrameInfo<U::UnwindRule>;
+    type Error = Error;
+
+    fn next(&mut self) -> Result<Option<FrameInfo<U::UnwindRule>>, Error> {
+        self.next()
+    }
+}
+
+/// An unwind result together with the method which produced it.
+type UnwindResultWithMethod<R> = (UnwindResult<R>, UnwindMethod);
+
+/// The return address of a frame together with the method and the rule which found it.
+type ReturnAddressWithMethod<R> = (Option<u64>, UnwindMethod, Option<R>);
+
 /// This global generation counter makes it so that the cache can be shared
 /// between multiple unwinders.
 /// This is a u16, so if you make it wrap around by adding / removing modules
@@ -202,20 +476,40 @@ fn next_global_modules_generation() -> u16 {
 
 pub struct UnwinderInternal<
     D: Deref<Target = [u8]>,
-    A: Arch + DwarfUnwinding + CompactUnwindInfoUnwinding + InstructionAnalysis,
+    A: Arch
+        + DwarfUnwinding
+        + CompactUnwindInfoUnwinding
+        + ArmExidxUnwinding
+        + PeUnwinding
+        + OrcUnwinding
+        + StackScanning
+        + CallInstructionAnalysis
+        + BreakpadUnwinding
+        + InstructionAnalysis,
     P: AllocationPolicy<D>,
 > {
     /// sorted by avma_range.start
-    modules: Vec<Module<D>>,
+    modules: Vec<ModuleInternal<D, A::UnwindRule>>,
     /// Incremented every time modules is changed.
     modules_generation: u16,
+    /// Whether to scan the stack for return addresses when frame pointer unwinding fails.
+    stack_scanning: bool,
     _arch: PhantomData<A>,
     _allocation_policy: PhantomData<P>,
 }
 
 impl<
         D: Deref<Target = [u8]>,
-        A: Arch + DwarfUnwinding + CompactUnwindInfoUnwinding + InstructionAnalysis,
+        A: Arch
+            + DwarfUnwinding
+            + CompactUnwindInfoUnwinding
+            + ArmExidxUnwinding
+            + PeUnwinding
+            + OrcUnwinding
+            + StackScanning
+            + CallInstructionAnalysis
+            + BreakpadUnwinding
+            + InstructionAnalysis,
         P: AllocationPolicy<D>,
     > Default for UnwinderInternal<D, A, P>
 {
@@ -226,7 +520,16 @@ impl<
 
 impl<
         D: Deref<Target = [u8]>,
-        A: Arch + DwarfUnwinding + CompactUnwindInfoUnwinding + InstructionAnalysis,
+        A: Arch
+            + DwarfUnwinding
+            + CompactUnwindInfoUnwinding
+            + ArmExidxUnwinding
+            + PeUnwinding
+            + OrcUnwinding
+            + StackScanning
+            + CallInstructionAnalysis
+            + BreakpadUnwinding
+            + InstructionAnalysis,
         P: AllocationPolicy<D>,
     > UnwinderInternal<D, A, P>
 {
@@ -234,20 +537,50 @@ impl<
         Self {
             modules: Vec::new(),
             modules_generation: next_global_modules_generation(),
+            stack_scanning: false,
             _arch: PhantomData,
             _allocation_policy: PhantomData,
         }
     }
 
     pub fn add_module(&mut self, module: Module<D>) {
+        let mut errors = Vec::new();
+        let module = ModuleInternal::new::<A>(module, &mut |error| errors.push(error));
+        for error in errors {
+            unwind_event!(
+                warn,
+                "The module's unwind data has a problem",
+                module = module.name,
+                error = error,
+            );
+        }
+        self.insert_module(module);
+    }
+
+    pub fn try_add_module(&mut self, module: Module<D>) -> Result<(), ModuleError> {
+        let mut first_error = None;
+        let module = ModuleInternal::new::<A>(module, &mut |error| {
+            first_error.get_or_insert(error);
+        });
+        if let Some(error) = first_error {
+            return Err(error);
+        }
+        self.insert_module(module);
+        Ok(())
+    }
+
+    fn insert_module(&mut self, module: ModuleInternal<D, A::UnwindRule>) {
         let insertion_index = match self
             .modules
             .binary_search_by_key(&module.avma_range.start, |module| module.avma_range.start)
         {
             Ok(i) => {
-                eprintln!(
-                    "Now we have two modules at the same start address 0x{:x}. This can't be good.",
-                    module.avma_range.start
+                unwind_event!(
+                    warn,
+                    "Two modules have the same start address",
+                    module = module.name,
+                    existing_module = self.modules[i].name,
+                    address = format_args!("0x{:x}", module.avma_range.start),
                 );
                 i
             }
@@ -273,6 +606,10 @@ impl<
         self.modules.last().map_or(0, |m| m.avma_range.end)
     }
 
+    pub fn set_stack_scanning(&mut self, enabled: bool) {
+        self.stack_scanning = enabled;
+    }
+
     fn find_module_for_address(&self, address: u64) -> Option<(usize, u32)> {
         let (module_index, module) = match self
             .modules
@@ -301,24 +638,30 @@ impl<
         Some((module_index, relative_address))
     }
 
-    fn with_cache<F, G>(
+    /// Unwind using the cached rule for the address, or look up the rule using `callback`
+    /// and cache it. `record_info` is called with the method, the rule, whether the frame
+    /// is a signal frame, whether the cache was hit, and the error that was suppressed by
+    /// using the fallback rule.
+    fn with_cache<F, G, I>(
         &self,
         address: FrameAddress,
         regs: &mut A::UnwindRegs,
         cache: &mut Cache<D, A::UnwindRule, P>,
         read_stack: &mut F,
         callback: G,
+        record_info: I,
     ) -> Result<Option<u64>, Error>
     where
         F: FnMut(u64) -> Result<u64, ()>,
         G: FnOnce(
-            &Module<D>,
+            &ModuleInternal<D, A::UnwindRule>,
             FrameAddress,
             u32,
             &mut A::UnwindRegs,
             &mut Cache<D, A::UnwindRule, P>,
             &mut F,
-        ) -> Result<UnwindResult<A::UnwindRule>, UnwinderError>,
+        ) -> Result<UnwindResultWithMethod<A::UnwindRule>, UnwinderError>,
+        I: FnOnce(UnwindMethod, Option<A::UnwindRule>, bool, bool, Option<UnwinderError>),
     {
         let lookup_address = address.address_for_lookup();
         let is_first_frame = !address.is_return_address();
@@ -326,14 +669,20 @@ impl<
             .rule_cache
             .lookup(lookup_address, self.modules_generation)
         {
-            CacheResult::Hit(unwind_rule) => {
-                return unwind_rule.exec(is_first_frame, regs, read_stack);
+            CacheResult::Hit(unwind_rule, method) => {
+                let (return_address, method, rule) =
+                    self.exec_rule_or_scan(unwind_rule, method, is_first_frame, regs, read_stack)?;
+                let is_signal_frame = rule.is_some_and(|rule| rule.is_signal_frame());
+                record_info(method, rule, is_signal_frame, true, None);
+                return Ok(return_address);
             }
             CacheResult::Miss(handle) => handle,
         };
 
-        let unwind_rule = match self.find_module_for_address(lookup_address) {
-            None => A::UnwindRule::fallback_rule(),
+        let (unwind_rule, method, suppressed_error) = match self
+            .find_module_for_address(lookup_address)
+        {
+            None => (A::UnwindRule::fallback_rule(), UnwindMethod::Fallback, None),
             Some((module_index, relative_lookup_address)) => {
                 let module = &self.modules[module_index];
                 match callback(
@@ -344,22 +693,204 @@ impl<
                     cache,
                     read_stack,
                 ) {
-                    Ok(UnwindResult::ExecRule(rule)) => rule,
-                    Ok(UnwindResult::Uncacheable(return_address)) => {
-                        return Ok(Some(return_address))
+                    Ok((UnwindResult::ExecRule(rule), method)) => (rule, method, None),
+                    Ok((UnwindResult::ExecUncacheableRule(rule), method)) => {
+                        let (return_address, method, rule) =
+                            self.exec_rule_or_scan(rule, method, is_first_frame, regs, read_stack)?;
+                        let is_signal_frame = rule.is_some_and(|rule| rule.is_signal_frame());
+                        record_info(method, rule, is_signal_frame, false, None);
+                        return Ok(return_address);
+                    }
+                    Ok((UnwindResult::Uncacheable(return_address), method)) => {
+                        record_info(method, None, false, false, None);
+                        return Ok(Some(return_address));
                     }
-                    Err(_err) => {
-                        // eprintln!("Unwinder error: {}", err);
-                        A::UnwindRule::fallback_rule()
+                    Ok((UnwindResult::UncacheableSignalFrame(address), method)) => {
+                        record_info(method, None, true, false, None);
+                        return Ok(Some(address));
+                    }
+                    Err(err) => {
+                        unwind_event!(
+                            debug,
+                            "Could not use the module's unwind data, using the fallback rule",
+                            module = module.name,
+                            address = format_args!("0x{:x}", lookup_address),
+                            error = err,
+                        );
+                        (
+                            A::UnwindRule::fallback_rule(),
+                            UnwindMethod::Fallback,
+                            Some(err),
+                        )
                     }
                 }
             }
         };
-        cache.rule_cache.insert(cache_handle, unwind_rule);
-        unwind_rule.exec(is_first_frame, regs, read_stack)
+        cache.rule_cache.insert(cache_handle, unwind_rule, method);
+        let (return_address, method, rule) =
+            self.exec_rule_or_scan(unwind_rule, method, is_first_frame, regs, read_stack)?;
+        let is_signal_frame = rule.is_some_and(|rule| rule.is_signal_frame());
+        record_info(method, rule, is_signal_frame, false, suppressed_error);
+        Ok(return_address)
+    }
+
+    /// Executes the rule, which was found with `method`. If this fails for a frame
+    /// pointer rule and stack scanning is enabled, scans the stack for a return address
+    /// instead. Returns the return address, and the method and rule which found it: these
+    /// are [`UnwindMethod::StackScan`] and `None` if it was found by scanning.
+    fn exec_rule_or_scan<F>(
+        &self,
+        unwind_rule: A::UnwindRule,
+        method: UnwindMethod,
+        is_first_frame: bool,
+        regs: &mut A::UnwindRegs,
+        read_stack: &mut F,
+    ) -> Result<ReturnAddressWithMethod<A::UnwindRule>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let scan_start = if self.stack_scanning {
+            A::scan_start(unwind_rule, is_first_frame, regs)
+        } else {
+            None
+        };
+        let err = match unwind_rule.exec(is_first_frame, regs, read_stack) {
+            Ok(return_address) => return Ok((return_address, method, Some(unwind_rule))),
+            Err(err) => err,
+        };
+        let Some(scan_start) = scan_start else {
+            return Err(err);
+        };
+        match self.scan_stack(scan_start, regs, read_stack) {
+            Some(return_address) => {
+                unwind_event!(
+                    debug,
+                    "Frame pointer unwinding failed, found the caller by scanning the stack",
+                    error = err,
+                    address = format_args!("0x{:x}", return_address),
+                );
+                Ok((Some(return_address), UnwindMethod::StackScan, None))
+            }
+            None => Err(err),
+        }
+    }
+
+    /// Searches the stack upwards from `scan_start` for a value which points into the
+    /// text of a known module, right after a call instruction. If one is found, `regs`
+    /// are updated for the caller and the return address is returned.
+    fn scan_stack<F>(
+        &self,
+        scan_start: u64,
+        regs: &mut A::UnwindRegs,
+        read_stack: &mut F,
+    ) -> Option<u64>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        for word_index in 0..STACK_SCAN_WORDS {
+            let location = scan_start.checked_add(word_index * 8)?;
+            let value = read_stack(location).ok()?;
+            let return_address = A::return_address_from_stack_value(regs, value);
+            if self.is_return_address_after_call(return_address) {
+                A::set_scanned_frame(regs, location, value);
+                return Some(return_address);
+            }
+        }
+        None
+    }
+
+    /// Whether `address` is in the text of a known module and follows a call instruction,
+    /// according to the module's [`TextByteData`].
+    fn is_return_address_after_call(&self, address: u64) -> bool {
+        let Some((module_index, _)) = self.find_module_for_address(address.wrapping_sub(1)) else {
+            return false;
+        };
+        let module = &self.modules[module_index];
+        let Some(text) = &module.svma_info.text else {
+            return false;
+        };
+        let text = module.avma_range_for_svma_range(text);
+        address > text.start
+            && address <= text.end
+            && Self::is_preceded_by_call(module, address) == Some(true)
+    }
+
+    /// Whether the instruction which ends at `address` is a call instruction, or `None` if
+    /// the module's [`TextByteData`] doesn't contain it.
+    fn is_preceded_by_call(
+        module: &ModuleInternal<D, A::UnwindRule>,
+        address: u64,
+    ) -> Option<bool> {
+        let text_data = module.text_data.as_ref()?;
+        if address <= text_data.avma_range.start {
+            return None;
+        }
+        let end = usize::try_from(address - text_data.avma_range.start).ok()?;
+        let bytes = text_data.bytes.get(..end)?;
+        Some(A::is_preceded_by_call(bytes))
+    }
+
+    pub fn check_return_address(
+        &self,
+        address: u64,
+        checks: &ReturnAddressChecks,
+    ) -> Result<(), Error> {
+        if checks.is_empty() {
+            return Ok(());
+        }
+        let fail = |failure| Err(Error::ImplausibleReturnAddress(address, failure));
+        // The return address points after the call instruction, which can be the last
+        // instruction of the function, or even of the module.
+        let lookup_address = address.wrapping_sub(1);
+        let Some((module_index, _)) = self.find_module_for_address(lookup_address) else {
+            if checks.in_module || checks.in_executable_range {
+                return fail(ReturnAddressCheckFailure::NotInModule);
+            }
+            return Ok(());
+        };
+        let module = &self.modules[module_index];
+        if checks.in_executable_range {
+            let svma_info = &module.svma_info;
+            let sections = [
+                &svma_info.text,
+                &svma_info.text_env,
+                &svma_info.stubs,
+                &svma_info.stub_helper,
+            ];
+            // The executable segments also cover code outside the text section, e.g. in
+            // `.init` or `.plt`. Only fall back to the sections if we don't know them.
+            let sections = svma_info
+                .executable_segments
+                .is_empty()
+                .then_some(sections)
+                .into_iter()
+                .flatten()
+                .flatten();
+            let mut executable_ranges = svma_info
+                .executable_segments
+                .iter()
+                .chain(sections)
+                .map(|range| module.avma_range_for_svma_range(range))
+                .chain(
+                    module
+                        .text_data
+                        .as_ref()
+                        .map(|data| data.avma_range.clone()),
+                )
+                .peekable();
+            if executable_ranges.peek().is_some()
+                && !executable_ranges.any(|range| range.contains(&lookup_address))
+            {
+                return fail(ReturnAddressCheckFailure::NotInExecutableRange);
+            }
+        }
+        if checks.after_call && Self::is_preceded_by_call(module, address) == Some(false) {
+            return fail(ReturnAddressCheckFailure::NotAfterCall);
+        }
+        Ok(())
     }
 
-    pub fn unwind_frame<F>(
+    pub fn unwind_frame<F, E>(
         &self,
         address: FrameAddress,
         regs: &mut A::UnwindRegs,
@@ -367,77 +898,460 @@ impl<
         read_stack: &mut F,
     ) -> Result<Option<u64>, Error>
     where
-        F: FnMut(u64) -> Result<u64, ()>,
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        let read_error = Cell::new(None);
+        let mut read_stack = Self::recording_read_errors(read_stack, &read_error);
+        self.with_cache(
+            address,
+            regs,
+            cache,
+            &mut read_stack,
+            Self::unwind_frame_impl,
+            |_, _, _, _, _| {},
+        )
+        .map_err(|err| err.with_stack_read_error(read_error.get()))
+    }
+
+    pub fn unwind_frame_address<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut A::UnwindRegs,
+        cache: &mut Cache<D, A::UnwindRule, P>,
+        read_stack: &mut F,
+    ) -> Result<Option<FrameAddress>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        let read_error = Cell::new(None);
+        let mut read_stack = Self::recording_read_errors(read_stack, &read_error);
+        let mut is_signal_frame = false;
+        let next = self
+            .with_cache(
+                address,
+                regs,
+                cache,
+                &mut read_stack,
+                Self::unwind_frame_impl,
+                |_, _, signal_frame, _, _| {
+                    is_signal_frame = signal_frame;
+                },
+            )
+            .map_err(|err| err.with_stack_read_error(read_error.get()))?;
+        next.map(|next| Self::caller_address(next, is_signal_frame))
+            .transpose()
+    }
+
+    /// Wraps `read_stack` into a callback with the error type which is used internally,
+    /// and stores the reason of the last failed read in `read_error`.
+    fn recording_read_errors<'a, F, E>(
+        read_stack: &'a mut F,
+        read_error: &'a Cell<Option<StackReadError>>,
+    ) -> impl FnMut(u64) -> Result<u64, ()> + 'a
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        move |address| {
+            read_stack(address).map_err(|err| read_error.set(err.into_stack_read_error()))
+        }
+    }
+
+    /// The caller's address, which is the interrupted instruction pointer if the callee
+    /// is a signal frame.
+    fn caller_address(address: u64, is_signal_frame: bool) -> Result<FrameAddress, Error> {
+        if is_signal_frame {
+            Ok(FrameAddress::InstructionPointer(address))
+        } else {
+            FrameAddress::from_return_address(address).ok_or(Error::ReturnAddressIsNull)
+        }
+    }
+
+    pub fn unwind_frame_with_info<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut A::UnwindRegs,
+        cache: &mut Cache<D, A::UnwindRule, P>,
+        read_stack: &mut F,
+    ) -> Result<Option<FrameInfo<A::UnwindRule>>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
     {
-        self.with_cache(address, regs, cache, read_stack, Self::unwind_frame_impl)
+        let read_error = Cell::new(None);
+        let mut read_stack = Self::recording_read_errors(read_stack, &read_error);
+        let mut info = None;
+        let return_address = self
+            .with_cache(
+                address,
+                regs,
+                cache,
+                &mut read_stack,
+                Self::unwind_frame_impl,
+                |method, rule, is_signal_frame, cache_hit, suppressed_error| {
+                    info = Some((method, rule, is_signal_frame, cache_hit, suppressed_error));
+                },
+            )
+            .map_err(|err| err.with_stack_read_error(read_error.get()))?;
+        let (
+            Some(return_address),
+            Some((method, rule, is_signal_frame, cache_hit, suppressed_error)),
+        ) = (return_address, info)
+        else {
+            return Ok(None);
+        };
+        Ok(Some(FrameInfo {
+            address: Self::caller_address(return_address, is_signal_frame)?,
+            method,
+            rule,
+            cache_hit,
+            suppressed_error,
+        }))
     }
 
     fn unwind_frame_impl<F>(
-        module: &Module<D>,
+        module: &ModuleInternal<D, A::UnwindRule>,
+        address: FrameAddress,
+        rel_lookup_address: u32,
+        regs: &mut A::UnwindRegs,
+        cache: &mut Cache<D, A::UnwindRule, P>,
+        read_stack: &mut F,
+    ) -> Result<UnwindResultWithMethod<A::UnwindRule>, UnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        // An instruction pointer shares its cache entry with the return address one byte
+        // after it. If either of them is in the trampoline, the rule for the entry depends
+        // on the kind of the address, so it must not be cached.
+        let mut is_next_to_trampoline = false;
+        if let Some(rule) = A::UnwindRule::rule_for_linux_signal_frame() {
+            if Self::is_linux_sigreturn_trampoline(module, address.address()) {
+                return Ok((
+                    UnwindResult::ExecUncacheableRule(rule),
+                    UnwindMethod::SignalTrampoline,
+                ));
+            }
+            let address_sharing_cache_entry = if address.is_return_address() {
+                Some(address.address_for_lookup())
+            } else {
+                address.address().checked_add(1)
+            };
+            is_next_to_trampoline = address_sharing_cache_entry
+                .is_some_and(|address| Self::is_linux_sigreturn_trampoline(module, address));
+        }
+        let unwind_result = Self::unwind_frame_outside_trampoline(
+            module,
+            address,
+            rel_lookup_address,
+            regs,
+            cache,
+            read_stack,
+        );
+        match unwind_result {
+            Ok((UnwindResult::ExecRule(rule), method)) if is_next_to_trampoline => {
+                Ok((UnwindResult::ExecUncacheableRule(rule), method))
+            }
+            unwind_result => unwind_result,
+        }
+    }
+
+    fn unwind_frame_outside_trampoline<F>(
+        module: &ModuleInternal<D, A::UnwindRule>,
         address: FrameAddress,
         rel_lookup_address: u32,
         regs: &mut A::UnwindRegs,
         cache: &mut Cache<D, A::UnwindRule, P>,
         read_stack: &mut F,
-    ) -> Result<UnwindResult<A::UnwindRule>, UnwinderError>
+    ) -> Result<UnwindResultWithMethod<A::UnwindRule>, UnwinderError>
     where
         F: FnMut(u64) -> Result<u64, ()>,
     {
         let is_first_frame = !address.is_return_address();
-        let unwind_result = match &module.unwind_data {
-            ModuleUnwindDataInternal::CompactUnwindInfoAndEhFrame(unwind_data, eh_frame_data) => {
-                // eprintln!("unwinding with cui and eh_frame in module {}", module.name);
-                let text_bytes = module.text_data.as_ref().and_then(|data| {
-                    let offset_from_base =
-                        u32::try_from(data.avma_range.start.checked_sub(module.base_avma)?).ok()?;
-                    Some(TextBytes::new(offset_from_base, &data.bytes[..]))
-                });
-                let stubs_range = if let Some(stubs_range) = &module.svma_info.stubs {
-                    (
-                        (stubs_range.start - module.svma_info.base_svma) as u32,
-                        (stubs_range.end - module.svma_info.base_svma) as u32,
-                    )
-                } else {
-                    (0, 0)
-                };
-                let stub_helper_range =
-                    if let Some(stub_helper_range) = &module.svma_info.stub_helper {
-                        (
-                            (stub_helper_range.start - module.svma_info.base_svma) as u32,
-                            (stub_helper_range.end - module.svma_info.base_svma) as u32,
-                        )
-                    } else {
-                        (0, 0)
-                    };
-                let mut unwinder = CompactUnwindInfoUnwinder::<A>::new(
-                    &unwind_data[..],
-                    text_bytes,
-                    stubs_range,
-                    stub_helper_range,
+        let unwind_result = Self::unwind_frame_with_unwind_data(
+            module,
+            is_first_frame,
+            rel_lookup_address,
+            regs,
+            cache,
+            read_stack,
+        );
+        let is_uncovered = matches!(
+            unwind_result,
+            Ok(None)
+                | Err(UnwinderError::EhFrameHdrCouldNotFindAddress
+                    | UnwinderError::DwarfCfiIndexCouldNotFindAddress
+                    | UnwinderError::NoModuleUnwindData)
+        );
+        if let (true, Some(companion)) = (is_uncovered, &module.debug_companion) {
+            // The module's own unwind data does not cover this address. Try the debug companion.
+            if let Some(fde_offset) = companion
+                .index
+                .fde_offset_for_relative_address(rel_lookup_address)
+            {
+                let mut dwarf_unwinder = DwarfUnwinder::<_, A, P::GimliStorage>::new(
+                    EndianReader::new(ArcData(companion.debug_frame.clone()), LittleEndian),
+                    UnwindSectionType::DebugFrame,
+                    None,
+                    &mut cache.gimli_unwind_context,
+                    &module.name,
+                    &companion.svma_info,
                 );
+                if let Some(unwind_result) = dwarf_unwinder.try_unwind_frame_with_fde(
+                    regs,
+                    is_first_frame,
+                    rel_lookup_address,
+                    fde_offset,
+                    read_stack,
+                )? {
+                    return Ok((unwind_result, UnwindMethod::DebugCompanion));
+                }
+            }
+        }
+        if is_first_frame
+            && !matches!(unwind_result, Ok(Some(_)))
+            && module.unwind_data.is_dwarf_cfi_or_none()
+        {
+            // The DWARF CFI does not cover this address or could not be used, for example
+            // because the function is hand-written assembly without CFI directives. If we
+            // are in a prologue or an epilogue, instruction analysis gives us a good rule.
+            if let Some(rule) = Self::rule_from_instruction_analysis(module, rel_lookup_address) {
+                return Ok((
+                    UnwindResult::ExecRule(rule),
+                    UnwindMethod::PrologueEpilogueAnalysis,
+                ));
+            }
+        }
+        unwind_result.map(Self::unwind_result_or_uncovered)
+    }
 
-                let unwind_result = unwinder.unwind_frame(rel_lookup_address, is_first_frame)?;
-                match unwind_result {
-                    CuiUnwindResult::ExecRule(rule) => UnwindResult::ExecRule(rule),
-                    CuiUnwindResult::NeedDwarf(fde_offset) => {
-                        let eh_frame_data = match eh_frame_data {
-                            Some(data) => ArcData(data.clone()),
-                            None => return Err(UnwinderError::NoDwarfData),
-                        };
-                        let mut dwarf_unwinder = DwarfUnwinder::<_, A, P::GimliStorage>::new(
-                            EndianReader::new(eh_frame_data, LittleEndian),
-                            UnwindSectionType::EhFrame,
+    /// Analyzes the instructions around the address, using the module's text bytes.
+    /// Returns a rule if the address is in a prologue or an epilogue.
+    ///
+    /// The analysis only looks at the bytes from the start of the function, as far as the
+    /// DWARF CFI tells us where it is, so that the prologue detection doesn't walk back
+    /// into the previous function.
+    fn rule_from_instruction_analysis(
+        module: &ModuleInternal<D, A::UnwindRule>,
+        rel_lookup_address: u32,
+    ) -> Option<A::UnwindRule> {
+        let text_data = module.text_data.as_ref()?;
+        let address = module
+            .base_avma
+            .checked_add(u64::from(rel_lookup_address))?;
+        let text_start = match Self::function_start_from_dwarf_cfi(module, rel_lookup_address)
+            .and_then(|start| module.base_avma.checked_add(u64::from(start)))
+        {
+            Some(function_start) => function_start.max(text_data.avma_range.start),
+            None => text_data.avma_range.start,
+        };
+        let bytes_offset =
+            usize::try_from(text_start.checked_sub(text_data.avma_range.start)?).ok()?;
+        let text_bytes = text_data.bytes.get(bytes_offset..)?;
+        let pc_offset = usize::try_from(address.checked_sub(text_start)?).ok()?;
+        if pc_offset >= text_bytes.len() {
+            return None;
+        }
+        A::rule_from_instruction_analysis(text_bytes, pc_offset)
+    }
+
+    /// The start of the function which contains the address, or a lower bound for it,
+    /// according to the FDEs around the address in the module's DWARF CFI sections.
+    fn function_start_from_dwarf_cfi(
+        module: &ModuleInternal<D, A::UnwindRule>,
+        rel_lookup_address: u32,
+    ) -> Option<u32> {
+        let bound = |section_type, data: &[u8], svma_info, fde_offset: Option<u32>| {
+            dwarf::function_start_lower_bound::<A>(
+                section_type,
+                data,
+                svma_info,
+                fde_offset?,
+                rel_lookup_address,
+            )
+        };
+        let svma_info = &module.svma_info;
+        let module_bounds = match &module.unwind_data {
+            ModuleUnwindDataInternal::EhFrameHdrAndEhFrame(eh_frame_hdr, eh_frame) => [
+                bound(
+                    UnwindSectionType::EhFrame,
+                    eh_frame,
+                    svma_info,
+                    dwarf::eh_frame_hdr_fde_offset::<A>(
+                        eh_frame_hdr,
+                        svma_info,
+                        rel_lookup_address,
+                    ),
+                ),
+                None,
+            ],
+            ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrame(index, eh_frame) => [
+                bound(
+                    UnwindSectionType::EhFrame,
+                    eh_frame,
+                    svma_info,
+                    index.fde_offset_for_relative_address(rel_lookup_address),
+                ),
+                None,
+            ],
+            ModuleUnwindDataInternal::DwarfCfiIndexAndDebugFrame(index, debug_frame) => [
+                bound(
+                    UnwindSectionType::DebugFrame,
+                    debug_frame,
+                    svma_info,
+                    index.fde_offset_for_relative_address(rel_lookup_address),
+                ),
+                None,
+            ],
+            ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrameAndDebugFrame(
+                eh_frame_index,
+                eh_frame,
+                debug_frame_index,
+                debug_frame,
+            ) => [
+                bound(
+                    UnwindSectionType::EhFrame,
+                    eh_frame,
+                    svma_info,
+                    eh_frame_index.fde_offset_for_relative_address(rel_lookup_address),
+                ),
+                bound(
+                    UnwindSectionType::DebugFrame,
+                    debug_frame,
+                    svma_info,
+                    debug_frame_index.fde_offset_for_relative_address(rel_lookup_address),
+                ),
+            ],
+            _ => [None, None],
+        };
+        let companion_bound = module.debug_companion.as_ref().and_then(|companion| {
+            bound(
+                UnwindSectionType::DebugFrame,
+                &companion.debug_frame,
+                &companion.svma_info,
+                companion
+                    .index
+                    .fde_offset_for_relative_address(rel_lookup_address),
+            )
+        });
+        module_bounds
+            .into_iter()
+            .chain([companion_bound])
+            .flatten()
+            .max()
+    }
+
+    /// Whether `address` is in the Linux signal return trampoline, according to the
+    /// module's text bytes. This is checked with the address itself, not with the lookup
+    /// address, because a signal handler returns to the start of the trampoline.
+    fn is_linux_sigreturn_trampoline(
+        module: &ModuleInternal<D, A::UnwindRule>,
+        address: u64,
+    ) -> bool {
+        let Some(text_data) = module.text_data.as_ref() else {
+            return false;
+        };
+        let Some(pc_offset) = address
+            .checked_sub(text_data.avma_range.start)
+            .and_then(|offset| usize::try_from(offset).ok())
+        else {
+            return false;
+        };
+        pc_offset < text_data.bytes.len()
+            && A::is_linux_sigreturn_trampoline(&text_data.bytes, pc_offset)
+    }
+
+    fn unwind_result_or_uncovered(
+        unwind_result: Option<UnwindResultWithMethod<A::UnwindRule>>,
+    ) -> UnwindResultWithMethod<A::UnwindRule> {
+        unwind_result.unwrap_or((
+            UnwindResult::ExecRule(A::rule_if_uncovered_by_fde()),
+            UnwindMethod::UncoveredByFde,
+        ))
+    }
+
+    fn compact_unwind_info_unwinder<'a>(
+        module: &'a ModuleInternal<D, A::UnwindRule>,
+        unwind_data: &'a [u8],
+    ) -> CompactUnwindInfoUnwinder<'a, A> {
+        let text_bytes = module.text_data.as_ref().and_then(|data| {
+            let offset_from_base =
+                u32::try_from(data.avma_range.start.checked_sub(module.base_avma)?).ok()?;
+            Some(TextBytes::new(offset_from_base, &data.bytes[..]))
+        });
+        let stubs_range = if let Some(stubs_range) = &module.svma_info.stubs {
+            (
+                (stubs_range.start - module.svma_info.base_svma) as u32,
+                (stubs_range.end - module.svma_info.base_svma) as u32,
+            )
+        } else {
+            (0, 0)
+        };
+        let stub_helper_range = if let Some(stub_helper_range) = &module.svma_info.stub_helper {
+            (
+                (stub_helper_range.start - module.svma_info.base_svma) as u32,
+                (stub_helper_range.end - module.svma_info.base_svma) as u32,
+            )
+        } else {
+            (0, 0)
+        };
+        CompactUnwindInfoUnwinder::<A>::new(unwind_data, text_bytes, stubs_range, stub_helper_range)
+    }
+
+    /// Unwind using the module's own unwind data. Returns `Ok(None)` if the module's DWARF
+    /// CFI does not cover the address.
+    fn unwind_frame_with_unwind_data<F>(
+        module: &ModuleInternal<D, A::UnwindRule>,
+        is_first_frame: bool,
+        rel_lookup_address: u32,
+        regs: &mut A::UnwindRegs,
+        cache: &mut Cache<D, A::UnwindRule, P>,
+        read_stack: &mut F,
+    ) -> Result<Option<UnwindResultWithMethod<A::UnwindRule>>, UnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        if let Some(rule) = module.flattened_rule(rel_lookup_address) {
+            return Ok(Some((
+                UnwindResult::ExecRule(rule),
+                UnwindMethod::FlattenedRuleTable,
+            )));
+        }
+        let unwind_result = match &module.unwind_data {
+            ModuleUnwindDataInternal::CompactUnwindInfoAndEhFrame(unwind_data, eh_frame_data) => {
+                let mut unwinder = Self::compact_unwind_info_unwinder(module, unwind_data);
+                let unwind_result = unwinder.unwind_frame(rel_lookup_address, is_first_frame)?;
+                match unwind_result {
+                    CuiUnwindResult::ExecRule(rule) => Some((
+                        UnwindResult::ExecRule(rule),
+                        UnwindMethod::CompactUnwindInfo,
+                    )),
+                    CuiUnwindResult::ExecRuleFromInstructionAnalysis(rule) => Some((
+                        UnwindResult::ExecRule(rule),
+                        UnwindMethod::PrologueEpilogueAnalysis,
+                    )),
+                    CuiUnwindResult::NeedDwarf(fde_offset) => {
+                        let eh_frame_data = match eh_frame_data {
+                            Some(data) => ArcData(data.clone()),
+                            None => return Err(UnwinderError::NoDwarfData),
+                        };
+                        let mut dwarf_unwinder = DwarfUnwinder::<_, A, P::GimliStorage>::new(
+                            EndianReader::new(eh_frame_data, LittleEndian),
+                            UnwindSectionType::EhFrame,
                             None,
                             &mut cache.gimli_unwind_context,
+                            &module.name,
                             &module.svma_info,
                         );
-                        dwarf_unwinder.unwind_frame_with_fde(
+                        let unwind_result = dwarf_unwinder.unwind_frame_with_fde(
                             regs,
                             is_first_frame,
                             rel_lookup_address,
                             fde_offset,
                             read_stack,
-                        )?
+                        )?;
+                        Some((unwind_result, UnwindMethod::CompactUnwindInfoDwarf))
                     }
                 }
             }
@@ -449,18 +1363,21 @@ impl<
                     UnwindSectionType::EhFrame,
                     Some(eh_frame_hdr_data),
                     &mut cache.gimli_unwind_context,
+                    &module.name,
                     &module.svma_info,
                 );
                 let fde_offset = dwarf_unwinder
                     .get_fde_offset_for_relative_address(rel_lookup_address)
                     .ok_or(UnwinderError::EhFrameHdrCouldNotFindAddress)?;
-                dwarf_unwinder.unwind_frame_with_fde(
-                    regs,
-                    is_first_frame,
-                    rel_lookup_address,
-                    fde_offset,
-                    read_stack,
-                )?
+                dwarf_unwinder
+                    .try_unwind_frame_with_fde(
+                        regs,
+                        is_first_frame,
+                        rel_lookup_address,
+                        fde_offset,
+                        read_stack,
+                    )?
+                    .map(|unwind_result| (unwind_result, UnwindMethod::EhFrameHdr))
             }
             ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrame(index, eh_frame_data) => {
                 let eh_frame_data = ArcData(eh_frame_data.clone());
@@ -469,18 +1386,21 @@ impl<
                     UnwindSectionType::EhFrame,
                     None,
                     &mut cache.gimli_unwind_context,
+                    &module.name,
                     &module.svma_info,
                 );
                 let fde_offset = index
                     .fde_offset_for_relative_address(rel_lookup_address)
                     .ok_or(UnwinderError::DwarfCfiIndexCouldNotFindAddress)?;
-                dwarf_unwinder.unwind_frame_with_fde(
-                    regs,
-                    is_first_frame,
-                    rel_lookup_address,
-                    fde_offset,
-                    read_stack,
-                )?
+                dwarf_unwinder
+                    .try_unwind_frame_with_fde(
+                        regs,
+                        is_first_frame,
+                        rel_lookup_address,
+                        fde_offset,
+                        read_stack,
+                    )?
+                    .map(|unwind_result| (unwind_result, UnwindMethod::DwarfCfiIndex))
             }
             ModuleUnwindDataInternal::DwarfCfiIndexAndDebugFrame(index, debug_frame_data) => {
                 let debug_frame_data = ArcData(debug_frame_data.clone());
@@ -489,22 +1409,442 @@ impl<
                     UnwindSectionType::DebugFrame,
                     None,
                     &mut cache.gimli_unwind_context,
+                    &module.name,
                     &module.svma_info,
                 );
                 let fde_offset = index
                     .fde_offset_for_relative_address(rel_lookup_address)
                     .ok_or(UnwinderError::DwarfCfiIndexCouldNotFindAddress)?;
-                dwarf_unwinder.unwind_frame_with_fde(
-                    regs,
-                    is_first_frame,
+                dwarf_unwinder
+                    .try_unwind_frame_with_fde(
+                        regs,
+                        is_first_frame,
+                        rel_lookup_address,
+                        fde_offset,
+                        read_stack,
+                    )?
+                    .map(|unwind_result| (unwind_result, UnwindMethod::DwarfCfiIndex))
+            }
+            ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrameAndDebugFrame(
+                eh_frame_index,
+                eh_frame_data,
+                debug_frame_index,
+                debug_frame_data,
+            ) => {
+                // .debug_frame is checked first. When both sections cover an address,
+                // .debug_frame is usually the more complete one, because .eh_frame may
+                // only be accurate at call sites.
+                let sections = [
+                    (
+                        debug_frame_index,
+                        debug_frame_data,
+                        UnwindSectionType::DebugFrame,
+                    ),
+                    (eh_frame_index, eh_frame_data, UnwindSectionType::EhFrame),
+                ];
+                let mut unwind_result = None;
+                for (index, data, section_type) in sections {
+                    let fde_offset = match index.fde_offset_for_relative_address(rel_lookup_address)
+                    {
+                        Some(fde_offset) => fde_offset,
+                        None => continue,
+                    };
+                    let mut dwarf_unwinder = DwarfUnwinder::<_, A, P::GimliStorage>::new(
+                        EndianReader::new(ArcData(data.clone()), LittleEndian),
+                        section_type,
+                        None,
+                        &mut cache.gimli_unwind_context,
+                        &module.name,
+                        &module.svma_info,
+                    );
+                    unwind_result = dwarf_unwinder.try_unwind_frame_with_fde(
+                        regs,
+                        is_first_frame,
+                        rel_lookup_address,
+                        fde_offset,
+                        read_stack,
+                    )?;
+                    if unwind_result.is_some() {
+                        break;
+                    }
+                }
+                unwind_result.map(|unwind_result| (unwind_result, UnwindMethod::DwarfCfiIndex))
+            }
+            ModuleUnwindDataInternal::ArmExidx(exidx_data, extab_data) => {
+                let exidx_svma = match &module.svma_info.arm_exidx {
+                    Some(range) => range.start,
+                    None => return Err(UnwinderError::NoArmExidxAddress),
+                };
+                let extab_svma = module
+                    .svma_info
+                    .arm_extab
+                    .as_ref()
+                    .map_or(0, |range| range.start);
+                let unwinder = ArmExidxUnwinder::<A>::new(
+                    &exidx_data[..],
+                    extab_data.as_deref(),
+                    exidx_svma,
+                    extab_svma,
+                    module.svma_info.base_svma,
+                );
+                let unwind_result =
+                    unwinder.unwind_frame(rel_lookup_address, is_first_frame, regs, read_stack)?;
+                Some((unwind_result, UnwindMethod::ArmExidx))
+            }
+            ModuleUnwindDataInternal::PeUnwindInfo(pdata, xdata) => {
+                let xdata_rva = match &module.svma_info.xdata {
+                    Some(range) => range
+                        .start
+                        .checked_sub(module.svma_info.base_svma)
+                        .and_then(|rva| u32::try_from(rva).ok())
+                        .ok_or(UnwinderError::PeXdataAddressOutOfRange(range.start))?,
+                    None => return Err(UnwinderError::NoPeXdataAddress),
+                };
+                let text_bytes = module.text_data.as_ref().and_then(|data| {
+                    let offset_from_base =
+                        u32::try_from(data.avma_range.start.checked_sub(module.base_avma)?).ok()?;
+                    Some(TextBytes::new(offset_from_base, &data.bytes[..]))
+                });
+                let unwinder = PeUnwinder::<A>::new(&pdata[..], &xdata[..], xdata_rva, text_bytes);
+                let unwind_result =
+                    unwinder.unwind_frame(rel_lookup_address, is_first_frame, regs, read_stack)?;
+                Some((unwind_result, UnwindMethod::PeUnwindInfo))
+            }
+            ModuleUnwindDataInternal::Orc(orc_unwind_ip, orc_unwind) => {
+                let orc_unwind_ip_rel_address = match &module.svma_info.orc_unwind_ip {
+                    Some(range) => range
+                        .start
+                        .checked_sub(module.svma_info.base_svma)
+                        .and_then(|rel_address| u32::try_from(rel_address).ok())
+                        .ok_or(UnwinderError::OrcUnwindIpAddressOutOfRange(range.start))?,
+                    None => return Err(UnwinderError::NoOrcUnwindIpAddress),
+                };
+                let unwinder = OrcUnwinder::<A>::new(
+                    &orc_unwind_ip[..],
+                    &orc_unwind[..],
+                    orc_unwind_ip_rel_address,
+                );
+                let unwind_result =
+                    unwinder.unwind_frame(rel_lookup_address, is_first_frame, regs, read_stack)?;
+                Some((unwind_result, UnwindMethod::Orc))
+            }
+            ModuleUnwindDataInternal::BreakpadSym(index, sym_data) => {
+                let unwinder = BreakpadUnwinder::<A>::new(&sym_data[..], index);
+                let unwind_result =
+                    unwinder.unwind_frame(rel_lookup_address, is_first_frame, regs, read_stack)?;
+                Some((unwind_result, UnwindMethod::Breakpad))
+            }
+            ModuleUnwindDataInternal::None => return Err(UnwinderError::NoModuleUnwindData),
+        };
+        Ok(unwind_result)
+    }
+}
+
+impl<
+        D: Deref<Target = [u8]>,
+        A: Arch
+            + DwarfUnwinding
+            + CompactUnwindInfoUnwinding
+            + ArmExidxUnwinding
+            + PeUnwinding
+            + OrcUnwinding
+            + StackScanning
+            + CallInstructionAnalysis
+            + BreakpadUnwinding
+            + InstructionAnalysis
+            + FullRegsUnwinding,
+        P: AllocationPolicy<D>,
+    > UnwinderInternal<D, A, P>
+{
+    /// Unwind a single frame in full register unwinding mode. The regular unwind registers
+    /// are unwound exactly like in `unwind_frame`. Afterwards, the callee-saved registers
+    /// are recovered from the DWARF CFI or compact unwind info of the frame's function.
+    /// If they cannot be recovered, they become unknown.
+    pub fn unwind_frame_full<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut A::FullUnwindRegs,
+        cache: &mut Cache<D, A::UnwindRule, P>,
+        read_stack: &mut F,
+    ) -> Result<Option<u64>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        let old_regs = regs.clone();
+        let read_error = Cell::new(None);
+        let mut read_stack = Self::recording_read_errors(read_stack, &read_error);
+        let mut is_signal_frame = false;
+        let return_address = self
+            .with_cache(
+                address,
+                A::unwind_regs_mut(regs),
+                cache,
+                &mut read_stack,
+                Self::unwind_frame_impl,
+                |_, _, signal_frame, _, _| {
+                    is_signal_frame = signal_frame;
+                },
+            )
+            .map_err(|err| err.with_stack_read_error(read_error.get()))?;
+        if return_address.is_none() {
+            return Ok(None);
+        }
+
+        let cfa = A::cfa_after_unwinding(regs);
+        let values = A::callee_saved_values_mut(regs);
+        if is_signal_frame {
+            // The interrupted code's callee-saved registers are in the signal frame,
+            // and they are not read from there.
+            values.fill(None);
+            return Ok(return_address);
+        }
+        let recovered = match self.find_module_for_address(address.address_for_lookup()) {
+            Some((module_index, relative_lookup_address)) => Self::recover_callee_saved_regs(
+                &self.modules[module_index],
+                address,
+                relative_lookup_address,
+                &old_regs,
+                cfa,
+                values,
+                cache,
+                &mut read_stack,
+            ),
+            None => Ok(false),
+        };
+        if recovered != Ok(true) {
+            values.fill(None);
+        }
+        Ok(return_address)
+    }
+
+    /// Writes the caller's values of `A::CALLEE_SAVED_REGS` into `values`, based on the
+    /// register values `old_regs` of the frame that is being unwound. Returns `Ok(false)`
+    /// if the module's unwind data does not describe the callee-saved registers at this
+    /// address.
+    #[allow(clippy::too_many_arguments)]
+    fn recover_callee_saved_regs<F>(
+        module: &ModuleInternal<D, A::UnwindRule>,
+        address: FrameAddress,
+        rel_lookup_address: u32,
+        old_regs: &A::FullUnwindRegs,
+        cfa: u64,
+        values: &mut [Option<u64>],
+        cache: &mut Cache<D, A::UnwindRule, P>,
+        read_stack: &mut F,
+    ) -> Result<bool, UnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let is_first_frame = !address.is_return_address();
+        let registers = A::CALLEE_SAVED_REGS;
+        let recovered = match &module.unwind_data {
+            ModuleUnwindDataInternal::CompactUnwindInfoAndEhFrame(unwind_data, eh_frame_data) => {
+                let mut unwinder = Self::compact_unwind_info_unwinder(module, unwind_data);
+                match unwinder.callee_saved_regs(rel_lookup_address, is_first_frame)? {
+                    CuiCalleeSavedResult::SameValue => {
+                        for (register, value) in registers.iter().zip(values.iter_mut()) {
+                            *value = old_regs.get(*register);
+                        }
+                        true
+                    }
+                    CuiCalleeSavedResult::Opcode(opcode) => {
+                        for (register, value) in registers.iter().zip(values.iter_mut()) {
+                            let rule = A::callee_saved_rule_for_cui_opcode(opcode, *register);
+                            *value = rule.exec(old_regs.get(*register), cfa, read_stack);
+                        }
+                        true
+                    }
+                    CuiCalleeSavedResult::NeedDwarf(fde_offset) => {
+                        let eh_frame_data = match eh_frame_data {
+                            Some(data) => ArcData(data.clone()),
+                            None => return Err(UnwinderError::NoDwarfData),
+                        };
+                        let mut dwarf_unwinder = DwarfUnwinder::<_, A, P::GimliStorage>::new(
+                            EndianReader::new(eh_frame_data, LittleEndian),
+                            UnwindSectionType::EhFrame,
+                            None,
+                            &mut cache.gimli_unwind_context,
+                            &module.name,
+                            &module.svma_info,
+                        );
+                        dwarf_unwinder.try_recover_registers_with_fde(
+                            old_regs,
+                            registers,
+                            values,
+                            rel_lookup_address,
+                            fde_offset,
+                            read_stack,
+                        )?
+                    }
+                }
+            }
+            ModuleUnwindDataInternal::EhFrameHdrAndEhFrame(eh_frame_hdr, eh_frame_data) => {
+                let mut dwarf_unwinder = DwarfUnwinder::<_, A, P::GimliStorage>::new(
+                    EndianReader::new(ArcData(eh_frame_data.clone()), LittleEndian),
+                    UnwindSectionType::EhFrame,
+                    Some(&eh_frame_hdr[..]),
+                    &mut cache.gimli_unwind_context,
+                    &module.name,
+                    &module.svma_info,
+                );
+                match dwarf_unwinder.get_fde_offset_for_relative_address(rel_lookup_address) {
+                    Some(fde_offset) => dwarf_unwinder.try_recover_registers_with_fde(
+                        old_regs,
+                        registers,
+                        values,
+                        rel_lookup_address,
+                        fde_offset,
+                        read_stack,
+                    )?,
+                    None => false,
+                }
+            }
+            ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrame(index, data) => {
+                let sections = [(index, data, UnwindSectionType::EhFrame, &module.svma_info)];
+                Self::recover_callee_saved_regs_with_dwarf_cfi_index(
+                    sections,
+                    &module.name,
                     rel_lookup_address,
-                    fde_offset,
+                    old_regs,
+                    values,
+                    cache,
                     read_stack,
                 )?
             }
-            ModuleUnwindDataInternal::None => return Err(UnwinderError::NoModuleUnwindData),
+            ModuleUnwindDataInternal::DwarfCfiIndexAndDebugFrame(index, data) => {
+                let sections = [(
+                    index,
+                    data,
+                    UnwindSectionType::DebugFrame,
+                    &module.svma_info,
+                )];
+                Self::recover_callee_saved_regs_with_dwarf_cfi_index(
+                    sections,
+                    &module.name,
+                    rel_lookup_address,
+                    old_regs,
+                    values,
+                    cache,
+                    read_stack,
+                )?
+            }
+            ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrameAndDebugFrame(
+                eh_frame_index,
+                eh_frame_data,
+                debug_frame_index,
+                debug_frame_data,
+            ) => {
+                // Same order as in unwind_frame_with_unwind_data.
+                let sections = [
+                    (
+                        debug_frame_index,
+                        debug_frame_data,
+                        UnwindSectionType::DebugFrame,
+                        &module.svma_info,
+                    ),
+                    (
+                        eh_frame_index,
+                        eh_frame_data,
+                        UnwindSectionType::EhFrame,
+                        &module.svma_info,
+                    ),
+                ];
+                Self::recover_callee_saved_regs_with_dwarf_cfi_index(
+                    sections,
+                    &module.name,
+                    rel_lookup_address,
+                    old_regs,
+                    values,
+                    cache,
+                    read_stack,
+                )?
+            }
+            ModuleUnwindDataInternal::ArmExidx(..)
+            | ModuleUnwindDataInternal::PeUnwindInfo(..)
+            | ModuleUnwindDataInternal::Orc(..)
+            | ModuleUnwindDataInternal::BreakpadSym(..) => return Ok(false),
+            ModuleUnwindDataInternal::None => false,
         };
-        Ok(unwind_result)
+        let recovered = match (recovered, &module.debug_companion) {
+            (false, Some(companion)) => {
+                let sections = [(
+                    &companion.index,
+                    &companion.debug_frame,
+                    UnwindSectionType::DebugFrame,
+                    &companion.svma_info,
+                )];
+                Self::recover_callee_saved_regs_with_dwarf_cfi_index(
+                    sections,
+                    &module.name,
+                    rel_lookup_address,
+                    old_regs,
+                    values,
+                    cache,
+                    read_stack,
+                )?
+            }
+            _ => recovered,
+        };
+        if !recovered
+            && is_first_frame
+            && module.unwind_data.is_dwarf_cfi_or_none()
+            && Self::rule_from_instruction_analysis(module, rel_lookup_address).is_some()
+        {
+            // In a prologue or epilogue, the callee-saved registers are either not saved
+            // yet or already restored.
+            for (register, value) in registers.iter().zip(values.iter_mut()) {
+                *value = old_regs.get(*register);
+            }
+            return Ok(true);
+        }
+        Ok(recovered)
+    }
+
+    /// Recovers the callee-saved registers with the first of the `sections` which covers
+    /// the lookup address.
+    fn recover_callee_saved_regs_with_dwarf_cfi_index<F, const N: usize>(
+        sections: [(
+            &DwarfCfiIndex<D>,
+            &Arc<D>,
+            UnwindSectionType,
+            &ModuleSvmaInfo,
+        ); N],
+        module_name: &str,
+        rel_lookup_address: u32,
+        old_regs: &A::FullUnwindRegs,
+        values: &mut [Option<u64>],
+        cache: &mut Cache<D, A::UnwindRule, P>,
+        read_stack: &mut F,
+    ) -> Result<bool, UnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        for (index, data, section_type, svma_info) in sections {
+            let fde_offset = match index.fde_offset_for_relative_address(rel_lookup_address) {
+                Some(fde_offset) => fde_offset,
+                None => continue,
+            };
+            let mut dwarf_unwinder = DwarfUnwinder::<_, A, P::GimliStorage>::new(
+                EndianReader::new(ArcData(data.clone()), LittleEndian),
+                section_type,
+                None,
+                &mut cache.gimli_unwind_context,
+                module_name,
+                svma_info,
+            );
+            if dwarf_unwinder.try_recover_registers_with_fde(
+                old_regs,
+                A::CALLEE_SAVED_REGS,
+                values,
+                rel_lookup_address,
+                fde_offset,
+                read_stack,
+            )? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
     }
 }
 
@@ -536,6 +1876,59 @@ pub enum ModuleUnwindData<D: Deref<Target = [u8]>> {
     /// DWARF CFI. We create a binary index for the FDEs when a module with this unwind
     /// data type is added.
     DebugFrame(D),
+    /// Used with ELF binaries which have both `.eh_frame` and `.debug_frame`, for example
+    /// binaries built with `-fno-asynchronous-unwind-tables` that contain hand-written
+    /// assembly, where some functions are only described in one of the two sections.
+    /// The first member is `.eh_frame`, the second is `.debug_frame`. We create a binary
+    /// index for the FDEs of both sections when a module with this unwind data type is
+    /// added. For each address, `.debug_frame` is consulted first, and `.eh_frame` is used
+    /// if `.debug_frame` does not cover the address.
+    EhFrameAndDebugFrame(D, D),
+    /// Used with 32-bit ARM ELF binaries (Android and Linux), in the `.ARM.exidx` and
+    /// `.ARM.extab` sections. Contains ARM EHABI unwind information. `.ARM.exidx` is a
+    /// sorted table with one entry per function; small functions have their unwind
+    /// opcodes inline in the table and the rest refer to `.ARM.extab`.
+    ///
+    /// The SVMA of `.ARM.exidx` needs to be supplied in [`ModuleSvmaInfo::arm_exidx`],
+    /// and the SVMA of `.ARM.extab` in [`ModuleSvmaInfo::arm_extab`].
+    ArmExidx(D, Option<D>),
+    /// Used with Windows x86_64 and aarch64 PE binaries, in the `.pdata` and `.xdata`
+    /// sections. `.pdata` contains a sorted table of RUNTIME_FUNCTION entries, which refer
+    /// to unwind info in `.xdata`. On aarch64, most `.pdata` entries contain packed unwind
+    /// data and don't refer to `.xdata` at all. Some linkers put the unwind info into `.rdata`
+    /// instead; in that case, supply the `.rdata` section as the second member.
+    ///
+    /// The SVMA of the section containing the unwind info needs to be supplied in
+    /// [`ModuleSvmaInfo::xdata`], and [`ModuleSvmaInfo::base_svma`] needs to be the
+    /// image base, because the addresses in these sections are relative to it.
+    PeUnwindInfo(D, D),
+    /// Used with the Linux kernel (`vmlinux`) and kernel modules on x86_64, in the
+    /// `.orc_unwind_ip` and `.orc_unwind` sections. `.orc_unwind_ip` is a sorted table of
+    /// place-relative instruction addresses, and `.orc_unwind` has one entry for each of
+    /// them, which describes how to find the caller's stack pointer and frame pointer.
+    ///
+    /// The SVMA of `.orc_unwind_ip` needs to be supplied in [`ModuleSvmaInfo::orc_unwind_ip`].
+    /// Addresses relative to [`ModuleSvmaInfo::base_svma`] need to fit into 32 bits, so for
+    /// `vmlinux`, the base SVMA should be the start of the kernel text rather than zero.
+    ///
+    /// Only the `orc_entry` layout of Linux 6.4 and later is supported. Modules with the
+    /// older layout are detected, and their ORC data is not used.
+    Orc(D, D),
+    /// Used with Breakpad symbol files (`.sym`), for example when only the symbol files
+    /// of the binaries are available. Contains the text of the entire symbol file. Its
+    /// `STACK CFI` records are used on x86_64 and aarch64. We create a binary index for
+    /// the records when a module with this unwind data type is added.
+    ///
+    /// `STACK WIN` records only exist for 32-bit x86 code, which framehop has no unwinder
+    /// for. The x86_64 unwinder evaluates their program strings with `$eip`, `$esp` and
+    /// `$ebp` standing for `rip`, `rsp` and `rbp`. The programs assume 4-byte stack slots,
+    /// so this only gives correct results if `read_stack` returns the 32-bit values of a
+    /// 32-bit process. The aarch64 unwinder ignores `STACK WIN` records.
+    ///
+    /// The addresses in the symbol file are relative to the module's base address, so
+    /// the `base_avma` passed to [`Module::new`] needs to be the address at which the
+    /// module's base, as seen by Breakpad's `dump_syms`, was loaded.
+    BreakpadSym(D),
     /// No unwind information is used. Unwinding in this module will use a fallback rule
     /// (usually frame pointer unwinding).
     None,
@@ -544,52 +1937,208 @@ pub enum ModuleUnwindData<D: Deref<Target = [u8]>> {
 enum ModuleUnwindDataInternal<D: Deref<Target = [u8]>> {
     CompactUnwindInfoAndEhFrame(D, Option<Arc<D>>),
     EhFrameHdrAndEhFrame(D, Arc<D>),
-    DwarfCfiIndexAndEhFrame(DwarfCfiIndex, Arc<D>),
-    DwarfCfiIndexAndDebugFrame(DwarfCfiIndex, Arc<D>),
+    DwarfCfiIndexAndEhFrame(DwarfCfiIndex<D>, Arc<D>),
+    DwarfCfiIndexAndDebugFrame(DwarfCfiIndex<D>, Arc<D>),
+    DwarfCfiIndexAndEhFrameAndDebugFrame(DwarfCfiIndex<D>, Arc<D>, DwarfCfiIndex<D>, Arc<D>),
+    ArmExidx(D, Option<D>),
+    PeUnwindInfo(D, D),
+    Orc(D, D),
+    BreakpadSym(BreakpadIndex, D),
     None,
 }
 
 impl<D: Deref<Target = [u8]>> ModuleUnwindDataInternal<D> {
-    fn new(unwind_data: ModuleUnwindData<D>, svma_info: &ModuleSvmaInfo) -> Self {
+    /// Whether the unwind data is DWARF CFI in `.eh_frame` / `.debug_frame`, or absent.
+    /// These are the cases in which instruction analysis is used for addresses which the
+    /// unwind data does not cover.
+    fn is_dwarf_cfi_or_none(&self) -> bool {
+        matches!(
+            self,
+            ModuleUnwindDataInternal::EhFrameHdrAndEhFrame(..)
+                | ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrame(..)
+                | ModuleUnwindDataInternal::DwarfCfiIndexAndDebugFrame(..)
+                | ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrameAndDebugFrame(..)
+                | ModuleUnwindDataInternal::None
+        )
+    }
+
+    /// Creates the indexes for the unwind data and validates it. Problems are passed to
+    /// `report_error`. Unwind data which could not be indexed is dropped, but unwind data
+    /// which only failed validation is kept.
+    fn new<A: DwarfUnwinding>(
+        unwind_data: ModuleUnwindData<D>,
+        svma_info: &ModuleSvmaInfo,
+        [serialized_eh_frame_index, serialized_debug_frame_index]: [Option<D>; 2],
+        report_error: &mut dyn FnMut(ModuleError),
+    ) -> Self {
+        let eh_frame_index = move |eh_frame: &D, report_error: &mut dyn FnMut(ModuleError)| {
+            Self::dwarf_cfi_index::<A>(
+                UnwindSectionType::EhFrame,
+                eh_frame,
+                serialized_eh_frame_index,
+                svma_info,
+                report_error,
+            )
+        };
+        let debug_frame_index =
+            move |debug_frame: &D, report_error: &mut dyn FnMut(ModuleError)| {
+                Self::dwarf_cfi_index::<A>(
+                    UnwindSectionType::DebugFrame,
+                    debug_frame,
+                    serialized_debug_frame_index,
+                    svma_info,
+                    report_error,
+                )
+            };
         match unwind_data {
             ModuleUnwindData::CompactUnwindInfoAndEhFrame(cui, eh_frame) => {
+                match UnwindInfo::parse(&cui).and_then(|unwind_info| unwind_info.functions().next())
+                {
+                    Ok(Some(_)) => {}
+                    Ok(None) => report_error(ModuleError::NoFunctions),
+                    Err(err) => report_error(ModuleError::BadCompactUnwindInfo(err)),
+                }
                 ModuleUnwindDataInternal::CompactUnwindInfoAndEhFrame(cui, eh_frame.map(Arc::new))
             }
             ModuleUnwindData::EhFrameHdrAndEhFrame(eh_frame_hdr, eh_frame) => {
+                if let Err(err) = check_eh_frame_hdr::<A>(&eh_frame_hdr, svma_info) {
+                    report_error(ModuleError::BadEhFrameHdr(err));
+                }
                 ModuleUnwindDataInternal::EhFrameHdrAndEhFrame(eh_frame_hdr, Arc::new(eh_frame))
             }
-            ModuleUnwindData::EhFrame(eh_frame) => {
-                match DwarfCfiIndex::try_new_eh_frame(&eh_frame, svma_info) {
-                    Ok(index) => {
-                        ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrame(index, Arc::new(eh_frame))
-                    }
-                    Err(_) => ModuleUnwindDataInternal::None,
+            ModuleUnwindData::EhFrame(eh_frame) => match eh_frame_index(&eh_frame, report_error) {
+                Some(index) => {
+                    ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrame(index, Arc::new(eh_frame))
                 }
-            }
+                None => ModuleUnwindDataInternal::None,
+            },
             ModuleUnwindData::DebugFrame(debug_frame) => {
-                match DwarfCfiIndex::try_new_debug_frame(&debug_frame, svma_info) {
-                    Ok(index) => ModuleUnwindDataInternal::DwarfCfiIndexAndDebugFrame(
+                match debug_frame_index(&debug_frame, report_error) {
+                    Some(index) => ModuleUnwindDataInternal::DwarfCfiIndexAndDebugFrame(
                         index,
                         Arc::new(debug_frame),
                     ),
-                    Err(_) => ModuleUnwindDataInternal::None,
+                    None => ModuleUnwindDataInternal::None,
                 }
             }
+            ModuleUnwindData::EhFrameAndDebugFrame(eh_frame, debug_frame) => {
+                match (
+                    eh_frame_index(&eh_frame, report_error),
+                    debug_frame_index(&debug_frame, report_error),
+                ) {
+                    (Some(eh_frame_index), Some(debug_frame_index)) => {
+                        ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrameAndDebugFrame(
+                            eh_frame_index,
+                            Arc::new(eh_frame),
+                            debug_frame_index,
+                            Arc::new(debug_frame),
+                        )
+                    }
+                    (Some(index), None) => {
+                        ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrame(index, Arc::new(eh_frame))
+                    }
+                    (None, Some(index)) => ModuleUnwindDataInternal::DwarfCfiIndexAndDebugFrame(
+                        index,
+                        Arc::new(debug_frame),
+                    ),
+                    (None, None) => ModuleUnwindDataInternal::None,
+                }
+            }
+            ModuleUnwindData::ArmExidx(exidx, extab) => {
+                if exidx.is_empty() {
+                    report_error(ModuleError::NoFunctions);
+                }
+                ModuleUnwindDataInternal::ArmExidx(exidx, extab)
+            }
+            ModuleUnwindData::PeUnwindInfo(pdata, xdata) => {
+                if pdata.is_empty() {
+                    report_error(ModuleError::NoFunctions);
+                }
+                ModuleUnwindDataInternal::PeUnwindInfo(pdata, xdata)
+            }
+            ModuleUnwindData::Orc(orc_unwind_ip, orc_unwind) => {
+                if orc_unwind_ip.is_empty() {
+                    report_error(ModuleError::NoFunctions);
+                } else if orc_unwind_ip.len() / 4 != orc_unwind.len() / 6 {
+                    report_error(ModuleError::OrcEntryCountMismatch);
+                } else if orc::uses_pre_6_4_layout(&orc_unwind) {
+                    // The entries would be misread, for example CALL entries as UNDEFINED.
+                    report_error(ModuleError::UnsupportedOrcLayout);
+                    return ModuleUnwindDataInternal::None;
+                }
+                ModuleUnwindDataInternal::Orc(orc_unwind_ip, orc_unwind)
+            }
+            ModuleUnwindData::BreakpadSym(sym_data) => match BreakpadIndex::try_new(&sym_data) {
+                Ok(index) => {
+                    if index.is_empty() {
+                        report_error(ModuleError::NoFunctions);
+                    }
+                    ModuleUnwindDataInternal::BreakpadSym(index, sym_data)
+                }
+                Err(err) => {
+                    report_error(ModuleError::BreakpadIndex(err));
+                    ModuleUnwindDataInternal::None
+                }
+            },
             ModuleUnwindData::None => ModuleUnwindDataInternal::None,
         }
     }
+
+    /// Uses the serialized index if it matches the section, and creates the index from
+    /// the section otherwise.
+    fn dwarf_cfi_index<A: DwarfUnwinding>(
+        section_type: UnwindSectionType,
+        section_data: &D,
+        serialized_index: Option<D>,
+        svma_info: &ModuleSvmaInfo,
+        report_error: &mut dyn FnMut(ModuleError),
+    ) -> Option<DwarfCfiIndex<D>> {
+        let serialized_index = serialized_index.and_then(|data| {
+            DwarfCfiIndex::from_serialized(
+                data,
+                section_type,
+                section_data,
+                svma_info.base_svma,
+                A::ADDRESS_SIZE,
+            )
+            .map_err(|err| report_error(ModuleError::BadSerializedCfiIndex(err)))
+            .ok()
+        });
+        let index = match serialized_index {
+            Some(index) => index,
+            None => DwarfCfiIndex::try_new_for_section(
+                section_type,
+                section_data,
+                svma_info,
+                A::ADDRESS_SIZE,
+            )
+            .map_err(|err| {
+                report_error(match section_type {
+                    UnwindSectionType::EhFrame => ModuleError::EhFrameIndex(err),
+                    UnwindSectionType::DebugFrame => ModuleError::DebugFrameIndex(err),
+                })
+            })
+            .ok()?,
+        };
+        if index.is_empty() {
+            report_error(ModuleError::NoFunctions);
+        }
+        Some(index)
+    }
 }
 
 /// Used to supply raw instruction bytes to the unwinder, which uses it to analyze
 /// instructions in order to provide high quality unwinding inside function prologues and
 /// epilogues.
 ///
-/// This is only needed on macOS, because mach-O `__unwind_info` and `__eh_frame` only
+/// This is mostly needed on macOS, because mach-O `__unwind_info` and `__eh_frame` only
 /// cares about accuracy in function bodies, not in function prologues and epilogues.
 ///
 /// On Linux, compilers produce `.eh_frame` and `.debug_frame` which provides correct
 /// unwind information for all instructions including those in function prologues and
-/// epilogues, so instruction analysis is not needed.
+/// epilogues. But some code is not covered by this unwind information, for example
+/// hand-written assembly without CFI directives. If the first frame's address is in
+/// such code, instruction analysis is used to detect prologues and epilogues.
 ///
 /// Type arguments:
 ///
@@ -650,10 +2199,153 @@ pub struct Module<D: Deref<Target = [u8]>> {
     /// Information about various addresses in the module.
     svma_info: ModuleSvmaInfo,
     /// The unwind data that should be used for unwinding addresses from this module.
-    unwind_data: ModuleUnwindDataInternal<D>,
+    /// This is processed when the module is added to an unwinder.
+    unwind_data: ModuleUnwindData<D>,
     /// The raw assembly bytes of this module. Used for instruction analysis to ensure
     /// correct unwinding inside function prologues and epilogues.
     text_data: Option<TextByteData<D>>,
+    /// The `.debug_frame` section of a separate debug file for this module, and the
+    /// section addresses of that debug file. See [`Module::set_debug_companion`].
+    debug_companion: Option<(D, ModuleSvmaInfo)>,
+    /// Serialized indexes for `.eh_frame` and `.debug_frame`. See [`Module::set_cfi_index`].
+    eh_frame_index: Option<D>,
+    debug_frame_index: Option<D>,
+    /// See [`Module::set_flatten_unwind_rules`].
+    flatten_unwind_rules: bool,
+}
+
+/// A [`Module`] whose unwind data has been processed for a specific CPU architecture,
+/// for example by creating an index of the FDEs in `.eh_frame`.
+struct ModuleInternal<D: Deref<Target = [u8]>, R: UnwindRule> {
+    #[allow(unused)]
+    name: String,
+    avma_range: Range<u64>,
+    base_avma: u64,
+    svma_info: ModuleSvmaInfo,
+    unwind_data: ModuleUnwindDataInternal<D>,
+    text_data: Option<TextByteData<D>>,
+    debug_companion: Option<DebugCompanionInternal<D>>,
+    /// The flattened unwind rules of the module's DWARF CFI sections, in the order in
+    /// which the sections are consulted. Empty unless requested with
+    /// [`Module::set_flatten_unwind_rules`].
+    flat_rule_tables: Vec<FlatRuleTable<R>>,
+}
+
+/// The indexed `.debug_frame` of a module's separate debug file.
+struct DebugCompanionInternal<D: Deref<Target = [u8]>> {
+    index: DwarfCfiIndex<D>,
+    debug_frame: Arc<D>,
+    svma_info: ModuleSvmaInfo,
+}
+
+impl<D: Deref<Target = [u8]>, R: UnwindRule> ModuleInternal<D, R> {
+    /// Processes the module. Problems with the unwind data are passed to `report_error`.
+    fn new<A: DwarfUnwinding<UnwindRule = R>>(
+        module: Module<D>,
+        report_error: &mut dyn FnMut(ModuleError),
+    ) -> Self {
+        let Module {
+            name,
+            avma_range,
+            base_avma,
+            svma_info,
+            unwind_data,
+            text_data,
+            debug_companion,
+            eh_frame_index,
+            debug_frame_index,
+            flatten_unwind_rules,
+        } = module;
+        let unwind_data = ModuleUnwindDataInternal::new::<A>(
+            unwind_data,
+            &svma_info,
+            [eh_frame_index, debug_frame_index],
+            report_error,
+        );
+        let debug_companion = debug_companion.and_then(|(debug_frame, svma_info)| {
+            let index =
+                DwarfCfiIndex::try_new_debug_frame(&debug_frame, &svma_info, A::ADDRESS_SIZE)
+                    .map_err(|err| report_error(ModuleError::DebugCompanionIndex(err)))
+                    .ok()?;
+            Some(DebugCompanionInternal {
+                index,
+                debug_frame: Arc::new(debug_frame),
+                svma_info,
+            })
+        });
+        let flat_rule_tables = match flatten_unwind_rules {
+            true => Self::flatten_unwind_rules::<A>(&unwind_data, &svma_info),
+            false => Vec::new(),
+        };
+        Self {
+            name,
+            avma_range,
+            base_avma,
+            svma_info,
+            unwind_data,
+            text_data,
+            debug_companion,
+            flat_rule_tables,
+        }
+    }
+
+    /// Creates the flattened rule tables for the DWARF CFI sections. A section whose
+    /// table can't be created is skipped; its problem has already been reported when
+    /// its index was created.
+    fn flatten_unwind_rules<A: DwarfUnwinding<UnwindRule = R>>(
+        unwind_data: &ModuleUnwindDataInternal<D>,
+        svma_info: &ModuleSvmaInfo,
+    ) -> Vec<FlatRuleTable<R>> {
+        let sections: &[(UnwindSectionType, &Arc<D>)] = match unwind_data {
+            ModuleUnwindDataInternal::EhFrameHdrAndEhFrame(_, eh_frame)
+            | ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrame(_, eh_frame) => {
+                &[(UnwindSectionType::EhFrame, eh_frame)]
+            }
+            ModuleUnwindDataInternal::DwarfCfiIndexAndDebugFrame(_, debug_frame) => {
+                &[(UnwindSectionType::DebugFrame, debug_frame)]
+            }
+            ModuleUnwindDataInternal::DwarfCfiIndexAndEhFrameAndDebugFrame(
+                _,
+                eh_frame,
+                _,
+                debug_frame,
+            ) => &[
+                (UnwindSectionType::DebugFrame, debug_frame),
+                (UnwindSectionType::EhFrame, eh_frame),
+            ],
+            _ => &[],
+        };
+        sections
+            .iter()
+            .filter_map(|(section_type, data)| {
+                FlatRuleTable::try_new_for_section::<A>(*section_type, data, svma_info).ok()
+            })
+            .collect()
+    }
+
+    /// Converts a range of stated virtual memory addresses in this module to the range
+    /// at which it is loaded in the process.
+    fn avma_range_for_svma_range(&self, svma_range: &Range<u64>) -> Range<u64> {
+        let to_avma = |svma: u64| {
+            self.base_avma
+                .wrapping_add(svma.wrapping_sub(self.svma_info.base_svma))
+        };
+        to_avma(svma_range.start)..to_avma(svma_range.end)
+    }
+
+    /// Looks up the rule for the address in the flattened rule tables. Returns `None` if
+    /// the unwind data needs to be consulted, for example because the rule tables don't
+    /// have a rule for the address.
+    fn flattened_rule(&self, rel_lookup_address: u32) -> Option<R> {
+        for table in &self.flat_rule_tables {
+            match table.lookup(rel_lookup_address) {
+                FlatRuleTableEntry::Rule(rule) => return Some(rule),
+                FlatRuleTableEntry::NotRepresentable => return None,
+                FlatRuleTableEntry::Uncovered => {}
+            }
+        }
+        None
+    }
 }
 
 /// The addresses of various sections in the module.
@@ -666,10 +2358,15 @@ pub struct Module<D: Deref<Target = [u8]>> {
 /// or as relative addresses. For example, DWARF CFI can have code addresses expressed as
 /// relative-to-.text addresses or as absolute SVMAs. And mach-O compact unwind info
 /// contains addresses relative to the image base address.
-#[derive(Clone, Debug, PartialEq, Eq)]
+///
+/// Sections which the module doesn't have can be left out with `..Default::default()`.
+/// This also keeps the struct literal compiling when fields for more sections are added.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct ModuleSvmaInfo {
     /// The image base address, as stated in the object. For mach-O objects, this is the
-    /// vmaddr of the `__TEXT` segment. For ELF objects, this is zero.
+    /// vmaddr of the `__TEXT` segment. For ELF objects, this is zero, except for the Linux
+    /// kernel image, whose relative addresses wouldn't fit into 32 bits; for an image with
+    /// ORC unwind data, [`Module::from_object_file`] uses the start of `.text`.
     ///
     /// This is used to convert between SVMAs and relative addresses.
     pub base_svma: u64,
@@ -710,6 +2407,28 @@ pub struct ModuleSvmaInfo {
     /// The address range of the `.got` section (Global Offset Table). This is used
     /// during DWARF CFI processing, to resolve got-relative addresses.
     pub got: Option<Range<u64>>,
+    /// The address range of the `.ARM.exidx` section. This is used to resolve the
+    /// place-relative offsets in the exception index table of 32-bit ARM binaries.
+    pub arm_exidx: Option<Range<u64>>,
+    /// The address range of the `.ARM.extab` section. `.ARM.exidx` entries whose unwind
+    /// instructions don't fit into the entry itself point into this table. This is used
+    /// to find the instructions for such entries.
+    pub arm_extab: Option<Range<u64>>,
+    /// The address range of the section which contains the unwind info that the
+    /// `.pdata` entries of a PE binary refer to. This is usually `.xdata`.
+    pub xdata: Option<Range<u64>>,
+    /// The address range of the `.orc_unwind_ip` section of the Linux kernel or of a
+    /// kernel module. This is used to resolve the place-relative instruction addresses
+    /// in the ORC table.
+    pub orc_unwind_ip: Option<Range<u64>>,
+    /// The address ranges of the executable segments: the `PT_LOAD` program headers
+    /// with `PF_X` of an ELF binary, the segments with execute protection of a mach-O
+    /// binary, or the executable sections of a PE binary. Besides the text section, these
+    /// also cover code in `.init`, `.fini`, `.plt` and similar sections.
+    ///
+    /// This is used by [`ReturnAddressChecks::in_executable_range`]. If this is empty,
+    /// the text, `text_env`, stubs and stub helper sections are used instead.
+    pub executable_segments: Vec<Range<u64>>,
 }
 
 impl<D: Deref<Target = [u8]>> Module<D> {
@@ -721,7 +2440,6 @@ impl<D: Deref<Target = [u8]>> Module<D> {
         unwind_data: ModuleUnwindData<D>,
         text_data: Option<TextByteData<D>>,
     ) -> Self {
-        let unwind_data = ModuleUnwindDataInternal::new(unwind_data, &svma_info);
         Self {
             name,
             avma_range,
@@ -729,6 +2447,101 @@ impl<D: Deref<Target = [u8]>> Module<D> {
             svma_info,
             unwind_data,
             text_data,
+            debug_companion: None,
+            eh_frame_index: None,
+            debug_frame_index: None,
+            flatten_unwind_rules: false,
         }
     }
+
+    /// Attach the `.debug_frame` section of a separate debug file for this module, for
+    /// example of a file found via the module's build ID in
+    /// `/usr/lib/debug/.build-id/xx/yyyy.debug` or via its `.gnu_debuglink`.
+    ///
+    /// Stripped libraries often only keep `.eh_frame`, which doesn't always cover every
+    /// function. The debug companion is consulted for addresses which the module's own
+    /// DWARF CFI does not cover, or if the module has no unwind data at all.
+    ///
+    /// `svma_info` describes the section addresses in the debug file. Usually these are
+    /// the same as the module's, but they are supplied separately because only the debug
+    /// file's sections are known to be present in it. Framehop does not access the file
+    /// system; the caller is responsible for finding the debug file and supplying its bytes.
+    pub fn set_debug_companion(&mut self, debug_frame: D, svma_info: ModuleSvmaInfo) {
+        self.debug_companion = Some((debug_frame, svma_info));
+    }
+
+    /// Creates the index of the FDEs in the module's `.eh_frame` or `.debug_frame` section
+    /// and returns it in a compact, versioned byte format. Returns `Ok(None)` if the module
+    /// doesn't have this section, or if it doesn't need an index because the section is
+    /// accompanied by `.eh_frame_hdr`.
+    ///
+    /// Creating the index requires parsing every CIE and FDE in the section, which is what
+    /// happens when a module is added to an unwinder. For large libraries which are added
+    /// often, you can store the serialized index, for example keyed by the module's build ID,
+    /// and pass it to [`Module::set_cfi_index`] next time.
+    ///
+    /// `address_size` is the size of a pointer in bytes on the module's architecture: 8 on
+    /// x86_64 and aarch64, 4 on 32-bit ARM.
+    pub fn serialize_cfi_index(
+        &self,
+        section_type: UnwindSectionType,
+        address_size: u8,
+    ) -> Result<Option<Vec<u8>>, DwarfCfiIndexError> {
+        let section_data = match (section_type, &self.unwind_data) {
+            (UnwindSectionType::EhFrame, ModuleUnwindData::EhFrame(eh_frame))
+            | (UnwindSectionType::EhFrame, ModuleUnwindData::EhFrameAndDebugFrame(eh_frame, _)) => {
+                eh_frame
+            }
+            (UnwindSectionType::DebugFrame, ModuleUnwindData::DebugFrame(debug_frame))
+            | (
+                UnwindSectionType::DebugFrame,
+                ModuleUnwindData::EhFrameAndDebugFrame(_, debug_frame),
+            ) => debug_frame,
+            _ => return Ok(None),
+        };
+        let index = DwarfCfiIndex::<D>::try_new_for_section(
+            section_type,
+            section_data,
+            &self.svma_info,
+            address_size,
+        )?;
+        Ok(Some(index.serialize(
+            section_type,
+            section_data,
+            self.svma_info.base_svma,
+            address_size,
+        )))
+    }
+
+    /// Supply an index which was created by [`Module::serialize_cfi_index`], so that the
+    /// index doesn't need to be created when the module is added to an unwinder. The
+    /// serialized data is used in place, so `index` can be memory-mapped from a file.
+    ///
+    /// When the module is added, the index is checked against the section, the module's
+    /// base address and the unwinder's architecture. If it doesn't match, for example
+    /// because the library was updated, [`Unwinder::try_add_module`] rejects the module
+    /// with [`ModuleError::BadSerializedCfiIndex`]. [`Unwinder::add_module`] creates the
+    /// index from the section as usual instead, and only reports the problem as a
+    /// tracing event.
+    pub fn set_cfi_index(&mut self, section_type: UnwindSectionType, index: D) {
+        match section_type {
+            UnwindSectionType::EhFrame => self.eh_frame_index = Some(index),
+            UnwindSectionType::DebugFrame => self.debug_frame_index = Some(index),
+        }
+    }
+
+    /// Convert the module's DWARF CFI into a sorted table of unwind rules when the module
+    /// is added, instead of evaluating the CFI when an address is unwound.
+    ///
+    /// Finding the rule for an address is then a single binary search, so that a miss in
+    /// the unwinder cache is almost as cheap as a hit. In exchange, adding the module takes
+    /// longer, because the CFI instructions of every FDE are evaluated, and the table takes
+    /// memory for every row which has a different rule than the previous row. Rows which
+    /// can't be expressed as an unwind rule are evaluated at unwind time as usual.
+    ///
+    /// This applies to `.eh_frame` and `.debug_frame`. `__unwind_info` doesn't need it,
+    /// because its opcodes are already looked up with a binary search.
+    pub fn set_flatten_unwind_rules(&mut self, flatten: bool) {
+        self.flatten_unwind_rules = flatten;
+    }
 }
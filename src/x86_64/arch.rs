@@ -1,6 +1,7 @@
 use super::unwind_rule::UnwindRuleX86_64;
 use super::unwindregs::UnwindRegsX86_64;
 use crate::arch::Arch;
+use crate::ehabi::ArmExidxUnwinding;
 
 /// The x86_64 CPU architecture.
 pub struct ArchX86_64;
@@ -8,3 +9,7 @@ impl Arch for ArchX86_64 {
     type UnwindRule = UnwindRuleX86_64;
     type UnwindRegs = UnwindRegsX86_64;
 }
+
+// Unwind data formats which are not used on x86_64. See the traits for what the default
+// implementations do.
+impl ArmExidxUnwinding for ArchX86_64 {}
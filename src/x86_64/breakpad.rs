@@ -0,0 +1,174 @@
+use super::arch::ArchX86_64;
+use super::unwind_rule::UnwindRuleX86_64;
+use super::unwindregs::UnwindRegsX86_64;
+use crate::breakpad::{
+    BreakpadCfiRecord, BreakpadStackRecord, BreakpadUnwinderError, BreakpadUnwinding, SimpleRule,
+};
+use crate::unwind_result::UnwindResult;
+
+/// Look up a register by its Breakpad name. `STACK WIN` programs describe 32-bit x86
+/// code and use the 32-bit register names, which we map to the 64-bit registers.
+fn register_value(regs: &UnwindRegsX86_64, name: &str) -> Option<u64> {
+    match name {
+        "$rip" | "$eip" => Some(regs.ip()),
+        "$rsp" | "$esp" => Some(regs.sp()),
+        "$rbp" | "$ebp" => Some(regs.bp()),
+        _ => None,
+    }
+}
+
+impl BreakpadUnwinding for ArchX86_64 {
+    fn unwind_frame<F>(
+        record: Option<BreakpadStackRecord>,
+        is_first_frame: bool,
+        regs: &mut UnwindRegsX86_64,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<UnwindRuleX86_64>, BreakpadUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let (return_address, new_sp, new_bp) = match record {
+            None => {
+                return Ok(UnwindResult::ExecRule(
+                    UnwindRuleX86_64::JustReturnIfFirstFrameOtherwiseFp,
+                ))
+            }
+            Some(BreakpadStackRecord::Cfi(record)) => {
+                if let Some(rule) = translate_into_unwind_rule(&record) {
+                    return Ok(UnwindResult::ExecRule(rule));
+                }
+
+                // Could not translate into a cacheable unwind rule. Evaluate the rules
+                // directly.
+                let register = |name: &str| register_value(regs, name);
+                let cfa = record
+                    .evaluate_rule(".cfa", None, register, read_stack)?
+                    .ok_or(BreakpadUnwinderError::NoCfaRule)?;
+                let return_address = record
+                    .evaluate_rule(".ra", Some(cfa), register, read_stack)?
+                    .ok_or(BreakpadUnwinderError::NoReturnAddressRule)?;
+                let new_bp = record
+                    .evaluate_rule("$rbp", Some(cfa), register, read_stack)?
+                    .unwrap_or(regs.bp());
+                (return_address, cfa, new_bp)
+            }
+            Some(BreakpadStackRecord::Win(record)) => {
+                let variables =
+                    record.evaluate_program(|name| register_value(regs, name), read_stack)?;
+                let return_address = variables
+                    .get("$eip")
+                    .ok_or(BreakpadUnwinderError::ProgramDidNotSetRegister("$eip"))?;
+                let new_sp = variables
+                    .get("$esp")
+                    .ok_or(BreakpadUnwinderError::ProgramDidNotSetRegister("$esp"))?;
+                let new_bp = variables.get("$ebp").unwrap_or(regs.bp());
+                (return_address, new_sp, new_bp)
+            }
+        };
+
+        if new_sp == regs.sp() && return_address == regs.ip() {
+            return Err(BreakpadUnwinderError::DidNotAdvance);
+        }
+        if !is_first_frame && new_sp < regs.sp() {
+            return Err(BreakpadUnwinderError::StackPointerMovedBackwards);
+        }
+        regs.set_ip(return_address);
+        regs.set_sp(new_sp);
+        regs.set_bp(new_bp);
+        Ok(UnwindResult::Uncacheable(return_address))
+    }
+}
+
+fn translate_into_unwind_rule(record: &BreakpadCfiRecord) -> Option<UnwindRuleX86_64> {
+    if record.simple_rule(".ra") != SimpleRule::LoadFromCfaPlusOffset(-8) {
+        return None;
+    }
+    let bp_rule = record.simple_rule("$rbp");
+    match record.simple_rule(".cfa") {
+        SimpleRule::RegisterPlusOffset("$rsp", offset) if offset % 8 == 0 => {
+            let sp_offset_by_8 = u16::try_from(offset / 8).ok()?;
+            match bp_rule {
+                SimpleRule::Missing | SimpleRule::RegisterPlusOffset("$rbp", 0) => {
+                    Some(UnwindRuleX86_64::OffsetSp { sp_offset_by_8 })
+                }
+                SimpleRule::LoadFromCfaPlusOffset(bp_offset) if bp_offset % 8 == 0 => {
+                    Some(UnwindRuleX86_64::OffsetSpAndRestoreBp {
+                        sp_offset_by_8,
+                        bp_storage_offset_from_sp_by_8: i16::try_from((offset + bp_offset) / 8)
+                            .ok()?,
+                    })
+                }
+                _ => None,
+            }
+        }
+        SimpleRule::RegisterPlusOffset("$rbp", 16)
+            if bp_rule == SimpleRule::LoadFromCfaPlusOffset(-16) =>
+        {
+            Some(UnwindRuleX86_64::UseFramePointer)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::breakpad::BreakpadIndex;
+
+    const SYM: &[u8] = b"MODULE Linux x86_64 0123456789ABCDEF0123456789ABCDEF0 libtest.so
+STACK CFI INIT 1000 40 .cfa: $rsp 8 + .ra: .cfa -8 + ^
+STACK CFI 1001 .cfa: $rsp 16 + $rbp: .cfa -16 + ^
+STACK CFI 1004 .cfa: $rbp 16 +
+STACK CFI 1030 .cfa: $rsp 8 + $rbp: $rbp
+STACK CFI INIT 2000 10 .cfa: $rsp 8 + .ra: .cfa -8 + ^ $rbp: .cfa 16 - ^ 8 +
+";
+
+    fn unwind(
+        address: u32,
+        regs: &mut UnwindRegsX86_64,
+        read_stack: &mut impl FnMut(u64) -> Result<u64, ()>,
+    ) -> Result<UnwindResult<UnwindRuleX86_64>, BreakpadUnwinderError> {
+        let index = BreakpadIndex::try_new(SYM).unwrap();
+        let record = index.lookup(SYM, address)?;
+        ArchX86_64::unwind_frame(record, true, regs, read_stack)
+    }
+
+    #[test]
+    fn test_rules() {
+        let mut regs = UnwindRegsX86_64::new(0x1000, 0x10, 0x20);
+        let mut read_stack = |_| Err(());
+        let expected = [
+            (0x1000, UnwindRuleX86_64::OffsetSp { sp_offset_by_8: 1 }),
+            (
+                0x1001,
+                UnwindRuleX86_64::OffsetSpAndRestoreBp {
+                    sp_offset_by_8: 2,
+                    bp_storage_offset_from_sp_by_8: 0,
+                },
+            ),
+            (0x1010, UnwindRuleX86_64::UseFramePointer),
+            (0x1030, UnwindRuleX86_64::OffsetSp { sp_offset_by_8: 1 }),
+            (0x1040, UnwindRuleX86_64::JustReturnIfFirstFrameOtherwiseFp),
+        ];
+        for (address, rule) in expected {
+            assert_eq!(
+                unwind(address, &mut regs, &mut read_stack),
+                Ok(UnwindResult::ExecRule(rule))
+            );
+        }
+    }
+
+    #[test]
+    fn test_evaluated() {
+        let stack = [1, 0x50, 0x2345, 4, 5, 6];
+        let mut read_stack = |addr: u64| stack.get((addr / 8) as usize).copied().ok_or(());
+        let mut regs = UnwindRegsX86_64::new(0x2004, 0x10, 0x28);
+        assert_eq!(
+            unwind(0x2004, &mut regs, &mut read_stack),
+            Ok(UnwindResult::Uncacheable(0x2345))
+        );
+        assert_eq!(regs.ip(), 0x2345);
+        assert_eq!(regs.sp(), 0x18);
+        assert_eq!(regs.bp(), 0x58);
+    }
+}
@@ -1,7 +1,9 @@
 use std::ops::Deref;
+use std::sync::Arc;
 
 use super::unwind_rule::*;
 use crate::cache::*;
+use crate::rule_cache::SharedRuleCache;
 
 /// The unwinder cache type for [`UnwinderX86_64`](super::UnwinderX86_64).
 pub struct CacheX86_64<D: Deref<Target = [u8]>, P: AllocationPolicy<D> = MayAllocateDuringUnwind>(
@@ -14,7 +16,25 @@ impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> CacheX86_64<D, P> {
         Self(Cache::new())
     }
 
-    /// Returns a snapshot of the cache usage statistics.
+    /// Create a new direct-mapped cache with room for `capacity` unwind rules.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_config(CacheConfig::with_capacity(capacity))
+    }
+
+    /// Create a new cache with the given size, associativity and hash function.
+    pub fn with_config(config: CacheConfig) -> Self {
+        Self(Cache::with_config(config))
+    }
+
+    /// Create a new cache which stores its unwind rules in `shared`. The rules are
+    /// shared with all other caches created from the same [`SharedCacheX86_64`], for
+    /// example with the caches of the other threads of a thread pool.
+    pub fn with_shared_rule_cache(shared: &SharedCacheX86_64) -> Self {
+        Self(Cache::with_shared_rule_cache(shared.0.clone()))
+    }
+
+    /// Returns a snapshot of the cache usage statistics. For a cache which was created
+    /// with `with_shared_rule_cache`, these are the statistics of the shared rule cache.
     pub fn stats(&self) -> CacheStats {
         self.0.rule_cache.stats()
     }
@@ -25,3 +45,37 @@ impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> Default for CacheX86_64<D,
         Self::new()
     }
 }
+
+/// A rule cache for [`UnwinderX86_64`](super::UnwinderX86_64) which can be shared between
+/// threads.
+///
+/// Each thread still needs its own [`CacheX86_64`], created with
+/// [`CacheX86_64::with_shared_rule_cache`], because the unwind context for DWARF CFI
+/// evaluation cannot be shared. Cloning a `SharedCacheX86_64` is cheap and refers to the
+/// same shared cache.
+#[derive(Clone)]
+pub struct SharedCacheX86_64(Arc<SharedRuleCache<UnwindRuleX86_64>>);
+
+impl SharedCacheX86_64 {
+    /// Create a new shared cache with 16 shards of 509 slots each.
+    pub fn new() -> Self {
+        Self::with_config(16, CacheConfig::with_capacity(16 * 509))
+    }
+
+    /// Create a new shared cache with `shard_count` shards. Each shard is protected by
+    /// its own lock. The capacity in `config` is the total capacity of all shards.
+    pub fn with_config(shard_count: usize, config: CacheConfig) -> Self {
+        Self(Arc::new(SharedRuleCache::with_config(shard_count, config)))
+    }
+
+    /// Returns a snapshot of the cache usage statistics, summed over all shards.
+    pub fn stats(&self) -> CacheStats {
+        self.0.stats()
+    }
+}
+
+impl Default for SharedCacheX86_64 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,73 @@
+use super::arch::ArchX86_64;
+use crate::call_instruction::CallInstructionAnalysis;
+
+impl CallInstructionAnalysis for ArchX86_64 {
+    fn is_preceded_by_call(text_bytes: &[u8]) -> bool {
+        let len = text_bytes.len();
+        // call rel32
+        if len >= 5 && text_bytes[len - 5] == 0xe8 {
+            return true;
+        }
+        // call r/m64, which is ff /2, with a ModRM byte, an optional SIB byte and an
+        // optional displacement. Prefixes don't matter because we only look backwards
+        // from the end of the instruction.
+        (2..=7).any(|insn_len| {
+            let Some(start) = len.checked_sub(insn_len) else {
+                return false;
+            };
+            let modrm = text_bytes[start + 1];
+            text_bytes[start] == 0xff
+                && (modrm >> 3) & 0b111 == 2
+                && indirect_call_len(modrm, text_bytes.get(start + 2).copied()) == Some(insn_len)
+        })
+    }
+}
+
+/// The length of an `ff /2` instruction with the given ModRM and SIB bytes.
+fn indirect_call_len(modrm: u8, sib: Option<u8>) -> Option<usize> {
+    let mode = modrm >> 6;
+    let rm = modrm & 0b111;
+    let has_sib = mode != 0b11 && rm == 0b100;
+    let displacement_len = match mode {
+        // [rip + disp32]
+        0b00 if rm == 0b101 => 4,
+        // SIB without a base register: [index * scale + disp32]
+        0b00 if has_sib && sib? & 0b111 == 0b101 => 4,
+        0b01 => 1,
+        0b10 => 4,
+        _ => 0,
+    };
+    Some(2 + usize::from(has_sib) + displacement_len)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_preceded_by_call() {
+        let is_call = ArchX86_64::is_preceded_by_call;
+        // call 0x1010
+        assert!(is_call(&[0x90, 0xe8, 0xe1, 0xff, 0xff, 0xff]));
+        // call rax
+        assert!(is_call(&[0xff, 0xd0]));
+        // call r11
+        assert!(is_call(&[0x41, 0xff, 0xd3]));
+        // call qword ptr [rax + 0x10]
+        assert!(is_call(&[0xff, 0x50, 0x10]));
+        // call qword ptr [rsp + 0x8]
+        assert!(is_call(&[0xff, 0x54, 0x24, 0x08]));
+        // call qword ptr [rip + 0x2fea]
+        assert!(is_call(&[0xff, 0x15, 0xea, 0x2f, 0x00, 0x00]));
+        // call qword ptr [rax * 8 + 0x1000]
+        assert!(is_call(&[0xff, 0x14, 0xc5, 0x00, 0x10, 0x00, 0x00]));
+
+        // jmp qword ptr [rip + 0x2fea]
+        assert!(!is_call(&[0xff, 0x25, 0xea, 0x2f, 0x00, 0x00]));
+        // mov dword ptr [rsp + 0x1c], eax
+        assert!(!is_call(&[0x89, 0x44, 0x24, 0x1c]));
+        // The ModRM byte says that the instruction is longer.
+        assert!(!is_call(&[0xff, 0x15, 0x00, 0x00]));
+        assert!(!is_call(&[]));
+    }
+}
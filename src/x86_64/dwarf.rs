@@ -3,7 +3,9 @@ use gimli::{
     UnwindTableRow, X86_64,
 };
 
-use super::{arch::ArchX86_64, unwind_rule::UnwindRuleX86_64, unwindregs::UnwindRegsX86_64};
+use super::arch::ArchX86_64;
+use super::unwind_rule::UnwindRuleX86_64;
+use super::unwindregs::{FullUnwindRegsX86_64, UnwindRegsX86_64};
 use crate::dwarf::{
     eval_cfa_rule, eval_register_rule, ConversionError, DwarfUnwindRegs, DwarfUnwinderError,
     DwarfUnwinding,
@@ -21,7 +23,22 @@ impl DwarfUnwindRegs for UnwindRegsX86_64 {
     }
 }
 
+impl DwarfUnwindRegs for FullUnwindRegsX86_64 {
+    fn get(&self, register: Register) -> Option<u64> {
+        match register {
+            X86_64::RBX => self.rbx(),
+            X86_64::R12 => self.r12(),
+            X86_64::R13 => self.r13(),
+            X86_64::R14 => self.r14(),
+            X86_64::R15 => self.r15(),
+            _ => self.regs().get(register),
+        }
+    }
+}
+
 impl DwarfUnwinding for ArchX86_64 {
+    const ADDRESS_SIZE: u8 = 8;
+
     fn unwind_frame<F, R, S>(
         unwind_info: &UnwindTableRow<R, S>,
         encoding: Encoding,
@@ -38,14 +55,6 @@ impl DwarfUnwinding for ArchX86_64 {
         let bp_rule = unwind_info.register(X86_64::RBP);
         let ra_rule = unwind_info.register(X86_64::RA);
 
-        match translate_into_unwind_rule(cfa_rule, &bp_rule, &ra_rule) {
-            Ok(unwind_rule) => return Ok(UnwindResult::ExecRule(unwind_rule)),
-            Err(_err) => {
-                // Could not translate into a cacheable unwind rule. Fall back to the generic path.
-                // eprintln!("Unwind rule translation failed: {:?}", err);
-            }
-        }
-
         let cfa = eval_cfa_rule::<R, _, S>(cfa_rule, encoding, regs)
             .ok_or(DwarfUnwinderError::CouldNotRecoverCfa)?;
 
@@ -77,6 +86,20 @@ impl DwarfUnwinding for ArchX86_64 {
         Ok(UnwindResult::Uncacheable(return_address))
     }
 
+    fn unwind_rule_for_row<R, S>(
+        unwind_info: &UnwindTableRow<R, S>,
+    ) -> Result<Self::UnwindRule, ConversionError>
+    where
+        R: Reader,
+        S: UnwindContextStorage<R>,
+    {
+        translate_into_unwind_rule(
+            unwind_info.cfa(),
+            &unwind_info.register(X86_64::RBP),
+            &unwind_info.register(X86_64::RA),
+        )
+    }
+
     fn rule_if_uncovered_by_fde() -> Self::UnwindRule {
         UnwindRuleX86_64::JustReturnIfFirstFrameOtherwiseFp
     }
@@ -0,0 +1,107 @@
+use gimli::{Register, X86_64};
+use macho_unwind_info::opcodes::{OpcodeX86_64, RegisterNameX86_64};
+
+use super::arch::ArchX86_64;
+use super::unwindregs::{FullUnwindRegsX86_64, UnwindRegsX86_64};
+use crate::full_regs::{CalleeSavedRule, FullRegsUnwinding};
+
+impl FullRegsUnwinding for ArchX86_64 {
+    type FullUnwindRegs = FullUnwindRegsX86_64;
+
+    const CALLEE_SAVED_REGS: &'static [Register] = &[
+        X86_64::RBX,
+        X86_64::R12,
+        X86_64::R13,
+        X86_64::R14,
+        X86_64::R15,
+    ];
+
+    fn unwind_regs_mut(regs: &mut FullUnwindRegsX86_64) -> &mut UnwindRegsX86_64 {
+        regs.regs_mut()
+    }
+
+    fn callee_saved_values_mut(regs: &mut FullUnwindRegsX86_64) -> &mut [Option<u64>] {
+        regs.callee_saved_mut()
+    }
+
+    fn cfa_after_unwinding(regs: &FullUnwindRegsX86_64) -> u64 {
+        regs.regs().sp()
+    }
+
+    fn callee_saved_rule_for_cui_opcode(opcode: u32, register: Register) -> CalleeSavedRule {
+        let name = match register {
+            X86_64::RBX => RegisterNameX86_64::Rbx,
+            X86_64::R12 => RegisterNameX86_64::R12,
+            X86_64::R13 => RegisterNameX86_64::R13,
+            X86_64::R14 => RegisterNameX86_64::R14,
+            X86_64::R15 => RegisterNameX86_64::R15,
+            _ => return CalleeSavedRule::Unknown,
+        };
+        match OpcodeX86_64::parse(opcode) {
+            // Only used for the first frame, where a function without unwind info is
+            // treated as a leaf function.
+            OpcodeX86_64::Null => CalleeSavedRule::SameValue,
+            OpcodeX86_64::FrameBased {
+                stack_offset_in_bytes,
+                saved_regs,
+            } => {
+                // The registers were pushed right after rbp, which is at CFA - 16. The
+                // last entry is at the lowest address, rbp - stack_offset_in_bytes.
+                let max_count = (stack_offset_in_bytes / 8) as usize;
+                match saved_regs
+                    .iter()
+                    .rev()
+                    .take(max_count)
+                    .position(|r| *r == Some(name))
+                {
+                    Some(pos) => CalleeSavedRule::SavedAtCfaOffset(
+                        -16 - i64::from(stack_offset_in_bytes) + pos as i64 * 8,
+                    ),
+                    None => CalleeSavedRule::SameValue,
+                }
+            }
+            OpcodeX86_64::FramelessImmediate { saved_regs, .. }
+            | OpcodeX86_64::FramelessIndirect { saved_regs, .. } => {
+                // The registers were pushed right after the return address, which is at
+                // CFA - 8. The first entry is at the highest address.
+                match saved_regs.iter().rev().flatten().position(|r| *r == name) {
+                    Some(pos) => CalleeSavedRule::SavedAtCfaOffset(-16 - pos as i64 * 8),
+                    None => CalleeSavedRule::SameValue,
+                }
+            }
+            OpcodeX86_64::Dwarf { .. }
+            | OpcodeX86_64::UnrecognizedKind(_)
+            | OpcodeX86_64::InvalidFrameless => CalleeSavedRule::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame_based() {
+        // push rbp; mov rbp, rsp; push r15; push r14; push rbx
+        // saved_regs = [r15, r14, rbx] in the low bits, stack offset 3 * 8.
+        let opcode = 0x0100_0000 | (3 << 16) | (5 << 6) | (4 << 3) | 1;
+        let rule = |register| ArchX86_64::callee_saved_rule_for_cui_opcode(opcode, register);
+        assert_eq!(rule(X86_64::R15), CalleeSavedRule::SavedAtCfaOffset(-24));
+        assert_eq!(rule(X86_64::R14), CalleeSavedRule::SavedAtCfaOffset(-32));
+        assert_eq!(rule(X86_64::RBX), CalleeSavedRule::SavedAtCfaOffset(-40));
+        assert_eq!(rule(X86_64::R12), CalleeSavedRule::SameValue);
+    }
+
+    #[test]
+    fn test_frameless() {
+        // Frameless with indirect stack size, which saved all six registers:
+        // push rbp; push r15; push r14; push r13; push r12; push rbx; sub rsp, ...
+        let opcode = 0x30df800;
+        let rule = |register| ArchX86_64::callee_saved_rule_for_cui_opcode(opcode, register);
+        assert_eq!(rule(X86_64::R15), CalleeSavedRule::SavedAtCfaOffset(-24));
+        assert_eq!(rule(X86_64::R14), CalleeSavedRule::SavedAtCfaOffset(-32));
+        assert_eq!(rule(X86_64::R13), CalleeSavedRule::SavedAtCfaOffset(-40));
+        assert_eq!(rule(X86_64::R12), CalleeSavedRule::SavedAtCfaOffset(-48));
+        assert_eq!(rule(X86_64::RBX), CalleeSavedRule::SavedAtCfaOffset(-56));
+    }
+}
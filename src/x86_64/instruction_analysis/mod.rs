@@ -7,6 +7,11 @@ mod prologue;
 use epilogue::unwind_rule_from_detected_epilogue;
 use prologue::unwind_rule_from_detected_prologue;
 
+/// `__restore_rt` in glibc and musl:
+/// mov rax, 15 ; rt_sigreturn
+/// syscall
+const SIGRETURN_TRAMPOLINE: &[u8] = &[0x48, 0xc7, 0xc0, 0x0f, 0x00, 0x00, 0x00, 0x0f, 0x05];
+
 impl InstructionAnalysis for ArchX86_64 {
     fn rule_from_prologue_analysis(
         text_bytes: &[u8],
@@ -21,4 +26,37 @@ impl InstructionAnalysis for ArchX86_64 {
     ) -> Option<Self::UnwindRule> {
         unwind_rule_from_detected_epilogue(text_bytes, pc_offset)
     }
+
+    fn is_linux_sigreturn_trampoline(text_bytes: &[u8], pc_offset: usize) -> bool {
+        // The pc is at the first instruction when a signal handler returns into the
+        // trampoline, and at the second one if the thread was sampled inside it.
+        [0, 7].into_iter().any(|offset| {
+            pc_offset
+                .checked_sub(offset)
+                .and_then(|start| text_bytes.get(start..)?.get(..SIGRETURN_TRAMPOLINE.len()))
+                == Some(SIGRETURN_TRAMPOLINE)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sigreturn_trampoline() {
+        let mut bytes = vec![0x0f, 0x1f, 0x40, 0x00];
+        bytes.extend_from_slice(SIGRETURN_TRAMPOLINE);
+        let is_trampoline =
+            |pc_offset| ArchX86_64::is_linux_sigreturn_trampoline(&bytes, pc_offset);
+        assert!(is_trampoline(4));
+        assert!(is_trampoline(11));
+        assert!(!is_trampoline(0));
+        assert!(!is_trampoline(5));
+        assert!(!is_trampoline(bytes.len()));
+        assert!(!ArchX86_64::is_linux_sigreturn_trampoline(
+            &bytes[..bytes.len() - 1],
+            4
+        ));
+    }
 }
@@ -24,7 +24,7 @@ impl CompactUnwindInfoUnwinding for ArchX86_64 {
                 ) {
                     // We are inside a prologue / epilogue. Ignore the opcode and use the rule from
                     // instruction analysis.
-                    return Ok(CuiUnwindResult::ExecRule(rule));
+                    return Ok(CuiUnwindResult::ExecRuleFromInstructionAnalysis(rule));
                 }
                 if opcode == OpcodeX86_64::Null
                     && function_bytes.starts_with(&[0x55, 0x48, 0x89, 0xe5])
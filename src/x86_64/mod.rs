@@ -1,16 +1,27 @@
 mod arch;
+mod breakpad;
 mod cache;
+mod call_instruction;
 mod dwarf;
+mod full_regs;
 mod instruction_analysis;
 mod macho;
+mod orc;
+mod pe;
+mod stack_scan;
 mod unwind_rule;
 mod unwinder;
 mod unwindregs;
 
 pub use arch::*;
 pub use cache::*;
+// These modules only contain trait impls at the moment, but anything public they get
+// is part of the arch module's API.
+#[allow(unused_imports)]
 pub use dwarf::*;
+#[allow(unused_imports)]
 pub use instruction_analysis::*;
+#[allow(unused_imports)]
 pub use macho::*;
 pub use unwind_rule::*;
 pub use unwinder::*;
@@ -0,0 +1,285 @@
+use super::arch::ArchX86_64;
+use super::unwind_rule::UnwindRuleX86_64;
+use super::unwindregs::UnwindRegsX86_64;
+use crate::add_signed::checked_add_signed;
+use crate::orc::*;
+use crate::unwind_result::UnwindResult;
+
+/// Offsets of the registers we need in `struct pt_regs`.
+const PT_REGS_BP: u64 = 32;
+const PT_REGS_IP: u64 = 128;
+const PT_REGS_SP: u64 = 152;
+
+/// Offsets of the registers in the hardware iret frame, which is the tail of `pt_regs`.
+const IRET_FRAME_IP: u64 = 0;
+const IRET_FRAME_SP: u64 = 24;
+
+impl OrcUnwinding for ArchX86_64 {
+    fn unwind_frame<F>(
+        entry: OrcEntry,
+        is_first_frame: bool,
+        regs: &mut UnwindRegsX86_64,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<UnwindRuleX86_64>, OrcUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        match entry.ty {
+            ORC_TYPE_UNDEFINED => return Err(OrcUnwinderError::UndefinedEntry),
+            ORC_TYPE_END_OF_STACK => {
+                return Ok(UnwindResult::ExecRule(UnwindRuleX86_64::EndOfStack))
+            }
+            ORC_TYPE_CALL | ORC_TYPE_REGS | ORC_TYPE_REGS_PARTIAL => {}
+            ty => return Err(OrcUnwinderError::UnknownType(ty)),
+        }
+        if let Some(rule) = translate_into_unwind_rule(entry) {
+            return Ok(UnwindResult::ExecRule(rule));
+        }
+
+        // Could not translate into a cacheable unwind rule. Evaluate the entry directly,
+        // the same way the kernel's ORC unwinder does.
+        let sp = regs.sp();
+        let bp = regs.bp();
+        let ip = regs.ip();
+        let mut read = |address: u64| {
+            read_stack(address).map_err(|_| OrcUnwinderError::CouldNotReadStack(address))
+        };
+        let add = |value: u64, offset: i16| {
+            checked_add_signed(value, i64::from(offset)).ok_or(OrcUnwinderError::IntegerOverflow)
+        };
+        let field = |base: u64, offset: u64| {
+            base.checked_add(offset)
+                .ok_or(OrcUnwinderError::IntegerOverflow)
+        };
+
+        let cfa = match entry.sp_reg {
+            ORC_REG_SP => add(sp, entry.sp_offset)?,
+            ORC_REG_BP => add(bp, entry.sp_offset)?,
+            ORC_REG_SP_INDIRECT => add(read(sp)?, entry.sp_offset)?,
+            ORC_REG_BP_INDIRECT => read(add(bp, entry.sp_offset)?)?,
+            reg => return Err(OrcUnwinderError::UntrackedRegister(reg)),
+        };
+
+        let (return_address, new_sp, new_bp) = match entry.ty {
+            ORC_TYPE_REGS => {
+                // The stack pointer points to a full `struct pt_regs`, for example at the
+                // entry of an interrupt or a syscall.
+                let return_address = read(field(cfa, PT_REGS_IP)?)?;
+                let new_sp = read(field(cfa, PT_REGS_SP)?)?;
+                let new_bp = read(field(cfa, PT_REGS_BP)?)?;
+                (return_address, new_sp, new_bp)
+            }
+            ORC_TYPE_REGS_PARTIAL => {
+                // The stack pointer points to the iret frame at the end of `struct pt_regs`.
+                // The frame pointer has not been saved yet.
+                let return_address = read(field(cfa, IRET_FRAME_IP)?)?;
+                let new_sp = read(field(cfa, IRET_FRAME_SP)?)?;
+                let new_bp = match entry.bp_reg {
+                    ORC_REG_UNDEFINED => bp,
+                    ORC_REG_PREV_SP => read(add(cfa, entry.bp_offset)?)?,
+                    ORC_REG_BP => read(add(bp, entry.bp_offset)?)?,
+                    reg => return Err(OrcUnwinderError::UntrackedRegister(reg)),
+                };
+                (return_address, new_sp, new_bp)
+            }
+            _ => {
+                let return_address_location = cfa
+                    .checked_sub(8)
+                    .ok_or(OrcUnwinderError::IntegerOverflow)?;
+                let return_address = read(return_address_location)?;
+                let new_bp = match entry.bp_reg {
+                    ORC_REG_UNDEFINED => bp,
+                    ORC_REG_PREV_SP => {
+                        let bp_location = add(cfa, entry.bp_offset)?;
+                        match read(bp_location) {
+                            Ok(new_bp) => new_bp,
+                            // Like for DWARF CFI, ignore errors when reading beyond the
+                            // stack pointer in the first frame.
+                            Err(_) if is_first_frame && bp_location < sp => bp,
+                            Err(err) => return Err(err),
+                        }
+                    }
+                    ORC_REG_BP => read(add(bp, entry.bp_offset)?)?,
+                    reg => return Err(OrcUnwinderError::UntrackedRegister(reg)),
+                };
+                (return_address, cfa, new_bp)
+            }
+        };
+        if return_address == 0 {
+            return Ok(UnwindResult::ExecRule(UnwindRuleX86_64::EndOfStack));
+        }
+        if new_sp == sp && return_address == ip {
+            return Err(OrcUnwinderError::DidNotAdvance);
+        }
+        regs.set_ip(return_address);
+        regs.set_sp(new_sp);
+        regs.set_bp(new_bp);
+        if entry.signal || entry.ty != ORC_TYPE_CALL {
+            // pt_regs frames are always interrupt or syscall entries, even when objtool
+            // didn't set the signal bit. The recovered address is where the caller was
+            // interrupted.
+            return Ok(UnwindResult::UncacheableSignalFrame(return_address));
+        }
+        Ok(UnwindResult::Uncacheable(return_address))
+    }
+}
+
+/// Translate a CALL entry into an unwind rule. Returns `None` for anything that can't be
+/// expressed with an [`UnwindRuleX86_64`], for example pt_regs frames, indirect stack
+/// pointers or interrupted callers.
+fn translate_into_unwind_rule(entry: OrcEntry) -> Option<UnwindRuleX86_64> {
+    if entry.ty != ORC_TYPE_CALL
+        || entry.signal
+        || entry.sp_offset % 8 != 0
+        || entry.bp_offset % 8 != 0
+    {
+        return None;
+    }
+    match (entry.sp_reg, entry.bp_reg) {
+        (ORC_REG_SP, ORC_REG_UNDEFINED) => {
+            let sp_offset_by_8 = u16::try_from(entry.sp_offset / 8).ok()?;
+            if sp_offset_by_8 == 0 {
+                return None;
+            }
+            Some(UnwindRuleX86_64::OffsetSp { sp_offset_by_8 })
+        }
+        (ORC_REG_SP, ORC_REG_PREV_SP) => {
+            let sp_offset_by_8 = u16::try_from(entry.sp_offset / 8).ok()?;
+            if sp_offset_by_8 == 0 {
+                return None;
+            }
+            // The rule's bp storage offset is relative to the callee's sp, and the
+            // entry's is relative to the caller's sp.
+            let bp_storage_offset_from_sp_by_8 = (entry.sp_offset + entry.bp_offset) / 8;
+            Some(UnwindRuleX86_64::OffsetSpAndRestoreBp {
+                sp_offset_by_8,
+                bp_storage_offset_from_sp_by_8,
+            })
+        }
+        (ORC_REG_BP, ORC_REG_PREV_SP) if entry.sp_offset == 16 && entry.bp_offset == -16 => {
+            Some(UnwindRuleX86_64::UseFramePointer)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(sp_reg: u8, sp_offset: i16, bp_reg: u8, bp_offset: i16, ty: u8) -> OrcEntry {
+        OrcEntry {
+            sp_offset,
+            bp_offset,
+            sp_reg,
+            bp_reg,
+            ty,
+            signal: false,
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        let mut regs = UnwindRegsX86_64::new(0x1000, 0x10, 0x20);
+        let mut read_stack = |_| Err(());
+        let mut unwind = |entry| ArchX86_64::unwind_frame(entry, false, &mut regs, &mut read_stack);
+        assert_eq!(
+            unwind(entry(ORC_REG_SP, 8, ORC_REG_UNDEFINED, 0, ORC_TYPE_CALL)),
+            Ok(UnwindResult::ExecRule(UnwindRuleX86_64::OffsetSp {
+                sp_offset_by_8: 1
+            }))
+        );
+        assert_eq!(
+            unwind(entry(ORC_REG_SP, 24, ORC_REG_PREV_SP, -16, ORC_TYPE_CALL)),
+            Ok(UnwindResult::ExecRule(
+                UnwindRuleX86_64::OffsetSpAndRestoreBp {
+                    sp_offset_by_8: 3,
+                    bp_storage_offset_from_sp_by_8: 1,
+                }
+            ))
+        );
+        assert_eq!(
+            unwind(entry(ORC_REG_BP, 16, ORC_REG_PREV_SP, -16, ORC_TYPE_CALL)),
+            Ok(UnwindResult::ExecRule(UnwindRuleX86_64::UseFramePointer))
+        );
+        assert_eq!(
+            unwind(entry(
+                ORC_REG_UNDEFINED,
+                0,
+                ORC_REG_UNDEFINED,
+                0,
+                ORC_TYPE_END_OF_STACK
+            )),
+            Ok(UnwindResult::ExecRule(UnwindRuleX86_64::EndOfStack))
+        );
+        assert_eq!(
+            unwind(entry(
+                ORC_REG_UNDEFINED,
+                0,
+                ORC_REG_UNDEFINED,
+                0,
+                ORC_TYPE_UNDEFINED
+            )),
+            Err(OrcUnwinderError::UndefinedEntry)
+        );
+        assert_eq!(
+            unwind(entry(6, 8, ORC_REG_UNDEFINED, 0, ORC_TYPE_CALL)),
+            Err(OrcUnwinderError::UntrackedRegister(6))
+        );
+    }
+
+    #[test]
+    fn test_pt_regs() {
+        // A full pt_regs at 0x100, which the sp points to.
+        let mut stack = vec![0u64; 64];
+        stack[(0x100 + PT_REGS_BP as usize) / 8] = 0x250;
+        stack[(0x100 + PT_REGS_IP as usize) / 8] = 0xffffffff81001234;
+        stack[(0x100 + PT_REGS_SP as usize) / 8] = 0x200;
+        let mut read_stack = |address: u64| stack.get(address as usize / 8).copied().ok_or(());
+        let mut regs = UnwindRegsX86_64::new(0xffffffff81000010, 0x100, 0x20);
+        let res = ArchX86_64::unwind_frame(
+            entry(ORC_REG_SP, 0, ORC_REG_UNDEFINED, 0, ORC_TYPE_REGS),
+            false,
+            &mut regs,
+            &mut read_stack,
+        );
+        assert_eq!(
+            res,
+            Ok(UnwindResult::UncacheableSignalFrame(0xffffffff81001234))
+        );
+
+        // The same with the signal bit, which objtool sets for interrupt entries.
+        let mut regs = UnwindRegsX86_64::new(0xffffffff81000010, 0x100, 0x20);
+        let res = ArchX86_64::unwind_frame(
+            OrcEntry {
+                signal: true,
+                ..entry(ORC_REG_SP, 0, ORC_REG_UNDEFINED, 0, ORC_TYPE_REGS)
+            },
+            false,
+            &mut regs,
+            &mut read_stack,
+        );
+        assert_eq!(
+            res,
+            Ok(UnwindResult::UncacheableSignalFrame(0xffffffff81001234))
+        );
+        assert_eq!(regs.ip(), 0xffffffff81001234);
+        assert_eq!(regs.sp(), 0x200);
+        assert_eq!(regs.bp(), 0x250);
+
+        // An iret frame at 0x100 + 8 * 16.
+        let mut regs = UnwindRegsX86_64::new(0xffffffff81000010, 0x100, 0x20);
+        let res = ArchX86_64::unwind_frame(
+            entry(ORC_REG_SP, 128, ORC_REG_UNDEFINED, 0, ORC_TYPE_REGS_PARTIAL),
+            false,
+            &mut regs,
+            &mut read_stack,
+        );
+        assert_eq!(
+            res,
+            Ok(UnwindResult::UncacheableSignalFrame(0xffffffff81001234))
+        );
+        assert_eq!(regs.sp(), 0x200);
+        assert_eq!(regs.bp(), 0x20);
+    }
+}
@@ -0,0 +1,633 @@
+use super::arch::ArchX86_64;
+use super::unwind_rule::UnwindRuleX86_64;
+use super::unwindregs::UnwindRegsX86_64;
+use crate::instruction_analysis::InstructionAnalysis;
+use crate::macho::TextBytes;
+use crate::pe::{read_u16, read_u32, PeSections, PeUnwinderError, PeUnwinding};
+use crate::unwind_result::UnwindResult;
+
+/// The number of the rbp register in unwind codes.
+const RBP: u8 = 5;
+
+/// The unwind info is followed by a RUNTIME_FUNCTION for the chained unwind info.
+const UNW_FLAG_CHAININFO: u8 = 0x4;
+
+/// Chains are usually one or two entries long. Stop following a chain after this many
+/// entries, so that malformed data can't make us loop forever.
+const MAX_CHAIN_LENGTH: usize = 32;
+
+/// An entry of the `.pdata` table. All addresses are relative to the image base.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct RuntimeFunction {
+    begin: u32,
+    end: u32,
+    unwind_info: u32,
+}
+
+impl RuntimeFunction {
+    const SIZE: usize = 12;
+
+    fn parse(data: &[u8], offset: usize) -> Option<Self> {
+        Some(Self {
+            begin: read_u32(data, offset)?,
+            end: read_u32(data, offset + 4)?,
+            unwind_info: read_u32(data, offset + 8)?,
+        })
+    }
+
+    /// Binary-search the `.pdata` table for the function which covers `address`.
+    fn lookup(pdata: &[u8], address: u32) -> Option<Self> {
+        let entry_count = pdata.len() / Self::SIZE;
+        let (mut low, mut high) = (0, entry_count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let entry = Self::parse(pdata, mid * Self::SIZE)?;
+            if entry.begin <= address {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        let entry = Self::parse(pdata, low.checked_sub(1)? * Self::SIZE)?;
+        if address < entry.end {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+}
+
+/// The parsed header of an UNWIND_INFO structure.
+#[derive(Clone, Copy)]
+struct UnwindInfo<'a> {
+    version: u8,
+    size_of_prolog: u8,
+    frame_register: u8,
+    frame_offset_by_16: u8,
+    codes: &'a [u8],
+    chained: Option<RuntimeFunction>,
+}
+
+impl<'a> UnwindInfo<'a> {
+    fn parse(sections: &PeSections<'a>, rva: u32) -> Result<Self, PeUnwinderError> {
+        let data = sections.xdata_at_rva(rva)?;
+        let header = data.get(..4).ok_or(PeUnwinderError::BadUnwindInfo(rva))?;
+        let version = header[0] & 0x7;
+        if version != 1 && version != 2 {
+            return Err(PeUnwinderError::UnsupportedVersion(version));
+        }
+        let flags = header[0] >> 3;
+        let code_count = usize::from(header[2]);
+        let codes = data
+            .get(4..4 + code_count * 2)
+            .ok_or(PeUnwinderError::BadUnwindInfo(rva))?;
+        let chained = if flags & UNW_FLAG_CHAININFO != 0 {
+            // The code array is padded to an even number of slots.
+            let offset = 4 + code_count.div_ceil(2) * 4;
+            Some(RuntimeFunction::parse(data, offset).ok_or(PeUnwinderError::BadUnwindInfo(rva))?)
+        } else {
+            None
+        };
+        Ok(Self {
+            version,
+            size_of_prolog: header[1],
+            frame_register: header[3] & 0xf,
+            frame_offset_by_16: header[3] >> 4,
+            codes,
+            chained,
+        })
+    }
+}
+
+/// An operation described by an unwind code. Unwind codes are stored in the reverse
+/// order of the prolog instructions they describe, so processing them in order undoes
+/// the prolog.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum UnwindOperation {
+    /// UWOP_PUSH_NONVOL: The register was pushed, undo it by popping.
+    PushNonVolatile(u8),
+    /// UWOP_ALLOC_SMALL / UWOP_ALLOC_LARGE: rsp was decremented by this many bytes.
+    Alloc(u32),
+    /// UWOP_SET_FPREG: The frame register was set to rsp + 16 * offset.
+    SetFramePointer { register: u8, offset_by_16: u8 },
+    /// UWOP_SAVE_NONVOL / UWOP_SAVE_NONVOL_FAR: The register was stored at this offset
+    /// from the frame base.
+    SaveNonVolatile(u8, u32),
+    /// UWOP_PUSH_MACHFRAME: The CPU pushed a machine frame, for example for an
+    /// interrupt. If the flag is set, an error code was pushed too.
+    PushMachineFrame(bool),
+    /// An operation which doesn't affect rsp or rbp, for example UWOP_SAVE_XMM128 or
+    /// UWOP_EPILOG.
+    Other,
+}
+
+/// Iterates over the operations that need to be undone in order to unwind from an
+/// address, following chained unwind info.
+#[derive(Clone)]
+struct UnwindOperations<'a> {
+    sections: PeSections<'a>,
+    info: UnwindInfo<'a>,
+    /// The offset of the lookup address from the start of the function. Unwind codes
+    /// for prolog instructions at or after this offset have not been executed yet.
+    /// This is `u32::MAX` for chained unwind info.
+    prolog_offset: u32,
+    code_index: usize,
+    chain_length: usize,
+}
+
+impl<'a> UnwindOperations<'a> {
+    fn new(
+        sections: PeSections<'a>,
+        function: RuntimeFunction,
+        rel_lookup_address: u32,
+    ) -> Result<Self, PeUnwinderError> {
+        let info = UnwindInfo::parse(&sections, function.unwind_info)?;
+        Ok(Self {
+            sections,
+            info,
+            prolog_offset: rel_lookup_address - function.begin,
+            code_index: 0,
+            chain_length: 0,
+        })
+    }
+
+    fn slot(&self, index: usize) -> Result<u16, PeUnwinderError> {
+        read_u16(self.info.codes, index * 2).ok_or(PeUnwinderError::TruncatedUnwindCodes)
+    }
+
+    fn next_operation(&mut self) -> Result<Option<UnwindOperation>, PeUnwinderError> {
+        loop {
+            if self.code_index * 2 >= self.info.codes.len() {
+                let chained = match self.info.chained {
+                    Some(chained) => chained,
+                    None => return Ok(None),
+                };
+                self.chain_length += 1;
+                if self.chain_length > MAX_CHAIN_LENGTH {
+                    return Err(PeUnwinderError::ChainTooLong);
+                }
+                self.info = UnwindInfo::parse(&self.sections, chained.unwind_info)?;
+                self.prolog_offset = u32::MAX;
+                self.code_index = 0;
+                continue;
+            }
+
+            let slot = self.slot(self.code_index)?;
+            let code_offset = (slot & 0xff) as u8;
+            let op = ((slot >> 8) & 0xf) as u8;
+            let op_info = (slot >> 12) as u8;
+            let (operation, slot_count) = match op {
+                0 => (UnwindOperation::PushNonVolatile(op_info), 1),
+                1 if op_info == 0 => {
+                    let size = u32::from(self.slot(self.code_index + 1)?) * 8;
+                    (UnwindOperation::Alloc(size), 2)
+                }
+                1 => {
+                    let low = u32::from(self.slot(self.code_index + 1)?);
+                    let high = u32::from(self.slot(self.code_index + 2)?);
+                    (UnwindOperation::Alloc(low | (high << 16)), 3)
+                }
+                2 => (UnwindOperation::Alloc(u32::from(op_info) * 8 + 8), 1),
+                3 => (
+                    UnwindOperation::SetFramePointer {
+                        register: self.info.frame_register,
+                        offset_by_16: self.info.frame_offset_by_16,
+                    },
+                    1,
+                ),
+                4 => {
+                    let offset = u32::from(self.slot(self.code_index + 1)?) * 8;
+                    (UnwindOperation::SaveNonVolatile(op_info, offset), 2)
+                }
+                5 => {
+                    let low = u32::from(self.slot(self.code_index + 1)?);
+                    let high = u32::from(self.slot(self.code_index + 2)?);
+                    (
+                        UnwindOperation::SaveNonVolatile(op_info, low | (high << 16)),
+                        3,
+                    )
+                }
+                6 if self.info.version == 2 => (UnwindOperation::Other, 1),
+                7 | 8 => (UnwindOperation::Other, 2),
+                9 => (UnwindOperation::Other, 3),
+                10 => (UnwindOperation::PushMachineFrame(op_info != 0), 1),
+                _ => return Err(PeUnwinderError::BadUnwindCode(op)),
+            };
+            self.code_index += slot_count;
+
+            let is_epilog_code = op == 6;
+            let executed = u32::from(code_offset) <= self.prolog_offset
+                || self.prolog_offset >= u32::from(self.info.size_of_prolog);
+            if is_epilog_code || !executed {
+                continue;
+            }
+            return Ok(Some(operation));
+        }
+    }
+}
+
+impl<'a> Iterator for UnwindOperations<'a> {
+    type Item = Result<UnwindOperation, PeUnwinderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_operation().transpose()
+    }
+}
+
+impl PeUnwinding for ArchX86_64 {
+    fn unwind_frame<F>(
+        sections: PeSections,
+        text_bytes: Option<TextBytes>,
+        rel_lookup_address: u32,
+        is_first_frame: bool,
+        regs: &mut UnwindRegsX86_64,
+        read_stack: &mut F,
+    ) -> Result<UnwindResult<UnwindRuleX86_64>, PeUnwinderError>
+    where
+        F: FnMut(u64) -> Result<u64, ()>,
+    {
+        let function = match RuntimeFunction::lookup(sections.pdata, rel_lookup_address) {
+            Some(function) => function,
+            None => {
+                // Functions without a .pdata entry are leaf functions, which don't touch
+                // the stack pointer. Leaf functions can't be callers, so for subsequent
+                // frames this means that the unwind info is incomplete.
+                return Ok(UnwindResult::ExecRule(
+                    UnwindRuleX86_64::JustReturnIfFirstFrameOtherwiseFp,
+                ));
+            }
+        };
+
+        if is_first_frame {
+            // The unwind codes describe the prolog, but not the epilogs. Like the Windows
+            // unwinder, we detect epilogs by looking at the instructions.
+            let function_bytes =
+                text_bytes.and_then(|bytes| bytes.function_bytes(function.begin, function.end));
+            if let Some(function_bytes) = function_bytes {
+                let pc_offset = (rel_lookup_address - function.begin) as usize;
+                if let Some(rule) = Self::rule_from_epilogue_analysis(function_bytes, pc_offset) {
+                    return Ok(UnwindResult::ExecRule(rule));
+                }
+            }
+        }
+
+        let operations = UnwindOperations::new(sections, function, rel_lookup_address)?;
+        if let Some(rule) = translate_into_unwind_rule(operations.clone())? {
+            return Ok(UnwindResult::ExecRule(rule));
+        }
+
+        // Could not translate into a cacheable unwind rule. Evaluate the unwind codes
+        // directly, the same way the Windows unwinder does.
+        let sp = regs.sp();
+        let bp = regs.bp();
+        let ip = regs.ip();
+
+        // Registers saved with UWOP_SAVE_NONVOL are stored relative to the frame base,
+        // which is computed from the frame register if it has been set up.
+        let mut frame_base = sp;
+        for operation in operations.clone() {
+            if let UnwindOperation::SetFramePointer {
+                register,
+                offset_by_16,
+            } = operation?
+            {
+                if register != RBP {
+                    return Err(PeUnwinderError::UntrackedFrameRegister(register));
+                }
+                frame_base = bp
+                    .checked_sub(u64::from(offset_by_16) * 16)
+                    .ok_or(PeUnwinderError::IntegerOverflow)?;
+                break;
+            }
+        }
+
+        let mut read = |address: u64| {
+            read_stack(address).map_err(|_| PeUnwinderError::CouldNotReadStack(address))
+        };
+        let mut vsp = sp;
+        let mut new_bp = bp;
+        let mut machine_frame = None;
+        for operation in operations {
+            match operation? {
+                UnwindOperation::PushNonVolatile(register) => {
+                    if register == RBP {
+                        new_bp = read(vsp)?;
+                    }
+                    vsp = vsp.checked_add(8).ok_or(PeUnwinderError::IntegerOverflow)?;
+                }
+                UnwindOperation::Alloc(size) => {
+                    vsp = vsp
+                        .checked_add(u64::from(size))
+                        .ok_or(PeUnwinderError::IntegerOverflow)?;
+                }
+                UnwindOperation::SetFramePointer { .. } => {
+                    vsp = frame_base;
+                }
+                UnwindOperation::SaveNonVolatile(register, offset) => {
+                    if register == RBP {
+                        let location = frame_base
+                            .checked_add(u64::from(offset))
+                            .ok_or(PeUnwinderError::IntegerOverflow)?;
+                        new_bp = read(location)?;
+                    }
+                }
+                UnwindOperation::PushMachineFrame(has_error_code) => {
+                    // The machine frame contains (error code), rip, cs, eflags, old rsp, ss.
+                    let base = if has_error_code {
+                        vsp.checked_add(8).ok_or(PeUnwinderError::IntegerOverflow)?
+                    } else {
+                        vsp
+                    };
+                    let rsp_location = base
+                        .checked_add(24)
+                        .ok_or(PeUnwinderError::IntegerOverflow)?;
+                    machine_frame = Some((read(base)?, read(rsp_location)?));
+                    break;
+                }
+                UnwindOperation::Other => {}
+            }
+        }
+
+        let (return_address, new_sp) = match machine_frame {
+            Some(machine_frame) => machine_frame,
+            None => (
+                read(vsp)?,
+                vsp.checked_add(8).ok_or(PeUnwinderError::IntegerOverflow)?,
+            ),
+        };
+        if new_sp == sp && return_address == ip {
+            return Err(PeUnwinderError::DidNotAdvance);
+        }
+        regs.set_ip(return_address);
+        regs.set_sp(new_sp);
+        regs.set_bp(new_bp);
+        Ok(UnwindResult::Uncacheable(return_address))
+    }
+}
+
+/// A stack location, relative to the stack pointer or to the frame register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Location {
+    Sp(i64),
+    Bp(i64),
+}
+
+/// Translate the unwind operations into an unwind rule. Returns `Ok(None)` for anything
+/// that can't be expressed with an [`UnwindRuleX86_64`], for example a frame pointer
+/// which isn't rbp, or a machine frame.
+fn translate_into_unwind_rule(
+    operations: UnwindOperations,
+) -> Result<Option<UnwindRuleX86_64>, PeUnwinderError> {
+    let mut frame_base = Location::Sp(0);
+    for operation in operations.clone() {
+        if let UnwindOperation::SetFramePointer {
+            register,
+            offset_by_16,
+        } = operation?
+        {
+            if register != RBP {
+                return Ok(None);
+            }
+            frame_base = Location::Bp(-(i64::from(offset_by_16) * 16));
+            break;
+        }
+    }
+
+    let mut vsp = Location::Sp(0);
+    let mut bp_location = None;
+    for operation in operations {
+        match operation? {
+            UnwindOperation::PushNonVolatile(register) => {
+                if register == RBP {
+                    bp_location = Some(vsp);
+                }
+                vsp = match vsp {
+                    Location::Sp(offset) => Location::Sp(offset + 8),
+                    Location::Bp(offset) => Location::Bp(offset + 8),
+                };
+            }
+            UnwindOperation::Alloc(size) => {
+                vsp = match vsp {
+                    Location::Sp(offset) => Location::Sp(offset + i64::from(size)),
+                    Location::Bp(offset) => Location::Bp(offset + i64::from(size)),
+                };
+            }
+            UnwindOperation::SetFramePointer { .. } => {
+                if let Some(Location::Sp(_)) = bp_location {
+                    // rbp was restored relative to rsp, but the rest is relative to rbp.
+                    return Ok(None);
+                }
+                vsp = frame_base;
+            }
+            UnwindOperation::SaveNonVolatile(register, offset) => {
+                if register == RBP {
+                    bp_location = Some(match frame_base {
+                        Location::Sp(base) => Location::Sp(base + i64::from(offset)),
+                        Location::Bp(base) => Location::Bp(base + i64::from(offset)),
+                    });
+                }
+            }
+            UnwindOperation::PushMachineFrame(_) => return Ok(None),
+            UnwindOperation::Other => {}
+        }
+    }
+
+    let rule = match (vsp, bp_location) {
+        (Location::Sp(0), None) => UnwindRuleX86_64::JustReturn,
+        (Location::Sp(offset), None) => match sp_offset_by_8(offset) {
+            Some(sp_offset_by_8) => UnwindRuleX86_64::OffsetSp { sp_offset_by_8 },
+            None => return Ok(None),
+        },
+        (Location::Sp(offset), Some(Location::Sp(bp_offset))) => {
+            let bp_storage_offset_from_sp_by_8 = match i16::try_from(bp_offset / 8) {
+                Ok(bp_offset_by_8) if bp_offset % 8 == 0 => bp_offset_by_8,
+                _ => return Ok(None),
+            };
+            match sp_offset_by_8(offset) {
+                Some(sp_offset_by_8) => UnwindRuleX86_64::OffsetSpAndRestoreBp {
+                    sp_offset_by_8,
+                    bp_storage_offset_from_sp_by_8,
+                },
+                None => return Ok(None),
+            }
+        }
+        (Location::Bp(8), Some(Location::Bp(0))) => UnwindRuleX86_64::UseFramePointer,
+        _ => return Ok(None),
+    };
+    Ok(Some(rule))
+}
+
+/// Convert the offset of the return address from sp into the sp offset used by
+/// [`UnwindRuleX86_64`], which includes popping the return address.
+fn sp_offset_by_8(return_address_offset: i64) -> Option<u16> {
+    if return_address_offset % 8 != 0 {
+        return None;
+    }
+    u16::try_from(return_address_offset / 8 + 1).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Build .pdata and .xdata for a single function at 0x1000..0x1100, with the
+    /// unwind info at RVA 0x2000.
+    fn sections_for_unwind_info(unwind_info: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut pdata = Vec::new();
+        pdata.extend_from_slice(&0x1000u32.to_le_bytes());
+        pdata.extend_from_slice(&0x1100u32.to_le_bytes());
+        pdata.extend_from_slice(&0x2000u32.to_le_bytes());
+        (pdata, unwind_info.to_vec())
+    }
+
+    fn unwind(
+        pdata: &[u8],
+        xdata: &[u8],
+        rel_lookup_address: u32,
+        regs: &mut UnwindRegsX86_64,
+        stack: &[u64],
+    ) -> Result<UnwindResult<UnwindRuleX86_64>, PeUnwinderError> {
+        let sections = PeSections {
+            pdata,
+            xdata,
+            xdata_rva: 0x2000,
+        };
+        let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+        ArchX86_64::unwind_frame(
+            sections,
+            None,
+            rel_lookup_address,
+            true,
+            regs,
+            &mut read_stack,
+        )
+    }
+
+    #[test]
+    fn test_push_and_alloc() {
+        // push rbp       ; offset 1
+        // push rbx       ; offset 2
+        // sub rsp, 0x28  ; offset 6
+        let (pdata, xdata) = sections_for_unwind_info(&[
+            0x01, 0x06, 0x03, 0x00, // version 1, prolog size 6, 3 codes, no frame reg
+            0x06, 0x42, // offset 6: UWOP_ALLOC_SMALL (0x28)
+            0x02, 0x30, // offset 2: UWOP_PUSH_NONVOL rbx
+            0x01, 0x50, // offset 1: UWOP_PUSH_NONVOL rbp
+            0x00, 0x00, // padding
+        ]);
+        let mut regs = UnwindRegsX86_64::new(0x1020, 0x10, 0x20);
+        let stack = [0; 16];
+        assert_eq!(
+            unwind(&pdata, &xdata, 0x1020, &mut regs, &stack),
+            Ok(UnwindResult::ExecRule(
+                UnwindRuleX86_64::OffsetSpAndRestoreBp {
+                    sp_offset_by_8: 8,
+                    bp_storage_offset_from_sp_by_8: 6,
+                }
+            ))
+        );
+        // Inside the prolog, after push rbp.
+        assert_eq!(
+            unwind(&pdata, &xdata, 0x1001, &mut regs, &stack),
+            Ok(UnwindResult::ExecRule(
+                UnwindRuleX86_64::OffsetSpAndRestoreBp {
+                    sp_offset_by_8: 2,
+                    bp_storage_offset_from_sp_by_8: 0,
+                }
+            ))
+        );
+        // At the function start.
+        assert_eq!(
+            unwind(&pdata, &xdata, 0x1000, &mut regs, &stack),
+            Ok(UnwindResult::ExecRule(UnwindRuleX86_64::JustReturn))
+        );
+        // Not covered by .pdata: a leaf function.
+        assert_eq!(
+            unwind(&pdata, &xdata, 0x1200, &mut regs, &stack),
+            Ok(UnwindResult::ExecRule(
+                UnwindRuleX86_64::JustReturnIfFirstFrameOtherwiseFp
+            ))
+        );
+    }
+
+    #[test]
+    fn test_frame_pointer() {
+        // push rbp        ; offset 1
+        // mov rbp, rsp    ; offset 4
+        let (pdata, xdata) = sections_for_unwind_info(&[
+            0x01, 0x04, 0x02, 0x05, // version 1, prolog size 4, 2 codes, frame reg rbp
+            0x04, 0x03, // offset 4: UWOP_SET_FPREG
+            0x01, 0x50, // offset 1: UWOP_PUSH_NONVOL rbp
+        ]);
+        let mut regs = UnwindRegsX86_64::new(0x1020, 0x10, 0x20);
+        let stack = [0; 16];
+        assert_eq!(
+            unwind(&pdata, &xdata, 0x1020, &mut regs, &stack),
+            Ok(UnwindResult::ExecRule(UnwindRuleX86_64::UseFramePointer))
+        );
+    }
+
+    #[test]
+    fn test_frame_pointer_with_offset() {
+        // push rbp            ; offset 1
+        // push rsi            ; offset 2
+        // sub rsp, 0x20       ; offset 6
+        // lea rbp, [rsp+0x20] ; offset 11
+        let (pdata, xdata) = sections_for_unwind_info(&[
+            0x01, 0x0b, 0x04, 0x25, // version 1, prolog size 11, 4 codes, rbp = rsp + 0x20
+            0x0b, 0x03, // offset 11: UWOP_SET_FPREG
+            0x06, 0x32, // offset 6: UWOP_ALLOC_SMALL (0x20)
+            0x02, 0x60, // offset 2: UWOP_PUSH_NONVOL rsi
+            0x01, 0x50, // offset 1: UWOP_PUSH_NONVOL rbp
+        ]);
+        // rbp = 0x40, so the frame base is 0x20. rsi is at 0x40, rbp at 0x48,
+        // the return address at 0x50.
+        let mut stack = [0; 16];
+        stack[0x48 / 8] = 0x70;
+        stack[0x50 / 8] = 0x123456;
+        let mut regs = UnwindRegsX86_64::new(0x1020, 0x8, 0x40);
+        assert_eq!(
+            unwind(&pdata, &xdata, 0x1020, &mut regs, &stack),
+            Ok(UnwindResult::Uncacheable(0x123456))
+        );
+        assert_eq!(regs.ip(), 0x123456);
+        assert_eq!(regs.sp(), 0x58);
+        assert_eq!(regs.bp(), 0x70);
+    }
+
+    #[test]
+    fn test_chained() {
+        // The primary function saved rbp and allocated 0x18 bytes. The chained entry at
+        // 0x1100 (for example a cold part of the function) saved rbx at rsp + 0x20.
+        let mut pdata = Vec::new();
+        for (begin, end, info) in [(0x1000u32, 0x1100u32, 0x2000u32), (0x1100, 0x1180, 0x2010)] {
+            pdata.extend_from_slice(&begin.to_le_bytes());
+            pdata.extend_from_slice(&end.to_le_bytes());
+            pdata.extend_from_slice(&info.to_le_bytes());
+        }
+        let xdata = [
+            // 0x2000: primary
+            0x01, 0x05, 0x02, 0x00, // version 1, prolog size 5, 2 codes
+            0x05, 0x22, // offset 5: UWOP_ALLOC_SMALL (0x18)
+            0x01, 0x50, // offset 1: UWOP_PUSH_NONVOL rbp
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // padding
+            // 0x2010: chained
+            0x21, 0x00, 0x02, 0x00, // version 1, UNW_FLAG_CHAININFO, 2 codes
+            0x00, 0x34, // UWOP_SAVE_NONVOL rbx
+            0x04, 0x00, // at offset 4 * 8
+            0x00, 0x10, 0x00, 0x00, 0x00, 0x11, 0x00, 0x00, 0x00, 0x20, 0x00,
+            0x00, // RUNTIME_FUNCTION of the primary
+        ];
+        let mut regs = UnwindRegsX86_64::new(0x1120, 0x10, 0x20);
+        let stack = [0; 16];
+        assert_eq!(
+            unwind(&pdata, &xdata, 0x1120, &mut regs, &stack),
+            Ok(UnwindResult::ExecRule(
+                UnwindRuleX86_64::OffsetSpAndRestoreBp {
+                    sp_offset_by_8: 5,
+                    bp_storage_offset_from_sp_by_8: 3,
+                }
+            ))
+        );
+    }
+}
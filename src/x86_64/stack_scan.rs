@@ -0,0 +1,22 @@
+use super::arch::ArchX86_64;
+use super::unwind_rule::UnwindRuleX86_64;
+use super::unwindregs::UnwindRegsX86_64;
+use crate::stack_scan::StackScanning;
+
+impl StackScanning for ArchX86_64 {
+    fn scan_start(
+        rule: UnwindRuleX86_64,
+        _is_first_frame: bool,
+        regs: &UnwindRegsX86_64,
+    ) -> Option<u64> {
+        match rule {
+            UnwindRuleX86_64::UseFramePointer => Some(regs.sp()),
+            _ => None,
+        }
+    }
+
+    fn set_scanned_frame(regs: &mut UnwindRegsX86_64, location: u64, value: u64) {
+        regs.set_ip(value);
+        regs.set_sp(location + 8);
+    }
+}
@@ -3,7 +3,20 @@ use crate::add_signed::checked_add_signed;
 use crate::error::Error;
 use crate::unwind_rule::UnwindRule;
 
-/// For all of these: return address is *(new_sp - 8)
+/// Offsets of the registers we need in the `ucontext_t` which the Linux kernel pushes for
+/// a signal handler. The stack pointer points to it when the handler returns into
+/// `__restore_rt`.
+const UCONTEXT_RBP: u64 = 120;
+const UCONTEXT_RSP: u64 = 160;
+const UCONTEXT_RIP: u64 = 168;
+
+/// Offsets of the registers we need in the `mcontext64` which the macOS kernel pushes for
+/// a signal handler, i.e. the offsets of `__ss.__rbp`, `__ss.__rsp` and `__ss.__rip`.
+const MCONTEXT_RBP: u64 = 64;
+const MCONTEXT_RSP: u64 = 72;
+const MCONTEXT_RIP: u64 = 144;
+
+/// For all of these except the signal frames: return address is *(new_sp - 8)
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum UnwindRuleX86_64 {
     /// (sp, bp) = (sp + 8, bp)
@@ -19,6 +32,20 @@ pub enum UnwindRuleX86_64 {
     },
     /// (sp, bp) = (bp + 16, *bp)
     UseFramePointer,
+    /// The end of the stack has been reached, there is no caller.
+    EndOfStack,
+    /// (ip, sp, bp) = (*(sp + 168), *(sp + 160), *(sp + 120))
+    /// Used for the Linux signal trampoline `__restore_rt`. The registers are restored
+    /// from the saved `ucontext_t`, and the new ip is the interrupted instruction, not a
+    /// return address.
+    LinuxSignalFrame,
+    /// (ip, sp, bp) = (*(m + 144), *(m + 72), *(m + 64)) with m = sp + 16 or sp + 24
+    /// Used for the macOS signal trampoline `_sigtramp`, whose frame pointer is at sp.
+    /// The kernel stores the `mcontext64` above the trampoline's frame, 16 or 24 bytes
+    /// above it depending on the alignment of the interrupted stack pointer. It's at
+    /// sp + 16 if the saved rbp there matches the rbp that `_sigtramp` pushed at sp.
+    /// The new ip is the interrupted instruction, not a return address.
+    MacOsSignalFrame,
 }
 
 impl UnwindRule for UnwindRuleX86_64 {
@@ -33,6 +60,18 @@ impl UnwindRule for UnwindRuleX86_64 {
     fn fallback_rule() -> Self {
         UnwindRuleX86_64::UseFramePointer
     }
+    fn rule_for_linux_signal_frame() -> Option<Self> {
+        Some(UnwindRuleX86_64::LinuxSignalFrame)
+    }
+    fn rule_for_macos_signal_frame() -> Option<Self> {
+        Some(UnwindRuleX86_64::MacOsSignalFrame)
+    }
+    fn is_signal_frame(self) -> bool {
+        matches!(
+            self,
+            UnwindRuleX86_64::LinuxSignalFrame | UnwindRuleX86_64::MacOsSignalFrame
+        )
+    }
 
     fn exec<F>(
         self,
@@ -45,6 +84,25 @@ impl UnwindRule for UnwindRuleX86_64 {
     {
         let sp = regs.sp();
         let (new_sp, new_bp) = match self {
+            UnwindRuleX86_64::EndOfStack => return Ok(None),
+            UnwindRuleX86_64::LinuxSignalFrame => {
+                let offsets = (UCONTEXT_RIP, UCONTEXT_RSP, UCONTEXT_RBP);
+                return restore_signal_frame(sp, offsets, regs, read_stack);
+            }
+            UnwindRuleX86_64::MacOsSignalFrame => {
+                let pushed_bp = read_stack(sp).map_err(|_| Error::CouldNotReadStack(sp))?;
+                let mut mcontext = sp.checked_add(16).ok_or(Error::IntegerOverflow)?;
+                let saved_bp_location = mcontext
+                    .checked_add(MCONTEXT_RBP)
+                    .ok_or(Error::IntegerOverflow)?;
+                let saved_bp = read_stack(saved_bp_location)
+                    .map_err(|_| Error::CouldNotReadStack(saved_bp_location))?;
+                if saved_bp != pushed_bp {
+                    mcontext += 8;
+                }
+                let offsets = (MCONTEXT_RIP, MCONTEXT_RSP, MCONTEXT_RBP);
+                return restore_signal_frame(mcontext, offsets, regs, read_stack);
+            }
             UnwindRuleX86_64::JustReturn => {
                 let new_sp = sp.checked_add(8).ok_or(Error::IntegerOverflow)?;
                 (new_sp, regs.bp())
@@ -169,6 +227,33 @@ impl UnwindRule for UnwindRuleX86_64 {
     }
 }
 
+/// Restores ip, sp and bp from the registers which the kernel saved at `base` when it
+/// delivered a signal. `offsets` are the offsets of the saved (ip, sp, bp) from `base`.
+fn restore_signal_frame<F>(
+    base: u64,
+    (ip_offset, sp_offset, bp_offset): (u64, u64, u64),
+    regs: &mut UnwindRegsX86_64,
+    read_stack: &mut F,
+) -> Result<Option<u64>, Error>
+where
+    F: FnMut(u64) -> Result<u64, ()>,
+{
+    let mut read = |offset: u64| {
+        let location = base.checked_add(offset).ok_or(Error::IntegerOverflow)?;
+        read_stack(location).map_err(|_| Error::CouldNotReadStack(location))
+    };
+    let ip = read(ip_offset)?;
+    let new_sp = read(sp_offset)?;
+    let new_bp = read(bp_offset)?;
+    if ip == 0 {
+        return Ok(None);
+    }
+    regs.set_ip(ip);
+    regs.set_sp(new_sp);
+    regs.set_bp(new_bp);
+    Ok(Some(ip))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -200,6 +285,52 @@ mod test {
         assert_eq!(res, Ok(None));
     }
 
+    #[test]
+    fn test_linux_signal_frame() {
+        let mut stack = [0; 24];
+        stack[2 + 15] = 0x70;
+        stack[2 + 20] = 0x100;
+        stack[2 + 21] = 0x100500;
+        let mut read_stack = |addr| Ok(stack[(addr / 8) as usize]);
+        let mut regs = UnwindRegsX86_64::new(0x100400, 0x10, 0x20);
+        let res = UnwindRuleX86_64::LinuxSignalFrame.exec(false, &mut regs, &mut read_stack);
+        assert_eq!(res, Ok(Some(0x100500)));
+        assert_eq!(regs.ip(), 0x100500);
+        assert_eq!(regs.sp(), 0x100);
+        assert_eq!(regs.bp(), 0x70);
+    }
+
+    #[test]
+    fn test_macos_signal_frame() {
+        // The mcontext64 is at sp + 16, and the rbp saved in it matches the one which
+        // _sigtramp pushed.
+        let mut stack = [0; 24];
+        stack[2] = 0x70;
+        stack[4 + 8] = 0x70;
+        stack[4 + 9] = 0x100;
+        stack[4 + 18] = 0x100500;
+        let mut read_stack = |addr| Ok(stack[(addr / 8) as usize]);
+        let mut regs = UnwindRegsX86_64::new(0x100400, 0x10, 0x10);
+        let res = UnwindRuleX86_64::MacOsSignalFrame.exec(false, &mut regs, &mut read_stack);
+        assert_eq!(res, Ok(Some(0x100500)));
+        assert_eq!(regs.ip(), 0x100500);
+        assert_eq!(regs.sp(), 0x100);
+        assert_eq!(regs.bp(), 0x70);
+
+        // The mcontext64 is at sp + 24.
+        let mut stack = [0; 25];
+        stack[2] = 0x70;
+        stack[5 + 8] = 0x70;
+        stack[5 + 9] = 0x108;
+        stack[5 + 18] = 0x100508;
+        let mut read_stack = |addr| Ok(stack[(addr / 8) as usize]);
+        let mut regs = UnwindRegsX86_64::new(0x100400, 0x10, 0x10);
+        let res = UnwindRuleX86_64::MacOsSignalFrame.exec(false, &mut regs, &mut read_stack);
+        assert_eq!(res, Ok(Some(0x100508)));
+        assert_eq!(regs.sp(), 0x108);
+        assert_eq!(regs.bp(), 0x70);
+    }
+
     #[test]
     fn test_overflow() {
         // This test makes sure that debug builds don't panic when trying to use frame pointer
@@ -2,11 +2,15 @@ use std::ops::Deref;
 
 use super::arch::ArchX86_64;
 use super::cache::CacheX86_64;
-use super::unwindregs::UnwindRegsX86_64;
+use super::unwind_rule::UnwindRuleX86_64;
+use super::unwindregs::{FullUnwindRegsX86_64, UnwindRegsX86_64};
 use crate::cache::{AllocationPolicy, MayAllocateDuringUnwind};
-use crate::error::Error;
+use crate::error::{Error, ModuleError};
+use crate::frame_info::FrameInfo;
+use crate::return_address_checks::ReturnAddressChecks;
+use crate::stack_memory::IntoStackReadError;
 use crate::unwinder::UnwinderInternal;
-use crate::unwinder::{Module, Unwinder};
+use crate::unwinder::{FrameInfoUnwinder, Module, Unwinder};
 use crate::FrameAddress;
 
 /// The unwinder for the x86_64 CPU architecture. Use the [`Unwinder`] trait for unwinding.
@@ -30,6 +34,43 @@ impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> UnwinderX86_64<D, P> {
     pub fn new() -> Self {
         Self(UnwinderInternal::new())
     }
+
+    /// Enable or disable stack scanning. It is disabled by default.
+    ///
+    /// When frame pointer unwinding fails, for example because `rbp` contains garbage in a
+    /// library which was built without frame pointers and without unwind information, the
+    /// stack is searched upwards from the stack pointer for a value which points into the
+    /// text of a known module, right after a call instruction. This needs the modules'
+    /// [`TextByteData`](crate::TextByteData) and `text` address ranges. Frames found this
+    /// way have the method [`UnwindMethod::StackScan`](crate::UnwindMethod::StackScan).
+    pub fn set_stack_scanning(&mut self, enabled: bool) {
+        self.0.set_stack_scanning(enabled);
+    }
+
+    /// Unwind a single frame in full register unwinding mode. This recovers the caller's
+    /// values of the callee-saved registers `rbx` and `r12` - `r15`, in addition to the registers
+    /// that [`Unwinder::unwind_frame`] recovers.
+    ///
+    /// This is slower than [`Unwinder::unwind_frame`], because the callee-saved registers
+    /// need to be looked up in the unwind information for every frame. They are recovered
+    /// from DWARF CFI and from compact unwind info. For frames whose function is described
+    /// by a different unwind information format, or not described at all, the callee-saved
+    /// registers become unknown (`None`). This is also the case for Linux signal
+    /// trampolines, for which the returned address is the interrupted instruction pointer.
+    pub fn unwind_frame_full<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut FullUnwindRegsX86_64,
+        cache: &mut CacheX86_64<D, P>,
+        read_stack: &mut F,
+    ) -> Result<Option<u64>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        self.0
+            .unwind_frame_full(address, regs, &mut cache.0, read_stack)
+    }
 }
 
 impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> Unwinder for UnwinderX86_64<D, P> {
@@ -41,6 +82,10 @@ impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> Unwinder for UnwinderX86_6
         self.0.add_module(module);
     }
 
+    fn try_add_module(&mut self, module: Module<D>) -> Result<(), ModuleError> {
+        self.0.try_add_module(module)
+    }
+
     fn remove_module(&mut self, module_address_range_start: u64) {
         self.0.remove_module(module_address_range_start);
     }
@@ -49,7 +94,15 @@ impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> Unwinder for UnwinderX86_6
         self.0.max_known_code_address()
     }
 
-    fn unwind_frame<F>(
+    fn check_return_address(
+        &self,
+        address: u64,
+        checks: &ReturnAddressChecks,
+    ) -> Result<(), Error> {
+        self.0.check_return_address(address, checks)
+    }
+
+    fn unwind_frame<F, E>(
         &self,
         address: FrameAddress,
         regs: &mut UnwindRegsX86_64,
@@ -57,8 +110,43 @@ impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> Unwinder for UnwinderX86_6
         read_stack: &mut F,
     ) -> Result<Option<u64>, Error>
     where
-        F: FnMut(u64) -> Result<u64, ()>,
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
     {
         self.0.unwind_frame(address, regs, &mut cache.0, read_stack)
     }
+
+    fn unwind_frame_address<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut UnwindRegsX86_64,
+        cache: &mut CacheX86_64<D, P>,
+        read_stack: &mut F,
+    ) -> Result<Option<FrameAddress>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        self.0
+            .unwind_frame_address(address, regs, &mut cache.0, read_stack)
+    }
+}
+
+impl<D: Deref<Target = [u8]>, P: AllocationPolicy<D>> FrameInfoUnwinder for UnwinderX86_64<D, P> {
+    type UnwindRule = UnwindRuleX86_64;
+
+    fn unwind_frame_with_info<F, E>(
+        &self,
+        address: FrameAddress,
+        regs: &mut UnwindRegsX86_64,
+        cache: &mut CacheX86_64<D, P>,
+        read_stack: &mut F,
+    ) -> Result<Option<FrameInfo<Self::UnwindRule>>, Error>
+    where
+        F: FnMut(u64) -> Result<u64, E>,
+        E: IntoStackReadError,
+    {
+        self.0
+            .unwind_frame_with_info(address, regs, &mut cache.0, read_stack)
+    }
 }
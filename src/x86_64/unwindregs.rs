@@ -51,3 +51,104 @@ impl Debug for UnwindRegsX86_64 {
             .finish()
     }
 }
+
+/// The registers used in full register unwinding mode on x86_64, see
+/// [`UnwinderX86_64::unwind_frame_full`](super::UnwinderX86_64::unwind_frame_full).
+///
+/// In addition to the regular [`UnwindRegsX86_64`], this contains the callee-saved
+/// registers `rbx` and `r12` - `r15`. Their values are optional: A value of `None` means
+/// that the register's value is not known, for example because it could not be recovered
+/// for a caller frame.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct FullUnwindRegsX86_64 {
+    regs: UnwindRegsX86_64,
+    /// rbx, r12, r13, r14, r15, in this order.
+    callee_saved: [Option<u64>; 5],
+}
+
+impl FullUnwindRegsX86_64 {
+    /// Create a set of full unwind registers. The callee-saved registers start out
+    /// as unknown; use the setters to supply their values.
+    pub fn new(regs: UnwindRegsX86_64) -> Self {
+        Self {
+            regs,
+            callee_saved: [None; 5],
+        }
+    }
+
+    /// The regular unwind registers.
+    #[inline(always)]
+    pub fn regs(&self) -> &UnwindRegsX86_64 {
+        &self.regs
+    }
+
+    /// Mutable access to the regular unwind registers.
+    #[inline(always)]
+    pub fn regs_mut(&mut self) -> &mut UnwindRegsX86_64 {
+        &mut self.regs
+    }
+
+    #[inline(always)]
+    pub fn rbx(&self) -> Option<u64> {
+        self.callee_saved[0]
+    }
+    #[inline(always)]
+    pub fn set_rbx(&mut self, rbx: Option<u64>) {
+        self.callee_saved[0] = rbx
+    }
+
+    #[inline(always)]
+    pub fn r12(&self) -> Option<u64> {
+        self.callee_saved[1]
+    }
+    #[inline(always)]
+    pub fn set_r12(&mut self, r12: Option<u64>) {
+        self.callee_saved[1] = r12
+    }
+
+    #[inline(always)]
+    pub fn r13(&self) -> Option<u64> {
+        self.callee_saved[2]
+    }
+    #[inline(always)]
+    pub fn set_r13(&mut self, r13: Option<u64>) {
+        self.callee_saved[2] = r13
+    }
+
+    #[inline(always)]
+    pub fn r14(&self) -> Option<u64> {
+        self.callee_saved[3]
+    }
+    #[inline(always)]
+    pub fn set_r14(&mut self, r14: Option<u64>) {
+        self.callee_saved[3] = r14
+    }
+
+    #[inline(always)]
+    pub fn r15(&self) -> Option<u64> {
+        self.callee_saved[4]
+    }
+    #[inline(always)]
+    pub fn set_r15(&mut self, r15: Option<u64>) {
+        self.callee_saved[4] = r15
+    }
+
+    pub(crate) fn callee_saved_mut(&mut self) -> &mut [Option<u64>; 5] {
+        &mut self.callee_saved
+    }
+}
+
+impl Debug for FullUnwindRegsX86_64 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FullUnwindRegsX86_64")
+            .field("ip", &HexNum(self.regs.ip))
+            .field("sp", &HexNum(self.regs.sp))
+            .field("bp", &HexNum(self.regs.bp))
+            .field("rbx", &self.rbx().map(HexNum))
+            .field("r12", &self.r12().map(HexNum))
+            .field("r13", &self.r13().map(HexNum))
+            .field("r14", &self.r14().map(HexNum))
+            .field("r15", &self.r15().map(HexNum))
+            .finish()
+    }
+}
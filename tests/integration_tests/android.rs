@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use framehop::arm::*;
+use framehop::FrameAddress;
+use framehop::Unwinder;
+
+use super::common;
+
+#[test]
+fn test_arm_exidx() {
+    let mut cache = CacheArm::<_>::new();
+    let mut unwinder = UnwinderArm::new();
+    common::add_object(
+        &mut unwinder,
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/android/arm/nightly-libmozglue.so"),
+        0x10000,
+    );
+
+    // Each stack slot is 4 bytes wide. The upper 32 bits of each value should be ignored.
+    let mut stack = [0u64; 0x20];
+    stack[0x20 / 4] = 0xffff_ffff_0000_0060; // caller fp
+    stack[0x24 / 4] = 0x10000 + 0x34c41; // return address into Thumb code
+    stack[0x60 / 4] = 0x70; // caller fp
+    stack[0x64 / 4] = 0x10000 + 0x34e21; // return address into Thumb code
+    let mut read_stack = |addr| stack.get((addr / 4) as usize).cloned().ok_or(());
+
+    // 0x34cb4: Compact model index: 1
+    //   0x97      vsp = r7
+    //   0x41      vsp = vsp - 8
+    //   0x84 0x0b pop {r4, r5, r7, r14}
+    //   0x00      vsp = vsp + 4
+    //   0xb0      finish
+    let mut regs = UnwindRegsArm::new(0x12345, 0x10, 0x20);
+    let res = unwinder.unwind_frame(
+        FrameAddress::from_instruction_pointer(0x10000 + 0x34cc0),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(0x10000 + 0x34c40)));
+    assert_eq!(regs.sp(), 0x2c);
+    assert_eq!(regs.fp(), 0x60);
+
+    // 0x34c18: Compact model index: 1
+    //   0x97      vsp = r7
+    //   0x46      vsp = vsp - 28
+    //   0x80 0xf0 pop {r8, r9, r10, r11}
+    //   0xab      pop {r4, r5, r6, r7, r14}
+    //   0xb0      finish
+    let res = unwinder.unwind_frame(
+        FrameAddress::from_return_address(0x10000 + 0x34c40).unwrap(),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(0x10000 + 0x34e20)));
+    assert_eq!(regs.sp(), 0x68);
+    assert_eq!(regs.fp(), 0x70);
+}
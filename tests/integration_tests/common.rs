@@ -1,7 +1,9 @@
-use std::{borrow::Cow, io::Read, ops::Range, path::Path};
-
-use object::{Object, ObjectSection, ObjectSegment};
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
 
+use framehop::x86_64::UnwindRegsX86_64;
 use framehop::*;
 
 pub fn add_object<U>(unwinder: &mut U, objpath: &Path, base_avma: u64)
@@ -12,168 +14,248 @@ where
     let mut file = std::fs::File::open(objpath).unwrap();
     file.read_to_end(&mut buf).unwrap();
 
-    fn section_data<'a>(section: &impl ObjectSection<'a>) -> Option<Vec<u8>> {
-        section.data().ok().map(|data| data.to_owned())
+    let module = Module::from_object_file(objpath.to_string_lossy().to_string(), &buf, base_avma)
+        .expect("Could not parse object file");
+    unwinder.add_module(module);
+}
+
+/// The directory of the x86_64 Linux fixtures which were compiled without frame pointers.
+pub fn nofp_fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/linux/x86_64/nofp")
+}
+
+/// The address at which the libmixed-cfi.so fixtures are loaded.
+pub const MIXED_CFI_BASE: u64 = 0x1000000;
+
+/// The instruction pointer of the thread on [`mixed_cfi_stack`]: in `asm_func`, after its
+/// `sub $0x18, %rsp`.
+pub const MIXED_CFI_PC: u64 = 0x1001045;
+
+/// The stack pointer of the thread on [`mixed_cfi_stack`].
+pub const MIXED_CFI_SP: u64 = 0x10;
+
+/// The stack of a thread in libmixed-cfi.so: `asm_func` was called from `c_func`, which
+/// was called from 0x123456. The stack starts at the stack pointer.
+static MIXED_CFI_STACK: [u8; 96] = {
+    let mut bytes = [0; 96];
+    let mut i = 0;
+    while i < 8 {
+        // asm_func's return address, at sp + 0x18.
+        bytes[24 + i] = 0x100102fu64.to_le_bytes()[i];
+        // c_func's return address, at sp + 0x48.
+        bytes[72 + i] = 0x123456u64.to_le_bytes()[i];
+        i += 1;
     }
+    bytes
+};
 
-    let file = object::File::parse(&buf[..]).expect("Could not parse object file");
-
-    let base_svma = relative_address_base(&file);
-
-    let text = file.section_by_name(".text");
-    let stubs = file.section_by_name("__stubs");
-    let stub_helper = file.section_by_name("__stub_helper");
-    let text_env = file.section_by_name("__text_env");
-    let unwind_info = file.section_by_name("__unwind_info");
-    let eh_frame = file.section_by_name(".eh_frame");
-    let got = file.section_by_name(".got");
-    let eh_frame_hdr = file.section_by_name(".eh_frame_hdr");
-    let debug_frame = file
-        .section_by_name(".debug_frame")
-        .or_else(|| file.section_by_name("__zdebug_frame"));
-
-    let unwind_data = match (
-        unwind_info.as_ref().and_then(section_data),
-        eh_frame.as_ref().and_then(section_data),
-        eh_frame_hdr.as_ref().and_then(section_data),
-        debug_frame,
-    ) {
-        (Some(unwind_info), eh_frame, _, _) => {
-            framehop::ModuleUnwindData::CompactUnwindInfoAndEhFrame(unwind_info, eh_frame)
-        }
-        (None, Some(eh_frame), Some(eh_frame_hdr), _) => {
-            framehop::ModuleUnwindData::EhFrameHdrAndEhFrame(eh_frame_hdr, eh_frame)
-        }
-        (None, Some(eh_frame), None, _) => framehop::ModuleUnwindData::EhFrame(eh_frame),
-        (None, None, _, Some(debug_frame)) => {
-            eprintln!("Have debug_frame!");
-            if let Some(section_data) = get_uncompressed_section_data(&debug_frame) {
-                let debug_frame_data = Vec::from(section_data);
-                framehop::ModuleUnwindData::DebugFrame(debug_frame_data)
-            } else {
-                framehop::ModuleUnwindData::None
-            }
-        }
-        (None, None, _, _) => framehop::ModuleUnwindData::None,
-    };
+/// Loads the libmixed-cfi.so variant `file_name` from the nofp fixtures at
+/// [`MIXED_CFI_BASE`].
+pub fn mixed_cfi_module(file_name: &str) -> Module<Vec<u8>> {
+    let data = std::fs::read(nofp_fixtures_dir().join(file_name)).unwrap();
+    Module::from_object_file("libmixed-cfi.so".to_string(), &data, MIXED_CFI_BASE).unwrap()
+}
+
+/// libmixed-cfi-stripped.so, with libmixed-cfi.so.debug as its debug companion.
+pub fn mixed_cfi_module_with_debug_companion() -> Module<Vec<u8>> {
+    let debug_file = std::fs::read(nofp_fixtures_dir().join("libmixed-cfi.so.debug")).unwrap();
+    let mut module = mixed_cfi_module("libmixed-cfi-stripped.so");
+    module
+        .set_debug_companion_from_object_file(&debug_file)
+        .unwrap();
+    module
+}
+
+/// The stack of a thread which is in `asm_func` of libmixed-cfi.so, see [`MIXED_CFI_PC`].
+pub fn mixed_cfi_stack() -> StackMemory<'static> {
+    StackMemory::new(MIXED_CFI_SP, &MIXED_CFI_STACK)
+}
+
+/// The unwind registers of the thread on [`mixed_cfi_stack`].
+pub fn mixed_cfi_regs() -> UnwindRegsX86_64 {
+    UnwindRegsX86_64::new(MIXED_CFI_PC, MIXED_CFI_SP, 0)
+}
+
+/// The addresses of the frames which are found by unwinding the thread on
+/// [`mixed_cfi_stack`]: the instruction pointer and two return addresses.
+pub const MIXED_CFI_ADDRESSES: [u64; 3] = [MIXED_CFI_PC, 0x100102f, 0x123456];
+
+/// The frames which are found by unwinding the thread on [`mixed_cfi_stack`].
+pub fn mixed_cfi_frames() -> Vec<FrameAddress> {
+    let [pc, return_addresses @ ..] = MIXED_CFI_ADDRESSES;
+    let return_addresses = return_addresses
+        .into_iter()
+        .map(|address| FrameAddress::from_return_address(address).unwrap());
+    std::iter::once(FrameAddress::from_instruction_pointer(pc))
+        .chain(return_addresses)
+        .collect()
+}
 
-    let text_data = if let Some(text_segment) = file
-        .segments()
-        .find(|segment| segment.name_bytes() == Ok(Some(b"__TEXT")))
-    {
-        let (start, size) = text_segment.file_range();
-        let avma_range = base_avma + start..base_avma + start + size;
-        text_segment
-            .data()
-            .ok()
-            .map(|data| TextByteData::new(data.to_owned(), avma_range))
-    } else if let Some(text_section) = &text {
-        if let Some((start, size)) = text_section.file_range() {
-            let avma_range = base_avma + start..base_avma + start + size;
-            text_section
-                .data()
-                .ok()
-                .map(|data| TextByteData::new(data.to_owned(), avma_range))
-        } else {
-            None
-        }
-    } else {
-        None
+/// Builds a Linux x86_64 minidump with one module, /usr/lib/libmixed-cfi.so with the code
+/// id "deadbeef", and one thread with id 42 on [`mixed_cfi_stack`]. The stack is also in
+/// the memory list.
+pub fn mixed_cfi_minidump() -> Vec<u8> {
+    let mut dump = vec![0u8; 32 + 4 * 12];
+    let append = |dump: &mut Vec<u8>, bytes: &[u8]| {
+        let rva = dump.len() as u32;
+        dump.extend_from_slice(bytes);
+        rva
     };
+    let le32 = |value: u32| value.to_le_bytes();
+    let le64 = |value: u64| value.to_le_bytes();
 
-    fn svma_range<'a>(section: &Option<impl ObjectSection<'a>>) -> Option<Range<u64>> {
-        section
-            .as_ref()
-            .map(|section| section.address()..section.address() + section.size())
-    }
+    let mut system_info = vec![0u8; 56];
+    system_info[0..2].copy_from_slice(&9u16.to_le_bytes()); // AMD64
+    system_info[20..24].copy_from_slice(&le32(0x8201)); // Linux
+    let system_info_rva = append(&mut dump, &system_info);
+
+    let name: Vec<u8> = "/usr/lib/libmixed-cfi.so"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    let name_rva = append(&mut dump, &le32(name.len() as u32));
+    append(&mut dump, &name);
+    let cv_record = [b"BpEL".as_slice(), &[0xde, 0xad, 0xbe, 0xef]].concat();
+    let cv_record_rva = append(&mut dump, &cv_record);
+    let mut module = vec![0u8; 108];
+    module[0..8].copy_from_slice(&le64(MIXED_CFI_BASE));
+    module[8..12].copy_from_slice(&le32(0x10000));
+    module[20..24].copy_from_slice(&le32(name_rva));
+    module[76..80].copy_from_slice(&le32(cv_record.len() as u32));
+    module[80..84].copy_from_slice(&le32(cv_record_rva));
+    let module_list_rva = append(&mut dump, &[le32(1).as_slice(), &module].concat());
 
-    let module = framehop::Module::new(
-        objpath.to_string_lossy().to_string(),
-        base_avma..(base_avma + buf.len() as u64),
-        base_avma,
-        ModuleSvmaInfo {
-            base_svma,
-            text: svma_range(&text),
-            text_env: svma_range(&text_env),
-            stubs: svma_range(&stubs),
-            stub_helper: svma_range(&stub_helper),
-            eh_frame: svma_range(&eh_frame),
-            eh_frame_hdr: svma_range(&eh_frame_hdr),
-            got: svma_range(&got),
-        },
-        unwind_data,
-        text_data,
+    let stack = mixed_cfi_stack().bytes();
+    let stack_rva = append(&mut dump, stack);
+    let mut context = vec![0u8; 1232];
+    context[152..160].copy_from_slice(&le64(MIXED_CFI_SP)); // rsp
+    context[248..256].copy_from_slice(&le64(MIXED_CFI_PC)); // rip
+    let context_rva = append(&mut dump, &context);
+    let mut thread = vec![0u8; 48];
+    thread[0..4].copy_from_slice(&le32(42));
+    thread[24..32].copy_from_slice(&le64(MIXED_CFI_SP));
+    thread[32..36].copy_from_slice(&le32(stack.len() as u32));
+    thread[36..40].copy_from_slice(&le32(stack_rva));
+    thread[40..44].copy_from_slice(&le32(context.len() as u32));
+    thread[44..48].copy_from_slice(&le32(context_rva));
+    let thread_list_rva = append(&mut dump, &[le32(1).as_slice(), &thread].concat());
+
+    let mut memory_descriptor = le64(MIXED_CFI_SP).to_vec();
+    memory_descriptor.extend_from_slice(&le32(stack.len() as u32));
+    memory_descriptor.extend_from_slice(&le32(stack_rva));
+    let memory_list_rva = append(
+        &mut dump,
+        &[le32(1).as_slice(), &memory_descriptor].concat(),
     );
-    unwinder.add_module(module);
-}
 
-fn get_uncompressed_section_data<'a>(
-    section: &impl object::ObjectSection<'a>,
-) -> Option<Cow<'a, [u8]>> {
-    let section_data = section.uncompressed_data().ok()?;
-
-    // Make sure the data is actually decompressed.
-    if section.name_bytes().ok()?.starts_with(b"__zdebug_")
-        && section_data.starts_with(b"ZLIB\0\0\0\0")
-    {
-        // Object's built-in compressed section handling didn't detect this as a
-        // compressed section. This happens on Go binaries which use compressed
-        // sections like __zdebug_ranges, which is generally uncommon on macOS, so
-        // object's mach-O parser doesn't handle them.
-        // But we want to handle them.
-        // Go stopped using zdebug sections for ELF files in https://github.com/golang/go/issues/50796
-        // but still uses them for mach-O builds.
-        let b = section_data.get(8..12)?;
-        let uncompressed_size = u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
-        let compressed_bytes = &section_data[12..];
-
-        let mut decompressed = Vec::with_capacity(uncompressed_size as usize);
-        let mut decompress = flate2::Decompress::new(true);
-        decompress
-            .decompress_vec(
-                compressed_bytes,
-                &mut decompressed,
-                flate2::FlushDecompress::Finish,
-            )
-            .ok()?;
-        Some(Cow::Owned(decompressed))
-    } else {
-        Some(section_data)
+    dump[0..4].copy_from_slice(b"MDMP");
+    dump[4..8].copy_from_slice(&le32(0xa793));
+    dump[8..12].copy_from_slice(&le32(4));
+    dump[12..16].copy_from_slice(&le32(32));
+    let streams = [
+        (7, 56, system_info_rva),
+        (4, 4 + 108, module_list_rva),
+        (3, 4 + 48, thread_list_rva),
+        (5, 4 + 16, memory_list_rva),
+    ];
+    for (i, (stream_type, size, rva)) in streams.into_iter().enumerate() {
+        let entry = 32 + i * 12;
+        dump[entry..entry + 4].copy_from_slice(&le32(stream_type));
+        dump[entry + 4..entry + 8].copy_from_slice(&le32(size));
+        dump[entry + 8..entry + 12].copy_from_slice(&le32(rva));
     }
+    dump
 }
 
-/// Relative addresses are u32 offsets which are relative to some "base address".
-///
-/// This function computes that base address. It is defined as follows:
-///
-///  - For Windows binaries, the base address is the "image base address".
-///  - For mach-O binaries, the base address is the vmaddr of the __TEXT segment.
-///  - For ELF binaries, the base address is zero.
-///
-/// Stand-alone mach-O dylibs usually have a base address of zero because their
-/// __TEXT segment is at address zero.
-///
-/// In the following cases, the base address is usually non-zero:
-///
-///  - The "image base address" of Windows binaries is usually non-zero.
-///  - mach-O executable files (not dylibs) usually have their __TEXT segment at
-///    address 0x100000000.
-///  - mach-O libraries in the dyld shared cache have a __TEXT segment at some
-///    non-zero address in the cache.
-pub fn relative_address_base<'data: 'file, 'file>(
-    object_file: &'file impl object::Object<'data, 'file>,
-) -> u64 {
-    if let Some(text_segment) = object_file
-        .segments()
-        .find(|s| s.name() == Ok(Some("__TEXT")))
-    {
-        // This is a mach-O image. "Relative addresses" are relative to the
-        // vmaddr of the __TEXT segment.
-        return text_segment.address();
-    }
+/// Builds an x86_64 Linux core dump with one thread with id 42 on [`mixed_cfi_stack`], and
+/// the dumped first page of /usr/lib/libmixed-cfi.so, whose build id is "deadbeef". If
+/// `extended_phnum` is true, the number of program headers is stored in section header 0,
+/// like in core dumps with more than 0xfffe program headers.
+pub fn mixed_cfi_core_dump(extended_phnum: bool) -> Vec<u8> {
+    let le16 = |value: u16| value.to_le_bytes();
+    let le32 = |value: u32| value.to_le_bytes();
+    let le64 = |value: u64| value.to_le_bytes();
+    let note = |note_type: u32, name: &[u8], desc: &[u8]| {
+        let mut note = [
+            le32(name.len() as u32),
+            le32(desc.len() as u32),
+            le32(note_type),
+        ]
+        .concat();
+        note.extend_from_slice(name);
+        note.resize((note.len() + 3) & !3, 0);
+        note.extend_from_slice(desc);
+        note.resize((note.len() + 3) & !3, 0);
+        note
+    };
+    let program_header = |p_type: u32, offset: usize, vaddr: u64, size: usize| {
+        let mut header = vec![0u8; 56];
+        header[0..4].copy_from_slice(&le32(p_type));
+        header[8..16].copy_from_slice(&le64(offset as u64));
+        header[16..24].copy_from_slice(&le64(vaddr));
+        header[32..40].copy_from_slice(&le64(size as u64));
+        header
+    };
+    let elf_header = |e_type: u16, phnum: u16| {
+        let mut header = vec![0u8; 64];
+        header[0..6].copy_from_slice(b"\x7fELF\x02\x01");
+        header[16..18].copy_from_slice(&le16(e_type));
+        header[18..20].copy_from_slice(&le16(62)); // x86_64
+        header[32..40].copy_from_slice(&le64(64));
+        header[54..56].copy_from_slice(&le16(56));
+        header[56..58].copy_from_slice(&le16(phnum));
+        header
+    };
+
+    // The dumped first page of libmixed-cfi.so, with a build id note.
+    let build_id_note = note(3, b"GNU\0", &[0xde, 0xad, 0xbe, 0xef]);
+    let mut module_page = elf_header(3, 1);
+    module_page.extend(program_header(4, 120, 0, build_id_note.len()));
+    module_page.extend(&build_id_note);
 
-    // For PE binaries, relative_address_base() returns the image base address.
-    // Otherwise it returns zero. This gives regular ELF images a base address of zero,
-    // which is what we want.
-    object_file.relative_address_base()
+    let mut prstatus = vec![0u8; 336];
+    prstatus[32..36].copy_from_slice(&le32(42));
+    prstatus[112 + 152..112 + 160].copy_from_slice(&le64(MIXED_CFI_SP)); // rsp
+    prstatus[112 + 128..112 + 136].copy_from_slice(&le64(MIXED_CFI_PC)); // rip
+    let mut file = [
+        le64(1),
+        le64(0x1000),
+        le64(MIXED_CFI_BASE),
+        le64(MIXED_CFI_BASE + 0x10000),
+        le64(0),
+    ]
+    .concat();
+    file.extend_from_slice(b"/usr/lib/libmixed-cfi.so\0");
+    let notes = [
+        note(1, b"CORE\0", &prstatus),
+        note(0x46494c45, b"CORE\0", &file),
+    ]
+    .concat();
+
+    let stack = mixed_cfi_stack().bytes();
+    let notes_offset = 64 + 3 * 56;
+    let stack_offset = notes_offset + notes.len();
+    let module_page_offset = stack_offset + stack.len();
+    let mut core = elf_header(4, if extended_phnum { 0xffff } else { 3 });
+    core.extend(program_header(4, notes_offset, 0, notes.len()));
+    core.extend(program_header(1, stack_offset, MIXED_CFI_SP, stack.len()));
+    core.extend(program_header(
+        1,
+        module_page_offset,
+        MIXED_CFI_BASE,
+        module_page.len(),
+    ));
+    core.extend(&notes);
+    core.extend(stack);
+    core.extend(&module_page);
+    if extended_phnum {
+        let section_header_offset = core.len() as u64;
+        core[40..48].copy_from_slice(&le64(section_header_offset)); // e_shoff
+        core[58..60].copy_from_slice(&le16(64)); // e_shentsize
+        core[60..62].copy_from_slice(&le16(1)); // e_shnum
+        let mut section_header = vec![0u8; 64];
+        section_header[44..48].copy_from_slice(&le32(3)); // sh_info
+        core.extend(section_header);
+    }
+    core
 }
@@ -3,7 +3,13 @@ use std::path::Path;
 use framehop::aarch64::*;
 use framehop::x86_64::*;
 use framehop::FrameAddress;
+use framehop::FrameInfoUnwinder;
 use framehop::Unwinder;
+use framehop::{
+    BatchOptions, EhFrameHdrError, Error, FrameInfo, Module, ModuleError, ModuleSvmaInfo,
+    ModuleUnwindData, ReturnAddressCheckFailure, ReturnAddressChecks, SerializedCfiIndexError,
+    StackMemory, StackReadError, StackStatus, UnwindMethod, UnwindSectionType, UnwinderError,
+};
 
 use super::common;
 
@@ -376,3 +382,1601 @@ fn test_libc_syscall_no_fde() {
     assert_eq!(regs.sp(), 0x338);
     assert_eq!(regs.bp(), 0x348);
 }
+
+#[test]
+fn test_breakpad_sym() {
+    let sym = b"MODULE Linux x86_64 0123456789ABCDEF0123456789ABCDEF0 libtest.so
+FUNC 1000 40 0 outer
+FUNC 2000 20 0 inner
+STACK CFI INIT 1000 40 .cfa: $rsp 8 + .ra: .cfa -8 + ^
+STACK CFI 1001 .cfa: $rsp 16 + $rbp: .cfa -16 + ^
+STACK CFI 1004 .cfa: $rbp 16 +
+STACK CFI INIT 2000 20 .cfa: $rsp 8 + .ra: .cfa -8 + ^
+STACK CFI 2004 .cfa: $rsp 32 +
+"
+    .to_vec();
+    let mut cache = CacheX86_64::<_>::new();
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(framehop::Module::new(
+        "libtest.so".to_string(),
+        0x1000000..0x1010000,
+        0x1000000,
+        framehop::ModuleSvmaInfo {
+            base_svma: 0,
+            text: None,
+            text_env: None,
+            stubs: None,
+            stub_helper: None,
+            eh_frame: None,
+            eh_frame_hdr: None,
+            got: None,
+            ..Default::default()
+        },
+        framehop::ModuleUnwindData::BreakpadSym(sym),
+        None,
+    ));
+
+    // inner has allocated 24 bytes; outer has set up a frame pointer.
+    let stack = [1, 2, 3, 0x1000020, 0x50, 0x1234567, 6, 7, 8, 9, 0, 0];
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+    let mut regs = UnwindRegsX86_64::new(0x1002010, 0x0, 0x20);
+    let mut frames = Vec::new();
+    let mut iter = unwinder.iter_frames(0x1002010, regs, &mut cache, &mut read_stack);
+    while let Ok(Some(frame)) = iter.next() {
+        frames.push(frame);
+    }
+    assert_eq!(
+        frames,
+        vec![
+            FrameAddress::from_instruction_pointer(0x1002010),
+            FrameAddress::from_return_address(0x1000020).unwrap(),
+            FrameAddress::from_return_address(0x1234567).unwrap(),
+        ]
+    );
+
+    let res = unwinder.unwind_frame(
+        FrameAddress::from_instruction_pointer(0x1002010),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(0x1000020)));
+    assert_eq!(regs.sp(), 0x20);
+    assert_eq!(regs.bp(), 0x20);
+}
+
+#[test]
+fn test_eh_frame_and_debug_frame() {
+    // libmixed-cfi.so was compiled with -fno-asynchronous-unwind-tables -g, so its C
+    // function c_func is only described in .debug_frame. The hand-written assembly
+    // function asm_func has .cfi directives, so it is only described in .eh_frame.
+    // libmixed-cfi-nohdr.so is the same library linked without .eh_frame_hdr, so both
+    // sections are indexed.
+    let mut cache = CacheX86_64::<_>::new();
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(common::mixed_cfi_module("libmixed-cfi-nohdr.so"));
+
+    let mut read_stack = common::mixed_cfi_stack().reader();
+
+    // In asm_func, after sub $0x18, %rsp.
+    let mut regs = common::mixed_cfi_regs();
+    let res = unwinder.unwind_frame(
+        FrameAddress::from_instruction_pointer(common::MIXED_CFI_PC),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(0x100102f)));
+    assert_eq!(regs.sp(), 0x30);
+
+    // In c_func, after the call to asm_func.
+    let res = unwinder.unwind_frame(
+        FrameAddress::from_return_address(0x100102f).unwrap(),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(0x123456)));
+    assert_eq!(regs.sp(), 0x60);
+}
+
+#[test]
+fn test_eh_frame_hdr_and_debug_frame() {
+    // With .eh_frame_hdr, .eh_frame is not indexed. The functions which are only
+    // described in .debug_frame are found by using .debug_frame as the debug companion.
+    let mut cache = CacheX86_64::<_>::new();
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(common::mixed_cfi_module("libmixed-cfi.so"));
+
+    let mut read_stack = common::mixed_cfi_stack().reader();
+    let mut iter = unwinder.iter_frames_with_info(
+        common::MIXED_CFI_PC,
+        common::mixed_cfi_regs(),
+        &mut cache,
+        &mut read_stack,
+    );
+    let mut frames = Vec::new();
+    while let Ok(Some(frame)) = iter.next() {
+        frames.push((frame.address, frame.method));
+    }
+    assert_eq!(
+        frames,
+        vec![
+            (
+                FrameAddress::from_instruction_pointer(0x1001045),
+                UnwindMethod::InstructionPointer
+            ),
+            (
+                FrameAddress::from_return_address(0x100102f).unwrap(),
+                UnwindMethod::EhFrameHdr
+            ),
+            (
+                FrameAddress::from_return_address(0x123456).unwrap(),
+                UnwindMethod::DebugCompanion
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_debug_companion() {
+    // libmixed-cfi-stripped.so is libmixed-cfi.so with its .debug_frame removed, and
+    // libmixed-cfi.so.debug is the separate debug file which has the .debug_frame.
+    // c_func is only described in .debug_frame, so it needs the debug companion.
+    let module = common::mixed_cfi_module_with_debug_companion();
+
+    let mut cache = CacheX86_64::<_>::new();
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(module);
+
+    let mut read_stack = common::mixed_cfi_stack().reader();
+
+    // In asm_func, after sub $0x18, %rsp. This is covered by .eh_frame.
+    let mut regs = common::mixed_cfi_regs();
+    let res = unwinder.unwind_frame(
+        FrameAddress::from_instruction_pointer(common::MIXED_CFI_PC),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(0x100102f)));
+    assert_eq!(regs.sp(), 0x30);
+
+    // In c_func, after the call to asm_func. This is only covered by the debug companion.
+    let res = unwinder.unwind_frame(
+        FrameAddress::from_return_address(0x100102f).unwrap(),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(0x123456)));
+    assert_eq!(regs.sp(), 0x60);
+}
+
+#[test]
+fn test_prologue_epilogue_without_cfi() {
+    // libnocfi.so has a hand-written function, nocfi_func, which has no CFI. It pushes
+    // rbx and r12, adjusts the stack pointer, and undoes all of that in its epilogue.
+    let mut cache = CacheX86_64::<_>::new();
+    let mut unwinder = UnwinderX86_64::new();
+    common::add_object(
+        &mut unwinder,
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/linux/x86_64/nofp/libnocfi.so"),
+        0x1000000,
+    );
+
+    let stack = [1, 2, 3, 0x1001006, 5, 6, 7, 8];
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+
+    // In the prologue, after push rbx, at push r12.
+    let mut regs = UnwindRegsX86_64::new(0x1001009, 0x10, 0x1234);
+    let res = unwinder.unwind_frame_with_info(
+        FrameAddress::from_instruction_pointer(0x1001009),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    let frame_info = res.unwrap().unwrap();
+    assert_eq!(
+        frame_info.address,
+        FrameAddress::from_return_address(0x1001006).unwrap()
+    );
+    assert_eq!(frame_info.method, UnwindMethod::PrologueEpilogueAnalysis);
+    assert_eq!(regs.sp(), 0x20);
+    assert_eq!(regs.bp(), 0x1234);
+
+    // In the epilogue, after pop r12, at pop rbx.
+    let mut regs = UnwindRegsX86_64::new(0x100101d, 0x10, 0x1234);
+    let res = unwinder.unwind_frame_with_info(
+        FrameAddress::from_instruction_pointer(0x100101d),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    let frame_info = res.unwrap().unwrap();
+    assert_eq!(
+        frame_info.address,
+        FrameAddress::from_return_address(0x1001006).unwrap()
+    );
+    assert_eq!(frame_info.method, UnwindMethod::PrologueEpilogueAnalysis);
+    assert_eq!(regs.sp(), 0x20);
+
+    // In the function body, instruction analysis doesn't find anything, so the rule for
+    // code without CFI is used.
+    let mut regs = UnwindRegsX86_64::new(0x1001016, 0x10, 0x1234);
+    let res = unwinder.unwind_frame_with_info(
+        FrameAddress::from_instruction_pointer(0x1001016),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    let frame_info = res.unwrap().unwrap();
+    assert_eq!(frame_info.method, UnwindMethod::UncoveredByFde);
+    assert_eq!(
+        frame_info.rule,
+        Some(UnwindRuleX86_64::JustReturnIfFirstFrameOtherwiseFp)
+    );
+
+    // nocfi_func2 has no CFI either, and it directly follows const_func, whose FDE ends
+    // with bytes which look like push instructions. The prologue analysis must stop at
+    // the end of that FDE. Here we're after push rbx, at sub rsp.
+    let mut regs = UnwindRegsX86_64::new(0x1001030, 0x10, 0x1234);
+    let res = unwinder.unwind_frame_with_info(
+        FrameAddress::from_instruction_pointer(0x1001030),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    let frame_info = res.unwrap().unwrap();
+    assert_eq!(
+        frame_info.address,
+        FrameAddress::from_return_address(0x1001006).unwrap()
+    );
+    assert_eq!(frame_info.method, UnwindMethod::PrologueEpilogueAnalysis);
+    assert_eq!(
+        frame_info.rule,
+        Some(UnwindRuleX86_64::OffsetSp { sp_offset_by_8: 2 })
+    );
+    assert_eq!(regs.sp(), 0x20);
+}
+
+#[test]
+fn test_full_regs() {
+    // libfullregs.so has two hand-written functions: outer_func pushes rbx and r12 and
+    // calls inner_func, which pushes r15 and rbx.
+    let mut cache = CacheX86_64::<_>::new();
+    let mut unwinder = UnwinderX86_64::new();
+    common::add_object(
+        &mut unwinder,
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/linux/x86_64/nofp/libfullregs.so"),
+        0x1000000,
+    );
+
+    let mut stack = [0; 10];
+    stack[2] = 0x1; // rbx, pushed by inner_func
+    stack[3] = 0xf0; // r15, pushed by inner_func
+    stack[4] = 0x100101a; // return address into outer_func
+    stack[6] = 0x12; // r12, pushed by outer_func
+    stack[7] = 0x11; // rbx, pushed by outer_func
+    stack[8] = 0x123456;
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+
+    // In inner_func, at the nop after overwriting rbx.
+    let mut regs = FullUnwindRegsX86_64::new(UnwindRegsX86_64::new(0x100102c, 0x10, 0x0));
+    regs.set_rbx(Some(0x3));
+    regs.set_r12(Some(0x2));
+    regs.set_r13(Some(0xd13));
+    regs.set_r15(Some(0xf15));
+    let res = unwinder.unwind_frame_full(
+        FrameAddress::from_instruction_pointer(0x100102c),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(0x100101a)));
+    assert_eq!(regs.regs().sp(), 0x28);
+    assert_eq!(regs.rbx(), Some(0x1));
+    assert_eq!(regs.r12(), Some(0x2));
+    assert_eq!(regs.r13(), Some(0xd13));
+    assert_eq!(regs.r14(), None);
+    assert_eq!(regs.r15(), Some(0xf0));
+
+    // In outer_func, after the call to inner_func.
+    let res = unwinder.unwind_frame_full(
+        FrameAddress::from_return_address(0x100101a).unwrap(),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(0x123456)));
+    assert_eq!(regs.regs().sp(), 0x48);
+    assert_eq!(regs.rbx(), Some(0x11));
+    assert_eq!(regs.r12(), Some(0x12));
+    assert_eq!(regs.r13(), Some(0xd13));
+    assert_eq!(regs.r15(), Some(0xf0));
+}
+
+#[test]
+fn test_frame_info() {
+    let module = common::mixed_cfi_module_with_debug_companion();
+
+    let mut cache = CacheX86_64::<_>::new();
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(module);
+
+    let mut read_stack = common::mixed_cfi_stack().reader();
+
+    // The second pass gets all rules from the cache.
+    for cache_hit in [false, true] {
+        let mut iter = unwinder.iter_frames_with_info(
+            common::MIXED_CFI_PC,
+            common::mixed_cfi_regs(),
+            &mut cache,
+            &mut read_stack,
+        );
+        let mut frames = Vec::new();
+        while let Ok(Some(frame)) = iter.next() {
+            frames.push(frame);
+        }
+        assert_eq!(
+            frames,
+            vec![
+                FrameInfo {
+                    address: FrameAddress::from_instruction_pointer(0x1001045),
+                    method: UnwindMethod::InstructionPointer,
+                    rule: None,
+                    cache_hit: false,
+                    suppressed_error: None,
+                },
+                FrameInfo {
+                    address: FrameAddress::from_return_address(0x100102f).unwrap(),
+                    method: UnwindMethod::EhFrameHdr,
+                    rule: Some(UnwindRuleX86_64::OffsetSp { sp_offset_by_8: 4 }),
+                    cache_hit,
+                    suppressed_error: None,
+                },
+                FrameInfo {
+                    address: FrameAddress::from_return_address(0x123456).unwrap(),
+                    method: UnwindMethod::DebugCompanion,
+                    rule: Some(UnwindRuleX86_64::OffsetSp { sp_offset_by_8: 6 }),
+                    cache_hit,
+                    suppressed_error: None,
+                },
+            ]
+        );
+    }
+}
+
+#[test]
+fn test_frame_info_suppressed_error() {
+    let mut cache = CacheX86_64::<Vec<u8>>::new();
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(Module::new(
+        "no-unwind-data.so".to_string(),
+        0x1000000..0x1010000,
+        0x1000000,
+        ModuleSvmaInfo {
+            base_svma: 0,
+            text: None,
+            text_env: None,
+            stubs: None,
+            stub_helper: None,
+            eh_frame: None,
+            eh_frame_hdr: None,
+            got: None,
+            ..Default::default()
+        },
+        ModuleUnwindData::None,
+        None,
+    ));
+
+    let stack = [0, 0, 0x30, 0x1000123, 0, 0, 0, 0];
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+    let mut regs = UnwindRegsX86_64::new(0x1000100, 0x8, 0x10);
+    let res = unwinder.unwind_frame_with_info(
+        FrameAddress::from_instruction_pointer(0x1000100),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(
+        res,
+        Ok(Some(FrameInfo {
+            address: FrameAddress::from_return_address(0x1000123).unwrap(),
+            method: UnwindMethod::Fallback,
+            rule: Some(UnwindRuleX86_64::UseFramePointer),
+            cache_hit: false,
+            suppressed_error: Some(UnwinderError::NoModuleUnwindData),
+        }))
+    );
+    assert_eq!(regs.sp(), 0x20);
+    assert_eq!(regs.bp(), 0x30);
+}
+
+/// A tracing subscriber which records the fields of all events.
+struct EventRecorder(std::sync::Mutex<Vec<Vec<(String, String)>>>);
+
+impl EventRecorder {
+    fn new() -> Self {
+        Self(std::sync::Mutex::new(Vec::new()))
+    }
+}
+
+impl tracing::Subscriber for &'static EventRecorder {
+    fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        tracing::span::Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        struct Visitor(Vec<(String, String)>);
+        impl tracing::field::Visit for Visitor {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                self.0
+                    .push((field.name().to_string(), format!("{value:?}")));
+            }
+        }
+        let mut visitor = Visitor(Vec::new());
+        event.record(&mut visitor);
+        self.0.lock().unwrap().push(visitor.0);
+    }
+
+    fn enter(&self, _span: &tracing::span::Id) {}
+
+    fn exit(&self, _span: &tracing::span::Id) {}
+}
+
+#[test]
+fn test_tracing_events() {
+    let recorder: &'static EventRecorder = Box::leak(Box::new(EventRecorder::new()));
+    let svma_info = ModuleSvmaInfo {
+        base_svma: 0,
+        text: None,
+        text_env: None,
+        stubs: None,
+        stub_helper: None,
+        eh_frame: Some(0x1000..0x1010),
+        eh_frame_hdr: None,
+        got: None,
+        ..Default::default()
+    };
+    tracing::subscriber::with_default(recorder, || {
+        let mut unwinder = UnwinderX86_64::<_>::new();
+        for name in ["libfirst.so", "libsecond.so"] {
+            unwinder.add_module(Module::new(
+                name.to_string(),
+                0x1000000..0x1010000,
+                0x1000000,
+                svma_info.clone(),
+                ModuleUnwindData::EhFrame(vec![0xff; 16]),
+                None,
+            ));
+        }
+    });
+
+    let events = recorder.0.lock().unwrap();
+    let field = |event: &[(String, String)], name: &str| {
+        event
+            .iter()
+            .find(|(field_name, _)| field_name == name)
+            .map(|(_, value)| value.clone())
+    };
+    assert_eq!(events.len(), 3);
+    assert_eq!(field(&events[0], "module").as_deref(), Some("libfirst.so"));
+    assert!(field(&events[0], "error").unwrap().contains(".eh_frame"));
+    assert_eq!(field(&events[1], "module").as_deref(), Some("libsecond.so"));
+    assert_eq!(
+        field(&events[2], "message").as_deref(),
+        Some("Two modules have the same start address")
+    );
+    assert_eq!(field(&events[2], "module").as_deref(), Some("libsecond.so"));
+    assert_eq!(
+        field(&events[2], "existing_module").as_deref(),
+        Some("libfirst.so")
+    );
+    assert_eq!(field(&events[2], "address").as_deref(), Some("0x1000000"));
+}
+
+#[test]
+fn test_try_add_module() {
+    let module = common::mixed_cfi_module("libmixed-cfi-stripped.so");
+    let mut unwinder = UnwinderX86_64::<_>::new();
+    assert_eq!(unwinder.try_add_module(module), Ok(()));
+    assert_ne!(unwinder.max_known_code_address(), 0);
+
+    let svma_info = ModuleSvmaInfo {
+        base_svma: 0,
+        text: None,
+        text_env: None,
+        stubs: None,
+        stub_helper: None,
+        eh_frame: Some(0x1000..0x1010),
+        eh_frame_hdr: Some(0x2000..0x2008),
+        got: None,
+        ..Default::default()
+    };
+    let mut unwinder = UnwinderX86_64::<Vec<u8>>::new();
+    let result = unwinder.try_add_module(Module::new(
+        "libbad.so".to_string(),
+        0x1000000..0x1010000,
+        0x1000000,
+        svma_info.clone(),
+        ModuleUnwindData::EhFrame(vec![0xff; 16]),
+        None,
+    ));
+    assert!(matches!(result, Err(ModuleError::EhFrameIndex(_))));
+    // The module was not added.
+    assert_eq!(unwinder.max_known_code_address(), 0);
+
+    // An .eh_frame_hdr with an eh_frame_ptr but without a search table.
+    let eh_frame_hdr = vec![1, 0x03, 0xff, 0xff, 0x00, 0x10, 0x00, 0x00];
+    let notable_module = || {
+        Module::new(
+            "libnotable.so".to_string(),
+            0x1000000..0x1010000,
+            0x1000000,
+            svma_info.clone(),
+            ModuleUnwindData::EhFrameHdrAndEhFrame(eh_frame_hdr.clone(), vec![0; 16]),
+            None,
+        )
+    };
+    let result = unwinder.try_add_module(notable_module());
+    assert_eq!(
+        result,
+        Err(ModuleError::BadEhFrameHdr(EhFrameHdrError::NoSearchTable))
+    );
+    assert_eq!(unwinder.max_known_code_address(), 0);
+
+    // The rejected module can still be added with add_module.
+    unwinder.add_module(notable_module());
+    assert_eq!(unwinder.max_known_code_address(), 0x1010000);
+}
+
+#[test]
+fn test_shared_rule_cache() {
+    let module = common::mixed_cfi_module_with_debug_companion();
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(module);
+
+    let unwind = |cache: &mut CacheX86_64<_>| {
+        let mut read_stack = common::mixed_cfi_stack().reader();
+        let mut iter = unwinder.iter_frames(
+            common::MIXED_CFI_PC,
+            common::mixed_cfi_regs(),
+            cache,
+            &mut read_stack,
+        );
+        let mut frames = Vec::new();
+        while let Ok(Some(frame)) = iter.next() {
+            frames.push(frame);
+        }
+        assert_eq!(frames, common::mixed_cfi_frames());
+    };
+
+    let shared = SharedCacheX86_64::new();
+    // Warm up the shared cache on this thread.
+    unwind(&mut CacheX86_64::with_shared_rule_cache(&shared));
+    // The other threads have their own caches, but they get the rules from the shared cache.
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| unwind(&mut CacheX86_64::with_shared_rule_cache(&shared)));
+        }
+    });
+    let stats = shared.stats();
+    assert_eq!(stats.misses(), 3);
+    assert_eq!(stats.hits(), 12);
+}
+
+#[test]
+fn test_serialized_cfi_index() {
+    let new_module = || common::mixed_cfi_module("libmixed-cfi-nohdr.so");
+    let module = new_module();
+    let eh_frame_index = module
+        .serialize_cfi_index(UnwindSectionType::EhFrame, 8)
+        .unwrap()
+        .unwrap();
+    let debug_frame_index = module
+        .serialize_cfi_index(UnwindSectionType::DebugFrame, 8)
+        .unwrap()
+        .unwrap();
+
+    let check_unwind = |unwinder: &UnwinderX86_64<Vec<u8>>| {
+        let mut cache = CacheX86_64::<_>::new();
+        let mut read_stack = common::mixed_cfi_stack().reader();
+        let mut iter = unwinder.iter_frames_with_info(
+            common::MIXED_CFI_PC,
+            common::mixed_cfi_regs(),
+            &mut cache,
+            &mut read_stack,
+        );
+        let mut frames = Vec::new();
+        while let Ok(Some(frame)) = iter.next() {
+            frames.push((frame.address, frame.method));
+        }
+        assert_eq!(
+            frames,
+            vec![
+                (
+                    FrameAddress::from_instruction_pointer(0x1001045),
+                    UnwindMethod::InstructionPointer
+                ),
+                (
+                    FrameAddress::from_return_address(0x100102f).unwrap(),
+                    UnwindMethod::DwarfCfiIndex
+                ),
+                (
+                    FrameAddress::from_return_address(0x123456).unwrap(),
+                    UnwindMethod::DwarfCfiIndex
+                ),
+            ]
+        );
+    };
+
+    let mut module = new_module();
+    module.set_cfi_index(UnwindSectionType::EhFrame, eh_frame_index.clone());
+    module.set_cfi_index(UnwindSectionType::DebugFrame, debug_frame_index);
+    let mut unwinder = UnwinderX86_64::new();
+    assert_eq!(unwinder.try_add_module(module), Ok(()));
+    check_unwind(&unwinder);
+
+    // An index for a different architecture is rejected, and a new index is created.
+    let mut module = new_module();
+    let arm_index = module
+        .serialize_cfi_index(UnwindSectionType::EhFrame, 4)
+        .unwrap()
+        .unwrap();
+    module.set_cfi_index(UnwindSectionType::EhFrame, arm_index);
+    let mut unwinder = UnwinderX86_64::new();
+    assert_eq!(
+        unwinder.try_add_module(module),
+        Err(ModuleError::BadSerializedCfiIndex(
+            SerializedCfiIndexError::WrongSection
+        ))
+    );
+    let mut module = new_module();
+    let mut huge_index = eh_frame_index.clone();
+    // Claim u32::MAX entries.
+    huge_index[20..24].copy_from_slice(&[0xff; 4]);
+    module.set_cfi_index(UnwindSectionType::EhFrame, huge_index);
+    assert_eq!(
+        unwinder.try_add_module(module),
+        Err(ModuleError::BadSerializedCfiIndex(
+            SerializedCfiIndexError::Truncated
+        ))
+    );
+    let mut module = new_module();
+    let mut changed_index = eh_frame_index;
+    // Change the section hash.
+    changed_index[40] ^= 1;
+    module.set_cfi_index(UnwindSectionType::EhFrame, changed_index.clone());
+    assert_eq!(
+        unwinder.try_add_module(module),
+        Err(ModuleError::BadSerializedCfiIndex(
+            SerializedCfiIndexError::SectionMismatch
+        ))
+    );
+    let mut module = new_module();
+    module.set_cfi_index(UnwindSectionType::EhFrame, changed_index);
+    unwinder.add_module(module);
+    check_unwind(&unwinder);
+}
+
+#[test]
+fn test_flattened_rule_table() {
+    let mut module = common::mixed_cfi_module("libmixed-cfi-nohdr.so");
+    module.set_flatten_unwind_rules(true);
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(module);
+
+    let mut read_stack = common::mixed_cfi_stack().reader();
+    let mut cache = CacheX86_64::<_>::new();
+    let mut iter = unwinder.iter_frames_with_info(
+        common::MIXED_CFI_PC,
+        common::mixed_cfi_regs(),
+        &mut cache,
+        &mut read_stack,
+    );
+    let mut frames = Vec::new();
+    while let Ok(Some(frame)) = iter.next() {
+        frames.push((frame.address, frame.method, frame.rule));
+    }
+    assert_eq!(
+        frames,
+        vec![
+            (
+                FrameAddress::from_instruction_pointer(0x1001045),
+                UnwindMethod::InstructionPointer,
+                None
+            ),
+            // The rule from .eh_frame.
+            (
+                FrameAddress::from_return_address(0x100102f).unwrap(),
+                UnwindMethod::FlattenedRuleTable,
+                Some(UnwindRuleX86_64::OffsetSp { sp_offset_by_8: 4 })
+            ),
+            // The rule from .debug_frame, for an address which .eh_frame doesn't cover.
+            (
+                FrameAddress::from_return_address(0x123456).unwrap(),
+                UnwindMethod::FlattenedRuleTable,
+                Some(UnwindRuleX86_64::OffsetSp { sp_offset_by_8: 6 })
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_flattened_rule_table_matches_cfi_evaluation() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/linux/x86_64/nofp/libc.so.6");
+    let data = std::fs::read(path).unwrap();
+    let base_avma = 0x7f0000000000;
+    let mut lazy_unwinder = UnwinderX86_64::new();
+    lazy_unwinder
+        .add_module(Module::from_object_file("libc.so.6".to_string(), &data, base_avma).unwrap());
+    let mut flat_unwinder = UnwinderX86_64::new();
+    let mut module = Module::from_object_file("libc.so.6".to_string(), &data, base_avma).unwrap();
+    module.set_flatten_unwind_rules(true);
+    flat_unwinder.add_module(module);
+
+    let mut lazy_cache = CacheX86_64::<_>::new();
+    let mut flat_cache = CacheX86_64::<_>::new();
+    let mut read_stack = |addr: u64| Ok::<_, ()>(addr.wrapping_add(0x1000));
+    let mut flat_rule_count = 0;
+    // Check addresses throughout .text, as first frames and as return addresses.
+    for address in (base_avma + 0x2c6c0..base_avma + 0x1be49d).step_by(37) {
+        for frame_address in [
+            FrameAddress::from_instruction_pointer(address),
+            FrameAddress::from_return_address(address + 1).unwrap(),
+        ] {
+            let mut lazy_regs = UnwindRegsX86_64::new(address, 0x10000, 0x20000);
+            let mut flat_regs = lazy_regs;
+            let lazy_info = lazy_unwinder.unwind_frame_with_info(
+                frame_address,
+                &mut lazy_regs,
+                &mut lazy_cache,
+                &mut read_stack,
+            );
+            let flat_info = flat_unwinder.unwind_frame_with_info(
+                frame_address,
+                &mut flat_regs,
+                &mut flat_cache,
+                &mut read_stack,
+            );
+            if let Ok(Some(info)) = &flat_info {
+                if info.method == UnwindMethod::FlattenedRuleTable {
+                    flat_rule_count += 1;
+                }
+            }
+            let without_method = |info: Result<Option<FrameInfo<_>>, _>| {
+                info.map(|info| info.map(|info| (info.address, info.rule)))
+            };
+            assert_eq!(
+                without_method(flat_info),
+                without_method(lazy_info),
+                "{frame_address:?}"
+            );
+            assert_eq!(flat_regs, lazy_regs, "{frame_address:?}");
+        }
+    }
+    assert!(flat_rule_count > 10000);
+}
+
+#[test]
+fn test_flattened_rule_table_section_order() {
+    // conflict_func is described by both .eh_frame and .debug_frame. Only .debug_frame
+    // knows that the nop is after sub $24, %rsp. It is consulted first, whether the rules
+    // are flattened or not.
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures/linux/x86_64/nofp/libconflicting-cfi.so");
+    let data = std::fs::read(path).unwrap();
+    let mut stack = [0; 8];
+    stack[2] = 0xbad;
+    stack[5] = 0x123456;
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+    for flatten in [false, true] {
+        let mut module =
+            Module::from_object_file("libconflicting-cfi.so".to_string(), &data, 0x1000000)
+                .unwrap();
+        module.set_flatten_unwind_rules(flatten);
+        let mut unwinder = UnwinderX86_64::new();
+        unwinder.add_module(module);
+        let mut cache = CacheX86_64::<_>::new();
+        let mut regs = UnwindRegsX86_64::new(0x1001004, 0x10, 0x1234);
+        let res = unwinder.unwind_frame_with_info(
+            FrameAddress::from_instruction_pointer(0x1001004),
+            &mut regs,
+            &mut cache,
+            &mut read_stack,
+        );
+        let frame_info = res.unwrap().unwrap();
+        assert_eq!(
+            frame_info.address,
+            FrameAddress::from_return_address(0x123456).unwrap(),
+            "flatten: {flatten}"
+        );
+        let expected_method = match flatten {
+            true => UnwindMethod::FlattenedRuleTable,
+            false => UnwindMethod::DwarfCfiIndex,
+        };
+        assert_eq!(frame_info.method, expected_method);
+        assert_eq!(regs.sp(), 0x30);
+    }
+}
+
+#[test]
+fn test_orc() {
+    // A synthetic kernel image with six ORC entries. The ORC entries are six bytes
+    // each: sp_offset, bp_offset and the sp_reg / bp_reg / type / signal bit fields.
+    let base = 0xffffffff81000000;
+    let orc_unwind_ip_svma = base + 0x800000;
+    let mut orc_unwind_ip = Vec::new();
+    let mut orc_unwind = Vec::new();
+    let entries: [(u64, i16, i16, u16); 6] = [
+        // CALL, sp_reg SP, bp_reg UNDEFINED
+        (0x1000, 16, 0, 0x205),
+        // CALL, sp_reg BP, bp_reg PREV_SP
+        (0x2000, 16, -16, 0x214),
+        // END_OF_STACK
+        (0x3000, 0, 0, 0x100),
+        // REGS with the signal bit, sp_reg SP: an interrupt entry with pt_regs at sp
+        (0x4000, 0, 0, 0xb05),
+        // REGS without the signal bit, sp_reg SP
+        (0x5000, 0, 0, 0x305),
+        // REGS_PARTIAL without the signal bit, sp_reg SP: the iret frame is at sp + 128
+        (0x6000, 128, 0, 0x405),
+    ];
+    for (i, (address, sp_offset, bp_offset, bits)) in entries.into_iter().enumerate() {
+        let place = orc_unwind_ip_svma + 4 * i as u64;
+        let offset = (base + address).wrapping_sub(place) as i32;
+        orc_unwind_ip.extend_from_slice(&offset.to_le_bytes());
+        orc_unwind.extend_from_slice(&sp_offset.to_le_bytes());
+        orc_unwind.extend_from_slice(&bp_offset.to_le_bytes());
+        orc_unwind.extend_from_slice(&bits.to_le_bytes());
+    }
+    let svma_info = ModuleSvmaInfo {
+        base_svma: base,
+        text: Some(base..base + 0x800000),
+        text_env: None,
+        stubs: None,
+        stub_helper: None,
+        eh_frame: None,
+        eh_frame_hdr: None,
+        got: None,
+        orc_unwind_ip: Some(orc_unwind_ip_svma..orc_unwind_ip_svma + 24),
+        ..Default::default()
+    };
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(Module::new(
+        "vmlinux".to_string(),
+        base..base + 0x1000000,
+        base,
+        svma_info,
+        ModuleUnwindData::Orc(orc_unwind_ip, orc_unwind),
+        None,
+    ));
+
+    let mut stack = [0; 64];
+    stack[3] = base + 0x2010;
+    stack[8] = 0x0;
+    stack[9] = base + 0x3010;
+    // pt_regs at 0x100: bp, ip and sp.
+    stack[(0x100 + 32) / 8] = 0x40;
+    stack[(0x100 + 128) / 8] = base + 0x1000;
+    stack[(0x100 + 152) / 8] = 0x10;
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+    let mut cache = CacheX86_64::<_>::new();
+    let mut iter = unwinder.iter_frames_with_info(
+        base + 0x1010,
+        UnwindRegsX86_64::new(base + 0x1010, 0x10, 0x40),
+        &mut cache,
+        &mut read_stack,
+    );
+    let mut frames = Vec::new();
+    while let Some(frame) = iter.next().unwrap() {
+        frames.push((frame.address, frame.method, frame.rule));
+    }
+    let call_frames = vec![
+        (
+            FrameAddress::from_return_address(base + 0x2010).unwrap(),
+            UnwindMethod::Orc,
+            Some(UnwindRuleX86_64::OffsetSp { sp_offset_by_8: 2 }),
+        ),
+        (
+            FrameAddress::from_return_address(base + 0x3010).unwrap(),
+            UnwindMethod::Orc,
+            Some(UnwindRuleX86_64::UseFramePointer),
+        ),
+    ];
+    assert_eq!(
+        frames,
+        [
+            vec![(
+                FrameAddress::from_instruction_pointer(base + 0x1010),
+                UnwindMethod::InstructionPointer,
+                None
+            )],
+            call_frames.clone()
+        ]
+        .concat()
+    );
+
+    // In an interrupt entry. The code was interrupted at the start of the first entry, so
+    // that address must be looked up as is, not as a return address. pt_regs frames are
+    // signal frames whether or not the signal bit is set.
+    for ip in [base + 0x4010, base + 0x5010, base + 0x6010] {
+        let mut iter = unwinder.iter_frames_with_info(
+            ip,
+            UnwindRegsX86_64::new(ip, 0x100, 0x40),
+            &mut cache,
+            &mut read_stack,
+        );
+        let mut frames = Vec::new();
+        while let Some(frame) = iter.next().unwrap() {
+            frames.push((frame.address, frame.method, frame.rule));
+        }
+        assert_eq!(
+            frames,
+            [
+                vec![
+                    (
+                        FrameAddress::from_instruction_pointer(ip),
+                        UnwindMethod::InstructionPointer,
+                        None
+                    ),
+                    (
+                        FrameAddress::from_instruction_pointer(base + 0x1000),
+                        UnwindMethod::Orc,
+                        None
+                    )
+                ],
+                call_frames.clone()
+            ]
+            .concat(),
+            "ip: {ip:#x}"
+        );
+    }
+
+    // The pre-6.4 layout, in which CALL entries have type 0, is rejected.
+    let old_orc_unwind = [8i16.to_le_bytes(), 0i16.to_le_bytes(), 0x5u16.to_le_bytes()].concat();
+    let result = UnwinderX86_64::<Vec<u8>>::new().try_add_module(Module::new(
+        "vmlinux-5.15".to_string(),
+        base..base + 0x1000000,
+        base,
+        ModuleSvmaInfo {
+            base_svma: base,
+            orc_unwind_ip: Some(orc_unwind_ip_svma..orc_unwind_ip_svma + 4),
+            ..Default::default()
+        },
+        ModuleUnwindData::Orc(
+            0x800000i32.wrapping_neg().to_le_bytes().to_vec(),
+            old_orc_unwind,
+        ),
+        None,
+    ));
+    assert_eq!(result, Err(ModuleError::UnsupportedOrcLayout));
+}
+
+#[test]
+fn test_orc_from_object_file() {
+    // vmlinux-orc has its text at 0xffffffff81000000. It's loaded at a different address,
+    // like a kernel with KASLR.
+    let base = 0xffffffff8a000000;
+    let mut unwinder = UnwinderX86_64::new();
+    common::add_object(
+        &mut unwinder,
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/linux/x86_64/kernel/vmlinux-orc"),
+        base,
+    );
+
+    let mut stack = [0; 8];
+    stack[2] = base + 6; // return address into outer_func, after the call
+    stack[4] = 0x123456;
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+    let mut cache = CacheX86_64::<_>::new();
+    // In inner_func, at the nop.
+    let mut regs = UnwindRegsX86_64::new(base + 8, 0x10, 0x1234);
+    let frame_info = unwinder
+        .unwind_frame_with_info(
+            FrameAddress::from_instruction_pointer(base + 8),
+            &mut regs,
+            &mut cache,
+            &mut read_stack,
+        )
+        .unwrap()
+        .unwrap();
+    assert_eq!(frame_info.method, UnwindMethod::Orc);
+    assert_eq!(
+        frame_info.address,
+        FrameAddress::from_return_address(base + 6).unwrap()
+    );
+    // In outer_func, after push %rbx.
+    let frame_info = unwinder
+        .unwind_frame_with_info(frame_info.address, &mut regs, &mut cache, &mut read_stack)
+        .unwrap()
+        .unwrap();
+    assert_eq!(frame_info.method, UnwindMethod::Orc);
+    assert_eq!(
+        frame_info.rule,
+        Some(UnwindRuleX86_64::OffsetSp { sp_offset_by_8: 2 })
+    );
+    assert_eq!(
+        frame_info.address,
+        FrameAddress::from_return_address(0x123456).unwrap()
+    );
+    assert_eq!(regs.sp(), 0x28);
+}
+
+#[test]
+fn test_minidump() {
+    use framehop::minidump::{Minidump, MinidumpError};
+
+    let dump = common::mixed_cfi_minidump();
+    let dump = Minidump::parse(&dump).unwrap();
+    let modules = dump.modules().unwrap();
+    assert_eq!(modules.len(), 1);
+    assert_eq!(modules[0].name, "/usr/lib/libmixed-cfi.so");
+    assert_eq!(modules[0].code_id().as_deref(), Some("deadbeef"));
+
+    let mut unwinder = UnwinderX86_64::new();
+    let modules = dump
+        .unwinder_modules(|module| match module.code_id().as_deref() {
+            Some("deadbeef") => {
+                std::fs::read(common::nofp_fixtures_dir().join("libmixed-cfi.so")).ok()
+            }
+            _ => None,
+        })
+        .unwrap();
+    for module in modules {
+        unwinder.add_module(module);
+    }
+
+    let threads = dump.threads().unwrap();
+    assert_eq!(threads.len(), 1);
+    assert_eq!(threads[0].thread_id, 42);
+    assert!(threads[0].regs_aarch64().is_err());
+    let (pc, regs) = threads[0].regs_x86_64().unwrap();
+    assert_eq!(regs, common::mixed_cfi_regs());
+    let mut read_stack = threads[0].stack().reader();
+    let mut cache = CacheX86_64::<_>::new();
+    let mut iter = unwinder.iter_frames(pc, regs, &mut cache, &mut read_stack);
+    let mut frames = Vec::new();
+    while let Ok(Some(frame)) = iter.next() {
+        frames.push(frame);
+    }
+    assert_eq!(frames, common::mixed_cfi_frames());
+
+    let memory = dump.memory().unwrap();
+    assert_eq!(memory.read_u64(0x28), Ok(0x100102f));
+
+    // A binary which can't be parsed doesn't prevent adding the module.
+    let modules = dump.unwinder_modules(|_| Some(vec![0; 16])).unwrap();
+    assert_eq!(modules.len(), 1);
+    let mut unwinder = UnwinderX86_64::<Vec<u8>>::new();
+    unwinder.add_module(modules.into_iter().next().unwrap());
+    assert_eq!(unwinder.max_known_code_address(), 0x1010000);
+
+    // The module's base address is where the start of the file is mapped, which is not
+    // the address of the binary's base if its first segment has a non-zero vaddr. The
+    // first segment of vmlinux-orc is one page below its text.
+    let mut module = dump.modules().unwrap().remove(0);
+    module.base_address = 0xffffffff90fff000;
+    let vmlinux = std::fs::read(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/linux/x86_64/kernel/vmlinux-orc"),
+    )
+    .unwrap();
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(module.to_module(Some(&vmlinux)).unwrap());
+    let text = 0xffffffff91000000;
+    let mut stack = [0; 8];
+    stack[2] = text + 6;
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+    let mut regs = UnwindRegsX86_64::new(text + 8, 0x10, 0x1234);
+    let frame_info = unwinder
+        .unwind_frame_with_info(
+            FrameAddress::from_instruction_pointer(text + 8),
+            &mut regs,
+            &mut CacheX86_64::<_>::new(),
+            &mut read_stack,
+        )
+        .unwrap()
+        .unwrap();
+    assert_eq!(frame_info.method, UnwindMethod::Orc);
+    assert_eq!(
+        frame_info.address,
+        FrameAddress::from_return_address(text + 6).unwrap()
+    );
+
+    let mut module = dump.modules().unwrap().remove(0);
+    module.base_address = u64::MAX - 0xfff;
+    assert_eq!(
+        module.to_module(None).err(),
+        Some(MinidumpError::ModuleRangeOverflow(
+            "/usr/lib/libmixed-cfi.so".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_coredump() {
+    use framehop::coredump::CoreDump;
+
+    for extended_phnum in [false, true] {
+        let core = common::mixed_cfi_core_dump(extended_phnum);
+        let core = CoreDump::parse(&core).unwrap();
+        let modules = core.modules();
+        assert_eq!(modules.len(), 1);
+        assert_eq!(modules[0].path, "/usr/lib/libmixed-cfi.so");
+        assert_eq!(modules[0].avma_range, 0x1000000..0x1010000);
+        assert_eq!(modules[0].code_id().as_deref(), Some("deadbeef"));
+
+        let mut unwinder = UnwinderX86_64::new();
+        let modules = core.unwinder_modules(|module| match module.code_id().as_deref() {
+            Some("deadbeef") => {
+                std::fs::read(common::nofp_fixtures_dir().join("libmixed-cfi.so")).ok()
+            }
+            _ => None,
+        });
+        for module in modules {
+            unwinder.add_module(module);
+        }
+
+        let threads = core.threads();
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].thread_id, 42);
+        assert!(threads[0].regs_aarch64().is_err());
+        let (pc, regs) = threads[0].regs_x86_64().unwrap();
+        assert_eq!(regs, common::mixed_cfi_regs());
+        let mut read_stack = core.memory().reader();
+        let mut cache = CacheX86_64::<_>::new();
+        let mut iter = unwinder.iter_frames(pc, regs, &mut cache, &mut read_stack);
+        let mut frames = Vec::new();
+        while let Ok(Some(frame)) = iter.next() {
+            frames.push(frame);
+        }
+        assert_eq!(frames, common::mixed_cfi_frames());
+    }
+
+    // Mapped files which aren't binaries are added without unwind information.
+    let core = common::mixed_cfi_core_dump(false);
+    let core = CoreDump::parse(&core).unwrap();
+    let modules = core.unwinder_modules(|_| Some(b"not an ELF file".to_vec()));
+    assert_eq!(modules.len(), 1);
+    let mut unwinder = UnwinderX86_64::<Vec<u8>>::new();
+    unwinder.add_module(modules.into_iter().next().unwrap());
+    assert_eq!(unwinder.max_known_code_address(), 0x1010000);
+}
+
+#[test]
+fn test_unwind_batch() {
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(common::mixed_cfi_module("libmixed-cfi.so"));
+
+    let stack = common::mixed_cfi_stack();
+    let samples = || {
+        [
+            (common::MIXED_CFI_PC, common::mixed_cfi_regs()),
+            // The stack pointer is outside of the captured stack.
+            (0x1001045, UnwindRegsX86_64::new(0x1001045, 0x1000, 0)),
+            // Outside of any module, with a null frame pointer.
+            (0x123456, UnwindRegsX86_64::new(0x123456, 0x10, 0)),
+        ]
+        .map(|(pc, regs)| (pc, regs, stack.reader()))
+    };
+
+    let mut cache = CacheX86_64::<_>::new();
+    let batch = unwinder.unwind_batch(samples(), &mut cache, &BatchOptions::default());
+    assert_eq!(batch.len(), 3);
+    assert_eq!(batch.stack(0), &common::MIXED_CFI_ADDRESSES);
+    assert_eq!(
+        batch.frames(0).collect::<Vec<_>>(),
+        common::mixed_cfi_frames()
+    );
+    assert_eq!(batch.status(0), StackStatus::Complete);
+    assert_eq!(batch.stack(1), &[0x1001045]);
+    assert_eq!(
+        batch.status(1),
+        StackStatus::Error(Error::CouldNotReadStackMemory(StackReadError::AboveEnd(
+            0x1018
+        )))
+    );
+    assert_eq!(batch.stack(2), &[0x123456]);
+    assert_eq!(batch.status(2), StackStatus::Complete);
+    assert_eq!(batch.addresses().len(), 5);
+
+    // Callbacks which don't say why a read failed report the plain address.
+    let mut read_stack = |address| stack.read_u64(address).map_err(|_| ());
+    let mut regs = UnwindRegsX86_64::new(0x1001045, 0x1000, 0);
+    assert_eq!(
+        unwinder.unwind_frame(
+            FrameAddress::from_instruction_pointer(0x1001045),
+            &mut regs,
+            &mut cache,
+            &mut read_stack,
+        ),
+        Err(Error::CouldNotReadStack(0x1018))
+    );
+
+    let options = BatchOptions {
+        sort_by_pc: true,
+        max_frames: 2,
+        ..Default::default()
+    };
+    let sorted = unwinder.unwind_batch(samples(), &mut cache, &options);
+    // The third sample has the lowest pc, so it was unwound first.
+    assert_eq!(
+        sorted.addresses(),
+        &[0x123456, 0x1001045, 0x100102f, 0x1001045]
+    );
+    assert_eq!(
+        sorted.iter().collect::<Vec<_>>(),
+        vec![
+            (&[0x1001045, 0x100102f][..], StackStatus::FrameLimit),
+            (&[0x1001045][..], batch.status(1)),
+            (&[0x123456][..], StackStatus::Complete),
+        ]
+    );
+
+    // More addresses than fit into one word of the instruction pointer bits.
+    let many_samples = (0..30).map(|_| {
+        (
+            common::MIXED_CFI_PC,
+            common::mixed_cfi_regs(),
+            stack.reader(),
+        )
+    });
+    let batch = unwinder.unwind_batch(many_samples, &mut cache, &BatchOptions::default());
+    assert_eq!(batch.addresses().len(), 90);
+    for index in 0..batch.len() {
+        assert_eq!(
+            batch.frames(index).collect::<Vec<_>>(),
+            common::mixed_cfi_frames()
+        );
+    }
+}
+
+#[test]
+fn test_stack_scanning() {
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(common::mixed_cfi_module("libmixed-cfi.so"));
+
+    // The instruction pointer is outside of any module, and rbp is garbage, so frame
+    // pointer unwinding fails.
+    let mut stack = [0u64; 14];
+    // Inside c_func, but not after a call instruction.
+    stack[4] = 0x1001033;
+    // After the call in c_func.
+    stack[5] = 0x100102f;
+    // c_func's return address, found with its CFI.
+    stack[11] = 0x123456;
+    let stack_bytes = stack.map(u64::to_le_bytes).concat();
+    let mut read_stack = StackMemory::new(0, &stack_bytes).reader();
+    let regs = UnwindRegsX86_64::new(0x123456, 0x20, 0x8);
+    let mut cache = CacheX86_64::<_>::new();
+
+    let mut iter = unwinder.iter_frames(0x123456, regs, &mut cache, &mut read_stack);
+    assert_eq!(
+        iter.next(),
+        Ok(Some(FrameAddress::from_instruction_pointer(0x123456)))
+    );
+    assert_eq!(iter.next(), Err(Error::FramepointerUnwindingMovedBackwards));
+
+    unwinder.set_stack_scanning(true);
+    let mut iter = unwinder.iter_frames_with_info(0x123456, regs, &mut cache, &mut read_stack);
+    let mut frames = Vec::new();
+    let result = loop {
+        match iter.next() {
+            Ok(Some(frame)) => frames.push((frame.address, frame.method, frame.rule)),
+            result => break result,
+        }
+    };
+    assert_eq!(
+        frames,
+        vec![
+            (
+                FrameAddress::from_instruction_pointer(0x123456),
+                UnwindMethod::InstructionPointer,
+                None
+            ),
+            (
+                FrameAddress::from_return_address(0x100102f).unwrap(),
+                UnwindMethod::StackScan,
+                None
+            ),
+            (
+                FrameAddress::from_return_address(0x123456).unwrap(),
+                UnwindMethod::DebugCompanion,
+                Some(UnwindRuleX86_64::OffsetSp { sp_offset_by_8: 6 })
+            ),
+        ]
+    );
+    // Nothing above the last frame looks like a return address.
+    assert_eq!(result, Err(Error::FramepointerUnwindingMovedBackwards));
+}
+
+#[test]
+fn test_return_address_checks() {
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(common::mixed_cfi_module("libmixed-cfi.so"));
+    let [pc, asm_func_return_address, c_func_return_address] = common::MIXED_CFI_ADDRESSES;
+
+    let unwind = |return_address: u64, checks: ReturnAddressChecks| {
+        // asm_func's caller is found with its CFI, and the caller's caller with the
+        // frame pointer fallback. asm_func's return address is replaced with
+        // `return_address`.
+        let stack = common::mixed_cfi_stack();
+        let mut read_stack = |address| match address {
+            _ if address == common::MIXED_CFI_SP + 0x18 => Ok(return_address),
+            _ => stack.reader()(address),
+        };
+        let mut cache = CacheX86_64::<_>::new();
+        let mut iter = unwinder
+            .iter_frames(pc, common::mixed_cfi_regs(), &mut cache, &mut read_stack)
+            .with_return_address_checks(checks);
+        let mut frames = Vec::new();
+        loop {
+            match iter.next() {
+                Ok(Some(frame)) => frames.push(frame.address()),
+                result => return (frames, result),
+            }
+        }
+    };
+
+    // Without checks, the address outside of any module is accepted.
+    assert_eq!(
+        unwind(asm_func_return_address, ReturnAddressChecks::default()),
+        (common::MIXED_CFI_ADDRESSES.to_vec(), Ok(None))
+    );
+    assert_eq!(
+        unwind(asm_func_return_address, ReturnAddressChecks::all()),
+        (
+            vec![pc, asm_func_return_address],
+            Err(Error::ImplausibleReturnAddress(
+                c_func_return_address,
+                ReturnAddressCheckFailure::NotInModule
+            ))
+        )
+    );
+    // Inside c_func, but not after a call instruction.
+    let mut after_call = ReturnAddressChecks::default();
+    after_call.after_call = true;
+    assert_eq!(
+        unwind(0x1001033, after_call),
+        (
+            vec![pc],
+            Err(Error::ImplausibleReturnAddress(
+                0x1001033,
+                ReturnAddressCheckFailure::NotAfterCall
+            ))
+        )
+    );
+    // Inside the module, but in .eh_frame_hdr, which is not in an executable segment.
+    let mut in_executable_range = ReturnAddressChecks::default();
+    in_executable_range.in_executable_range = true;
+    assert_eq!(
+        unwind(0x1002010, in_executable_range),
+        (
+            vec![pc],
+            Err(Error::ImplausibleReturnAddress(
+                0x1002010,
+                ReturnAddressCheckFailure::NotInExecutableRange
+            ))
+        )
+    );
+    // In .plt, outside of .text but in the same executable PT_LOAD segment.
+    assert_eq!(
+        unwinder.check_return_address(0x1001010, &in_executable_range),
+        Ok(())
+    );
+    assert_eq!(
+        unwinder.check_return_address(asm_func_return_address, &ReturnAddressChecks::all()),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_signal_trampoline_x86_64() {
+    let mut cache = CacheX86_64::<_>::new();
+    let mut unwinder = UnwinderX86_64::new();
+    common::add_object(
+        &mut unwinder,
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/linux/x86_64/nofp/libc.so.6"),
+        0x0,
+    );
+
+    // __restore_rt:
+    // 4651f  nop                          ; Begin of unwind block (FDE at 0x25f8, CIE "zRS")
+    // 46520  mov        rax, 0xf
+    // 46527  syscall
+    //
+    // The signal handler returned to 0x46520. The stack pointer points to the ucontext_t.
+    let mut stack = [0; 32];
+    stack[15] = 0x1000; // uc_mcontext.rbp
+    stack[20] = 0x800; // uc_mcontext.rsp
+    stack[21] = 0x12345; // uc_mcontext.rip
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+
+    // An instruction pointer at the nop shares its cache entry with the return address
+    // 0x46520. Unwinding it must not cache a rule for the return address.
+    let unwind_nop = |cache: &mut CacheX86_64<_>, read_stack: &mut _| {
+        let mut regs = UnwindRegsX86_64::new(0x4651f, 0x0, 0x50);
+        unwinder
+            .unwind_frame_with_info(
+                FrameAddress::from_instruction_pointer(0x4651f),
+                &mut regs,
+                cache,
+                read_stack,
+            )
+            .map(|info| info.map(|info| info.cache_hit))
+    };
+    assert_eq!(unwind_nop(&mut cache, &mut read_stack), Ok(Some(false)));
+
+    let mut regs = UnwindRegsX86_64::new(0x46520, 0x0, 0x50);
+    let res = unwinder.unwind_frame_with_info(
+        FrameAddress::from_return_address(0x46520).unwrap(),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(
+        res,
+        Ok(Some(FrameInfo {
+            address: FrameAddress::InstructionPointer(0x12345),
+            method: UnwindMethod::SignalTrampoline,
+            rule: Some(UnwindRuleX86_64::LinuxSignalFrame),
+            cache_hit: false,
+            suppressed_error: None,
+        }))
+    );
+    assert_eq!(regs.ip(), 0x12345);
+    assert_eq!(regs.sp(), 0x800);
+    assert_eq!(regs.bp(), 0x1000);
+    assert_eq!(unwind_nop(&mut cache, &mut read_stack), Ok(Some(false)));
+
+    // Sampled at the syscall instruction, inside the trampoline.
+    let mut regs = UnwindRegsX86_64::new(0x46527, 0x0, 0x50);
+    let res = unwinder.unwind_frame_address(
+        FrameAddress::from_instruction_pointer(0x46527),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(FrameAddress::InstructionPointer(0x12345))));
+}
+
+#[test]
+fn test_signal_trampoline_aarch64() {
+    let mut cache = CacheAarch64::<_>::new();
+    let mut unwinder = UnwinderAarch64::new();
+    common::add_object(
+        &mut unwinder,
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/linux/aarch64/vdso.so"),
+        0x0,
+    );
+
+    // __kernel_rt_sigreturn:
+    // 5bc  nop
+    // 5c0  mov        x8, #0x8b       ; Begin of unwind block (FDE at 0xe0, CIE "zRS")
+    // 5c4  svc        #0
+    //
+    // The thread was sampled at the svc instruction. The stack pointer points to the
+    // rt_sigframe, and the signal interrupted the function at 0x5a8, which hasn't
+    // stored lr yet.
+    let mut stack = [0; 98];
+    stack[68] = 0x300; // uc.uc_mcontext.regs[29]
+    stack[69] = 0x1234; // uc.uc_mcontext.regs[30]
+    stack[70] = 0x2f0; // uc.uc_mcontext.sp
+    stack[71] = 0x5a8; // uc.uc_mcontext.pc
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+    let regs = UnwindRegsAarch64::new(0x400, 0x0, 0x10);
+    let mut iter = unwinder
+        .iter_frames(0x5c4, regs, &mut cache, &mut read_stack)
+        .with_return_address_checks(ReturnAddressChecks::all());
+    assert_eq!(
+        iter.next(),
+        Ok(Some(FrameAddress::InstructionPointer(0x5c4)))
+    );
+    // The interrupted instruction pointer doesn't follow a call, but it's not checked.
+    assert_eq!(
+        iter.next(),
+        Ok(Some(FrameAddress::InstructionPointer(0x5a8)))
+    );
+    assert_eq!(
+        iter.next(),
+        Err(Error::ImplausibleReturnAddress(
+            0x1234,
+            ReturnAddressCheckFailure::NotInModule
+        ))
+    );
+
+    // Without checks, the interrupted function is unwound with its lr, and then the
+    // frame pointer chain ends at 0x300.
+    let mut iter = unwinder.iter_frames(0x5c4, regs, &mut cache, &mut read_stack);
+    assert_eq!(
+        iter.next(),
+        Ok(Some(FrameAddress::InstructionPointer(0x5c4)))
+    );
+    assert_eq!(
+        iter.next(),
+        Ok(Some(FrameAddress::InstructionPointer(0x5a8)))
+    );
+    assert_eq!(
+        iter.next(),
+        Ok(Some(FrameAddress::from_return_address(0x1234).unwrap()))
+    );
+    assert_eq!(iter.next(), Ok(None));
+
+    // A batch keeps the kind of each address.
+    let samples = [(0x5c4, regs, &mut read_stack)];
+    let batch = unwinder.unwind_batch(samples, &mut cache, &BatchOptions::default());
+    assert_eq!(batch.stack(0), &[0x5c4, 0x5a8, 0x1234]);
+    assert_eq!(
+        batch.frames(0).collect::<Vec<_>>(),
+        vec![
+            FrameAddress::InstructionPointer(0x5c4),
+            FrameAddress::InstructionPointer(0x5a8),
+            FrameAddress::from_return_address(0x1234).unwrap(),
+        ]
+    );
+    assert_eq!(batch.frame_address(2), batch.frames(0).last().unwrap());
+}
+
+#[test]
+fn test_signal_frame_cfi_aarch64() {
+    // Without text bytes, the trampoline is recognized by the "S" augmentation of the
+    // CIE of its FDE.
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/linux/aarch64/vdso.so");
+    let data = std::fs::read(path).unwrap();
+    let mut cache = CacheAarch64::<Vec<u8>>::new();
+    let mut unwinder = UnwinderAarch64::new();
+    unwinder.add_module(Module::new(
+        "vdso.so".to_string(),
+        0x0..0x1000,
+        0x0,
+        ModuleSvmaInfo {
+            base_svma: 0,
+            text: Some(0x300..0x5c8),
+            text_env: None,
+            stubs: None,
+            stub_helper: None,
+            eh_frame: Some(0x5c8..0x6bc),
+            eh_frame_hdr: None,
+            got: None,
+            ..Default::default()
+        },
+        ModuleUnwindData::EhFrame(data[0x5c8..0x6bc].to_vec()),
+        None,
+    ));
+
+    let mut stack = [0; 72];
+    stack[68] = 0x300;
+    stack[69] = 0x1234;
+    stack[70] = 0x2f0;
+    stack[71] = 0x5a8;
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+    let mut regs = UnwindRegsAarch64::new(0x400, 0x0, 0x10);
+    let res = unwinder.unwind_frame_with_info(
+        FrameAddress::from_instruction_pointer(0x5c4),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(
+        res,
+        Ok(Some(FrameInfo {
+            address: FrameAddress::InstructionPointer(0x5a8),
+            method: UnwindMethod::DwarfCfiIndex,
+            rule: Some(UnwindRuleAarch64::LinuxSignalFrame),
+            cache_hit: false,
+            suppressed_error: None,
+        }))
+    );
+    assert_eq!(regs.lr(), 0x1234);
+    assert_eq!(regs.sp(), 0x2f0);
+    assert_eq!(regs.fp(), 0x300);
+}
@@ -4,6 +4,7 @@ use fallible_iterator::FallibleIterator;
 use framehop::aarch64::*;
 use framehop::x86_64::*;
 use framehop::FrameAddress;
+use framehop::FrameInfoUnwinder;
 use framehop::Unwinder;
 
 use super::common;
@@ -92,6 +93,7 @@ fn test_root_doc_comment() {
             eh_frame: Some(0x100237f80..0x100237ffc),
             eh_frame_hdr: None,
             got: Some(0x100238000..0x100238010),
+            ..Default::default()
         },
         ModuleUnwindData::CompactUnwindInfoAndEhFrame(vec![/* __unwind_info */], None),
         Some(TextByteData::new(
@@ -1146,3 +1148,162 @@ fn test_go_debug_frame() {
     );
     assert_eq!(res, Ok(None));
 }
+
+/// Builds an `__unwind_info` section with one regular second-level page, for functions
+/// which start at the given (relative address, encoding) pairs and end at `text_end`.
+fn unwind_info(functions: &[(u32, u32)], text_end: u32) -> Vec<u8> {
+    const HEADER_LEN: u32 = 28;
+    const INDEX_LEN: u32 = 24;
+    let page_offset = HEADER_LEN + INDEX_LEN;
+    let mut data = Vec::new();
+    // Version, common encodings, personalities, and an index with one page and the
+    // sentinel entry.
+    for value in [1, HEADER_LEN, 0, HEADER_LEN, 0, HEADER_LEN, 2] {
+        data.extend(u32::to_le_bytes(value));
+    }
+    for value in [functions[0].0, page_offset, page_offset] {
+        data.extend(u32::to_le_bytes(value));
+    }
+    for value in [text_end, 0, page_offset] {
+        data.extend(u32::to_le_bytes(value));
+    }
+    // A regular page: kind, entry page offset, entry count, and the entries.
+    data.extend(u32::to_le_bytes(2));
+    data.extend(u16::to_le_bytes(8));
+    data.extend(u16::to_le_bytes(functions.len() as u16));
+    for &(address, encoding) in functions {
+        data.extend(u32::to_le_bytes(address));
+        data.extend(u32::to_le_bytes(encoding));
+    }
+    data
+}
+
+/// Builds an `__eh_frame` section at `eh_frame_svma` with a signal trampoline FDE for
+/// `sigtramp`. The CIE has the augmentation "zRS", like the CIE of `_sigtramp` in
+/// libsystem_platform.dylib. The FDE is at offset 0x18.
+fn sigtramp_eh_frame(
+    eh_frame_svma: u64,
+    sigtramp: std::ops::Range<u64>,
+    return_address_register: u8,
+    sp_register: u8,
+) -> Vec<u8> {
+    let mut cie = vec![0, 0, 0, 0, 1];
+    cie.extend(b"zRS\0");
+    // Code alignment 1, data alignment -8, augmentation data: pc-relative sdata4 pointers,
+    // DW_CFA_def_cfa sp, 8.
+    cie.extend([
+        1,
+        0x78,
+        return_address_register,
+        1,
+        0x1b,
+        0x0c,
+        sp_register,
+        8,
+    ]);
+    cie.resize(0x14, 0);
+    let mut data = u32::to_le_bytes(cie.len() as u32).to_vec();
+    data.extend(cie);
+
+    let fde_offset = data.len() as u64;
+    let pc_begin_svma = eh_frame_svma + fde_offset + 8;
+    let mut fde = u32::to_le_bytes(fde_offset as u32 + 4).to_vec();
+    fde.extend(i32::to_le_bytes(
+        sigtramp.start.wrapping_sub(pc_begin_svma) as i32
+    ));
+    fde.extend(u32::to_le_bytes((sigtramp.end - sigtramp.start) as u32));
+    fde.resize(0x14, 0);
+    data.extend(u32::to_le_bytes(fde.len() as u32));
+    data.extend(fde);
+    data.extend(u32::to_le_bytes(0));
+    data
+}
+
+/// A module at 0x10000000 whose `_sigtramp` at 0x10001100..0x10001140 is covered by a
+/// DWARF entry in its compact unwind info, with the given DWARF mode encoding.
+fn sigtramp_module(
+    dwarf_mode: u32,
+    return_address_register: u8,
+    sp_register: u8,
+) -> framehop::Module<Vec<u8>> {
+    let eh_frame = sigtramp_eh_frame(0x3000, 0x1100..0x1140, return_address_register, sp_register);
+    let functions = [(0x1000, 0), (0x1100, dwarf_mode | 0x18), (0x1140, 0)];
+    framehop::Module::new(
+        "libsystem_platform.dylib".to_string(),
+        0x10000000..0x10004000,
+        0x10000000,
+        framehop::ModuleSvmaInfo {
+            base_svma: 0,
+            text: Some(0x1000..0x2000),
+            eh_frame: Some(0x3000..0x3000 + eh_frame.len() as u64),
+            ..Default::default()
+        },
+        framehop::ModuleUnwindData::CompactUnwindInfoAndEhFrame(
+            unwind_info(&functions, 0x2000),
+            Some(eh_frame),
+        ),
+        None,
+    )
+}
+
+#[test]
+fn test_sigtramp_x86_64() {
+    let mut cache = CacheX86_64::<_>::new();
+    let mut unwinder = UnwinderX86_64::new();
+    unwinder.add_module(sigtramp_module(0x04000000, 16, 7));
+
+    // The signal handler returned into _sigtramp, whose frame pointer is at 0x10. The
+    // interrupted stack pointer was 16-byte aligned, so the mcontext64 is at 0x28.
+    let mut stack = [0; 48];
+    stack[2] = 0x70; // the interrupted rbp, pushed by _sigtramp
+    stack[5 + 8] = 0x70; // __ss.__rbp
+    stack[5 + 9] = 0x800; // __ss.__rsp
+    stack[5 + 18] = 0x12345; // __ss.__rip
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+    let mut regs = UnwindRegsX86_64::new(0x10001120, 0x10, 0x10);
+    let res = unwinder.unwind_frame_with_info(
+        FrameAddress::from_return_address(0x10001120).unwrap(),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(
+        res,
+        Ok(Some(framehop::FrameInfo {
+            address: FrameAddress::InstructionPointer(0x12345),
+            method: framehop::UnwindMethod::CompactUnwindInfoDwarf,
+            rule: Some(UnwindRuleX86_64::MacOsSignalFrame),
+            cache_hit: false,
+            suppressed_error: None,
+        }))
+    );
+    assert_eq!(regs.sp(), 0x800);
+    assert_eq!(regs.bp(), 0x70);
+}
+
+#[test]
+fn test_sigtramp_aarch64() {
+    let mut cache = CacheAarch64::<_>::new();
+    let mut unwinder = UnwinderAarch64::new();
+    unwinder.add_module(sigtramp_module(0x03000000, 30, 31));
+
+    // The signal handler returned into _sigtramp, whose frame pointer is at 0x20, so the
+    // user_sigframe64 starts at 0x30.
+    let mut stack = [0; 64];
+    stack[6 + 53] = 0x300; // mctx.__ss.__fp
+    stack[6 + 54] = 0x1234; // mctx.__ss.__lr
+    stack[6 + 55] = 0x2f0; // mctx.__ss.__sp
+    stack[6 + 56] = 0x5a8; // mctx.__ss.__pc
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+    let mut regs = UnwindRegsAarch64::new(0x10001120, 0x10, 0x20);
+    let res = unwinder.unwind_frame_address(
+        FrameAddress::from_return_address(0x10001120).unwrap(),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(FrameAddress::InstructionPointer(0x5a8))));
+    assert_eq!(regs.lr(), 0x1234);
+    assert_eq!(regs.sp(), 0x2f0);
+    assert_eq!(regs.fp(), 0x300);
+}
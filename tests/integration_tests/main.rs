@@ -1,3 +1,5 @@
+mod android;
 mod common;
 mod linux;
 mod macos;
+mod windows;
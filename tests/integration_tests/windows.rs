@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use framehop::x86_64::*;
+use framehop::FrameAddress;
+use framehop::Unwinder;
+
+use super::common;
+
+#[test]
+fn test_pe_unwind_info_x86_64() {
+    let mut cache = CacheX86_64::<_>::new();
+    let mut unwinder = UnwinderX86_64::new();
+    common::add_object(
+        &mut unwinder,
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/windows/x86_64/seh.dll"),
+        0x10000000,
+    );
+
+    //  fp_func:
+    //  1000  push rbp
+    //  1001  push rbx
+    //  1002  mov rbp, rsp
+    //  1005  sub rsp, 0x20
+    //  1009  call leaf_func
+    //  100e  ...
+    //  nofp_func:
+    //  1015  push rsi
+    //  1016  sub rsp, 0x30
+    //  101a  call fp_func
+    //  101f  ...
+    //  leaf_func:
+    //  1025  nop
+    //  1026  ret
+    let mut stack = [0u64; 0x20];
+    stack[0x10 / 8] = 0x1000100e; // return address into fp_func
+    stack[0x38 / 8] = 0xbbbb; // saved rbx
+    stack[0x40 / 8] = 0xa0; // saved rbp
+    stack[0x48 / 8] = 0x1000101f; // return address into nofp_func
+    stack[0x80 / 8] = 0x5555; // saved rsi
+    stack[0x88 / 8] = 0x123456; // return address into the caller of nofp_func
+    let mut read_stack = |addr| stack.get((addr / 8) as usize).cloned().ok_or(());
+
+    // leaf_func has no .pdata entry, so the return address is at rsp.
+    let mut regs = UnwindRegsX86_64::new(0x10001026, 0x10, 0x38);
+    let res = unwinder.unwind_frame(
+        FrameAddress::from_instruction_pointer(0x10001026),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(0x1000100e)));
+    assert_eq!(regs.sp(), 0x18);
+    assert_eq!(regs.bp(), 0x38);
+
+    // fp_func has set up rbp as its frame register.
+    let res = unwinder.unwind_frame(
+        FrameAddress::from_return_address(0x1000100e).unwrap(),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(0x1000101f)));
+    assert_eq!(regs.sp(), 0x50);
+    assert_eq!(regs.bp(), 0xa0);
+
+    // nofp_func only adjusts rsp.
+    let res = unwinder.unwind_frame(
+        FrameAddress::from_return_address(0x1000101f).unwrap(),
+        &mut regs,
+        &mut cache,
+        &mut read_stack,
+    );
+    assert_eq!(res, Ok(Some(0x123456)));
+    assert_eq!(regs.sp(), 0x90);
+    assert_eq!(regs.bp(), 0xa0);
+}